@@ -0,0 +1,708 @@
+//! iCalendar files
+//!
+//! Reads the events of `.ics` files (RFC 5545) and expands them into
+//! instances: recurring events follow their `RRULE`, minus `EXDATE`s and
+//! instances moved or cancelled by a `RECURRENCE-ID` override. Times with a
+//! `TZID` use the file's `VTIMEZONE` definitions, falling back to the local
+//! time zone for zones the file does not define.
+
+use super::rrule::RecurrenceRule;
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use std::collections::BTreeMap;
+
+/// Most occurrences of one recurring event looked through
+const MAX_OCCURRENCES: usize = 100_000;
+
+/// Time zone a date-time is in
+#[derive(Debug, Clone, PartialEq)]
+pub enum Zone {
+    /// UTC, written with a trailing `Z`
+    Utc,
+    /// Local time of whoever reads the calendar
+    Floating,
+    /// A zone named by a `TZID` parameter
+    Named(String),
+}
+
+/// A date or date-time value
+#[derive(Debug, Clone, PartialEq)]
+pub struct IcsTime {
+    /// Wall clock time in `zone`; midnight for dates
+    pub local: NaiveDateTime,
+    /// Time zone of `local`
+    pub zone: Zone,
+    /// Whether the value is a date without a time
+    pub all_day: bool,
+}
+
+/// An event as written in the file
+#[derive(Debug, Clone, Default)]
+pub struct CalendarEvent {
+    /// Unique ID shared by a recurring event and its overrides
+    pub uid: String,
+    /// Title
+    pub summary: String,
+    /// Description
+    pub description: String,
+    /// Location
+    pub location: String,
+    /// Linked URL, including conference links some providers add
+    pub url: String,
+    /// Start
+    pub start: Option<IcsTime>,
+    /// End, if given
+    pub end: Option<IcsTime>,
+    /// Length, if given instead of an end
+    pub duration: Option<Duration>,
+    /// Recurrence rule
+    pub rule: Option<RecurrenceRule>,
+    /// Start times of instances removed from the recurrence
+    pub exceptions: Vec<IcsTime>,
+    /// Original start of the instance this event overrides
+    pub recurrence_id: Option<IcsTime>,
+    /// Whether the event is cancelled
+    pub cancelled: bool,
+}
+
+/// A single instance of an event
+#[derive(Debug, Clone)]
+pub struct EventInstance<'a> {
+    /// Event the instance belongs to
+    pub event: &'a CalendarEvent,
+    /// Start
+    pub start: DateTime<Utc>,
+    /// End
+    pub end: DateTime<Utc>,
+    /// Whether the event lasts whole days
+    pub all_day: bool,
+}
+
+/// Time zone defined by a `VTIMEZONE` component
+#[derive(Debug, Clone, Default)]
+struct ZoneDefinition {
+    observances: Vec<Observance>,
+}
+
+/// Standard or daylight saving time within a zone
+#[derive(Debug, Clone)]
+struct Observance {
+    /// First onset, in the local time before it
+    start: NaiveDateTime,
+    /// Offset from UTC before the onset, in seconds
+    offset_from: i32,
+    /// Offset from UTC from the onset, in seconds
+    offset_to: i32,
+    /// Rule for later onsets
+    rule: Option<RecurrenceRule>,
+}
+
+/// The events and time zones of one or more files
+#[derive(Debug, Clone, Default)]
+pub struct Calendar {
+    /// Events, including overrides of recurring ones
+    pub events: Vec<CalendarEvent>,
+    zones: BTreeMap<String, ZoneDefinition>,
+}
+
+impl Calendar {
+    /// Parse the contents of an `.ics` file.
+    ///
+    /// Malformed events and time zones are skipped rather than failing the
+    /// whole file.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let lines = unfold(text);
+        if !lines
+            .iter()
+            .any(|line| line.eq_ignore_ascii_case("BEGIN:VCALENDAR"))
+        {
+            return Err("Not an iCalendar file".to_string());
+        }
+
+        let mut calendar = Self::default();
+        let mut event: Option<CalendarEvent> = None;
+        let mut in_alarm = false;
+        let mut zone: Option<(String, ZoneDefinition)> = None;
+        // Why the time zone being read cannot be used, if it cannot
+        let mut zone_error: Option<String> = None;
+        let mut observance: Option<(Option<NaiveDateTime>, i32, i32, Option<RecurrenceRule>)> =
+            None;
+
+        for line in &lines {
+            let Some(property) = Property::parse(line) else {
+                continue;
+            };
+            match (property.name.as_str(), property.value.as_str()) {
+                ("BEGIN", "VEVENT") => event = Some(CalendarEvent::default()),
+                // Alarms have descriptions of their own
+                ("BEGIN", "VALARM") => in_alarm = true,
+                ("END", "VALARM") => in_alarm = false,
+                _ if in_alarm => {}
+                ("END", "VEVENT") => {
+                    if let Some(event) = event.take().filter(|event| event.start.is_some()) {
+                        calendar.events.push(event);
+                    }
+                }
+                ("BEGIN", "VTIMEZONE") => {
+                    zone = Some((String::new(), ZoneDefinition::default()));
+                    zone_error = None;
+                }
+                ("END", "VTIMEZONE") => match (zone.take(), zone_error.take()) {
+                    (Some((id, _)), Some(e)) => eprintln!("Ignoring time zone {id}: {e}"),
+                    (Some((id, definition)), None) => {
+                        calendar.zones.insert(id, definition);
+                    }
+                    (None, _) => {}
+                },
+                ("BEGIN", "STANDARD" | "DAYLIGHT") => observance = Some((None, 0, 0, None)),
+                ("END", "STANDARD" | "DAYLIGHT") => {
+                    if let (Some((Some(start), offset_from, offset_to, rule)), Some((_, zone))) =
+                        (observance.take(), zone.as_mut())
+                    {
+                        zone.observances.push(Observance {
+                            start,
+                            offset_from,
+                            offset_to,
+                            rule,
+                        });
+                    }
+                }
+                _ if observance.is_some() => {
+                    let (start, offset_from, offset_to, rule) = observance.as_mut().unwrap();
+                    match property.name.as_str() {
+                        "DTSTART" => *start = property.time().map(|time| time.local),
+                        "TZOFFSETFROM" | "TZOFFSETTO" => match parse_offset(&property.value) {
+                            Ok(offset) if property.name == "TZOFFSETFROM" => *offset_from = offset,
+                            Ok(offset) => *offset_to = offset,
+                            Err(e) => {
+                                zone_error.get_or_insert(e);
+                            }
+                        },
+                        "RRULE" => *rule = RecurrenceRule::parse(&property.value).ok(),
+                        _ => {}
+                    }
+                }
+                ("TZID", id) if zone.is_some() => {
+                    if let Some((zone_id, _)) = zone.as_mut() {
+                        *zone_id = id.to_string();
+                    }
+                }
+                _ => {
+                    if let Some(event) = event.as_mut() {
+                        event.set_property(&property);
+                    }
+                }
+            }
+        }
+
+        Ok(calendar)
+    }
+
+    /// Add the events and time zones of another calendar
+    pub fn merge(&mut self, other: Calendar) {
+        self.events.extend(other.events);
+        self.zones.extend(other.zones);
+    }
+
+    /// The instances of all events that overlap the time from `from` to
+    /// `to`, ordered by start
+    pub fn instances(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<EventInstance<'_>> {
+        let mut overrides: BTreeMap<&str, Vec<DateTime<Utc>>> = BTreeMap::new();
+        for event in &self.events {
+            if let Some(time) = event
+                .recurrence_id
+                .as_ref()
+                .and_then(|time| self.to_utc(time))
+            {
+                overrides.entry(&event.uid).or_default().push(time);
+            }
+        }
+
+        // No zone is a day or more off UTC, so occurrences whose wall clock
+        // end is a day before `from` are over and need no converting
+        let earliest = from.naive_utc() - Duration::days(1);
+
+        let mut instances = Vec::new();
+        for event in &self.events {
+            let Some(start) = &event.start else {
+                continue;
+            };
+            if event.cancelled {
+                continue;
+            }
+            let overridden = overrides.get(event.uid.as_str());
+            let exceptions: Vec<DateTime<Utc>> = event
+                .exceptions
+                .iter()
+                .filter_map(|time| self.to_utc(time))
+                .collect();
+
+            let length = self.length(event, start);
+            let rule = event
+                .rule
+                .as_ref()
+                .filter(|_| event.recurrence_id.is_none())
+                .map(|rule| self.resolve_until(rule, start));
+            let starts: Box<dyn Iterator<Item = NaiveDateTime>> = match &rule {
+                Some(rule) => Box::new(rule.occurrences(start.local).take(MAX_OCCURRENCES)),
+                None => Box::new(std::iter::once(start.local)),
+            };
+
+            for local in starts {
+                if local + length < earliest {
+                    continue;
+                }
+                let time = |local| IcsTime {
+                    local,
+                    ..start.clone()
+                };
+                let (Some(instance_start), Some(instance_end)) = (
+                    self.to_utc(&time(local)),
+                    self.to_utc(&time(local + length)),
+                ) else {
+                    continue;
+                };
+                if instance_start >= to {
+                    break;
+                }
+                let replaced = event.recurrence_id.is_none()
+                    && overridden.is_some_and(|times| times.contains(&instance_start));
+                if instance_end > from && !exceptions.contains(&instance_start) && !replaced {
+                    instances.push(EventInstance {
+                        event,
+                        start: instance_start,
+                        end: instance_end,
+                        all_day: start.all_day,
+                    });
+                }
+            }
+        }
+
+        instances.sort_by_key(|instance| instance.start);
+        instances
+    }
+
+    /// Length of an event's instances in wall clock time
+    fn length(&self, event: &CalendarEvent, start: &IcsTime) -> Duration {
+        if let Some(end) = &event.end {
+            // Convert the end to the start's zone in case they differ
+            match (self.to_utc(start), self.to_utc(end)) {
+                (Some(start_utc), Some(end_utc)) if end.zone != start.zone => end_utc - start_utc,
+                _ => end.local - start.local,
+            }
+        } else if let Some(duration) = event.duration {
+            duration
+        } else if start.all_day {
+            Duration::days(1)
+        } else {
+            Duration::zero()
+        }
+    }
+
+    /// A copy of a rule with a UTC `UNTIL` moved into the event's zone
+    fn resolve_until(&self, rule: &RecurrenceRule, start: &IcsTime) -> RecurrenceRule {
+        let mut rule = rule.clone();
+        if let (true, Some(until)) = (rule.until_is_utc, rule.until) {
+            let until = Utc.from_utc_datetime(&until);
+            rule.until = Some(match &start.zone {
+                Zone::Utc => until.naive_utc(),
+                Zone::Floating => until.with_timezone(&Local).naive_local(),
+                Zone::Named(name) => match self.zones.get(name) {
+                    Some(zone) => {
+                        until.naive_utc()
+                            + Duration::seconds(zone.offset_at(until.naive_utc()).into())
+                    }
+                    None => until.with_timezone(&Local).naive_local(),
+                },
+            });
+            rule.until_is_utc = false;
+        }
+        rule
+    }
+
+    /// The UTC time of a value, resolving its zone
+    pub fn to_utc(&self, time: &IcsTime) -> Option<DateTime<Utc>> {
+        let local = |naive: &NaiveDateTime| {
+            Local
+                .from_local_datetime(naive)
+                .earliest()
+                .map(|time| time.with_timezone(&Utc))
+        };
+        match &time.zone {
+            Zone::Utc => Some(Utc.from_utc_datetime(&time.local)),
+            Zone::Floating => local(&time.local),
+            Zone::Named(name) => match self.zones.get(name) {
+                Some(zone) => Some(Utc.from_utc_datetime(
+                    &(time.local - Duration::seconds(zone.offset_at(time.local).into())),
+                )),
+                None => local(&time.local),
+            },
+        }
+    }
+}
+
+impl ZoneDefinition {
+    /// Offset from UTC in seconds at a wall clock time in the zone
+    fn offset_at(&self, local: NaiveDateTime) -> i32 {
+        let mut latest: Option<(NaiveDateTime, i32)> = None;
+        for observance in &self.observances {
+            let onset = match &observance.rule {
+                Some(rule) => rule
+                    .occurrences(observance.start)
+                    .take_while(|onset| *onset <= local)
+                    .last(),
+                None => Some(observance.start).filter(|onset| *onset <= local),
+            };
+            if let Some(onset) = onset {
+                if latest.is_none_or(|(latest, _)| onset > latest) {
+                    latest = Some((onset, observance.offset_to));
+                }
+            }
+        }
+
+        latest.map(|(_, offset)| offset).unwrap_or_else(|| {
+            self.observances
+                .iter()
+                .min_by_key(|observance| observance.start)
+                .map_or(0, |observance| observance.offset_from)
+        })
+    }
+}
+
+impl CalendarEvent {
+    /// Record a property of the event
+    fn set_property(&mut self, property: &Property) {
+        match property.name.as_str() {
+            "UID" => self.uid = property.value.clone(),
+            "SUMMARY" => self.summary = unescape(&property.value),
+            "DESCRIPTION" => self.description = unescape(&property.value),
+            "LOCATION" => self.location = unescape(&property.value),
+            "URL" | "X-GOOGLE-CONFERENCE" | "CONFERENCE" => {
+                self.url.push_str(&property.value);
+                self.url.push('\n');
+            }
+            "DTSTART" => self.start = property.time(),
+            "DTEND" => self.end = property.time(),
+            "DURATION" => self.duration = parse_duration(&property.value),
+            "RRULE" => match RecurrenceRule::parse(&property.value) {
+                Ok(rule) => self.rule = Some(rule),
+                Err(e) => eprintln!("Ignoring recurrence of event {}: {e}", self.uid),
+            },
+            "EXDATE" => self.exceptions.extend(property.times()),
+            "RECURRENCE-ID" => self.recurrence_id = property.time(),
+            "STATUS" => self.cancelled = property.value.eq_ignore_ascii_case("CANCELLED"),
+            _ => {}
+        }
+    }
+
+    /// All text of the event, for matching against filters
+    pub fn text(&self) -> String {
+        [
+            self.summary.as_str(),
+            &self.description,
+            &self.location,
+            &self.url,
+        ]
+        .join("\n")
+    }
+}
+
+/// A content line split into its parts
+struct Property {
+    /// Upper-cased property name
+    name: String,
+    /// Parameters, with upper-cased names
+    parameters: BTreeMap<String, String>,
+    /// Raw value
+    value: String,
+}
+
+impl Property {
+    fn parse(line: &str) -> Option<Self> {
+        // The value starts at the first colon outside a quoted parameter
+        let mut quoted = false;
+        let split = line.char_indices().find_map(|(index, c)| match c {
+            '"' => {
+                quoted = !quoted;
+                None
+            }
+            ':' if !quoted => Some(index),
+            _ => None,
+        })?;
+        let (head, value) = (&line[..split], &line[split + 1..]);
+
+        let mut parts = head.split(';');
+        let name = parts.next()?.to_ascii_uppercase();
+        let parameters = parts
+            .filter_map(|parameter| parameter.split_once('='))
+            .map(|(name, value)| {
+                (
+                    name.to_ascii_uppercase(),
+                    value.trim_matches('"').to_string(),
+                )
+            })
+            .collect();
+
+        Some(Self {
+            name,
+            parameters,
+            value: value.to_string(),
+        })
+    }
+
+    /// The value as a single date or date-time
+    fn time(&self) -> Option<IcsTime> {
+        self.times().into_iter().next()
+    }
+
+    /// The value as a list of dates or date-times
+    fn times(&self) -> Vec<IcsTime> {
+        let tzid = self.parameters.get("TZID");
+        self.value
+            .split(',')
+            .filter_map(|value| {
+                let value = value.trim();
+                if let Some(utc) = value.strip_suffix('Z') {
+                    let local = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+                    return Some(IcsTime {
+                        local,
+                        zone: Zone::Utc,
+                        all_day: false,
+                    });
+                }
+
+                let zone = tzid.map_or(Zone::Floating, |tzid| Zone::Named(tzid.clone()));
+                if let Ok(local) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+                    return Some(IcsTime {
+                        local,
+                        zone,
+                        all_day: false,
+                    });
+                }
+                let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+                Some(IcsTime {
+                    local: date.and_hms_opt(0, 0, 0)?,
+                    zone: Zone::Floating,
+                    all_day: true,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Join folded lines, which continue on lines starting with whitespace
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.trim_end_matches('\r');
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ if line.is_empty() => {}
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// Undo the escaping of a text value
+fn unescape(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => text.push('\n'),
+            Some(escaped) => text.push(escaped),
+            None => {}
+        }
+    }
+    text
+}
+
+/// Parse a UTC offset such as `+0130` into seconds
+fn parse_offset(value: &str) -> Result<i32, String> {
+    let invalid = || format!("Invalid UTC offset \"{value}\"");
+    let (sign, digits) = match value.split_at_checked(1) {
+        Some(("+", digits)) => (1, digits),
+        Some(("-", digits)) => (-1, digits),
+        _ => return Err(invalid()),
+    };
+    if digits.len() < 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let number = |range: std::ops::Range<usize>| digits.get(range).map_or(Ok(0), str::parse);
+    let hours: i32 = number(0..2).map_err(|_| invalid())?;
+    let minutes: i32 = number(2..4).map_err(|_| invalid())?;
+    let seconds: i32 = number(4..6).map_err(|_| invalid())?;
+    Ok(sign * (hours * 3600 + minutes * 60 + seconds))
+}
+
+/// Parse a duration such as `PT1H30M` or `-P1W`
+fn parse_duration(value: &str) -> Option<Duration> {
+    let (sign, rest) = match value.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let rest = rest.strip_prefix('P')?;
+
+    let mut seconds = 0i64;
+    let mut number = String::new();
+    for c in rest.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => {}
+            unit => {
+                let value: i64 = number.parse().ok()?;
+                number.clear();
+                seconds += value
+                    * match unit {
+                        'W' => 7 * 86_400,
+                        'D' => 86_400,
+                        'H' => 3600,
+                        'M' => 60,
+                        'S' => 1,
+                        _ => return None,
+                    };
+            }
+        }
+    }
+    number.is_empty().then(|| Duration::seconds(sign * seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CALENDAR: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VTIMEZONE\r
+TZID:Europe/Berlin\r
+BEGIN:DAYLIGHT\r
+TZOFFSETFROM:+0100\r
+TZOFFSETTO:+0200\r
+DTSTART:19700329T020000\r
+RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU\r
+END:DAYLIGHT\r
+BEGIN:STANDARD\r
+TZOFFSETFROM:+0200\r
+TZOFFSETTO:+0100\r
+DTSTART:19701025T030000\r
+RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU\r
+END:STANDARD\r
+END:VTIMEZONE\r
+BEGIN:VEVENT\r
+UID:standup\r
+SUMMARY:Stand-up\\, daily\r
+DESCRIPTION:Join at https://meet.google.com/abc-defg-hij\\nor dial in\r
+DTSTART;TZID=Europe/Berlin:20240318T093000\r
+DTEND;TZID=Europe/Berlin:20240318T094500\r
+RRULE:FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR\r
+EXDATE;TZID=Europe/Berlin:20240402T093000\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:standup\r
+RECURRENCE-ID;TZID=Europe/Berlin:20240403T093000\r
+SUMMARY:Stand-up (moved)\r
+DTSTART;TZID=Europe/Berlin:20240403T110000\r
+DURATION:PT15M\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:holiday\r
+SUMMARY:Holiday\r
+DTSTART;VALUE=DATE:20240401\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:cancelled\r
+SUMMARY:Cancelled meeting\r
+STATUS:CANCELLED\r
+DTSTART:20240402T120000Z\r
+DTEND:20240402T130000Z\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_instances() {
+        let calendar = Calendar::parse(CALENDAR).unwrap();
+        let event = &calendar.events[0];
+        assert_eq!(event.summary, "Stand-up, daily");
+        assert!(event
+            .text()
+            .contains("meet.google.com/abc-defg-hij\nor dial in"));
+
+        // Friday before and Monday after the switch to summer time
+        let instances = calendar.instances(utc(2024, 3, 29, 0, 0), utc(2024, 4, 2, 0, 0));
+        let times: Vec<_> = instances
+            .iter()
+            .filter(|instance| !instance.all_day)
+            .map(|instance| (instance.start, instance.end))
+            .collect();
+        assert_eq!(
+            times,
+            vec![
+                (utc(2024, 3, 29, 8, 30), utc(2024, 3, 29, 8, 45)),
+                (utc(2024, 4, 1, 7, 30), utc(2024, 4, 1, 7, 45)),
+            ]
+        );
+        let holiday = instances.iter().find(|instance| instance.all_day).unwrap();
+        assert_eq!(holiday.end - holiday.start, Duration::days(1));
+
+        // Tuesday is excluded, Wednesday moved and the cancelled event left out
+        let instances = calendar.instances(utc(2024, 4, 2, 0, 0), utc(2024, 4, 4, 0, 0));
+        let starts: Vec<_> = instances
+            .iter()
+            .map(|instance| (instance.event.summary.as_str(), instance.start))
+            .collect();
+        assert_eq!(starts, vec![("Stand-up (moved)", utc(2024, 4, 3, 9, 0))]);
+    }
+
+    #[test]
+    fn test_values() {
+        assert_eq!(parse_offset("+0130"), Ok(5400));
+        assert_eq!(parse_offset("-0500"), Ok(-18_000));
+        assert!(parse_offset("0100").is_err());
+
+        assert_eq!(parse_duration("PT1H30M"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("P1W"), Some(Duration::weeks(1)));
+        assert_eq!(parse_duration("-PT15M"), Some(Duration::minutes(-15)));
+        assert_eq!(parse_duration("PT"), Some(Duration::zero()));
+        assert_eq!(parse_duration("P1X"), None);
+
+        assert_eq!(
+            unfold("SUMMARY:A long\r\n  title\r\nUID:1\r\n"),
+            vec!["SUMMARY:A long title", "UID:1"]
+        );
+
+        let property =
+            Property::parse(r#"ATTENDEE;CN="Doe: Jane":mailto:jane@example.com"#).unwrap();
+        assert_eq!(property.parameters["CN"], "Doe: Jane");
+        assert_eq!(property.value, "mailto:jane@example.com");
+
+        assert!(Calendar::parse("hello").is_err());
+    }
+
+    #[test]
+    fn test_malformed_zone_is_skipped() {
+        let text = CALENDAR.replacen("TZOFFSETTO:+0200", "TZOFFSETTO:2 hours", 1);
+        let calendar = Calendar::parse(&text).unwrap();
+        assert!(calendar.zones.is_empty());
+
+        // Events in the zone fall back to local time
+        let standup = &calendar.events[0];
+        let start = standup.start.as_ref().unwrap();
+        assert_eq!(
+            calendar.to_utc(start),
+            Local
+                .from_local_datetime(&start.local)
+                .earliest()
+                .map(|time| time.with_timezone(&Utc))
+        );
+    }
+}
@@ -0,0 +1,718 @@
+//! Meeting pre-warm
+//!
+//! Watches a local iCalendar file, or a directory of them as kept by
+//! vdirsyncer, and switches lights on a few minutes before meetings that
+//! match the configured filters. Devices are put back once the meeting is
+//! over. If a camera session is still going by then, or when the watcher
+//! stops, the binding applied on the camera signal is left to put them back
+//! when the session ends. Nothing is changed for a meeting that starts during
+//! a camera session.
+
+pub mod ics;
+pub mod rrule;
+
+use crate::camera_monitor::strategies::create_device_set_selector;
+use crate::config::{CalendarConfig, CAMERA_SOURCE_ID};
+use crate::device::sink::DeviceSink;
+use crate::device::DeviceState;
+use crate::task::BackgroundTask;
+use crate::triggers::TriggerEngineState;
+use chrono::{DateTime, Local, Utc};
+use ics::Calendar;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::sync::{watch, Notify};
+
+/// Longest the watcher sleeps before checking the files and the wall clock
+/// again
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How far ahead meetings are looked for
+const LOOKAHEAD: chrono::Duration = chrono::Duration::hours(24);
+
+/// How far past the lookahead event instances are expanded, so the
+/// expansion is reused while the lookahead moves through it
+const EXPANSION_MARGIN: chrono::Duration = chrono::Duration::hours(24);
+
+/// Parts of the links video call services put in invitations
+const VIDEO_LINK_HOSTS: [&str; 10] = [
+    "zoom.us/",
+    "meet.google.com/",
+    "teams.microsoft.com/",
+    "teams.live.com/",
+    "webex.com/",
+    "whereby.com/",
+    "meet.jit.si/",
+    "chime.aws/",
+    "gotomeeting.com/",
+    "bluejeans.com/",
+];
+
+/// Global state type for the calendar watcher
+pub type CalendarWatcherState = Arc<tokio::sync::Mutex<CalendarWatcher>>;
+
+/// A meeting lights are switched on for
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Meeting {
+    /// Title of the meeting
+    pub summary: String,
+    /// Start
+    pub start: DateTime<Local>,
+    /// End
+    pub end: DateTime<Local>,
+}
+
+/// State of the calendar watcher, as reported to the frontend
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CalendarStatus {
+    /// Whether pre-warming is enabled
+    pub enabled: bool,
+    /// Why the calendar could not be read, if it could not
+    pub error: Option<String>,
+    /// Meeting lights are currently on for
+    pub warming: Option<Meeting>,
+    /// Matching meetings in the next day, including the current one
+    pub upcoming: Vec<Meeting>,
+}
+
+/// State shared between the watcher and its task
+#[derive(Default)]
+struct Shared {
+    config: CalendarConfig,
+    error: Option<String>,
+    warming: Option<Meeting>,
+    upcoming: Vec<Meeting>,
+}
+
+/// Watcher switching lights on ahead of meetings
+pub struct CalendarWatcher {
+    /// Sink used to read and change devices
+    sink: Arc<dyn DeviceSink>,
+    /// Trigger engine asked whether a camera session is active
+    trigger_engine: TriggerEngineState,
+    /// State shared with the watcher task
+    shared: Arc<Mutex<Shared>>,
+    /// Wakes the watcher task when the configuration changes
+    changed: Arc<Notify>,
+    /// Watcher task, while running
+    task: Option<BackgroundTask>,
+}
+
+impl CalendarWatcher {
+    /// Create a new, stopped calendar watcher
+    pub fn new(sink: Arc<dyn DeviceSink>, trigger_engine: TriggerEngineState) -> Self {
+        Self {
+            sink,
+            trigger_engine,
+            shared: Arc::new(Mutex::new(Shared::default())),
+            changed: Arc::new(Notify::new()),
+            task: None,
+        }
+    }
+
+    /// Start watching with the given configuration
+    pub fn start(&mut self, config: CalendarConfig) {
+        if self.is_running() {
+            return;
+        }
+        self.set_config(config);
+
+        let task = WatcherTask::new(
+            self.sink.clone(),
+            self.trigger_engine.clone(),
+            self.shared.clone(),
+        );
+        self.task = Some(BackgroundTask::spawn(|stop| {
+            task.run(self.changed.clone(), stop)
+        }));
+    }
+
+    /// Stop watching, putting back lights switched on for a meeting or
+    /// leaving that to the camera session in progress
+    pub async fn stop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.stop().await;
+        }
+    }
+
+    /// Check if the watcher is running
+    pub fn is_running(&self) -> bool {
+        self.task.as_ref().is_some_and(BackgroundTask::is_running)
+    }
+
+    /// Replace the configuration
+    pub fn set_config(&self, config: CalendarConfig) {
+        self.shared.lock().unwrap().config = config;
+        self.changed.notify_one();
+    }
+
+    /// Get the current state of the watcher
+    pub fn status(&self) -> CalendarStatus {
+        let shared = self.shared.lock().unwrap();
+        CalendarStatus {
+            enabled: shared.config.enabled,
+            error: shared.error.clone(),
+            warming: shared.warming.clone(),
+            upcoming: shared.upcoming.clone(),
+        }
+    }
+}
+
+/// Lights switched on for a meeting
+struct Warmed {
+    /// Meeting the lights are on for
+    meeting: Meeting,
+    /// States of the devices from before
+    snapshots: Vec<(String, DeviceState)>,
+}
+
+/// State owned by the watcher task
+struct WatcherTask {
+    /// Sink used to read and change devices
+    sink: Arc<dyn DeviceSink>,
+    /// Trigger engine asked whether a camera session is active, and left to
+    /// restore lights during one
+    trigger_engine: TriggerEngineState,
+    /// State shared with the watcher
+    shared: Arc<Mutex<Shared>>,
+    /// Events of the calendar files
+    calendar: Calendar,
+    /// Timed instances of the events, expanded on first use after a reload
+    expansion: Option<Expansion>,
+    /// Path and modification times of the files last read
+    loaded: Option<(PathBuf, Vec<(PathBuf, SystemTime)>)>,
+    /// Lights currently switched on for a meeting
+    warmed: Option<Warmed>,
+}
+
+impl WatcherTask {
+    fn new(
+        sink: Arc<dyn DeviceSink>,
+        trigger_engine: TriggerEngineState,
+        shared: Arc<Mutex<Shared>>,
+    ) -> Self {
+        Self {
+            sink,
+            trigger_engine,
+            shared,
+            calendar: Calendar::default(),
+            expansion: None,
+            loaded: None,
+            warmed: None,
+        }
+    }
+
+    /// Follow the calendar until stopped
+    async fn run(mut self, changed: Arc<Notify>, mut stop: watch::Receiver<bool>) {
+        loop {
+            let config = self.shared.lock().unwrap().config.clone();
+            self.reload(&config);
+            let now = Local::now();
+            let next_change = self.update(&config, now).await;
+
+            // The monotonic clock may stand still during suspend, so never
+            // sleep long without looking at the wall clock
+            let sleep = next_change
+                .and_then(|next_change| (next_change - now).to_std().ok())
+                .map_or(CHECK_INTERVAL, |until| until.min(CHECK_INTERVAL));
+
+            tokio::select! {
+                _ = tokio::time::sleep(sleep) => {}
+                _ = changed.notified() => {}
+                _ = stop.changed() => break,
+            }
+        }
+
+        self.release().await;
+    }
+
+    /// Read the calendar files again if they changed
+    fn reload(&mut self, config: &CalendarConfig) {
+        if !config.enabled || config.path.as_os_str().is_empty() {
+            self.calendar = Calendar::default();
+            self.expansion = None;
+            self.loaded = None;
+            self.shared.lock().unwrap().error = None;
+            return;
+        }
+
+        let files = match calendar_files(&config.path) {
+            Ok(files) => files,
+            Err(e) => {
+                self.calendar = Calendar::default();
+                self.expansion = None;
+                self.loaded = None;
+                self.shared.lock().unwrap().error =
+                    Some(format!("Failed to read {}: {e}", config.path.display()));
+                return;
+            }
+        };
+        if self
+            .loaded
+            .as_ref()
+            .is_some_and(|loaded| loaded.0 == config.path && loaded.1 == files)
+        {
+            return;
+        }
+
+        let mut calendar = Calendar::default();
+        let mut error = None;
+        for (path, _) in &files {
+            match std::fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| Calendar::parse(&text))
+            {
+                Ok(file) => calendar.merge(file),
+                Err(e) => {
+                    eprintln!("Failed to read calendar {}: {e}", path.display());
+                    error.get_or_insert(format!("Failed to read {}: {e}", path.display()));
+                }
+            }
+        }
+        println!(
+            "Loaded {} calendar events from {}",
+            calendar.events.len(),
+            config.path.display()
+        );
+
+        self.calendar = calendar;
+        self.expansion = None;
+        self.loaded = Some((config.path.clone(), files));
+        self.shared.lock().unwrap().error = error;
+    }
+
+    /// Switch lights on or back as meetings come and go, returning the next
+    /// time that happens
+    async fn update(
+        &mut self,
+        config: &CalendarConfig,
+        now: DateTime<Local>,
+    ) -> Option<DateTime<Local>> {
+        let meetings = if config.enabled {
+            let from = now.with_timezone(&Utc);
+            let expansion = match &mut self.expansion {
+                Some(expansion) if expansion.covers(from) => expansion,
+                expansion => expansion.insert(Expansion::new(&self.calendar, from)),
+            };
+            matching_meetings(expansion, config, now)
+        } else {
+            Vec::new()
+        };
+        let lead = chrono::Duration::minutes(config.lead_minutes.into());
+        let current = meetings
+            .iter()
+            .find(|meeting| meeting.start - lead <= now && now < meeting.end);
+        let camera_active = self
+            .trigger_engine
+            .lock()
+            .await
+            .signal_is_true(CAMERA_SOURCE_ID)
+            .await;
+
+        match (&mut self.warmed, current) {
+            (None, Some(meeting)) if !camera_active => {
+                println!("Switching lights on for meeting {}", meeting.summary);
+                self.warmed = Some(Warmed {
+                    meeting: meeting.clone(),
+                    snapshots: self.warm(config).await,
+                });
+            }
+            // Back-to-back meetings keep the lights on
+            (Some(warmed), Some(meeting)) => warmed.meeting = meeting.clone(),
+            (Some(_), None) => self.release().await,
+            _ => {}
+        }
+
+        let next_change = meetings
+            .iter()
+            .flat_map(|meeting| [meeting.start - lead, meeting.end])
+            .filter(|time| *time > now)
+            .min();
+
+        let mut shared = self.shared.lock().unwrap();
+        shared.warming = self.warmed.as_ref().map(|warmed| warmed.meeting.clone());
+        shared.upcoming = meetings;
+        next_change
+    }
+
+    /// Apply the configured state, returning the states of the devices it
+    /// changed
+    async fn warm(&self, config: &CalendarConfig) -> Vec<(String, DeviceState)> {
+        let devices = match self.sink.get_all_devices().await {
+            Ok(devices) => devices,
+            Err(e) => {
+                eprintln!("Failed to get devices for meeting: {e}");
+                return Vec::new();
+            }
+        };
+        let selector = create_device_set_selector(&config.devices);
+
+        let mut snapshots = Vec::new();
+        for device in devices {
+            if !selector.should_control_device(&device) {
+                continue;
+            }
+            match self
+                .sink
+                .apply_lighting_state(&device.serial_number, &config.action)
+                .await
+            {
+                Ok(()) => snapshots.push((device.serial_number.clone(), device.state())),
+                Err(e) => eprintln!(
+                    "Failed to switch on {} for meeting: {e}",
+                    device.serial_number
+                ),
+            }
+        }
+        snapshots
+    }
+
+    /// Put back the devices switched on for a meeting, or leave that to the
+    /// camera session in progress
+    async fn release(&mut self) {
+        let Some(warmed) = self.warmed.take() else {
+            return;
+        };
+
+        let snapshots = match self
+            .trigger_engine
+            .lock()
+            .await
+            .restore_on_release(CAMERA_SOURCE_ID, warmed.snapshots)
+            .await
+        {
+            Ok(()) => {
+                println!(
+                    "Meeting {} is over, lights restored when the camera session ends",
+                    warmed.meeting.summary
+                );
+                return;
+            }
+            Err(snapshots) => snapshots,
+        };
+
+        println!(
+            "Meeting {} is over, restoring lights",
+            warmed.meeting.summary
+        );
+        for (serial_number, snapshot) in snapshots {
+            if let Err(e) = self
+                .sink
+                .apply_device_state(&serial_number, &snapshot)
+                .await
+            {
+                eprintln!("Failed to restore state of {serial_number}: {e}");
+            }
+        }
+    }
+}
+
+/// The `.ics` files at a path with their modification times, in order
+fn calendar_files(path: &Path) -> std::io::Result<Vec<(PathBuf, SystemTime)>> {
+    let mut paths = if path.is_dir() {
+        std::fs::read_dir(path)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension.eq_ignore_ascii_case("ics"))
+            })
+            .collect()
+    } else {
+        vec![path.to_path_buf()]
+    };
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let modified = std::fs::metadata(&path)?.modified()?;
+            Ok((path, modified))
+        })
+        .collect()
+}
+
+/// Timed instances of the calendar's events over a stretch of time
+struct Expansion {
+    /// Start of the stretch
+    from: DateTime<Utc>,
+    /// End of the stretch
+    to: DateTime<Utc>,
+    /// Instances overlapping the stretch, ordered by start
+    instances: Vec<Occurrence>,
+}
+
+/// An instance of a timed event
+struct Occurrence {
+    /// Title of the event
+    summary: String,
+    /// All text of the event, lower-cased for matching against filters
+    text: String,
+    /// Start
+    start: DateTime<Utc>,
+    /// End
+    end: DateTime<Utc>,
+}
+
+impl Expansion {
+    /// Expand the instances from `from` to the end of the lookahead and the
+    /// margin after it
+    fn new(calendar: &Calendar, from: DateTime<Utc>) -> Self {
+        let to = from + LOOKAHEAD + EXPANSION_MARGIN;
+        let instances = calendar
+            .instances(from, to)
+            .into_iter()
+            .filter(|instance| !instance.all_day)
+            .map(|instance| Occurrence {
+                summary: instance.event.summary.clone(),
+                text: instance.event.text().to_lowercase(),
+                start: instance.start,
+                end: instance.end,
+            })
+            .collect();
+        Self {
+            from,
+            to,
+            instances,
+        }
+    }
+
+    /// Whether the expansion holds every instance within the lookahead from
+    /// `from`
+    fn covers(&self, from: DateTime<Utc>) -> bool {
+        self.from <= from && from + LOOKAHEAD <= self.to
+    }
+}
+
+/// Timed meetings matching the filters that are going on or start within
+/// the next day
+fn matching_meetings(
+    expansion: &Expansion,
+    config: &CalendarConfig,
+    now: DateTime<Local>,
+) -> Vec<Meeting> {
+    let from = now.with_timezone(&Utc);
+    expansion
+        .instances
+        .iter()
+        .filter(|instance| instance.end > from && instance.start < from + LOOKAHEAD)
+        .filter(|instance| matches_filters(&instance.text, config))
+        .map(|instance| Meeting {
+            summary: instance.summary.clone(),
+            start: instance.start.with_timezone(&Local),
+            end: instance.end.with_timezone(&Local),
+        })
+        .collect()
+}
+
+/// Whether an event's lower-cased text passes the video link and keyword
+/// filters
+fn matches_filters(text: &str, config: &CalendarConfig) -> bool {
+    let has_video_link = VIDEO_LINK_HOSTS.iter().any(|host| text.contains(host));
+    let has_keyword = config.keywords.is_empty()
+        || config
+            .keywords
+            .iter()
+            .any(|keyword| text.contains(&keyword.to_lowercase()));
+    (has_video_link || !config.require_video_link) && has_keyword
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera_monitor::source::ScriptedEventSource;
+    use crate::camera_monitor::CameraMonitor;
+    use crate::config::AutoToggleConfig;
+    use crate::device::sink::RecordingDeviceSink;
+    use crate::test_support::{test_monitor, KEY_LIGHT};
+    use crate::triggers::camera::CameraTriggerSource;
+    use crate::triggers::TriggerEngine;
+    use chrono::TimeZone;
+
+    const CALENDAR: &str = "BEGIN:VCALENDAR
+BEGIN:VEVENT
+UID:call
+SUMMARY:Customer call
+LOCATION:https://example.zoom.us/j/123456
+DTSTART:20240607T100000Z
+DTEND:20240607T103000Z
+END:VEVENT
+BEGIN:VEVENT
+UID:lunch
+SUMMARY:Lunch
+DTSTART:20240607T120000Z
+DTEND:20240607T130000Z
+END:VEVENT
+BEGIN:VEVENT
+UID:review
+SUMMARY:Design review
+DESCRIPTION:https://meet.google.com/abc-defg-hij
+DTSTART:20240607T140000Z
+DTEND:20240607T150000Z
+END:VEVENT
+END:VCALENDAR
+";
+
+    fn at(hour: u32, minute: u32) -> DateTime<Local> {
+        Utc.with_ymd_and_hms(2024, 6, 7, hour, minute, 0)
+            .unwrap()
+            .with_timezone(&Local)
+    }
+
+    fn config() -> CalendarConfig {
+        CalendarConfig {
+            enabled: true,
+            ..CalendarConfig::default()
+        }
+    }
+
+    fn auto_toggle() -> AutoToggleConfig {
+        AutoToggleConfig {
+            enabled: true,
+            ..AutoToggleConfig::default()
+        }
+    }
+
+    /// Start an engine running auto-toggle on the sessions of `monitor`
+    async fn start_engine(
+        sink: Arc<RecordingDeviceSink>,
+        monitor: &CameraMonitor,
+    ) -> TriggerEngineState {
+        let mut engine = TriggerEngine::new(sink);
+        engine
+            .start(
+                vec![auto_toggle().binding()],
+                Vec::new(),
+                vec![Box::new(CameraTriggerSource::new(monitor))],
+            )
+            .await;
+        Arc::new(tokio::sync::Mutex::new(engine))
+    }
+
+    #[test]
+    fn test_filters() {
+        let calendar = Calendar::parse(CALENDAR).unwrap();
+        let expansion = Expansion::new(&calendar, at(9, 0).with_timezone(&Utc));
+        let summaries = |config: &CalendarConfig| -> Vec<String> {
+            matching_meetings(&expansion, config, at(9, 0))
+                .into_iter()
+                .map(|meeting| meeting.summary)
+                .collect()
+        };
+
+        assert_eq!(summaries(&config()), vec!["Customer call", "Design review"]);
+        assert_eq!(
+            summaries(&CalendarConfig {
+                require_video_link: false,
+                ..config()
+            }),
+            vec!["Customer call", "Lunch", "Design review"]
+        );
+        assert_eq!(
+            summaries(&CalendarConfig {
+                keywords: vec!["REVIEW".to_string()],
+                ..config()
+            }),
+            vec!["Design review"]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_warms_and_restores() {
+        let sink = Arc::new(RecordingDeviceSink::new(&[KEY_LIGHT]));
+        let initial = sink.device_state(KEY_LIGHT);
+        let mut monitor = test_monitor();
+        let engine = start_engine(sink.clone(), &monitor).await;
+        let mut task = WatcherTask::new(
+            sink.clone(),
+            engine,
+            Arc::new(Mutex::new(Shared::default())),
+        );
+        task.calendar = Calendar::parse(CALENDAR).unwrap();
+        let config = config();
+
+        assert_eq!(task.update(&config, at(9, 50)).await, Some(at(9, 55)));
+        assert_eq!(sink.device_state(KEY_LIGHT), initial);
+
+        let expanded_from = task.expansion.as_ref().unwrap().from;
+
+        assert_eq!(task.update(&config, at(9, 56)).await, Some(at(10, 30)));
+        assert!(sink.device_state(KEY_LIGHT).is_on);
+        // The instances expanded at the previous check are reused
+        assert_eq!(task.expansion.as_ref().unwrap().from, expanded_from);
+        assert_eq!(
+            task.shared
+                .lock()
+                .unwrap()
+                .warming
+                .as_ref()
+                .unwrap()
+                .summary,
+            "Customer call"
+        );
+
+        task.update(&config, at(10, 31)).await;
+        assert_eq!(sink.device_state(KEY_LIGHT), initial);
+
+        // Lights stay on while the call runs over
+        task.update(&config, at(13, 56)).await;
+        monitor
+            .start_monitoring_with_source(
+                auto_toggle(),
+                Box::new(ScriptedEventSource::new(Vec::new()).with_open_count(1)),
+            )
+            .unwrap();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        task.update(&config, at(15, 1)).await;
+        assert!(sink.device_state(KEY_LIGHT).is_on);
+
+        // Auto-toggle puts back the state from before the meeting
+        monitor.stop_monitoring().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        task.update(&config, at(15, 2)).await;
+        assert_eq!(sink.device_state(KEY_LIGHT), initial);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stop_during_camera_session() {
+        let sink = Arc::new(RecordingDeviceSink::new(&[KEY_LIGHT]));
+        let initial = sink.device_state(KEY_LIGHT);
+        let mut monitor = test_monitor();
+        let engine = start_engine(sink.clone(), &monitor).await;
+        let mut task = WatcherTask::new(
+            sink.clone(),
+            engine,
+            Arc::new(Mutex::new(Shared {
+                config: config(),
+                ..Shared::default()
+            })),
+        );
+        task.calendar = Calendar::parse(CALENDAR).unwrap();
+
+        task.update(&config(), at(9, 56)).await;
+        assert!(sink.device_state(KEY_LIGHT).is_on);
+        // The session starts from the lights switched on for the meeting
+        monitor
+            .start_monitoring_with_source(
+                auto_toggle(),
+                Box::new(ScriptedEventSource::new(Vec::new()).with_open_count(1)),
+            )
+            .unwrap();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert!(monitor.is_session_active());
+
+        // Stopped before the camera monitor, as on shutdown
+        let (stop_tx, stop_rx) = watch::channel(false);
+        let handle = tokio::spawn(task.run(Arc::new(Notify::new()), stop_rx));
+        stop_tx.send(true).unwrap();
+        handle.await.unwrap();
+        assert!(sink.device_state(KEY_LIGHT).is_on);
+
+        monitor.stop_monitoring().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(sink.device_state(KEY_LIGHT), initial);
+    }
+}
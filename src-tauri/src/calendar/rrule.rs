@@ -0,0 +1,526 @@
+//! Recurrence rules
+//!
+//! Expands the `RRULE` property of iCalendar events (RFC 5545) for the
+//! daily, weekly, monthly and yearly frequencies with `INTERVAL`, `COUNT`,
+//! `UNTIL`, `BYMONTH`, `BYMONTHDAY`, `BYDAY` (with ordinals such as `-1FR`),
+//! `BYSETPOS` and `WKST`. Occurrences keep the time of day of the first one.
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Weekday};
+
+/// Most periods looked through before giving up on finding occurrences
+const MAX_PERIODS: u32 = 100_000;
+
+/// How often a rule repeats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// A parsed recurrence rule
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecurrenceRule {
+    /// How often the rule repeats
+    pub frequency: Frequency,
+    /// Number of periods between repeats
+    pub interval: u32,
+    /// Total number of occurrences, including the first
+    pub count: Option<u32>,
+    /// Last time an occurrence may start, in the event's local time
+    pub until: Option<NaiveDateTime>,
+    /// Whether `until` was given in UTC and still needs converting to the
+    /// event's local time
+    pub until_is_utc: bool,
+    /// Months (1-12) occurrences fall in
+    pub by_month: Vec<u32>,
+    /// Days of the month, negative counting from the end
+    pub by_month_day: Vec<i32>,
+    /// Weekdays, with an optional ordinal within the month or year
+    pub by_day: Vec<(Option<i32>, Weekday)>,
+    /// Positions to keep among the occurrences of each period
+    pub by_set_pos: Vec<i32>,
+    /// First day of the week
+    pub week_start: Weekday,
+}
+
+impl RecurrenceRule {
+    /// Parse the value of an `RRULE` property.
+    ///
+    /// Parts this module does not support, such as `BYHOUR`, are ignored.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut frequency = None;
+        let mut rule = Self {
+            frequency: Frequency::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            until_is_utc: false,
+            by_month: Vec::new(),
+            by_month_day: Vec::new(),
+            by_day: Vec::new(),
+            by_set_pos: Vec::new(),
+            week_start: Weekday::Mon,
+        };
+
+        for part in value.split(';').filter(|part| !part.is_empty()) {
+            let (name, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid recurrence rule part \"{part}\""))?;
+            let number = |text: &str| -> Result<i32, String> {
+                text.parse()
+                    .map_err(|_| format!("Invalid {name} value \"{text}\""))
+            };
+
+            match name.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return Err(format!("Unsupported frequency \"{value}\"")),
+                    })
+                }
+                "INTERVAL" => {
+                    rule.interval = u32::try_from(number(value)?)
+                        .ok()
+                        .filter(|interval| *interval > 0)
+                        .ok_or_else(|| format!("Invalid INTERVAL value \"{value}\""))?
+                }
+                "COUNT" => {
+                    rule.count = Some(
+                        u32::try_from(number(value)?)
+                            .map_err(|_| format!("Invalid COUNT value \"{value}\""))?,
+                    )
+                }
+                "UNTIL" => {
+                    rule.until_is_utc = value.ends_with('Z');
+                    rule.until = Some(parse_until(value.trim_end_matches('Z'))?);
+                }
+                "BYMONTH" => {
+                    for month in value.split(',') {
+                        rule.by_month.push(
+                            u32::try_from(number(month)?)
+                                .ok()
+                                .filter(|month| (1..=12).contains(month))
+                                .ok_or_else(|| format!("Invalid BYMONTH value \"{month}\""))?,
+                        );
+                    }
+                }
+                "BYMONTHDAY" => {
+                    for day in value.split(',') {
+                        rule.by_month_day.push(number(day)?);
+                    }
+                }
+                "BYDAY" => {
+                    for day in value.split(',') {
+                        let split = day.len().saturating_sub(2);
+                        let (ordinal, weekday) = day.split_at(split);
+                        let ordinal = match ordinal {
+                            "" => None,
+                            ordinal => Some(number(ordinal.trim_start_matches('+'))?),
+                        };
+                        rule.by_day.push((ordinal, parse_weekday(weekday)?));
+                    }
+                }
+                "BYSETPOS" => {
+                    for position in value.split(',') {
+                        rule.by_set_pos.push(number(position)?);
+                    }
+                }
+                "WKST" => rule.week_start = parse_weekday(value)?,
+                _ => {}
+            }
+        }
+
+        rule.frequency = frequency.ok_or("Recurrence rule has no FREQ")?;
+        Ok(rule)
+    }
+
+    /// The start times of the occurrences, in order, beginning with `start`
+    /// itself
+    pub fn occurrences(&self, start: NaiveDateTime) -> Occurrences<'_> {
+        Occurrences {
+            rule: self,
+            start,
+            period: 0,
+            pending: Vec::new(),
+            emitted: 0,
+            done: false,
+        }
+    }
+
+    /// The dates of the occurrences in one period, in order
+    fn period_dates(&self, start: NaiveDate, period: u32) -> Vec<NaiveDate> {
+        let steps = i64::from(period) * i64::from(self.interval);
+        let mut dates = match self.frequency {
+            Frequency::Daily => {
+                let date = start + Duration::days(steps);
+                let matches = (self.by_month_day.is_empty()
+                    || self
+                        .by_month_day
+                        .iter()
+                        .any(|day| month_day(date, *day) == Some(date)))
+                    && (self.by_day.is_empty()
+                        || self
+                            .by_day
+                            .iter()
+                            .any(|(_, weekday)| date.weekday() == *weekday));
+                if matches {
+                    vec![date]
+                } else {
+                    Vec::new()
+                }
+            }
+            Frequency::Weekly => {
+                let days_into_week = (7 + start.weekday().num_days_from_monday()
+                    - self.week_start.num_days_from_monday())
+                    % 7;
+                let week =
+                    start - Duration::days(i64::from(days_into_week)) + Duration::weeks(steps);
+                let weekdays: Vec<Weekday> = if self.by_day.is_empty() {
+                    vec![start.weekday()]
+                } else {
+                    self.by_day.iter().map(|(_, weekday)| *weekday).collect()
+                };
+                (0..7)
+                    .map(|offset| week + Duration::days(offset))
+                    .filter(|date| weekdays.contains(&date.weekday()))
+                    .collect()
+            }
+            Frequency::Monthly => {
+                let months = i64::from(start.year()) * 12 + i64::from(start.month0()) + steps;
+                let Some(first) = first_of_month(months) else {
+                    return Vec::new();
+                };
+                self.month_dates(first, start.day())
+            }
+            Frequency::Yearly => {
+                let Some(year) = i32::try_from(i64::from(start.year()) + steps).ok() else {
+                    return Vec::new();
+                };
+                if self.by_month.is_empty()
+                    && self.by_month_day.is_empty()
+                    && !self.by_day.is_empty()
+                {
+                    match (
+                        NaiveDate::from_ymd_opt(year, 1, 1),
+                        NaiveDate::from_ymd_opt(year, 12, 31),
+                    ) {
+                        (Some(first), Some(last)) => weekday_dates(&self.by_day, first, last),
+                        _ => Vec::new(),
+                    }
+                } else {
+                    let months: Vec<u32> = if !self.by_month.is_empty() {
+                        self.by_month.clone()
+                    } else if !self.by_month_day.is_empty() {
+                        (1..=12).collect()
+                    } else {
+                        vec![start.month()]
+                    };
+                    months
+                        .into_iter()
+                        .filter_map(|month| NaiveDate::from_ymd_opt(year, month, 1))
+                        .flat_map(|first| self.month_dates(first, start.day()))
+                        .collect()
+                }
+            }
+        };
+
+        if !self.by_month.is_empty() {
+            dates.retain(|date| self.by_month.contains(&date.month()));
+        }
+        dates.sort();
+        dates.dedup();
+
+        if self.by_set_pos.is_empty() {
+            return dates;
+        }
+        let mut selected: Vec<NaiveDate> = self
+            .by_set_pos
+            .iter()
+            .filter_map(|position| match *position {
+                position if position > 0 => dates.get(position as usize - 1).copied(),
+                position if position < 0 => dates
+                    .len()
+                    .checked_sub(position.unsigned_abs() as usize)
+                    .map(|index| dates[index]),
+                _ => None,
+            })
+            .collect();
+        selected.sort();
+        selected.dedup();
+        selected
+    }
+
+    /// The dates of the occurrences in the month starting at `first`
+    fn month_dates(&self, first: NaiveDate, default_day: u32) -> Vec<NaiveDate> {
+        if !self.by_month_day.is_empty() {
+            self.by_month_day
+                .iter()
+                .filter_map(|day| month_day(first, *day))
+                .filter(|date| {
+                    self.by_day.is_empty()
+                        || self
+                            .by_day
+                            .iter()
+                            .any(|(_, weekday)| date.weekday() == *weekday)
+                })
+                .collect()
+        } else if !self.by_day.is_empty() {
+            weekday_dates(&self.by_day, first, last_of_month(first))
+        } else {
+            first.with_day(default_day).into_iter().collect()
+        }
+    }
+}
+
+/// Iterator over the start times of a rule's occurrences
+pub struct Occurrences<'a> {
+    rule: &'a RecurrenceRule,
+    start: NaiveDateTime,
+    period: u32,
+    /// Occurrences of the current period not yet returned, latest first
+    pending: Vec<NaiveDateTime>,
+    emitted: u32,
+    done: bool,
+}
+
+impl Iterator for Occurrences<'_> {
+    type Item = NaiveDateTime;
+
+    fn next(&mut self) -> Option<NaiveDateTime> {
+        if self.done || self.rule.count.is_some_and(|count| self.emitted >= count) {
+            return None;
+        }
+
+        // The first occurrence is always the start itself
+        let next = if self.emitted == 0 {
+            Some(self.start)
+        } else {
+            loop {
+                if let Some(next) = self.pending.pop() {
+                    break Some(next);
+                }
+                if self.period >= MAX_PERIODS {
+                    break None;
+                }
+                let mut dates: Vec<NaiveDateTime> = self
+                    .rule
+                    .period_dates(self.start.date(), self.period)
+                    .into_iter()
+                    .map(|date| date.and_time(self.start.time()))
+                    .filter(|time| *time > self.start)
+                    .collect();
+                dates.reverse();
+                self.pending = dates;
+                self.period += 1;
+            }
+        };
+
+        match next {
+            Some(next) if self.rule.until.is_none_or(|until| next <= until) => {
+                self.emitted += 1;
+                Some(next)
+            }
+            _ => {
+                self.done = true;
+                None
+            }
+        }
+    }
+}
+
+fn parse_weekday(text: &str) -> Result<Weekday, String> {
+    Ok(match text.to_ascii_uppercase().as_str() {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return Err(format!("Invalid weekday \"{text}\"")),
+    })
+}
+
+/// Parse an `UNTIL` date or date-time without its UTC marker; a date alone
+/// includes the whole day
+fn parse_until(text: &str) -> Result<NaiveDateTime, String> {
+    NaiveDateTime::parse_from_str(text, "%Y%m%dT%H%M%S")
+        .or_else(|_| {
+            NaiveDate::parse_from_str(text, "%Y%m%d")
+                .map(|date| date.and_hms_opt(23, 59, 59).unwrap_or_default())
+        })
+        .map_err(|_| format!("Invalid UNTIL value \"{text}\""))
+}
+
+/// The first day of a month given as months since year 0
+fn first_of_month(months: i64) -> Option<NaiveDate> {
+    let year = i32::try_from(months.div_euclid(12)).ok()?;
+    NaiveDate::from_ymd_opt(year, months.rem_euclid(12) as u32 + 1, 1)
+}
+
+fn last_of_month(first: NaiveDate) -> NaiveDate {
+    first_of_month(i64::from(first.year()) * 12 + i64::from(first.month0()) + 1)
+        .and_then(|next| next.pred_opt())
+        .unwrap_or(first)
+}
+
+/// A day of the month containing `date`, negative counting from the end
+fn month_day(date: NaiveDate, day: i32) -> Option<NaiveDate> {
+    let first = date.with_day(1)?;
+    let last = last_of_month(first);
+    match day {
+        day if day > 0 => first.with_day(day as u32),
+        day if day < 0 => last
+            .checked_sub_signed(Duration::days(i64::from(day.unsigned_abs()) - 1))
+            .filter(|date| *date >= first),
+        _ => None,
+    }
+}
+
+/// The dates from `first` to `last` falling on the given weekdays; an
+/// ordinal picks only the nth such weekday in the range, from the end if
+/// negative
+fn weekday_dates(
+    by_day: &[(Option<i32>, Weekday)],
+    first: NaiveDate,
+    last: NaiveDate,
+) -> Vec<NaiveDate> {
+    let mut dates = Vec::new();
+    for (ordinal, weekday) in by_day {
+        let matching: Vec<NaiveDate> = first
+            .iter_days()
+            .take_while(|date| *date <= last)
+            .filter(|date| date.weekday() == *weekday)
+            .collect();
+        match ordinal {
+            None => dates.extend(matching),
+            Some(ordinal) if *ordinal > 0 => {
+                dates.extend(matching.get(*ordinal as usize - 1));
+            }
+            Some(ordinal) => {
+                let index = matching.len().checked_sub(ordinal.unsigned_abs() as usize);
+                dates.extend(index.map(|index| matching[index]));
+            }
+        }
+    }
+    dates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn expand(rule: &str, start: NaiveDateTime, limit: usize) -> Vec<NaiveDateTime> {
+        RecurrenceRule::parse(rule)
+            .unwrap()
+            .occurrences(start)
+            .take(limit)
+            .collect()
+    }
+
+    #[test]
+    fn test_weekly_and_daily() {
+        // Monday 2024-06-03
+        assert_eq!(
+            expand("FREQ=WEEKLY;BYDAY=MO,WE;COUNT=4", at(2024, 6, 3, 9, 0), 10),
+            vec![
+                at(2024, 6, 3, 9, 0),
+                at(2024, 6, 5, 9, 0),
+                at(2024, 6, 10, 9, 0),
+                at(2024, 6, 12, 9, 0),
+            ]
+        );
+        assert_eq!(
+            expand("FREQ=WEEKLY;INTERVAL=2", at(2024, 6, 3, 9, 0), 3),
+            vec![
+                at(2024, 6, 3, 9, 0),
+                at(2024, 6, 17, 9, 0),
+                at(2024, 7, 1, 9, 0),
+            ]
+        );
+        assert_eq!(
+            expand(
+                "FREQ=DAILY;INTERVAL=3;UNTIL=20240609T090000Z",
+                at(2024, 6, 3, 9, 0),
+                10
+            ),
+            vec![
+                at(2024, 6, 3, 9, 0),
+                at(2024, 6, 6, 9, 0),
+                at(2024, 6, 9, 9, 0)
+            ]
+        );
+        assert_eq!(
+            expand("FREQ=DAILY;BYDAY=MO,TU,WE,TH,FR", at(2024, 6, 7, 9, 0), 2),
+            vec![at(2024, 6, 7, 9, 0), at(2024, 6, 10, 9, 0)]
+        );
+    }
+
+    #[test]
+    fn test_monthly_and_yearly() {
+        // Last Friday of the month
+        assert_eq!(
+            expand("FREQ=MONTHLY;BYDAY=-1FR", at(2024, 5, 31, 16, 0), 3),
+            vec![
+                at(2024, 5, 31, 16, 0),
+                at(2024, 6, 28, 16, 0),
+                at(2024, 7, 26, 16, 0),
+            ]
+        );
+        // Months without a 31st are skipped
+        assert_eq!(
+            expand("FREQ=MONTHLY", at(2024, 1, 31, 10, 0), 3),
+            vec![
+                at(2024, 1, 31, 10, 0),
+                at(2024, 3, 31, 10, 0),
+                at(2024, 5, 31, 10, 0),
+            ]
+        );
+        // Last working day of the month
+        assert_eq!(
+            expand(
+                "FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1",
+                at(2024, 5, 31, 17, 0),
+                3
+            ),
+            vec![
+                at(2024, 5, 31, 17, 0),
+                at(2024, 6, 28, 17, 0),
+                at(2024, 7, 31, 17, 0),
+            ]
+        );
+        // Second Sunday of March, as in time zone definitions
+        assert_eq!(
+            expand("FREQ=YEARLY;BYMONTH=3;BYDAY=2SU", at(2007, 3, 11, 2, 0), 20).last(),
+            Some(&at(2026, 3, 8, 2, 0))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for rule in [
+            "",
+            "INTERVAL=2",
+            "FREQ=HOURLY",
+            "FREQ=DAILY;INTERVAL=0",
+            "FREQ=WEEKLY;BYDAY=XX",
+        ] {
+            assert!(
+                RecurrenceRule::parse(rule).is_err(),
+                "{rule:?} should not parse"
+            );
+        }
+    }
+}
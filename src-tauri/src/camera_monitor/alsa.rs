@@ -0,0 +1,150 @@
+//! ALSA capture stream inspection
+//!
+//! Detects microphone use by reading the status of every capture substream
+//! from procfs (`/proc/asound/card*/pcm*c/sub*/status`). The kernel offers no
+//! change notifications for these files, so they are polled.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Procfs directory with the ALSA card information
+pub const ASOUND_PATH: &str = "/proc/asound";
+
+/// Substream states in which audio is being captured
+const CAPTURING_STATES: [&str; 3] = ["RUNNING", "XRUN", "DRAINING"];
+
+/// A capture substream that is currently recording
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureStream {
+    /// Substream path relative to the root, e.g. `card0/pcm0c/sub0`
+    pub name: String,
+    /// Process that opened the substream, if reported
+    pub owner_pid: Option<u32>,
+}
+
+/// List the capture substreams currently recording under an asound root
+pub fn active_capture_streams(root: &Path) -> Vec<CaptureStream> {
+    let mut streams: Vec<CaptureStream> = matching_dirs(root, "card", "")
+        .into_iter()
+        .flat_map(|card| matching_dirs(&card, "pcm", "c"))
+        .flat_map(|pcm| matching_dirs(&pcm, "sub", ""))
+        .filter_map(|substream| {
+            let status = fs::read_to_string(substream.join("status")).ok()?;
+            let owner_pid = parse_capturing_status(&status)?;
+            let name = substream
+                .strip_prefix(root)
+                .unwrap_or(&substream)
+                .to_string_lossy()
+                .to_string();
+
+            Some(CaptureStream { name, owner_pid })
+        })
+        .collect();
+
+    streams.sort_by(|a, b| a.name.cmp(&b.name));
+    streams
+}
+
+/// Names of the processes owning the given capture substreams
+pub fn owner_process_names(streams: &[CaptureStream]) -> Vec<String> {
+    let mut names: Vec<String> = streams
+        .iter()
+        .filter_map(|stream| stream.owner_pid)
+        .map(|pid| {
+            fs::read_to_string(Path::new("/proc").join(pid.to_string()).join("comm"))
+                .map(|comm| comm.trim().to_string())
+                .unwrap_or_else(|_| pid.to_string())
+        })
+        .collect();
+
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Parse a substream status file.
+///
+/// Returns `None` unless the substream is capturing, otherwise the owner PID
+/// if the kernel reports one.
+fn parse_capturing_status(status: &str) -> Option<Option<u32>> {
+    let field = |key: &str| {
+        status.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            (name.trim() == key).then(|| value.trim())
+        })
+    };
+
+    let state = field("state")?;
+    if !CAPTURING_STATES.contains(&state) {
+        return None;
+    }
+
+    Some(field("owner_pid").and_then(|pid| pid.parse().ok()))
+}
+
+/// Subdirectories named `<prefix><digits><suffix>`
+fn matching_dirs(dir: &Path, prefix: &str, suffix: &str) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| {
+                    let name = entry.file_name();
+                    let name = name.to_string_lossy();
+                    name.strip_prefix(prefix)
+                        .and_then(|rest| rest.strip_suffix(suffix))
+                        .is_some_and(|index| {
+                            !index.is_empty() && index.chars().all(|c| c.is_ascii_digit())
+                        })
+                })
+                .map(|entry| entry.path())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fake_asound;
+
+    const RUNNING_STATUS: &str = "state: RUNNING\nowner_pid   : 4242\ntrigger_time: 1.0\n";
+
+    #[test]
+    fn test_parse_capturing_status() {
+        assert_eq!(parse_capturing_status(RUNNING_STATUS), Some(Some(4242)));
+        assert_eq!(parse_capturing_status("state: XRUN\n"), Some(None));
+        assert_eq!(
+            parse_capturing_status("state: PREPARED\nowner_pid: 1\n"),
+            None
+        );
+        assert_eq!(parse_capturing_status("closed\n"), None);
+    }
+
+    #[test]
+    fn test_active_capture_streams() {
+        let root = fake_asound(
+            "streams",
+            &[
+                ("card0/pcm0c/sub0", RUNNING_STATUS),
+                ("card0/pcm0c/sub1", "closed\n"),
+                ("card0/pcm0p/sub0", RUNNING_STATUS),
+                ("card1/pcm2c/sub0", "state: RUNNING\n"),
+            ],
+        );
+
+        assert_eq!(
+            active_capture_streams(root.path()),
+            vec![
+                CaptureStream {
+                    name: "card0/pcm0c/sub0".to_string(),
+                    owner_pid: Some(4242),
+                },
+                CaptureStream {
+                    name: "card1/pcm2c/sub0".to_string(),
+                    owner_pid: None,
+                },
+            ]
+        );
+    }
+}
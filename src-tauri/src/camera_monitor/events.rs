@@ -0,0 +1,55 @@
+//! Camera monitor events
+//!
+//! Events published by the monitoring task, and the changes auto-toggle
+//! makes to devices. They are forwarded to the frontend as Tauri events, so
+//! it does not have to poll for changes.
+
+use crate::camera_monitor::history::CameraSession;
+use serde::Serialize;
+
+/// Event emitted when a camera session starts
+pub const SESSION_STARTED_EVENT: &str = "camera-session-started";
+
+/// Event emitted when a camera session ends
+pub const SESSION_ENDED_EVENT: &str = "camera-session-ended";
+
+/// Event emitted when auto-toggle changes devices
+pub const AUTO_TOGGLE_APPLIED_EVENT: &str = "auto-toggle-applied";
+
+/// Kind of change auto-toggle made to devices
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum AutoToggleAction {
+    /// The in-call state was applied
+    InCallState,
+    /// Devices were restored to their state before the session
+    Restore,
+}
+
+/// Devices changed by auto-toggle in one step
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct AutoToggleApplied {
+    /// What was done to the devices
+    pub action: AutoToggleAction,
+    /// Serial numbers of the changed devices
+    pub serial_numbers: Vec<String>,
+}
+
+/// Event published by the camera monitor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MonitorEvent {
+    /// A camera session started
+    SessionStarted(CameraSession),
+    /// A camera session ended
+    SessionEnded(CameraSession),
+}
+
+impl MonitorEvent {
+    /// Name of the Tauri event this is emitted as
+    pub fn name(&self) -> &'static str {
+        match self {
+            MonitorEvent::SessionStarted(_) => SESSION_STARTED_EVENT,
+            MonitorEvent::SessionEnded(_) => SESSION_ENDED_EVENT,
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Camera session history
+//!
+//! Keeps a bounded record of recent camera sessions in memory and mirrors it
+//! to a JSON file, so the history survives restarts.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::PathBuf;
+
+/// Name of the file holding the session history
+pub const HISTORY_FILE_NAME: &str = "camera_history.json";
+
+/// Maximum number of sessions kept in the history
+pub const MAX_HISTORY_ENTRIES: usize = 100;
+
+/// A single camera session
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CameraSession {
+    /// When the session started
+    pub started_at: DateTime<Utc>,
+    /// When the session ended, or `None` while it is running
+    pub ended_at: Option<DateTime<Utc>>,
+    /// Capture devices opened during the session, as video nodes (e.g.
+    /// `video0`) or ALSA substreams (e.g. `card0/pcm0c/sub0`)
+    pub cameras: Vec<String>,
+    /// Names of the processes holding a capture device open when the session
+    /// started
+    pub processes: Vec<String>,
+    /// Serial numbers of the devices auto-toggle changed during the session
+    pub affected_devices: Vec<String>,
+}
+
+/// Bounded history of camera sessions
+#[derive(Debug)]
+pub struct SessionHistory {
+    /// Sessions, oldest first
+    entries: VecDeque<CameraSession>,
+    /// Maximum number of sessions kept
+    capacity: usize,
+    /// File the history is saved to, if any
+    path: Option<PathBuf>,
+}
+
+impl SessionHistory {
+    /// Create an empty history kept in memory only
+    pub fn in_memory(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity,
+            path: None,
+        }
+    }
+
+    /// Load the history from a file, starting empty if it cannot be read
+    pub fn load(path: PathBuf, capacity: usize) -> Self {
+        let mut entries: VecDeque<CameraSession> = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                eprintln!("Failed to parse camera history {}: {e}", path.display());
+                VecDeque::new()
+            }),
+            Err(_) => VecDeque::new(),
+        };
+
+        while entries.len() > capacity {
+            entries.pop_front();
+        }
+
+        Self {
+            entries,
+            capacity,
+            path: Some(path),
+        }
+    }
+
+    /// Add a session, or update it if it is the latest one recorded.
+    ///
+    /// The oldest sessions are dropped once the history is full.
+    pub fn record(&mut self, session: CameraSession) {
+        match self.entries.back_mut() {
+            Some(latest) if latest.started_at == session.started_at => *latest = session,
+            _ => {
+                self.entries.push_back(session);
+                while self.entries.len() > self.capacity {
+                    self.entries.pop_front();
+                }
+            }
+        }
+
+        if let Err(e) = self.save() {
+            eprintln!("Failed to save camera history: {e}");
+        }
+    }
+
+    /// Get all sessions, most recent first
+    pub fn entries(&self) -> Vec<CameraSession> {
+        self.entries.iter().rev().cloned().collect()
+    }
+
+    /// Write the history to its file, if it has one
+    fn save(&self) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let contents = serde_json::to_string_pretty(&self.entries)?;
+        fs::write(path, contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use chrono::Duration;
+
+    fn session(started_at: DateTime<Utc>) -> CameraSession {
+        CameraSession {
+            started_at,
+            ended_at: None,
+            cameras: vec!["video0".to_string()],
+            processes: Vec::new(),
+            affected_devices: vec!["ABC123".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_record_updates_latest_session() {
+        let mut history = SessionHistory::in_memory(10);
+        let started_at = Utc::now();
+
+        history.record(session(started_at));
+        history.record(CameraSession {
+            ended_at: Some(started_at + Duration::minutes(5)),
+            ..session(started_at)
+        });
+
+        let entries = history.entries();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].ended_at.is_some());
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mut history = SessionHistory::in_memory(3);
+        let start = Utc::now();
+
+        for minutes in 0..5 {
+            history.record(session(start + Duration::minutes(minutes)));
+        }
+
+        let entries = history.entries();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].started_at, start + Duration::minutes(4));
+        assert_eq!(entries[2].started_at, start + Duration::minutes(2));
+    }
+
+    #[test]
+    fn test_history_round_trips_through_file() {
+        let dir = TempDir::new("session-history");
+        let path = dir.join("history.json");
+        let started_at = Utc::now();
+
+        let mut history = SessionHistory::load(path.clone(), 10);
+        history.record(session(started_at));
+
+        let reloaded = SessionHistory::load(path, 10);
+        assert_eq!(reloaded.entries(), vec![session(started_at)]);
+    }
+}
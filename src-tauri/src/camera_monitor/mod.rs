@@ -1,11 +1,17 @@
 //! Camera Auto-Toggle Module
 //!
 //! This module provides automatic camera detection and lighting control functionality.
-//! It monitors `/dev/video*` devices and automatically toggles Litra device power
-//! based on camera activity.
+//! It monitors `/dev/video*` devices, and optionally ALSA capture streams, and
+//! automatically toggles Litra device power based on camera or microphone activity.
 
+pub mod alsa;
+pub mod events;
+pub mod history;
 pub mod monitor;
+pub mod session;
+pub mod source;
 pub mod strategies;
+pub mod v4l2;
 
 pub use monitor::CameraMonitor;
 
@@ -1,51 +1,100 @@
 //! Core camera monitoring implementation
 //!
-//! This module contains the main camera monitoring logic using inotify
-//! to detect camera device activity on Linux systems.
+//! This module contains the main camera monitoring logic. Camera activity is
+//! read from a [`CameraEventSource`] (inotify on `/dev` in production) and
+//! turned into camera sessions, so the whole loop can be driven
+//! deterministically in tests. Lights are not switched here: the camera
+//! trigger source publishes the sessions to the trigger engine, which runs
+//! auto-toggle as a built-in binding.
 
 use crate::camera_monitor::{
-    strategies::{create_device_selector, DeviceSelector},
+    events::MonitorEvent,
+    history::{CameraSession, SessionHistory, HISTORY_FILE_NAME, MAX_HISTORY_ENTRIES},
+    session::{SessionAction, SessionStateMachine},
+    source::{
+        CameraEvent, CameraEventSource, CombinedEventSource, InotifyEventSource,
+        MicrophoneEventSource,
+    },
     CameraMonitorResult,
 };
-use crate::commands::DeviceManagerState;
-use crate::config::AutoToggleConfig;
-use inotify::{EventMask, Inotify, WatchMask};
-use std::time::{Duration, Instant};
-use tokio::sync::mpsc;
-use tokio::time::sleep;
-
-const MONITOR_PATH: &str = "/dev";
-const VIDEO_DEVICE_FILTER: &str = "video*";
+use crate::config::{self, ActivitySource, AutoToggleConfig};
+use crate::task::BackgroundTask;
+use chrono::Utc;
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::time::Instant;
+
+/// Interval between polls of the event source and the session timers
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Interval between reconciling the tracked session count with the system
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Number of events buffered for slow subscribers
+const EVENT_CHANNEL_CAPACITY: usize = 32;
+
+/// Monitor state published by the monitoring task
+#[derive(Debug, Clone, Default)]
+struct MonitorStatus {
+    /// Number of currently open camera handles
+    device_count: usize,
+    /// Whether a camera session is active
+    session_active: bool,
+}
 
 /// Main camera monitor structure
 pub struct CameraMonitor {
-    /// Device manager reference
-    device_manager: DeviceManagerState,
-    /// Current monitoring state
-    is_monitoring: bool,
-    /// Current camera device count
-    device_count: usize,
-    /// Last event timestamp for debouncing
-    last_event_time: Option<Instant>,
-    /// Monitoring task handle
-    monitor_handle: Option<tokio::task::JoinHandle<()>>,
-    /// Devices currently controlled by auto-toggle
-    controlled_devices: Vec<String>,
-    /// Channel for stopping monitoring
-    stop_tx: Option<mpsc::Sender<()>>,
+    /// State published by the monitoring task
+    status: Arc<RwLock<MonitorStatus>>,
+    /// Channel for publishing monitor events
+    events: broadcast::Sender<MonitorEvent>,
+    /// History of camera sessions
+    history: Arc<Mutex<SessionHistory>>,
+    /// Monitoring task, while running
+    task: Option<BackgroundTask>,
+    /// Channel for passing configuration changes to the monitoring task
+    config_tx: Option<mpsc::UnboundedSender<AutoToggleConfig>>,
+    /// Channel for requesting an immediate reconciliation
+    reconcile_tx: Option<mpsc::UnboundedSender<()>>,
+    /// Channel for reporting devices auto-toggle changed
+    affected_tx: Option<mpsc::UnboundedSender<Vec<String>>>,
+    /// Activity source the monitoring task was started with, if known
+    activity_source: Option<ActivitySource>,
+}
+
+impl Default for CameraMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CameraMonitor {
-    /// Create a new camera monitor
-    pub fn new(device_manager: DeviceManagerState) -> Self {
+    /// Create a new camera monitor with the session history kept in the data
+    /// directory
+    pub fn new() -> Self {
+        let history = SessionHistory::load(
+            config::data_dir().join(HISTORY_FILE_NAME),
+            MAX_HISTORY_ENTRIES,
+        );
+
+        Self::with_history(history)
+    }
+
+    /// Create a new camera monitor recording sessions to the given history
+    pub fn with_history(history: SessionHistory) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+
         Self {
-            device_manager,
-            is_monitoring: false,
-            device_count: 0,
-            last_event_time: None,
-            monitor_handle: None,
-            controlled_devices: Vec::new(),
-            stop_tx: None,
+            status: Arc::new(RwLock::new(MonitorStatus::default())),
+            events,
+            history: Arc::new(Mutex::new(history)),
+            task: None,
+            config_tx: None,
+            reconcile_tx: None,
+            affected_tx: None,
+            activity_source: None,
         }
     }
 
@@ -54,301 +103,639 @@ impl CameraMonitor {
         &mut self,
         auto_toggle_config: AutoToggleConfig,
     ) -> CameraMonitorResult<()> {
-        if self.is_monitoring {
+        if !self.can_start(&auto_toggle_config)? {
             return Ok(());
         }
 
-        if !auto_toggle_config.enabled {
-            return Err("Auto-toggle is disabled in configuration".into());
+        let activity_source = auto_toggle_config.activity_source;
+        let source: Box<dyn CameraEventSource> = match activity_source {
+            ActivitySource::Camera => Box::new(InotifyEventSource::new()?),
+            ActivitySource::Microphone => Box::new(MicrophoneEventSource::new()),
+            ActivitySource::CameraOrMicrophone => Box::new(CombinedEventSource::new(vec![
+                Box::new(InotifyEventSource::new()?),
+                Box::new(MicrophoneEventSource::new()),
+            ])),
+        };
+
+        self.start_monitoring_with_source(auto_toggle_config, source)?;
+        self.activity_source = Some(activity_source);
+        Ok(())
+    }
+
+    /// Start camera monitoring with events read from the given source
+    pub fn start_monitoring_with_source(
+        &mut self,
+        auto_toggle_config: AutoToggleConfig,
+        source: Box<dyn CameraEventSource>,
+    ) -> CameraMonitorResult<()> {
+        if !self.can_start(&auto_toggle_config)? {
+            return Ok(());
         }
 
-        // Initialize device count to 0 - we'll track actual usage through events
-        self.device_count = 0;
+        // The monitor task reconciles the real count on startup
+        *self.status.write().expect("Failed to write monitor status") = MonitorStatus::default();
 
-        // Create stop channel
-        let (stop_tx, stop_rx) = mpsc::channel(1);
-        self.stop_tx = Some(stop_tx);
+        // Create config channel
+        let (config_tx, config_rx) = mpsc::unbounded_channel();
+        self.config_tx = Some(config_tx);
 
-        // Start monitoring task
-        let monitor_handle = self.start_monitor_task(auto_toggle_config, stop_rx).await?;
-        self.monitor_handle = Some(monitor_handle);
+        // Create reconcile channel
+        let (reconcile_tx, reconcile_rx) = mpsc::unbounded_channel();
+        self.reconcile_tx = Some(reconcile_tx);
+
+        // Create affected devices channel
+        let (affected_tx, affected_rx) = mpsc::unbounded_channel();
+        self.affected_tx = Some(affected_tx);
 
-        self.is_monitoring = true;
+        // Start monitoring task
+        let task = MonitorTask {
+            machine: SessionStateMachine::new(
+                Duration::from_millis(auto_toggle_config.on_delay_ms),
+                Duration::from_millis(auto_toggle_config.off_grace_ms),
+            ),
+            source,
+            status: self.status.clone(),
+            events: self.events.clone(),
+            history: self.history.clone(),
+            current_session: None,
+            session_cameras: BTreeSet::new(),
+        };
+        self.task = Some(BackgroundTask::spawn(|stop_rx| async move {
+            if let Err(e) = task
+                .run(stop_rx, config_rx, reconcile_rx, affected_rx)
+                .await
+            {
+                eprintln!("Camera monitor error: {e}");
+            }
+        }));
 
         Ok(())
     }
 
-    /// Stop camera monitoring
+    /// Stop camera monitoring.
+    ///
+    /// A session still active is ended before the monitoring task exits, so
+    /// the lights auto-toggle changed are restored.
     pub async fn stop_monitoring(&mut self) -> CameraMonitorResult<()> {
-        if !self.is_monitoring {
+        let Some(task) = self.task.take() else {
             return Ok(());
-        }
+        };
 
-        // Send stop signal
-        if let Some(stop_tx) = self.stop_tx.take() {
-            let _ = stop_tx.send(()).await;
-        }
+        self.config_tx = None;
+        self.reconcile_tx = None;
+        self.affected_tx = None;
+        self.activity_source = None;
 
-        // Wait for monitoring task to complete
-        if let Some(handle) = self.monitor_handle.take() {
-            handle.abort();
-        }
+        // Wait for monitoring task to end the session
+        task.stop().await;
 
-        self.is_monitoring = false;
-        self.device_count = 0;
-        self.last_event_time = None;
+        *self.status.write().expect("Failed to write monitor status") = MonitorStatus::default();
 
         Ok(())
     }
 
     /// Check if monitoring is active
     pub fn is_monitoring(&self) -> bool {
-        self.is_monitoring
+        self.task.as_ref().is_some_and(BackgroundTask::is_running)
     }
 
     /// Get current device count
     pub fn get_device_count(&self) -> usize {
-        self.device_count
+        self.status
+            .read()
+            .expect("Failed to read monitor status")
+            .device_count
     }
 
-    /// Get controlled devices
-    pub fn get_controlled_devices(&self) -> Vec<String> {
-        self.controlled_devices.clone()
+    /// Check if a camera session is active
+    pub fn is_session_active(&self) -> bool {
+        self.status
+            .read()
+            .expect("Failed to read monitor status")
+            .session_active
     }
 
-    /// Start the monitoring task
-    async fn start_monitor_task(
-        &self,
-        config: AutoToggleConfig,
-        mut stop_rx: mpsc::Receiver<()>,
-    ) -> CameraMonitorResult<tokio::task::JoinHandle<()>> {
-        let device_manager = self.device_manager.clone();
+    /// Apply a new configuration to the running monitor.
+    ///
+    /// Takes effect without restarting: timers already running use the new
+    /// delays. Does nothing while monitoring is inactive.
+    pub fn update_config(&self, auto_toggle_config: AutoToggleConfig) {
+        if let Some(config_tx) = &self.config_tx {
+            let _ = config_tx.send(auto_toggle_config);
+        }
+    }
 
-        let handle = tokio::spawn(async move {
-            if let Err(e) = Self::monitor_loop(config, device_manager, &mut stop_rx).await {
-                eprintln!("Camera monitor error: {e}");
-            }
-        });
+    /// Compare the tracked sessions with the system right away.
+    ///
+    /// Used when events may have been missed, e.g. across a system suspend.
+    /// Does nothing while monitoring is inactive.
+    pub fn reconcile_now(&self) {
+        if let Some(reconcile_tx) = &self.reconcile_tx {
+            let _ = reconcile_tx.send(());
+        }
+    }
 
-        Ok(handle)
+    /// Record devices auto-toggle changed in the session in progress.
+    ///
+    /// Does nothing while no session is active.
+    pub fn record_affected_devices(&self, serial_numbers: Vec<String>) {
+        if let Some(affected_tx) = &self.affected_tx {
+            let _ = affected_tx.send(serial_numbers);
+        }
     }
 
-    /// Main monitoring loop
-    async fn monitor_loop(
-        config: AutoToggleConfig,
-        device_manager: DeviceManagerState,
-        stop_rx: &mut mpsc::Receiver<()>,
+    /// Activity source of the running monitor, if started from configuration
+    pub fn activity_source(&self) -> Option<ActivitySource> {
+        self.activity_source
+    }
+
+    /// Subscribe to events published by the monitor
+    pub fn subscribe(&self) -> broadcast::Receiver<MonitorEvent> {
+        self.events.subscribe()
+    }
+
+    /// Get the recorded camera sessions, most recent first
+    pub fn get_history(&self) -> Vec<CameraSession> {
+        self.history
+            .lock()
+            .expect("Failed to lock camera history")
+            .entries()
+    }
+
+    /// Check whether monitoring should be started with the given configuration
+    fn can_start(&self, auto_toggle_config: &AutoToggleConfig) -> CameraMonitorResult<bool> {
+        if self.is_monitoring() {
+            return Ok(false);
+        }
+
+        if !auto_toggle_config.enabled {
+            return Err("Auto-toggle is disabled in configuration".into());
+        }
+
+        Ok(true)
+    }
+}
+
+/// State owned by the monitoring task
+struct MonitorTask {
+    /// Source of camera events
+    source: Box<dyn CameraEventSource>,
+    /// Session state machine
+    machine: SessionStateMachine,
+    /// State shared with the owning monitor
+    status: Arc<RwLock<MonitorStatus>>,
+    /// Channel for publishing monitor events
+    events: broadcast::Sender<MonitorEvent>,
+    /// History of camera sessions
+    history: Arc<Mutex<SessionHistory>>,
+    /// Record of the session in progress
+    current_session: Option<CameraSession>,
+    /// Cameras opened since the last session ended
+    session_cameras: BTreeSet<String>,
+}
+
+impl MonitorTask {
+    /// Run the monitoring loop until stopped, ending the session on the way
+    /// out
+    async fn run(
+        mut self,
+        stop_rx: watch::Receiver<bool>,
+        mut config_rx: mpsc::UnboundedReceiver<AutoToggleConfig>,
+        mut reconcile_rx: mpsc::UnboundedReceiver<()>,
+        mut affected_rx: mpsc::UnboundedReceiver<Vec<String>>,
     ) -> CameraMonitorResult<()> {
-        let mut inotify = Inotify::init()?;
-        let _watch_descriptor = inotify.watches().add(
-            MONITOR_PATH,
-            WatchMask::OPEN | WatchMask::CLOSE_WRITE | WatchMask::CLOSE_NOWRITE,
-        )?;
-
-        // Initialize device count to 0 - we'll track actual usage through events
-        let mut device_count = 0;
-        let mut last_event_time: Option<Instant> = None;
-        let mut controlled_devices: Vec<String> = Vec::new();
-
-        // Create device selector
-        let device_selector = create_device_selector(&config.strategy);
-
-        println!(
-            "Camera monitoring started, watching: {MONITOR_PATH}, tracking actual camera usage"
-        );
+        let result = self
+            .monitor_loop(
+                &stop_rx,
+                &mut config_rx,
+                &mut reconcile_rx,
+                &mut affected_rx,
+            )
+            .await;
+
+        // Do not leave the lights in the in-call state once nothing watches
+        // the camera any more
+        if self.current_session.is_some() {
+            println!("Camera monitoring stopping, ending session");
+            self.end_session();
+        }
+        self.publish_status();
 
-        // Start with no active cameras - devices will only turn on when cameras are actually opened
-        println!("Monitoring camera activity, devices will turn on when cameras are opened");
+        println!("Camera monitoring stopped");
+        result
+    }
 
-        let mut buffer = [0; 1024];
+    /// Main monitoring loop
+    async fn monitor_loop(
+        &mut self,
+        stop_rx: &watch::Receiver<bool>,
+        config_rx: &mut mpsc::UnboundedReceiver<AutoToggleConfig>,
+        reconcile_rx: &mut mpsc::UnboundedReceiver<()>,
+        affected_rx: &mut mpsc::UnboundedReceiver<Vec<String>>,
+    ) -> CameraMonitorResult<()> {
+        println!("Camera monitoring started, tracking actual camera usage");
+
+        // A call may already be in progress, so start from the sessions that
+        // are open right now rather than from zero
+        let open_count = self.source.count_open_sessions();
+        let action = self.machine.set_open_count(open_count, Instant::now());
+        self.apply_session_action(action);
+        self.publish_status();
+
+        let mut last_reconcile_time = Instant::now();
+
+        // Run until a stop signal arrives or the monitor goes away
+        while let Ok(false) = stop_rx.has_changed() {
+            // Pick up configuration changes, keeping only the latest
+            let mut new_config = None;
+            while let Ok(config) = config_rx.try_recv() {
+                new_config = Some(config);
+            }
+            if let Some(config) = new_config {
+                self.apply_config(config);
+            }
 
-        loop {
-            // Check for stop signal
-            if let Ok(()) = stop_rx.try_recv() {
-                break;
+            // Add devices auto-toggle changed to the session record
+            while let Ok(serial_numbers) = affected_rx.try_recv() {
+                self.record_affected_devices(serial_numbers);
             }
 
-            // Read inotify events (non-blocking)
-            match inotify.read_events(&mut buffer) {
-                Ok(events) => {
-                    let mut video_events = Vec::new();
-
-                    // Filter for video device events
-                    for event in events {
-                        if let Some(name) = event.name {
-                            if let Some(name_str) = name.to_str() {
-                                if name_str.starts_with(&VIDEO_DEVICE_FILTER.replace("*", "")) {
-                                    video_events.push((name_str.to_string(), event.mask));
-                                }
+            // Feed camera events through the session state machine
+            for event in self.source.next_events(POLL_INTERVAL).await? {
+                let action = match event {
+                    CameraEvent::Opened(device_name) => {
+                        println!("Camera opened: {device_name}");
+                        if let Some(current) = &mut self.current_session {
+                            if !current.cameras.contains(&device_name) {
+                                current.cameras.push(device_name.clone());
                             }
                         }
+                        self.session_cameras.insert(device_name);
+                        self.machine.camera_opened(Instant::now())
                     }
-
-                    // Process video events
-                    if !video_events.is_empty() {
-                        device_count = Self::process_video_events(
-                            video_events,
-                            device_count,
-                            &*device_selector,
-                            &device_manager,
-                            &mut controlled_devices,
-                        )
-                        .await?;
-
-                        last_event_time = Some(Instant::now());
-                    }
-                }
-                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                    // No events available, check debounce
-                    if let Some(event_time) = last_event_time {
-                        if event_time.elapsed() >= Duration::from_millis(config.debounce_ms) {
-                            // Debounce period expired, finalize state
-                            Self::finalize_device_state(
-                                device_count,
-                                &device_manager,
-                                &mut controlled_devices,
-                            )
-                            .await?;
-                            last_event_time = None;
+                    CameraEvent::Closed(device_name) => {
+                        println!("Camera closed: {device_name}");
+                        let action = self.machine.camera_closed(Instant::now());
+                        // Forget cameras that were only probed
+                        if self.machine.open_count() == 0 && self.current_session.is_none() {
+                            self.session_cameras.clear();
                         }
+                        action
                     }
+                };
+                self.apply_session_action(action);
+            }
+
+            // Act on an expired turn-on delay or turn-off grace period
+            let action = self.machine.poll(Instant::now());
+            self.apply_session_action(action);
+
+            // Periodically correct drift from missed events, but not while
+            // a delay or grace period is running unless asked to
+            let mut reconcile_requested = false;
+            while reconcile_rx.try_recv().is_ok() {
+                reconcile_requested = true;
+            }
+            if reconcile_requested
+                || (!self.machine.is_pending()
+                    && last_reconcile_time.elapsed() >= RECONCILE_INTERVAL)
+            {
+                let actual_count = self.source.count_open_sessions();
+                if actual_count != self.machine.open_count() {
+                    println!(
+                        "Reconciling camera sessions: tracked {}, actually open {actual_count}",
+                        self.machine.open_count()
+                    );
+                    let action = self.machine.set_open_count(actual_count, Instant::now());
+                    self.apply_session_action(action);
                 }
-                Err(e) => {
-                    eprintln!("Inotify error: {e}");
-                }
+                last_reconcile_time = Instant::now();
             }
 
-            // Small delay to prevent busy waiting
-            sleep(Duration::from_millis(100)).await;
+            self.publish_status();
         }
 
-        println!("Camera monitoring stopped");
         Ok(())
     }
 
-    /// Process video device events
-    async fn process_video_events(
-        events: Vec<(String, EventMask)>,
-        mut device_count: usize,
-        device_selector: &dyn DeviceSelector,
-        device_manager: &DeviceManagerState,
-        controlled_devices: &mut Vec<String>,
-    ) -> CameraMonitorResult<usize> {
-        let mut open_count = 0;
-        let mut close_count = 0;
-
-        // Count open/close events
-        for (device_name, mask) in events {
-            match mask {
-                EventMask::OPEN => {
-                    open_count += 1;
-                    println!("Camera opened: {device_name}");
-                }
-                EventMask::CLOSE_WRITE | EventMask::CLOSE_NOWRITE => {
-                    close_count += 1;
-                    println!("Camera closed: {device_name}");
-                }
-                _ => {}
+    /// Switch to a new configuration while running
+    fn apply_config(&mut self, config: AutoToggleConfig) {
+        println!("Applying updated auto-toggle configuration");
+        self.machine.set_delays(
+            Duration::from_millis(config.on_delay_ms),
+            Duration::from_millis(config.off_grace_ms),
+        );
+    }
+
+    /// Share the current session count with the monitor
+    fn publish_status(&self) {
+        let mut status = self.status.write().expect("Failed to write monitor status");
+        status.device_count = self.machine.open_count();
+        status.session_active = self.current_session.is_some();
+    }
+
+    /// Carry out an action requested by the session state machine
+    fn apply_session_action(&mut self, action: Option<SessionAction>) {
+        match action {
+            Some(SessionAction::Activate) => {
+                println!("Camera session active");
+                self.start_session();
+            }
+            Some(SessionAction::Deactivate) => {
+                println!("Camera session ended");
+                self.end_session();
             }
+            None => {}
+        }
+    }
+
+    /// Record and announce a new session
+    fn start_session(&mut self) {
+        if self.current_session.is_some() {
+            return;
         }
 
-        // Update device count based on net change
-        let net_change = open_count - close_count;
-        if net_change > 0 {
-            device_count = device_count.saturating_add(net_change as usize);
-        } else if net_change < 0 {
-            device_count = device_count.saturating_sub((-net_change) as usize);
+        let current = CameraSession {
+            started_at: Utc::now(),
+            ended_at: None,
+            cameras: self.session_cameras.iter().cloned().collect(),
+            processes: self.source.camera_processes(),
+            affected_devices: Vec::new(),
+        };
+        self.record_session(&current);
+        let _ = self
+            .events
+            .send(MonitorEvent::SessionStarted(current.clone()));
+        self.current_session = Some(current);
+    }
+
+    /// Close the record of the session in progress and announce its end
+    fn end_session(&mut self) {
+        self.session_cameras.clear();
+
+        if let Some(mut current) = self.current_session.take() {
+            current.ended_at = Some(Utc::now());
+            self.record_session(&current);
+            let _ = self.events.send(MonitorEvent::SessionEnded(current));
         }
+    }
+
+    /// Add devices to the record of the session in progress
+    fn record_affected_devices(&mut self, serial_numbers: Vec<String>) {
+        let Some(current) = &mut self.current_session else {
+            return;
+        };
 
-        // React to any camera activity changes
-        if net_change != 0 {
-            println!("Camera activity change: {net_change}, total active sessions: {device_count}");
-
-            if device_count > 0 && net_change > 0 {
-                // Turn on devices when cameras are opened
-                println!("Cameras detected, turning on devices");
-                Self::turn_on_devices(device_selector, device_manager, controlled_devices).await?;
-            } else if device_count == 0 {
-                // Turn off devices immediately when no active camera sessions
-                println!("No active camera sessions, turning off devices");
-                Self::turn_off_devices(device_manager, controlled_devices).await?;
+        for serial_number in serial_numbers {
+            if !current.affected_devices.contains(&serial_number) {
+                current.affected_devices.push(serial_number);
             }
         }
+        let current = current.clone();
+        self.record_session(&current);
+    }
 
-        Ok(device_count)
+    /// Save a session to the history
+    fn record_session(&self, session: &CameraSession) {
+        self.history
+            .lock()
+            .expect("Failed to lock camera history")
+            .record(session.clone());
     }
+}
 
-    /// Finalize device state after debounce period
-    async fn finalize_device_state(
-        device_count: usize,
-        device_manager: &DeviceManagerState,
-        controlled_devices: &mut Vec<String>,
-    ) -> CameraMonitorResult<()> {
-        if device_count == 0 {
-            println!("Debounce period completed, no active camera sessions - turning off devices");
-            // Turn off devices after debounce
-            Self::turn_off_devices(device_manager, controlled_devices).await?;
-        } else {
-            println!(
-                "Debounce period completed, {device_count} camera sessions still active - keeping devices on"
-            );
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera_monitor::source::{ScriptStep, ScriptedEventSource};
+    use crate::test_support::{test_monitor, FILL_LIGHT, KEY_LIGHT};
+    use tokio::time::sleep;
 
-        Ok(())
+    fn opened(node: &str) -> ScriptStep {
+        ScriptStep::Event(CameraEvent::Opened(node.to_string()))
     }
 
-    /// Turn on devices based on strategy
-    async fn turn_on_devices(
-        device_selector: &dyn DeviceSelector,
-        device_manager: &DeviceManagerState,
-        controlled_devices: &mut Vec<String>,
-    ) -> CameraMonitorResult<()> {
-        let devices = {
-            let dm = device_manager.lock().await;
-            dm.get_all_devices()?
-        };
+    fn closed(node: &str) -> ScriptStep {
+        ScriptStep::Event(CameraEvent::Closed(node.to_string()))
+    }
 
-        for device in devices {
-            if device_selector.should_control_device(&device) && !device.is_on {
-                // Turn on device
-                let success = {
-                    let dm = device_manager.lock().await;
-                    if let Ok(handle) = dm.get_device_handle(&device.serial_number) {
-                        handle.set_on(true).is_ok()
-                    } else {
-                        false
-                    }
-                };
+    fn test_config(on_delay_ms: u64, off_grace_ms: u64) -> AutoToggleConfig {
+        AutoToggleConfig {
+            enabled: true,
+            on_delay_ms,
+            off_grace_ms,
+            ..AutoToggleConfig::default()
+        }
+    }
 
-                if success {
-                    controlled_devices.push(device.serial_number.clone());
-                }
-            }
+    /// Start a monitor replaying `script`
+    fn start_monitor(config: AutoToggleConfig, source: ScriptedEventSource) -> CameraMonitor {
+        let mut monitor = test_monitor();
+        monitor
+            .start_monitoring_with_source(config, Box::new(source))
+            .unwrap();
+        monitor
+    }
+
+    /// Names of the events published so far
+    fn event_names(events: &mut broadcast::Receiver<MonitorEvent>) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            names.push(event.name());
         }
+        names
+    }
 
-        Ok(())
+    #[tokio::test(start_paused = true)]
+    async fn test_session_follows_delays() {
+        let source =
+            ScriptedEventSource::new(vec![(0, opened("video0")), (5000, closed("video0"))]);
+        let monitor = start_monitor(test_config(1000, 3000), source);
+
+        sleep(Duration::from_millis(500)).await;
+        assert_eq!(monitor.get_device_count(), 1);
+        assert!(!monitor.is_session_active());
+
+        sleep(Duration::from_millis(1500)).await;
+        assert!(monitor.is_session_active());
+
+        // Still within the grace period after the close
+        sleep(Duration::from_millis(5000)).await;
+        assert_eq!(monitor.get_device_count(), 0);
+        assert!(monitor.is_session_active());
+
+        sleep(Duration::from_millis(2000)).await;
+        assert!(!monitor.is_session_active());
     }
 
-    /// Turn off devices based on strategy
-    async fn turn_off_devices(
-        device_manager: &DeviceManagerState,
-        controlled_devices: &mut Vec<String>,
-    ) -> CameraMonitorResult<()> {
-        // Only turn off devices we turned on
-        for serial_number in controlled_devices.drain(..) {
-            // Turn off device
-            let success = {
-                let dm = device_manager.lock().await;
-                if let Ok(handle) = dm.get_device_handle(&serial_number) {
-                    handle.set_on(false).is_ok()
-                } else {
-                    false
-                }
-            };
+    #[tokio::test(start_paused = true)]
+    async fn test_overlapping_sessions_end_with_last_close() {
+        let source = ScriptedEventSource::new(vec![
+            (0, opened("video0")),
+            (2000, opened("video2")),
+            (4000, closed("video0")),
+            (10000, closed("video2")),
+        ]);
+        let mut monitor = test_monitor();
+        let mut events = monitor.subscribe();
+        monitor
+            .start_monitoring_with_source(test_config(500, 1000), Box::new(source))
+            .unwrap();
+
+        // First camera closed while the second is still open
+        sleep(Duration::from_millis(8000)).await;
+        assert!(monitor.is_session_active());
+        assert_eq!(monitor.get_device_count(), 1);
+
+        sleep(Duration::from_millis(4000)).await;
+        assert!(!monitor.is_session_active());
+        assert_eq!(
+            event_names(&mut events),
+            vec!["camera-session-started", "camera-session-ended"]
+        );
+    }
 
-            if success {}
-        }
+    #[tokio::test(start_paused = true)]
+    async fn test_rapid_reopen_keeps_session() {
+        let source = ScriptedEventSource::new(vec![
+            (0, opened("video0")),
+            (3000, closed("video0")),
+            (3200, opened("video0")),
+            (3400, closed("video0")),
+            (3600, opened("video0")),
+            (8000, closed("video0")),
+        ]);
+        let mut monitor = test_monitor();
+        let mut events = monitor.subscribe();
+        monitor
+            .start_monitoring_with_source(test_config(1000, 2000), Box::new(source))
+            .unwrap();
+
+        sleep(Duration::from_millis(7000)).await;
+        assert!(monitor.is_session_active());
+        assert_eq!(event_names(&mut events), vec!["camera-session-started"]);
+
+        sleep(Duration::from_millis(4000)).await;
+        assert!(!monitor.is_session_active());
+        assert_eq!(event_names(&mut events), vec!["camera-session-ended"]);
+    }
 
-        Ok(())
+    #[tokio::test(start_paused = true)]
+    async fn test_missed_close_is_reconciled() {
+        let source = ScriptedEventSource::new(vec![
+            (0, opened("video0")),
+            (
+                5000,
+                ScriptStep::Missed(CameraEvent::Closed("video0".to_string())),
+            ),
+        ]);
+        let monitor = start_monitor(test_config(1000, 1000), source);
+
+        sleep(Duration::from_millis(10000)).await;
+        assert!(monitor.is_session_active());
+
+        // The next reconciliation finds no open camera and ends the session
+        sleep(RECONCILE_INTERVAL + Duration::from_secs(2)).await;
+        assert!(!monitor.is_session_active());
+        assert_eq!(monitor.get_device_count(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reconcile_now_corrects_missed_close() {
+        let source = ScriptedEventSource::new(vec![
+            (0, opened("video0")),
+            (
+                5000,
+                ScriptStep::Missed(CameraEvent::Closed("video0".to_string())),
+            ),
+        ]);
+        let monitor = start_monitor(test_config(1000, 1000), source);
+
+        sleep(Duration::from_millis(6000)).await;
+        assert!(monitor.is_session_active());
+
+        // No need to wait for the periodic reconciliation
+        monitor.reconcile_now();
+        sleep(Duration::from_millis(2000)).await;
+        assert!(!monitor.is_session_active());
+        assert_eq!(monitor.get_device_count(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_camera_open_at_start_activates_immediately() {
+        let source = ScriptedEventSource::new(vec![(5000, closed("video0"))]).with_open_count(1);
+        let monitor = start_monitor(test_config(1000, 1000), source);
+
+        sleep(Duration::from_millis(200)).await;
+        assert!(monitor.is_session_active());
+
+        sleep(Duration::from_millis(7000)).await;
+        assert!(!monitor.is_session_active());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stop_while_active_ends_session() {
+        let source = ScriptedEventSource::new(vec![(0, opened("video0"))]);
+        let mut monitor = test_monitor();
+        let mut events = monitor.subscribe();
+        monitor
+            .start_monitoring_with_source(test_config(0, 3000), Box::new(source))
+            .unwrap();
+
+        sleep(Duration::from_millis(1000)).await;
+        assert!(monitor.is_session_active());
+        assert!(monitor.is_monitoring());
+
+        monitor.stop_monitoring().await.unwrap();
+        assert!(!monitor.is_monitoring());
+        assert!(!monitor.is_session_active());
+        assert_eq!(monitor.get_device_count(), 0);
+        assert_eq!(
+            event_names(&mut events),
+            vec!["camera-session-started", "camera-session-ended"]
+        );
+        assert!(monitor.get_history()[0].ended_at.is_some());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_session_history_records_affected_devices() {
+        let source = ScriptedEventSource::new(vec![
+            (0, opened("video0")),
+            (3000, closed("video0")),
+            (3200, opened("video0")),
+            (6000, closed("video0")),
+        ]);
+        let monitor = start_monitor(test_config(1000, 1000), source);
+
+        sleep(Duration::from_millis(2000)).await;
+        monitor.record_affected_devices(vec![KEY_LIGHT.to_string()]);
+        monitor.record_affected_devices(vec![KEY_LIGHT.to_string(), FILL_LIGHT.to_string()]);
+
+        sleep(Duration::from_millis(8000)).await;
+        // Nothing to record once the session is over
+        monitor.record_affected_devices(vec!["OTHER".to_string()]);
+        sleep(Duration::from_millis(500)).await;
+
+        let history = monitor.get_history();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].cameras, vec!["video0"]);
+        assert_eq!(history[0].affected_devices, vec![KEY_LIGHT, FILL_LIGHT]);
+        assert!(history[0].ended_at.is_some());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_config_update_applies_to_running_session() {
+        let source =
+            ScriptedEventSource::new(vec![(0, opened("video0")), (2000, closed("video0"))]);
+        let monitor = start_monitor(test_config(0, 30000), source);
+
+        sleep(Duration::from_millis(1000)).await;
+        assert!(monitor.is_session_active());
+
+        monitor.update_config(test_config(0, 1000));
+
+        // The shortened grace period applies to the session in progress
+        sleep(Duration::from_millis(2500)).await;
+        assert!(!monitor.is_session_active());
     }
 }
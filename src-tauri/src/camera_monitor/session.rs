@@ -0,0 +1,405 @@
+//! Camera session state machine
+//!
+//! This module decides when camera activity should switch the controlled
+//! lights on and off. It is driven purely by open/close counts and explicit
+//! timestamps, so it holds no I/O and can be tested deterministically.
+//!
+//! A camera must stay open for the turn-on delay before lights are switched on,
+//! which filters out the brief probes applications make when enumerating
+//! cameras. Once the last camera closes, the lights are kept on for the
+//! turn-off grace period, so an application reopening the camera (for example
+//! when switching cameras in a call) does not make them flicker.
+
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Action the monitor should take in response to a state change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionAction {
+    /// Apply the in-call state to the controlled devices
+    Activate,
+    /// Restore the controlled devices to their state before the session
+    Deactivate,
+}
+
+/// Current phase of the camera session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionPhase {
+    /// No camera is open
+    Idle,
+    /// A camera is open, waiting for the turn-on delay to pass
+    PendingOn { since: Instant },
+    /// A camera session is active and the in-call state is applied
+    Active,
+    /// The last camera closed, waiting for the turn-off grace period to pass
+    PendingOff { since: Instant },
+}
+
+/// State machine tracking camera sessions with turn-on delay and turn-off grace
+#[derive(Debug, Clone)]
+pub struct SessionStateMachine {
+    /// How long a camera must stay open before lights are switched on
+    on_delay: Duration,
+    /// How long lights stay on after the last camera closes
+    off_grace: Duration,
+    /// Number of currently open camera handles
+    open_count: usize,
+    /// Current phase
+    phase: SessionPhase,
+}
+
+impl SessionStateMachine {
+    /// Create a new, idle state machine
+    pub fn new(on_delay: Duration, off_grace: Duration) -> Self {
+        Self {
+            on_delay,
+            off_grace,
+            open_count: 0,
+            phase: SessionPhase::Idle,
+        }
+    }
+
+    /// Change the turn-on delay and turn-off grace period.
+    ///
+    /// A delay or grace period already running is measured against the new
+    /// duration from when it started.
+    pub fn set_delays(&mut self, on_delay: Duration, off_grace: Duration) {
+        self.on_delay = on_delay;
+        self.off_grace = off_grace;
+    }
+
+    /// Number of currently open camera handles
+    pub fn open_count(&self) -> usize {
+        self.open_count
+    }
+
+    /// Whether a delay or grace period is currently running
+    pub fn is_pending(&self) -> bool {
+        matches!(
+            self.phase,
+            SessionPhase::PendingOn { .. } | SessionPhase::PendingOff { .. }
+        )
+    }
+
+    /// Record a camera being opened.
+    ///
+    /// Opening another camera while a session is already active asks for the
+    /// in-call state to be applied again, so devices that became available
+    /// since the session started are picked up.
+    pub fn camera_opened(&mut self, now: Instant) -> Option<SessionAction> {
+        self.open_count += 1;
+
+        match self.phase {
+            SessionPhase::Idle => self.start_pending_on(now),
+            SessionPhase::PendingOn { .. } => None,
+            SessionPhase::Active => Some(SessionAction::Activate),
+            SessionPhase::PendingOff { .. } => {
+                // Camera reopened within the grace period, keep lights on
+                self.phase = SessionPhase::Active;
+                None
+            }
+        }
+    }
+
+    /// Record a camera being closed.
+    ///
+    /// Close events without a matching open are ignored.
+    pub fn camera_closed(&mut self, now: Instant) -> Option<SessionAction> {
+        if self.open_count == 0 {
+            return None;
+        }
+
+        self.open_count -= 1;
+        if self.open_count > 0 {
+            return None;
+        }
+
+        self.all_cameras_closed(now)
+    }
+
+    /// Replace the open count with one observed directly on the system.
+    ///
+    /// Used to correct drift from missed events. Cameras found open while idle
+    /// belong to an established session rather than a probe, so the session is
+    /// activated without waiting for the turn-on delay.
+    pub fn set_open_count(&mut self, open_count: usize, now: Instant) -> Option<SessionAction> {
+        let previous = self.open_count;
+        self.open_count = open_count;
+
+        if open_count == 0 {
+            return if previous > 0 {
+                self.all_cameras_closed(now)
+            } else {
+                None
+            };
+        }
+
+        match self.phase {
+            SessionPhase::Idle | SessionPhase::PendingOn { .. } => {
+                self.phase = SessionPhase::Active;
+                Some(SessionAction::Activate)
+            }
+            SessionPhase::PendingOff { .. } => {
+                self.phase = SessionPhase::Active;
+                None
+            }
+            SessionPhase::Active => None,
+        }
+    }
+
+    /// Advance timers, returning an action once a delay or grace period expires
+    pub fn poll(&mut self, now: Instant) -> Option<SessionAction> {
+        match self.phase {
+            SessionPhase::PendingOn { since }
+                if now.saturating_duration_since(since) >= self.on_delay =>
+            {
+                self.phase = SessionPhase::Active;
+                Some(SessionAction::Activate)
+            }
+            SessionPhase::PendingOff { since }
+                if now.saturating_duration_since(since) >= self.off_grace =>
+            {
+                self.phase = SessionPhase::Idle;
+                Some(SessionAction::Deactivate)
+            }
+            _ => None,
+        }
+    }
+
+    /// Enter the turn-on delay, or activate at once without one
+    fn start_pending_on(&mut self, now: Instant) -> Option<SessionAction> {
+        if self.on_delay.is_zero() {
+            self.phase = SessionPhase::Active;
+            return Some(SessionAction::Activate);
+        }
+
+        self.phase = SessionPhase::PendingOn { since: now };
+        None
+    }
+
+    /// Handle the open count dropping to zero
+    fn all_cameras_closed(&mut self, now: Instant) -> Option<SessionAction> {
+        match self.phase {
+            SessionPhase::PendingOn { .. } => {
+                // Closed before the turn-on delay passed, treat as a probe
+                self.phase = SessionPhase::Idle;
+                None
+            }
+            SessionPhase::Active if self.off_grace.is_zero() => {
+                self.phase = SessionPhase::Idle;
+                Some(SessionAction::Deactivate)
+            }
+            SessionPhase::Active => {
+                self.phase = SessionPhase::PendingOff { since: now };
+                None
+            }
+            SessionPhase::Idle | SessionPhase::PendingOff { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Synthetic camera event for driving the state machine
+    #[derive(Debug, Clone, Copy)]
+    enum Event {
+        Open,
+        Close,
+        Reconcile(usize),
+    }
+
+    /// Feed a timed script of events through the state machine, polling every
+    /// 100 ms like the monitor loop, and collect the actions with the time in
+    /// milliseconds at which they were emitted.
+    fn run_script(
+        on_delay_ms: u64,
+        off_grace_ms: u64,
+        script: &[(u64, Event)],
+        until_ms: u64,
+    ) -> Vec<(u64, SessionAction)> {
+        let start = Instant::now();
+        let mut machine = SessionStateMachine::new(
+            Duration::from_millis(on_delay_ms),
+            Duration::from_millis(off_grace_ms),
+        );
+        let mut actions = Vec::new();
+        let mut script = script.iter().peekable();
+
+        for tick in 0..=until_ms / 100 {
+            let now_ms = tick * 100;
+            let now = start + Duration::from_millis(now_ms);
+
+            while let Some((_, event)) = script.next_if(|(at, _)| *at <= now_ms) {
+                let action = match event {
+                    Event::Open => machine.camera_opened(now),
+                    Event::Close => machine.camera_closed(now),
+                    Event::Reconcile(count) => machine.set_open_count(*count, now),
+                };
+                actions.extend(action.map(|action| (now_ms, action)));
+            }
+
+            actions.extend(machine.poll(now).map(|action| (now_ms, action)));
+        }
+
+        actions
+    }
+
+    #[test]
+    fn test_short_probe_is_ignored() {
+        let actions = run_script(1000, 3000, &[(0, Event::Open), (300, Event::Close)], 5000);
+
+        assert!(actions.is_empty());
+    }
+
+    #[test]
+    fn test_session_activates_after_delay_and_deactivates_after_grace() {
+        let actions = run_script(1000, 3000, &[(0, Event::Open), (5000, Event::Close)], 10000);
+
+        assert_eq!(
+            actions,
+            vec![
+                (1000, SessionAction::Activate),
+                (8000, SessionAction::Deactivate)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reopen_within_grace_keeps_lights_on() {
+        let actions = run_script(
+            1000,
+            3000,
+            &[
+                (0, Event::Open),
+                (5000, Event::Close),
+                (6000, Event::Open),
+                (9000, Event::Close),
+            ],
+            15000,
+        );
+
+        assert_eq!(
+            actions,
+            vec![
+                (1000, SessionAction::Activate),
+                (12000, SessionAction::Deactivate)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reopen_after_grace_starts_new_session() {
+        let actions = run_script(
+            1000,
+            2000,
+            &[
+                (0, Event::Open),
+                (2000, Event::Close),
+                (5000, Event::Open),
+                (7000, Event::Close),
+            ],
+            10000,
+        );
+
+        assert_eq!(
+            actions,
+            vec![
+                (1000, SessionAction::Activate),
+                (4000, SessionAction::Deactivate),
+                (6000, SessionAction::Activate),
+                (9000, SessionAction::Deactivate),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_overlapping_sessions_end_with_last_close() {
+        let actions = run_script(
+            500,
+            1000,
+            &[
+                (0, Event::Open),
+                (1000, Event::Open),
+                (2000, Event::Close),
+                (4000, Event::Close),
+            ],
+            6000,
+        );
+
+        assert_eq!(
+            actions,
+            vec![
+                (500, SessionAction::Activate),
+                (1000, SessionAction::Activate),
+                (5000, SessionAction::Deactivate),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_zero_delays_act_immediately() {
+        let actions = run_script(0, 0, &[(0, Event::Open), (1000, Event::Close)], 2000);
+
+        assert_eq!(
+            actions,
+            vec![
+                (0, SessionAction::Activate),
+                (1000, SessionAction::Deactivate)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unmatched_close_is_ignored() {
+        let actions = run_script(
+            0,
+            1000,
+            &[(0, Event::Close), (100, Event::Open), (500, Event::Close)],
+            3000,
+        );
+
+        assert_eq!(
+            actions,
+            vec![
+                (100, SessionAction::Activate),
+                (1500, SessionAction::Deactivate)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shortened_grace_applies_to_running_grace_period() {
+        let start = Instant::now();
+        let mut machine =
+            SessionStateMachine::new(Duration::from_millis(0), Duration::from_millis(10000));
+
+        assert_eq!(machine.camera_opened(start), Some(SessionAction::Activate));
+        assert_eq!(machine.camera_closed(start), None);
+
+        machine.set_delays(Duration::from_millis(0), Duration::from_millis(1000));
+        assert_eq!(
+            machine.poll(start + Duration::from_millis(1000)),
+            Some(SessionAction::Deactivate)
+        );
+    }
+
+    #[test]
+    fn test_reconcile_activates_existing_session_immediately() {
+        let actions = run_script(
+            1000,
+            1000,
+            &[(0, Event::Reconcile(1)), (3000, Event::Reconcile(0))],
+            5000,
+        );
+
+        assert_eq!(
+            actions,
+            vec![
+                (0, SessionAction::Activate),
+                (4000, SessionAction::Deactivate)
+            ]
+        );
+    }
+}
@@ -0,0 +1,414 @@
+//! Camera event sources
+//!
+//! The monitor consumes capture device open/close events through the
+//! [`CameraEventSource`] trait. Cameras are watched with inotify on `/dev`,
+//! microphones by polling ALSA's procfs status files, and tests replace both
+//! with a scripted source.
+
+use crate::camera_monitor::{alsa, v4l2, CameraMonitorResult};
+use async_trait::async_trait;
+use inotify::{EventMask, Inotify, WatchMask};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// A capture device open or close observed by an event source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CameraEvent {
+    /// A capture device was opened
+    Opened(String),
+    /// A capture device was closed
+    Closed(String),
+}
+
+/// Source of camera (or other capture device) activity events
+#[async_trait]
+pub trait CameraEventSource: Send {
+    /// Wait for camera events, returning an empty batch if none arrive
+    /// within `timeout`
+    async fn next_events(&mut self, timeout: Duration) -> CameraMonitorResult<Vec<CameraEvent>>;
+
+    /// Count the capture handles open right now, independent of events
+    fn count_open_sessions(&mut self) -> usize;
+
+    /// Names of the processes currently using a capture device, where known
+    fn camera_processes(&mut self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// Event source watching `/dev` with inotify for video capture node activity
+pub struct InotifyEventSource {
+    /// Inotify instance with a watch on `/dev`
+    inotify: Inotify,
+    /// Capture capability of every video node seen so far
+    capture_nodes: HashMap<String, bool>,
+    /// Buffer for reading inotify events
+    buffer: [u8; 1024],
+}
+
+impl InotifyEventSource {
+    /// Create a new inotify event source
+    pub fn new() -> CameraMonitorResult<Self> {
+        // Probe node capabilities before the watch exists, so opening the
+        // nodes for VIDIOC_QUERYCAP does not register as camera activity
+        let capture_nodes = v4l2::probe_capture_nodes(true);
+
+        let inotify = Inotify::init()?;
+        inotify.watches().add(
+            v4l2::DEV_PATH,
+            WatchMask::OPEN | WatchMask::CLOSE_WRITE | WatchMask::CLOSE_NOWRITE,
+        )?;
+
+        Ok(Self {
+            inotify,
+            capture_nodes,
+            buffer: [0; 1024],
+        })
+    }
+
+    /// Paths of all known video capture nodes
+    fn capture_node_paths(&self) -> Vec<PathBuf> {
+        self.capture_nodes
+            .iter()
+            .filter(|(_, is_capture)| **is_capture)
+            .map(|(name, _)| Path::new(v4l2::DEV_PATH).join(name))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl CameraEventSource for InotifyEventSource {
+    async fn next_events(&mut self, timeout: Duration) -> CameraMonitorResult<Vec<CameraEvent>> {
+        let mut camera_events = Vec::new();
+
+        // Read inotify events (non-blocking)
+        match self.inotify.read_events(&mut self.buffer) {
+            Ok(events) => {
+                // Filter for events on video capture nodes, skipping metadata
+                // and other non-capture nodes
+                for event in events {
+                    let Some(name) = event.name.and_then(|name| name.to_str()) else {
+                        continue;
+                    };
+
+                    if !v4l2::is_video_node_name(name)
+                        || !*self
+                            .capture_nodes
+                            .entry(name.to_string())
+                            .or_insert_with(|| v4l2::is_capture_node(name, false))
+                    {
+                        continue;
+                    }
+
+                    match event.mask {
+                        EventMask::OPEN => {
+                            camera_events.push(CameraEvent::Opened(name.to_string()))
+                        }
+                        EventMask::CLOSE_WRITE | EventMask::CLOSE_NOWRITE => {
+                            camera_events.push(CameraEvent::Closed(name.to_string()))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                // Small delay to prevent busy waiting
+                sleep(timeout).await;
+            }
+            Err(e) => {
+                eprintln!("Inotify error: {e}");
+                sleep(timeout).await;
+            }
+        }
+
+        Ok(camera_events)
+    }
+
+    fn count_open_sessions(&mut self) -> usize {
+        v4l2::count_open_handles(&self.capture_node_paths())
+    }
+
+    fn camera_processes(&mut self) -> Vec<String> {
+        v4l2::processes_using(&self.capture_node_paths())
+    }
+}
+
+/// Event source polling ALSA for capture substreams that are recording
+pub struct MicrophoneEventSource {
+    /// Root of the asound procfs tree
+    root: PathBuf,
+    /// Substreams recording at the last poll
+    active: BTreeSet<String>,
+}
+
+impl MicrophoneEventSource {
+    /// Create a microphone event source reading `/proc/asound`
+    pub fn new() -> Self {
+        Self::with_root(PathBuf::from(alsa::ASOUND_PATH))
+    }
+
+    /// Create a microphone event source reading the given asound tree
+    ///
+    /// Substreams already recording are taken as known, so they are counted
+    /// by [`CameraEventSource::count_open_sessions`] rather than reported as
+    /// opened on the first poll.
+    pub fn with_root(root: PathBuf) -> Self {
+        let active = active_stream_names(&root);
+        Self { root, active }
+    }
+}
+
+/// Names of the capture substreams recording under `root`
+fn active_stream_names(root: &Path) -> BTreeSet<String> {
+    alsa::active_capture_streams(root)
+        .into_iter()
+        .map(|stream| stream.name)
+        .collect()
+}
+
+#[async_trait]
+impl CameraEventSource for MicrophoneEventSource {
+    async fn next_events(&mut self, timeout: Duration) -> CameraMonitorResult<Vec<CameraEvent>> {
+        sleep(timeout).await;
+
+        let active = active_stream_names(&self.root);
+
+        let opened = active
+            .difference(&self.active)
+            .map(|name| CameraEvent::Opened(name.clone()));
+        let closed = self
+            .active
+            .difference(&active)
+            .map(|name| CameraEvent::Closed(name.clone()));
+        let events = opened.chain(closed).collect();
+
+        self.active = active;
+        Ok(events)
+    }
+
+    fn count_open_sessions(&mut self) -> usize {
+        // Later polls report changes relative to the count given here
+        self.active = active_stream_names(&self.root);
+        self.active.len()
+    }
+
+    fn camera_processes(&mut self) -> Vec<String> {
+        alsa::owner_process_names(&alsa::active_capture_streams(&self.root))
+    }
+}
+
+/// Event source merging the events of several sources
+pub struct CombinedEventSource {
+    /// Sources to merge
+    sources: Vec<Box<dyn CameraEventSource>>,
+}
+
+impl CombinedEventSource {
+    /// Create a source merging the given sources
+    pub fn new(sources: Vec<Box<dyn CameraEventSource>>) -> Self {
+        Self { sources }
+    }
+}
+
+#[async_trait]
+impl CameraEventSource for CombinedEventSource {
+    async fn next_events(&mut self, timeout: Duration) -> CameraMonitorResult<Vec<CameraEvent>> {
+        // Poll every source without waiting, then wait once if all were quiet
+        let mut events = Vec::new();
+        for source in &mut self.sources {
+            events.extend(source.next_events(Duration::ZERO).await?);
+        }
+
+        if events.is_empty() {
+            sleep(timeout).await;
+        }
+        Ok(events)
+    }
+
+    fn count_open_sessions(&mut self) -> usize {
+        self.sources
+            .iter_mut()
+            .map(|source| source.count_open_sessions())
+            .sum()
+    }
+
+    fn camera_processes(&mut self) -> Vec<String> {
+        let mut processes: Vec<String> = self
+            .sources
+            .iter_mut()
+            .flat_map(|source| source.camera_processes())
+            .collect();
+        processes.sort();
+        processes.dedup();
+        processes
+    }
+}
+
+/// A step in a [`ScriptedEventSource`] script
+#[cfg(test)]
+#[derive(Debug, Clone)]
+pub enum ScriptStep {
+    /// The event happens and is reported to the monitor
+    Event(CameraEvent),
+    /// The event happens but is never reported, as if inotify dropped it
+    Missed(CameraEvent),
+}
+
+/// Event source replaying a timed script, for tests.
+///
+/// Times are relative to the creation of the source and use Tokio's clock,
+/// so tests running with paused time are fully deterministic. The open count
+/// reported for reconciliation follows every scripted step, including missed
+/// ones.
+#[cfg(test)]
+pub struct ScriptedEventSource {
+    /// Time the script started
+    start: tokio::time::Instant,
+    /// Remaining steps with their offset from the start
+    script: std::collections::VecDeque<(Duration, ScriptStep)>,
+    /// Number of cameras actually open
+    open_count: usize,
+}
+
+#[cfg(test)]
+impl ScriptedEventSource {
+    /// Create a source replaying `script`, given as milliseconds from now
+    pub fn new(script: Vec<(u64, ScriptStep)>) -> Self {
+        Self {
+            start: tokio::time::Instant::now(),
+            script: script
+                .into_iter()
+                .map(|(at_ms, step)| (Duration::from_millis(at_ms), step))
+                .collect(),
+            open_count: 0,
+        }
+    }
+
+    /// Start with cameras already open before the monitor starts
+    pub fn with_open_count(mut self, open_count: usize) -> Self {
+        self.open_count = open_count;
+        self
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl CameraEventSource for ScriptedEventSource {
+    async fn next_events(&mut self, timeout: Duration) -> CameraMonitorResult<Vec<CameraEvent>> {
+        sleep(timeout).await;
+
+        let elapsed = self.start.elapsed();
+        let mut camera_events = Vec::new();
+        while let Some((_, step)) = self.script.front().filter(|(at, _)| *at <= elapsed) {
+            let (event, reported) = match step.clone() {
+                ScriptStep::Event(event) => (event, true),
+                ScriptStep::Missed(event) => (event, false),
+            };
+            self.script.pop_front();
+
+            match event {
+                CameraEvent::Opened(_) => self.open_count += 1,
+                CameraEvent::Closed(_) => self.open_count = self.open_count.saturating_sub(1),
+            }
+            if reported {
+                camera_events.push(event);
+            }
+        }
+
+        Ok(camera_events)
+    }
+
+    fn count_open_sessions(&mut self) -> usize {
+        self.open_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AutoToggleConfig;
+    use crate::test_support::{fake_asound, test_monitor};
+    use std::fs;
+
+    /// Capture substream of the fake asound trees
+    const SUBSTREAM: &str = "card0/pcm0c/sub0";
+
+    #[tokio::test]
+    async fn test_microphone_source_reports_stream_changes() {
+        let root = fake_asound("changes", &[(SUBSTREAM, "closed\n")]);
+        let substream = root.join(SUBSTREAM);
+
+        let mut source = MicrophoneEventSource::with_root(root.path().to_path_buf());
+        let idle = source.next_events(Duration::ZERO).await.unwrap();
+
+        fs::write(substream.join("status"), "state: RUNNING\n").unwrap();
+        let recording = source.next_events(Duration::ZERO).await.unwrap();
+        let open_count = source.count_open_sessions();
+
+        fs::write(substream.join("status"), "closed\n").unwrap();
+        let stopped = source.next_events(Duration::ZERO).await.unwrap();
+
+        let name = SUBSTREAM.to_string();
+        assert!(idle.is_empty());
+        assert_eq!(recording, vec![CameraEvent::Opened(name.clone())]);
+        assert_eq!(open_count, 1);
+        assert_eq!(stopped, vec![CameraEvent::Closed(name)]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_microphone_recording_at_start_ends_with_stream() {
+        let root = fake_asound("at-start", &[(SUBSTREAM, "state: RUNNING\n")]);
+        let substream = root.join(SUBSTREAM);
+
+        let mut monitor = test_monitor();
+        let config = AutoToggleConfig {
+            enabled: true,
+            on_delay_ms: 0,
+            off_grace_ms: 0,
+            ..AutoToggleConfig::default()
+        };
+        let source = MicrophoneEventSource::with_root(root.path().to_path_buf());
+        monitor
+            .start_monitoring_with_source(config, Box::new(source))
+            .unwrap();
+
+        sleep(Duration::from_secs(1)).await;
+        assert!(monitor.is_session_active());
+
+        fs::write(substream.join("status"), "closed\n").unwrap();
+        sleep(Duration::from_secs(1)).await;
+        assert!(!monitor.is_session_active());
+        assert!(monitor.get_history()[0].ended_at.is_some());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_combined_source_merges_sources() {
+        let camera = ScriptedEventSource::new(vec![(
+            0,
+            ScriptStep::Event(CameraEvent::Opened("video0".to_string())),
+        )]);
+        let microphone = ScriptedEventSource::new(vec![(
+            0,
+            ScriptStep::Event(CameraEvent::Opened("card0/pcm0c/sub0".to_string())),
+        )]);
+        let mut source = CombinedEventSource::new(vec![Box::new(camera), Box::new(microphone)]);
+
+        let events = source
+            .next_events(Duration::from_millis(100))
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(source.count_open_sessions(), 2);
+
+        // Quiet sources still wait for the timeout
+        let start = tokio::time::Instant::now();
+        assert!(source
+            .next_events(Duration::from_millis(100))
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(start.elapsed(), Duration::from_millis(100));
+    }
+}
@@ -1,16 +1,16 @@
 //! Strategy pattern implementation for device selection
 //!
 //! This module provides different strategies for selecting which devices
-//! should be controlled by the auto-toggle functionality.
+//! trigger bindings, auto-toggle among them, control.
 
-use crate::config::AutoToggleStrategy;
+use crate::config::DeviceSet;
 use crate::device::DeviceInfo;
 use async_trait::async_trait;
 
 /// Trait for device selection strategies
 #[async_trait]
 pub trait DeviceSelector: Send + Sync {
-    /// Determine if a device should be controlled by auto-toggle
+    /// Determine if a device should be controlled
     fn should_control_device(&self, device: &DeviceInfo) -> bool;
 }
 
@@ -32,31 +32,31 @@ impl DeviceSelector for AllDevicesStrategy {
     }
 }
 
-/// Strategy that controls only a specific device
+/// Strategy that controls a fixed list of devices
 #[derive(Debug, Clone)]
-pub struct SelectedDeviceStrategy {
-    pub serial_number: String,
+pub struct DeviceListStrategy {
+    pub serial_numbers: Vec<String>,
 }
 
-impl SelectedDeviceStrategy {
-    pub fn new(serial_number: String) -> Self {
-        Self { serial_number }
+impl DeviceListStrategy {
+    pub fn new(serial_numbers: Vec<String>) -> Self {
+        Self { serial_numbers }
     }
 }
 
 #[async_trait]
-impl DeviceSelector for SelectedDeviceStrategy {
+impl DeviceSelector for DeviceListStrategy {
     fn should_control_device(&self, device: &DeviceInfo) -> bool {
-        device.is_connected && device.serial_number == self.serial_number
+        device.is_connected && self.serial_numbers.contains(&device.serial_number)
     }
 }
 
-/// Factory function to create device selectors from configuration
-pub fn create_device_selector(strategy: &AutoToggleStrategy) -> Box<dyn DeviceSelector> {
-    match strategy {
-        AutoToggleStrategy::AllDevices => Box::new(AllDevicesStrategy::new()),
-        AutoToggleStrategy::SelectedDevice { serial_number } => {
-            Box::new(SelectedDeviceStrategy::new(serial_number.clone()))
+/// Create a device selector for a device set
+pub fn create_device_set_selector(devices: &DeviceSet) -> Box<dyn DeviceSelector> {
+    match devices {
+        DeviceSet::AllDevices => Box::new(AllDevicesStrategy::new()),
+        DeviceSet::Devices { serial_numbers } => {
+            Box::new(DeviceListStrategy::new(serial_numbers.clone()))
         }
     }
 }
@@ -64,6 +64,7 @@ pub fn create_device_selector(strategy: &AutoToggleStrategy) -> Box<dyn DeviceSe
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::AutoToggleStrategy;
 
     fn create_test_device(serial: &str, connected: bool) -> DeviceInfo {
         DeviceInfo {
@@ -78,6 +79,7 @@ mod tests {
             min_temperature_kelvin: 2700,
             max_temperature_kelvin: 6500,
             brightness_percentage: 0,
+            active_preset: None,
         }
     }
 
@@ -93,7 +95,12 @@ mod tests {
 
     #[test]
     fn test_selected_device_strategy() {
-        let strategy = SelectedDeviceStrategy::new("ABC123".to_string());
+        let strategy = create_device_set_selector(
+            &AutoToggleStrategy::SelectedDevice {
+                serial_number: "ABC123".to_string(),
+            }
+            .devices(),
+        );
         let target_device = create_test_device("ABC123", true);
         let other_device = create_test_device("DEF456", true);
         let disconnected_target = create_test_device("ABC123", false);
@@ -102,4 +109,15 @@ mod tests {
         assert!(!strategy.should_control_device(&other_device));
         assert!(!strategy.should_control_device(&disconnected_target));
     }
+
+    #[test]
+    fn test_device_list_strategy() {
+        let strategy = create_device_set_selector(&DeviceSet::Devices {
+            serial_numbers: vec!["ABC123".to_string(), "GHI789".to_string()],
+        });
+
+        assert!(strategy.should_control_device(&create_test_device("ABC123", true)));
+        assert!(!strategy.should_control_device(&create_test_device("DEF456", true)));
+        assert!(!strategy.should_control_device(&create_test_device("GHI789", false)));
+    }
 }
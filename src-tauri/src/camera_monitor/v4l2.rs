@@ -0,0 +1,321 @@
+//! V4L2 device node inspection
+//!
+//! Modern UVC cameras expose more than one `/dev/video*` node per physical
+//! camera (typically a video-capture node and a metadata node). This module
+//! works out which nodes actually carry video capture so the monitor counts a
+//! camera session once, and gathers the details shown by the `list_cameras`
+//! command.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+/// Directory holding the video device nodes
+pub const DEV_PATH: &str = "/dev";
+
+/// Prefix shared by all V4L2 video device node names
+pub const VIDEO_NODE_PREFIX: &str = "video";
+
+/// Sysfs class directory for V4L2 devices
+const SYSFS_VIDEO4LINUX_PATH: &str = "/sys/class/video4linux";
+
+/// Udev database directory
+const UDEV_DATA_PATH: &str = "/run/udev/data";
+
+/// Directory with persistent by-id symlinks to V4L2 nodes
+const V4L_BY_ID_PATH: &str = "/dev/v4l/by-id";
+
+/// Procfs root, used to find processes holding a node open
+const PROC_PATH: &str = "/proc";
+
+/// `VIDIOC_QUERYCAP`, i.e. `_IOR('V', 0, struct v4l2_capability)`
+const VIDIOC_QUERYCAP: u64 = 0x8068_5600;
+
+/// The node supports the single-planar video capture API
+const V4L2_CAP_VIDEO_CAPTURE: u32 = 0x0000_0001;
+
+/// The node supports the multi-planar video capture API
+const V4L2_CAP_VIDEO_CAPTURE_MPLANE: u32 = 0x0000_1000;
+
+/// `device_caps` is filled in and describes this particular node
+const V4L2_CAP_DEVICE_CAPS: u32 = 0x8000_0000;
+
+/// Mirror of the kernel's `struct v4l2_capability`
+#[repr(C)]
+#[derive(Default)]
+struct V4l2Capability {
+    driver: [u8; 16],
+    card: [u8; 32],
+    bus_info: [u8; 32],
+    version: u32,
+    capabilities: u32,
+    device_caps: u32,
+    reserved: [u32; 3],
+}
+
+/// Information about a single V4L2 video node
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CameraInfo {
+    /// Human-readable device name reported by the driver
+    pub name: String,
+    /// Device node path (e.g. `/dev/video0`)
+    pub node_path: String,
+    /// Persistent `/dev/v4l/by-id` symlink pointing at this node, if any
+    pub by_id_path: Option<String>,
+    /// Whether the node supports video capture
+    pub is_capture: bool,
+    /// Whether any process currently has the node open
+    pub in_use: bool,
+}
+
+/// Check whether a `/dev` entry name refers to a V4L2 video node
+pub fn is_video_node_name(name: &str) -> bool {
+    name.strip_prefix(VIDEO_NODE_PREFIX)
+        .is_some_and(|index| !index.is_empty() && index.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// List the names of all video nodes currently present in `/dev`
+pub fn list_video_nodes() -> Vec<String> {
+    let mut nodes: Vec<String> = fs::read_dir(DEV_PATH)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .filter(|name| is_video_node_name(name))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    nodes.sort_by_key(|name| {
+        name.trim_start_matches(VIDEO_NODE_PREFIX)
+            .parse::<u32>()
+            .unwrap_or(u32::MAX)
+    });
+    nodes
+}
+
+/// Determine whether a video node supports video capture.
+///
+/// The udev database is consulted first since it needs no access to the node
+/// itself. When `allow_open` is set the node is then queried directly with
+/// `VIDIOC_QUERYCAP`; callers running alongside an inotify watch on `/dev`
+/// should pass `false`, because opening the node would show up as camera
+/// activity. As a last resort the sysfs `index` attribute is used: UVC drivers
+/// register the capture node at index 0 and the metadata node at index 1.
+pub fn is_capture_node(node_name: &str, allow_open: bool) -> bool {
+    if let Some(is_capture) = udev_capture_capability(node_name) {
+        return is_capture;
+    }
+
+    if allow_open {
+        if let Ok(caps) = query_capabilities(&Path::new(DEV_PATH).join(node_name)) {
+            return caps & (V4L2_CAP_VIDEO_CAPTURE | V4L2_CAP_VIDEO_CAPTURE_MPLANE) != 0;
+        }
+    }
+
+    fs::read_to_string(sysfs_attribute(node_name, "index"))
+        .map(|index| index.trim() == "0")
+        .unwrap_or(true)
+}
+
+/// Build a capture capability map for every video node currently present
+pub fn probe_capture_nodes(allow_open: bool) -> HashMap<String, bool> {
+    list_video_nodes()
+        .into_iter()
+        .map(|name| {
+            let is_capture = is_capture_node(&name, allow_open);
+            (name, is_capture)
+        })
+        .collect()
+}
+
+/// Collect details for every video node currently present
+pub fn list_cameras(allow_open: bool) -> Vec<CameraInfo> {
+    let by_id_links = by_id_links();
+
+    list_video_nodes()
+        .into_iter()
+        .map(|name| {
+            let node_path = Path::new(DEV_PATH).join(&name);
+            let by_id_path = by_id_links
+                .iter()
+                .find(|(_, target)| *target == node_path)
+                .map(|(link, _)| link.to_string_lossy().to_string());
+
+            CameraInfo {
+                name: fs::read_to_string(sysfs_attribute(&name, "name"))
+                    .map(|n| n.trim().to_string())
+                    .unwrap_or_else(|_| name.clone()),
+                is_capture: is_capture_node(&name, allow_open),
+                in_use: is_node_in_use(&node_path),
+                node_path: node_path.to_string_lossy().to_string(),
+                by_id_path,
+            }
+        })
+        .collect()
+}
+
+/// Check whether any process other than this one has the node open
+pub fn is_node_in_use(node_path: &Path) -> bool {
+    count_open_handles(&[node_path.to_path_buf()]) > 0
+}
+
+/// Count the open file descriptors other processes hold on the given nodes.
+///
+/// This walks `/proc/*/fd`, so only processes visible to the current user are
+/// taken into account. Every descriptor counts separately, matching the way
+/// the monitor counts inotify open events.
+pub fn count_open_handles(node_paths: &[PathBuf]) -> usize {
+    open_handles_by_process(node_paths)
+        .iter()
+        .map(|(_, count)| count)
+        .sum()
+}
+
+/// Names of the other processes holding any of the given nodes open
+pub fn processes_using(node_paths: &[PathBuf]) -> Vec<String> {
+    let mut names: Vec<String> = open_handles_by_process(node_paths)
+        .into_iter()
+        .map(|(process_path, _)| {
+            fs::read_to_string(process_path.join("comm"))
+                .map(|comm| comm.trim().to_string())
+                .unwrap_or_else(|_| {
+                    process_path
+                        .file_name()
+                        .map(|pid| pid.to_string_lossy().to_string())
+                        .unwrap_or_default()
+                })
+        })
+        .collect();
+
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Find the other processes holding any of the given nodes open, with the
+/// number of descriptors each one holds
+fn open_handles_by_process(node_paths: &[PathBuf]) -> Vec<(PathBuf, usize)> {
+    if node_paths.is_empty() {
+        return Vec::new();
+    }
+
+    let own_pid = std::process::id().to_string();
+
+    let Ok(processes) = fs::read_dir(PROC_PATH) else {
+        return Vec::new();
+    };
+
+    processes
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            name.chars().all(|c| c.is_ascii_digit()) && name != own_pid
+        })
+        .map(|entry| {
+            let count = fs::read_dir(entry.path().join("fd"))
+                .map(|fds| {
+                    fds.filter_map(|fd| fd.ok())
+                        .filter_map(|fd| fs::read_link(fd.path()).ok())
+                        .filter(|target| node_paths.contains(target))
+                        .count()
+                })
+                .unwrap_or(0);
+            (entry.path(), count)
+        })
+        .filter(|(_, count)| *count > 0)
+        .collect()
+}
+
+/// Query the V4L2 capabilities of a node with `VIDIOC_QUERYCAP`.
+///
+/// Returns the per-node capabilities when the driver reports them, otherwise
+/// the capabilities of the whole physical device.
+pub fn query_capabilities(node_path: &Path) -> std::io::Result<u32> {
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(node_path)?;
+
+    let mut cap = V4l2Capability::default();
+    // SAFETY: `cap` matches the kernel's `struct v4l2_capability` layout and
+    // outlives the call; the descriptor is valid while `file` is alive.
+    let result = unsafe { libc::ioctl(file.as_raw_fd(), VIDIOC_QUERYCAP as _, &mut cap) };
+    if result < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    if cap.capabilities & V4L2_CAP_DEVICE_CAPS != 0 {
+        Ok(cap.device_caps)
+    } else {
+        Ok(cap.capabilities)
+    }
+}
+
+/// Look up the capture capability recorded by udev's `v4l_id` helper
+fn udev_capture_capability(node_name: &str) -> Option<bool> {
+    let dev_numbers = fs::read_to_string(sysfs_attribute(node_name, "dev")).ok()?;
+    let udev_data =
+        fs::read_to_string(Path::new(UDEV_DATA_PATH).join(format!("c{}", dev_numbers.trim())))
+            .ok()?;
+
+    parse_udev_capture_capability(&udev_data)
+}
+
+/// Parse `ID_V4L_CAPABILITIES` out of a udev database entry
+fn parse_udev_capture_capability(udev_data: &str) -> Option<bool> {
+    udev_data
+        .lines()
+        .find_map(|line| line.strip_prefix("E:ID_V4L_CAPABILITIES="))
+        .map(|caps| caps.split(':').any(|cap| cap == "capture"))
+}
+
+/// Path of a sysfs attribute for the given video node
+fn sysfs_attribute(node_name: &str, attribute: &str) -> PathBuf {
+    Path::new(SYSFS_VIDEO4LINUX_PATH)
+        .join(node_name)
+        .join(attribute)
+}
+
+/// Resolve every `/dev/v4l/by-id` symlink to the node it points at
+fn by_id_links() -> Vec<(PathBuf, PathBuf)> {
+    fs::read_dir(V4L_BY_ID_PATH)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    let link = entry.path();
+                    fs::canonicalize(&link).ok().map(|target| (link, target))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_video_node_names() {
+        assert!(is_video_node_name("video0"));
+        assert!(is_video_node_name("video12"));
+        assert!(!is_video_node_name("video"));
+        assert!(!is_video_node_name("video0-meta"));
+        assert!(!is_video_node_name("vhost-net"));
+    }
+
+    #[test]
+    fn test_parse_udev_capture_capability() {
+        let capture = "S:v4l/by-id/usb-Cam-video-index0\nE:ID_V4L_VERSION=2\nE:ID_V4L_CAPABILITIES=:capture:\n";
+        let metadata = "E:ID_V4L_VERSION=2\nE:ID_V4L_CAPABILITIES=:\n";
+
+        assert_eq!(parse_udev_capture_capability(capture), Some(true));
+        assert_eq!(parse_udev_capture_capability(metadata), Some(false));
+        assert_eq!(parse_udev_capture_capability("E:ID_SERIAL=x\n"), None);
+    }
+}
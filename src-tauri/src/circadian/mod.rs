@@ -0,0 +1,16 @@
+//! Circadian lighting
+//!
+//! Moves the color temperature of the lights along with the day, cooler
+//! around noon and warmer in the evening, from sunrise and sunset computed
+//! for the configured location.
+
+pub mod scheduler;
+pub mod sun;
+
+pub use scheduler::CircadianScheduler;
+
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Global state type for the circadian scheduler
+pub type CircadianSchedulerState = Arc<Mutex<CircadianScheduler>>;
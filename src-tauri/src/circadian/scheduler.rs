@@ -0,0 +1,411 @@
+//! Circadian scheduler
+//!
+//! Periodically sets the color temperature, and optionally the brightness, of
+//! lit devices to the value the schedule gives for the time of day. Manual
+//! adjustments pause the schedule until it is resumed, and camera sessions
+//! take precedence over it.
+
+use crate::camera_monitor::strategies::create_device_set_selector;
+use crate::camera_monitor::CameraMonitorState;
+use crate::circadian::sun::{sun_times, SunTimes, MINUTES_PER_DAY};
+use crate::config::{CircadianAnchor, CircadianConfig, DayEvent, LightingState};
+use crate::device::sink::DeviceSink;
+use crate::device::DeviceInfo;
+use crate::task::BackgroundTask;
+use chrono::{Local, NaiveDate, Timelike};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{watch, Notify};
+
+/// Interval between updates of the devices
+const UPDATE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Step color temperatures are snapped to, in Kelvin
+const TEMPERATURE_STEP: f64 = 100.0;
+
+/// Lighting the schedule gives for a time of day
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CircadianTarget {
+    /// Color temperature, in Kelvin
+    pub temperature_kelvin: u16,
+    /// Brightness as a percentage, if the schedule sets it at this time
+    pub brightness_percentage: Option<u8>,
+}
+
+/// State of the schedule, as reported to the frontend
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CircadianStatus {
+    /// Whether the schedule is enabled
+    pub enabled: bool,
+    /// Whether the schedule is paused after a manual adjustment
+    pub paused: bool,
+    /// Today's sunrise, in minutes after local midnight
+    pub sunrise_minutes: u32,
+    /// Today's solar noon, in minutes after local midnight
+    pub solar_noon_minutes: u32,
+    /// Today's sunset, in minutes after local midnight
+    pub sunset_minutes: u32,
+    /// Lighting the schedule gives for now
+    pub target: Option<CircadianTarget>,
+}
+
+/// Schedule state shared with the scheduler task
+#[derive(Default)]
+struct ScheduleState {
+    /// Schedule configuration
+    config: CircadianConfig,
+    /// Whether the schedule is paused after a manual adjustment
+    paused: bool,
+}
+
+/// Scheduler moving the lighting along the circadian schedule
+pub struct CircadianScheduler {
+    /// Sink used to read and change devices
+    sink: Arc<dyn DeviceSink>,
+    /// Camera monitor whose sessions take precedence over the schedule
+    camera_monitor: CameraMonitorState,
+    /// Schedule state shared with the scheduler task
+    state: Arc<Mutex<ScheduleState>>,
+    /// Wakes the scheduler task when the schedule changes
+    changed: Arc<Notify>,
+    /// Scheduler task, while running
+    task: Option<BackgroundTask>,
+}
+
+impl CircadianScheduler {
+    /// Create a new, stopped scheduler
+    pub fn new(sink: Arc<dyn DeviceSink>, camera_monitor: CameraMonitorState) -> Self {
+        Self {
+            sink,
+            camera_monitor,
+            state: Arc::new(Mutex::new(ScheduleState::default())),
+            changed: Arc::new(Notify::new()),
+            task: None,
+        }
+    }
+
+    /// Start following the given schedule
+    pub fn start(&mut self, config: CircadianConfig) {
+        if self.is_running() {
+            return;
+        }
+        self.set_config(config);
+
+        let task = SchedulerTask {
+            sink: self.sink.clone(),
+            camera_monitor: self.camera_monitor.clone(),
+            state: self.state.clone(),
+            changed: self.changed.clone(),
+        };
+        self.task = Some(BackgroundTask::spawn(|stop| task.run(stop)));
+    }
+
+    /// Stop following the schedule
+    pub async fn stop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.stop().await;
+        }
+    }
+
+    /// Check if the scheduler is running
+    pub fn is_running(&self) -> bool {
+        self.task.as_ref().is_some_and(BackgroundTask::is_running)
+    }
+
+    /// Replace the schedule configuration.
+    ///
+    /// Enabling the schedule also resumes it.
+    pub fn set_config(&self, config: CircadianConfig) {
+        let mut state = self.state.lock().unwrap();
+        if config.enabled && !state.config.enabled {
+            state.paused = false;
+        }
+        state.config = config;
+        drop(state);
+        self.changed.notify_one();
+    }
+
+    /// Pause the schedule, e.g. because the user adjusted a light by hand
+    pub fn pause(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.config.enabled && !state.paused {
+            println!("Circadian schedule paused");
+            state.paused = true;
+        }
+    }
+
+    /// Resume a paused schedule, updating the devices straight away
+    pub fn resume(&self) {
+        self.state.lock().unwrap().paused = false;
+        self.changed.notify_one();
+    }
+
+    /// Get the state of the schedule for now
+    pub fn status(&self) -> CircadianStatus {
+        let state = self.state.lock().unwrap();
+        let (date, minute, utc_offset_minutes) = local_now();
+        let config = &state.config;
+        let sun = sun_times(date, config.latitude, config.longitude, utc_offset_minutes);
+        let minute_of_day = |minutes: f64| minutes.rem_euclid(MINUTES_PER_DAY).round() as u32;
+
+        CircadianStatus {
+            enabled: config.enabled,
+            paused: state.paused,
+            sunrise_minutes: minute_of_day(sun.sunrise),
+            solar_noon_minutes: minute_of_day(sun.solar_noon),
+            sunset_minutes: minute_of_day(sun.sunset),
+            target: target_at(&config.anchors, &sun, minute),
+        }
+    }
+}
+
+/// State owned by the scheduler task
+struct SchedulerTask {
+    /// Sink used to read and change devices
+    sink: Arc<dyn DeviceSink>,
+    /// Camera monitor whose sessions take precedence over the schedule
+    camera_monitor: CameraMonitorState,
+    /// Schedule state shared with the scheduler
+    state: Arc<Mutex<ScheduleState>>,
+    /// Notified when the schedule changes
+    changed: Arc<Notify>,
+}
+
+impl SchedulerTask {
+    /// Update the devices periodically until stopped
+    async fn run(self, mut stop: watch::Receiver<bool>) {
+        loop {
+            self.update().await;
+
+            tokio::select! {
+                _ = tokio::time::sleep(UPDATE_INTERVAL) => {}
+                _ = self.changed.notified() => {}
+                _ = stop.changed() => break,
+            }
+        }
+    }
+
+    /// Bring the lit devices to the lighting the schedule gives for now
+    async fn update(&self) {
+        let config = {
+            let state = self.state.lock().unwrap();
+            if !state.config.enabled || state.paused {
+                return;
+            }
+            state.config.clone()
+        };
+        if self.camera_monitor.lock().await.is_session_active() {
+            return;
+        }
+
+        let (date, minute, utc_offset_minutes) = local_now();
+        let sun = sun_times(date, config.latitude, config.longitude, utc_offset_minutes);
+        let Some(target) = target_at(&config.anchors, &sun, minute) else {
+            return;
+        };
+
+        let devices = match self.sink.get_all_devices().await {
+            Ok(devices) => devices,
+            Err(e) => {
+                eprintln!("Failed to get devices for circadian schedule: {e}");
+                return;
+            }
+        };
+        let selector = create_device_set_selector(&config.devices);
+
+        for device in devices {
+            if !device.is_on || !selector.should_control_device(&device) {
+                continue;
+            }
+            let Some(change) = change_for(&device, &target) else {
+                continue;
+            };
+            if let Err(e) = self
+                .sink
+                .apply_lighting_state(&device.serial_number, &change)
+                .await
+            {
+                eprintln!(
+                    "Failed to apply circadian schedule to {}: {e}",
+                    device.serial_number
+                );
+            }
+        }
+    }
+}
+
+/// Change bringing a device to the target, or `None` if it is already there
+fn change_for(device: &DeviceInfo, target: &CircadianTarget) -> Option<LightingState> {
+    let kelvin = device.clamp_temperature(target.temperature_kelvin);
+    let temperature_kelvin = (kelvin != device.temperature_kelvin).then_some(kelvin);
+    let brightness_percentage = target.brightness_percentage.filter(|percentage| {
+        device.calculate_lumens_from_percentage(*percentage) != device.brightness_lumens
+    });
+
+    (temperature_kelvin.is_some() || brightness_percentage.is_some()).then(|| LightingState {
+        temperature_kelvin,
+        brightness_percentage,
+        ..LightingState::default()
+    })
+}
+
+/// Today's local date, the minute of the day and the offset from UTC in
+/// minutes
+fn local_now() -> (NaiveDate, f64, i32) {
+    let now = Local::now();
+    let minute = f64::from(now.num_seconds_from_midnight()) / 60.0;
+    (
+        now.date_naive(),
+        minute,
+        now.offset().local_minus_utc() / 60,
+    )
+}
+
+/// Lighting at a minute of the day, interpolated between the anchors either
+/// side of it. Returns `None` without anchors.
+pub fn target_at(
+    anchors: &[CircadianAnchor],
+    sun: &SunTimes,
+    minute: f64,
+) -> Option<CircadianTarget> {
+    let mut points: Vec<(f64, &CircadianAnchor)> = anchors
+        .iter()
+        .map(|anchor| {
+            let event = match anchor.event {
+                DayEvent::Sunrise => sun.sunrise,
+                DayEvent::SolarNoon => sun.solar_noon,
+                DayEvent::Sunset => sun.sunset,
+            };
+            let time = (event + f64::from(anchor.offset_minutes)).rem_euclid(MINUTES_PER_DAY);
+            (time, anchor)
+        })
+        .collect();
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    // The schedule wraps around midnight
+    let (first, last) = (*points.first()?, *points.last()?);
+    let (start, from) = points
+        .iter()
+        .rev()
+        .find(|(time, _)| *time <= minute)
+        .copied()
+        .unwrap_or((last.0 - MINUTES_PER_DAY, last.1));
+    let (end, to) = points
+        .iter()
+        .find(|(time, _)| *time > minute)
+        .copied()
+        .unwrap_or((first.0 + MINUTES_PER_DAY, first.1));
+
+    let progress = if end > start {
+        (minute - start) / (end - start)
+    } else {
+        0.0
+    };
+    let lerp = |from: f64, to: f64| from + (to - from) * progress;
+
+    let kelvin = lerp(
+        f64::from(from.temperature_kelvin),
+        f64::from(to.temperature_kelvin),
+    );
+    Some(CircadianTarget {
+        temperature_kelvin: ((kelvin / TEMPERATURE_STEP).round() * TEMPERATURE_STEP) as u16,
+        brightness_percentage: from
+            .brightness_percentage
+            .zip(to.brightness_percentage)
+            .map(|(from, to)| lerp(f64::from(from), f64::from(to)).round() as u8),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{key_light_sink, test_monitor, KEY_LIGHT, LIT_STATE};
+
+    const SUN: SunTimes = SunTimes {
+        sunrise: 360.0,
+        solar_noon: 780.0,
+        sunset: 1200.0,
+    };
+
+    fn anchor(
+        event: DayEvent,
+        offset_minutes: i32,
+        kelvin: u16,
+        brightness: Option<u8>,
+    ) -> CircadianAnchor {
+        CircadianAnchor {
+            event,
+            offset_minutes,
+            temperature_kelvin: kelvin,
+            brightness_percentage: brightness,
+        }
+    }
+
+    fn temperature_at(anchors: &[CircadianAnchor], minute: f64) -> u16 {
+        target_at(anchors, &SUN, minute).unwrap().temperature_kelvin
+    }
+
+    #[test]
+    fn test_target_interpolates_between_anchors() {
+        let anchors = vec![
+            anchor(DayEvent::Sunset, 60, 2700, Some(20)),
+            anchor(DayEvent::Sunrise, 0, 3000, None),
+            anchor(DayEvent::SolarNoon, 0, 6500, Some(80)),
+        ];
+
+        assert_eq!(temperature_at(&anchors, 360.0), 3000);
+        assert_eq!(temperature_at(&anchors, 780.0), 6500);
+        // Halfway from sunrise to noon, snapped to the 100 K step
+        assert_eq!(temperature_at(&anchors, 570.0), 4800);
+        // Across midnight, from an hour after sunset to sunrise
+        assert_eq!(temperature_at(&anchors, 0.0), 2800);
+        assert_eq!(temperature_at(&anchors, 1300.0), 2700);
+
+        // Brightness only moves between anchors that both set it
+        let evening = target_at(&anchors, &SUN, 1020.0).unwrap();
+        assert_eq!(evening.brightness_percentage, Some(50));
+        let morning = target_at(&anchors, &SUN, 570.0).unwrap();
+        assert_eq!(morning.brightness_percentage, None);
+
+        assert_eq!(target_at(&[], &SUN, 0.0), None);
+        let single = [anchor(DayEvent::SolarNoon, 0, 4000, None)];
+        assert_eq!(temperature_at(&single, 0.0), 4000);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_schedule_pauses_and_resumes() {
+        let sink = key_light_sink(&[]);
+        let monitor = test_monitor();
+
+        // The same temperature all day, so the test does not depend on the clock
+        let config = CircadianConfig {
+            enabled: true,
+            anchors: vec![
+                anchor(DayEvent::Sunrise, 0, 3000, None),
+                anchor(DayEvent::Sunset, 0, 3000, None),
+            ],
+            ..CircadianConfig::default()
+        };
+        let mut scheduler =
+            CircadianScheduler::new(sink.clone(), Arc::new(tokio::sync::Mutex::new(monitor)));
+        scheduler.start(config);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(sink.device_state(KEY_LIGHT).temperature_kelvin, 3000);
+
+        scheduler.pause();
+        sink.set_device_state(KEY_LIGHT, LIT_STATE);
+        tokio::time::sleep(UPDATE_INTERVAL * 2).await;
+        assert_eq!(sink.device_state(KEY_LIGHT).temperature_kelvin, 5000);
+        assert!(scheduler.status().paused);
+
+        scheduler.resume();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(sink.device_state(KEY_LIGHT).temperature_kelvin, 3000);
+
+        scheduler.stop().await;
+    }
+}
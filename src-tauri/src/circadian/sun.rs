@@ -0,0 +1,106 @@
+//! Sunrise and sunset
+//!
+//! Computes the times of sunrise, solar noon and sunset offline with the
+//! NOAA solar position equations, which are accurate to a minute or two away
+//! from the poles.
+
+use chrono::{Datelike, NaiveDate};
+use std::f64::consts::PI;
+
+/// Zenith of the sun at sunrise and sunset in degrees, allowing for
+/// atmospheric refraction and the size of the sun's disc
+const SUNRISE_ZENITH: f64 = 90.833;
+
+/// Minutes in a day
+pub const MINUTES_PER_DAY: f64 = 1440.0;
+
+/// Times of the solar day, in minutes after local midnight
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SunTimes {
+    /// Sunrise
+    pub sunrise: f64,
+    /// Sun at its highest
+    pub solar_noon: f64,
+    /// Sunset
+    pub sunset: f64,
+}
+
+/// Compute the solar day at a place on a date.
+///
+/// `utc_offset_minutes` is the offset of local time from UTC. During polar
+/// night sunrise and sunset meet at solar noon, and during polar day they
+/// are twelve hours either side of it.
+pub fn sun_times(
+    date: NaiveDate,
+    latitude: f64,
+    longitude: f64,
+    utc_offset_minutes: i32,
+) -> SunTimes {
+    let days_in_year = if date.leap_year() { 366.0 } else { 365.0 };
+    // Fractional year at noon, in radians
+    let gamma = 2.0 * PI / days_in_year * f64::from(date.ordinal0());
+
+    let equation_of_time = 229.18
+        * (0.000075 + 0.001868 * gamma.cos()
+            - 0.032077 * gamma.sin()
+            - 0.014615 * (2.0 * gamma).cos()
+            - 0.040849 * (2.0 * gamma).sin());
+    let declination = 0.006918 - 0.399912 * gamma.cos() + 0.070257 * gamma.sin()
+        - 0.006758 * (2.0 * gamma).cos()
+        + 0.000907 * (2.0 * gamma).sin()
+        - 0.002697 * (3.0 * gamma).cos()
+        + 0.00148 * (3.0 * gamma).sin();
+
+    let latitude = latitude.to_radians();
+    let cos_hour_angle = SUNRISE_ZENITH.to_radians().cos() / (latitude.cos() * declination.cos())
+        - latitude.tan() * declination.tan();
+    let hour_angle = cos_hour_angle.clamp(-1.0, 1.0).acos().to_degrees();
+
+    let solar_noon = 720.0 - 4.0 * longitude - equation_of_time + f64::from(utc_offset_minutes);
+    SunTimes {
+        sunrise: solar_noon - 4.0 * hour_angle,
+        solar_noon,
+        sunset: solar_noon + 4.0 * hour_angle,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn assert_near(actual: f64, hours: u32, minutes: u32) {
+        let expected = f64::from(hours * 60 + minutes);
+        assert!(
+            (actual - expected).abs() <= 3.0,
+            "Expected {hours:02}:{minutes:02}, got {actual:.1} minutes"
+        );
+    }
+
+    #[test]
+    fn test_sun_times() {
+        // London at midsummer, in British Summer Time
+        let london = sun_times(date(2024, 6, 21), 51.5074, -0.1278, 60);
+        assert_near(london.sunrise, 4, 43);
+        assert_near(london.solar_noon, 13, 2);
+        assert_near(london.sunset, 21, 21);
+
+        // Sydney at midwinter, in Australian Eastern Standard Time
+        let sydney = sun_times(date(2024, 6, 21), -33.8688, 151.2093, 600);
+        assert_near(sydney.sunrise, 7, 0);
+        assert_near(sydney.sunset, 16, 54);
+    }
+
+    #[test]
+    fn test_polar_day_and_night() {
+        let day = sun_times(date(2024, 6, 21), 78.22, 15.65, 120);
+        assert_eq!(day.sunset - day.sunrise, MINUTES_PER_DAY);
+
+        let night = sun_times(date(2024, 12, 21), 78.22, 15.65, 60);
+        assert_eq!(night.sunrise, night.solar_noon);
+        assert_eq!(night.sunset, night.solar_noon);
+    }
+}
@@ -4,6 +4,7 @@
 //! brightness control in lumens and percentage, range validation, and increment/decrement
 //! operations with proper device-specific limits.
 use crate::error::AppError;
+use crate::undo::ChangeSource;
 use crate::AppState;
 use tauri::State;
 
@@ -27,6 +28,7 @@ pub async fn set_device_brightness(
     serial_number: String,
     lumens: u16,
 ) -> Result<(), AppError> {
+    let before = state.device_states(Some(&serial_number)).await;
     let manager = state.device_manager.lock().await;
 
     let handle = manager.get_device_handle(&serial_number)?;
@@ -51,6 +53,13 @@ pub async fn set_device_brightness(
             error_type: "BrightnessControlError".to_string(),
         })?;
 
+    drop(manager);
+    state
+        .record_change(ChangeSource::Ui, "Brightness", before)
+        .await;
+    state.notify_manual_change(&serial_number).await;
+    state.pause_circadian_schedule().await;
+
     Ok(())
 }
 
@@ -61,6 +70,7 @@ pub async fn set_device_brightness_percentage(
     serial_number: String,
     percentage: u8,
 ) -> Result<(), AppError> {
+    let before = state.device_states(Some(&serial_number)).await;
     let manager = state.device_manager.lock().await;
 
     let handle = manager.get_device_handle(&serial_number)?;
@@ -86,6 +96,13 @@ pub async fn set_device_brightness_percentage(
             error_type: "BrightnessControlError".to_string(),
         })?;
 
+    drop(manager);
+    state
+        .record_change(ChangeSource::Ui, "Brightness", before)
+        .await;
+    state.notify_manual_change(&serial_number).await;
+    state.pause_circadian_schedule().await;
+
     Ok(())
 }
 
@@ -132,6 +149,7 @@ pub async fn set_brightness_in_lumen(
     serial_number: String,
     lumens: u16,
 ) -> Result<(), AppError> {
+    let before = state.device_states(Some(&serial_number)).await;
     let manager = state.device_manager.lock().await;
 
     let handle = manager.get_device_handle(&serial_number)?;
@@ -143,5 +161,12 @@ pub async fn set_brightness_in_lumen(
             error_type: "BrightnessControlError".to_string(),
         })?;
 
+    drop(manager);
+    state
+        .record_change(ChangeSource::Ui, "Brightness", before)
+        .await;
+    state.notify_manual_change(&serial_number).await;
+    state.pause_circadian_schedule().await;
+
     Ok(())
 }
@@ -0,0 +1,61 @@
+//! Calendar related Tauri commands.
+//!
+//! This module contains the commands for configuring the lights switched on
+//! ahead of meetings from a local iCalendar file and for listing the
+//! meetings found.
+use crate::calendar::CalendarStatus;
+use crate::commands::camera_commands::validate_lighting_state;
+use crate::config::CalendarConfig;
+use crate::error::AppError;
+use crate::AppState;
+use tauri::State;
+
+/// Longest lead time before a meeting, in minutes
+const MAX_LEAD_MINUTES: u32 = 120;
+
+/// Get the calendar configuration.
+#[tauri::command]
+pub async fn get_calendar_config(state: State<'_, AppState>) -> Result<CalendarConfig, AppError> {
+    Ok(state.config_manager.get_config().calendar)
+}
+
+/// Update the calendar configuration.
+///
+/// The configuration is saved and takes effect immediately.
+#[tauri::command]
+pub async fn update_calendar_config(
+    state: State<'_, AppState>,
+    config: CalendarConfig,
+) -> Result<(), AppError> {
+    if config.enabled && !config.path.exists() {
+        return Err(AppError {
+            message: format!("Calendar {} does not exist", config.path.display()),
+            error_type: "ValidationError".to_string(),
+        });
+    }
+    if config.lead_minutes > MAX_LEAD_MINUTES {
+        return Err(AppError {
+            message: format!("Lead time must be at most {MAX_LEAD_MINUTES} minutes"),
+            error_type: "ValidationError".to_string(),
+        });
+    }
+    validate_lighting_state(&config.action, "Meeting")?;
+
+    state
+        .config_manager
+        .update_calendar_config(config.clone())
+        .map_err(|e| AppError {
+            message: format!("Failed to update config: {e}"),
+            error_type: "ConfigError".to_string(),
+        })?;
+
+    state.calendar_watcher.lock().await.set_config(config);
+
+    Ok(())
+}
+
+/// Get the meetings found in the calendar and the one lights are on for.
+#[tauri::command]
+pub async fn get_calendar_status(state: State<'_, AppState>) -> Result<CalendarStatus, AppError> {
+    Ok(state.calendar_watcher.lock().await.status())
+}
@@ -2,7 +2,9 @@
 //!
 //! This module contains all the commands related to camera monitoring,
 //! auto-toggle configuration, and device state management.
-use crate::config::AutoToggleConfig;
+use crate::camera_monitor::history::CameraSession;
+use crate::camera_monitor::v4l2::{self, CameraInfo};
+use crate::config::{AutoToggleConfig, LightingState, AUTO_TOGGLE_BINDING_NAME};
 use crate::error::AppError;
 use crate::AppState;
 use tauri::State;
@@ -63,14 +65,45 @@ pub async fn get_camera_device_count(state: State<'_, AppState>) -> Result<usize
 /// Get the list of devices currently controlled by auto-toggle.
 #[tauri::command]
 pub async fn get_controlled_devices(state: State<'_, AppState>) -> Result<Vec<String>, AppError> {
+    let engine = state.trigger_engine.lock().await;
+
+    Ok(engine.bound_devices(AUTO_TOGGLE_BINDING_NAME).await)
+}
+
+/// Get the recorded camera sessions, most recent first.
+///
+/// A session still in progress is included with no end time.
+#[tauri::command]
+pub async fn get_camera_history(
+    state: State<'_, AppState>,
+) -> Result<Vec<CameraSession>, AppError> {
     let monitor = state.camera_monitor.lock().await;
 
-    Ok(monitor.get_controlled_devices())
+    Ok(monitor.get_history())
+}
+
+/// List the V4L2 video nodes present on the system.
+///
+/// Each entry reports the node's name, paths, whether it supports video
+/// capture and whether it is currently in use. Nodes are only opened for a
+/// capability query while monitoring is inactive, so listing cameras never
+/// registers as camera activity.
+#[tauri::command]
+pub async fn list_cameras(state: State<'_, AppState>) -> Result<Vec<CameraInfo>, AppError> {
+    let allow_open = !state.camera_monitor.lock().await.is_monitoring();
+
+    Ok(v4l2::list_cameras(allow_open))
 }
 
 /// Debug command to check system state
 #[tauri::command]
 pub async fn debug_camera_system(state: State<'_, AppState>) -> Result<String, AppError> {
+    let controlled_devices = state
+        .trigger_engine
+        .lock()
+        .await
+        .bound_devices(AUTO_TOGGLE_BINDING_NAME)
+        .await;
     let monitor = state.camera_monitor.lock().await;
 
     let debug_info = format!(
@@ -82,7 +115,7 @@ pub async fn debug_camera_system(state: State<'_, AppState>) -> Result<String, A
         - Video devices found: {:?}",
         monitor.is_monitoring(),
         monitor.get_device_count(),
-        monitor.get_controlled_devices(),
+        controlled_devices,
         std::path::Path::new("/dev").exists(),
         std::fs::read_dir("/dev")
             .map(|entries| entries
@@ -106,22 +139,66 @@ pub async fn update_camera_config(
     println!("Received config update: {config:?}");
 
     // Basic validation
-    if config.debounce_ms < 100 || config.debounce_ms > 30000 {
+    if config.on_delay_ms > 10000 {
+        return Err(AppError {
+            message: "Turn-on delay must be between 0ms and 10000ms".to_string(),
+            error_type: "ValidationError".to_string(),
+        });
+    }
+
+    if config.off_grace_ms > 30000 {
         return Err(AppError {
-            message: "Debounce time must be between 100ms and 30000ms".to_string(),
+            message: "Turn-off grace period must be between 0ms and 30000ms".to_string(),
             error_type: "ValidationError".to_string(),
         });
     }
 
+    validate_lighting_state(&config.in_call_state, "In-call")?;
+
     state
         .config_manager
-        .update_auto_toggle_config(config)
+        .update_auto_toggle_config(config.clone())
         .map_err(|e| AppError {
             message: format!("Failed to update config: {e}"),
             error_type: "ConfigError".to_string(),
         })?;
 
     println!("Config saved successfully");
+
+    // Apply the change to the monitor without a restart
+    state
+        .apply_auto_toggle_config(config)
+        .await
+        .map_err(|e| AppError {
+            message: format!("Failed to apply camera config: {e}"),
+            error_type: "CameraMonitorError".to_string(),
+        })
+}
+
+/// Validate a lighting state, naming it by `label` in error messages
+pub(crate) fn validate_lighting_state(state: &LightingState, label: &str) -> Result<(), AppError> {
+    if state
+        .brightness_percentage
+        .is_some_and(|percentage| percentage > 100)
+    {
+        return Err(AppError {
+            message: format!("{label} brightness must be between 0% and 100%"),
+            error_type: "ValidationError".to_string(),
+        });
+    }
+
+    if state
+        .temperature_kelvin
+        .is_some_and(|kelvin| !(2700..=6500).contains(&kelvin) || kelvin % 100 != 0)
+    {
+        return Err(AppError {
+            message: format!(
+                "{label} temperature must be a multiple of 100 K between 2700 K and 6500 K"
+            ),
+            error_type: "ValidationError".to_string(),
+        });
+    }
+
     Ok(())
 }
 
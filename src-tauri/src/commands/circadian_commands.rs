@@ -0,0 +1,74 @@
+//! Circadian schedule related Tauri commands.
+//!
+//! This module contains the commands for configuring the color temperature
+//! schedule that follows the sun, inspecting it and resuming it after a
+//! manual adjustment paused it.
+use crate::circadian::scheduler::CircadianStatus;
+use crate::commands::camera_commands::validate_lighting_state;
+use crate::config::{CircadianConfig, LightingState};
+use crate::error::AppError;
+use crate::AppState;
+use tauri::State;
+
+/// Get the circadian schedule configuration.
+#[tauri::command]
+pub async fn get_circadian_config(state: State<'_, AppState>) -> Result<CircadianConfig, AppError> {
+    Ok(state.config_manager.get_config().circadian)
+}
+
+/// Update the circadian schedule configuration.
+///
+/// The configuration is saved and takes effect immediately.
+#[tauri::command]
+pub async fn update_circadian_config(
+    state: State<'_, AppState>,
+    config: CircadianConfig,
+) -> Result<(), AppError> {
+    if !(-90.0..=90.0).contains(&config.latitude) || !(-180.0..=180.0).contains(&config.longitude) {
+        return Err(AppError {
+            message: "Location must be a latitude between -90 and 90 and a longitude between -180 and 180".to_string(),
+            error_type: "ValidationError".to_string(),
+        });
+    }
+
+    if config.enabled && config.anchors.is_empty() {
+        return Err(AppError {
+            message: "Circadian schedule needs at least one anchor point".to_string(),
+            error_type: "ValidationError".to_string(),
+        });
+    }
+
+    for (index, anchor) in config.anchors.iter().enumerate() {
+        let lighting = LightingState {
+            temperature_kelvin: Some(anchor.temperature_kelvin),
+            brightness_percentage: anchor.brightness_percentage,
+            ..LightingState::default()
+        };
+        validate_lighting_state(&lighting, &format!("Anchor {}", index + 1))?;
+    }
+
+    state
+        .config_manager
+        .update_circadian_config(config.clone())
+        .map_err(|e| AppError {
+            message: format!("Failed to update config: {e}"),
+            error_type: "ConfigError".to_string(),
+        })?;
+
+    state.circadian_scheduler.lock().await.set_config(config);
+
+    Ok(())
+}
+
+/// Get today's sun times and the lighting the schedule gives for now.
+#[tauri::command]
+pub async fn get_circadian_status(state: State<'_, AppState>) -> Result<CircadianStatus, AppError> {
+    Ok(state.circadian_scheduler.lock().await.status())
+}
+
+/// Resume the circadian schedule after a manual adjustment paused it.
+#[tauri::command]
+pub async fn resume_circadian_schedule(state: State<'_, AppState>) -> Result<(), AppError> {
+    state.circadian_scheduler.lock().await.resume();
+    Ok(())
+}
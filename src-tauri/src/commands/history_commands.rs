@@ -0,0 +1,109 @@
+//! Device history related Tauri commands.
+//!
+//! This module contains the commands for configuring the recording of device
+//! state over time and for looking up and exporting the recorded samples.
+use crate::config::HistoryConfig;
+use crate::device_history::store::HistorySample;
+use crate::device_history::to_csv;
+use crate::error::AppError;
+use crate::AppState;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tauri::State;
+
+/// Longest time recorded samples can be kept, in days
+const MAX_RETENTION_DAYS: u32 = 365;
+
+/// Recorded state of a device over a time range
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceHistory {
+    /// Serial number of the device
+    pub serial_number: String,
+    /// Samples, oldest first, each holding from its time until the next
+    pub samples: Vec<HistorySample>,
+}
+
+/// Get the history configuration.
+#[tauri::command]
+pub async fn get_history_config(state: State<'_, AppState>) -> Result<HistoryConfig, AppError> {
+    Ok(state.config_manager.get_config().history)
+}
+
+/// Update the history configuration.
+///
+/// The configuration is saved and takes effect immediately.
+#[tauri::command]
+pub async fn update_history_config(
+    state: State<'_, AppState>,
+    config: HistoryConfig,
+) -> Result<(), AppError> {
+    if !(1..=MAX_RETENTION_DAYS).contains(&config.retention_days) {
+        return Err(AppError {
+            message: format!("Retention must be between 1 and {MAX_RETENTION_DAYS} days"),
+            error_type: "ValidationError".to_string(),
+        });
+    }
+
+    state
+        .config_manager
+        .update_history_config(config.clone())
+        .map_err(|e| AppError {
+            message: format!("Failed to update config: {e}"),
+            error_type: "ConfigError".to_string(),
+        })?;
+
+    state.history_recorder.lock().await.set_config(config);
+
+    Ok(())
+}
+
+/// Get the recorded state of a device between `from` and `to`.
+///
+/// The first sample is the device's state at `from`, if it was recorded.
+#[tauri::command]
+pub async fn query_device_history(
+    state: State<'_, AppState>,
+    serial_number: String,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<DeviceHistory, AppError> {
+    let samples = query(&state, &serial_number, from, to).await?;
+    Ok(DeviceHistory {
+        serial_number,
+        samples,
+    })
+}
+
+/// Export the recorded state of a device between `from` and `to` as CSV.
+#[tauri::command]
+pub async fn export_device_history_csv(
+    state: State<'_, AppState>,
+    serial_number: String,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<String, AppError> {
+    let samples = query(&state, &serial_number, from, to).await?;
+    Ok(to_csv(&samples))
+}
+
+/// Read the samples of a device from the history
+async fn query(
+    state: &AppState,
+    serial_number: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<HistorySample>, AppError> {
+    if from > to {
+        return Err(AppError {
+            message: "The start of the range must not be after its end".to_string(),
+            error_type: "ValidationError".to_string(),
+        });
+    }
+
+    Ok(state
+        .history_recorder
+        .lock()
+        .await
+        .query(serial_number, from, to)?)
+}
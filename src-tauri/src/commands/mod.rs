@@ -3,13 +3,35 @@
 /// This module contains all the Tauri commands that can be invoked from the frontend.
 /// Each command is properly documented and handles errors gracefully.
 pub mod brightness_commands;
+pub mod calendar_commands;
 pub mod camera_commands;
+pub mod circadian_commands;
 pub mod device_commands;
+pub mod history_commands;
 pub mod power_commands;
+pub mod preset_commands;
+pub mod profile_commands;
+pub mod rule_commands;
+pub mod scene_commands;
+pub mod scheduler_commands;
 pub mod temperature_commands;
+pub mod timer_commands;
+pub mod trigger_commands;
+pub mod undo_commands;
 
 pub use brightness_commands::*;
+pub use calendar_commands::*;
 pub use camera_commands::*;
+pub use circadian_commands::*;
 pub use device_commands::*;
+pub use history_commands::*;
 pub use power_commands::*;
+pub use preset_commands::*;
+pub use profile_commands::*;
+pub use rule_commands::*;
+pub use scene_commands::*;
+pub use scheduler_commands::*;
 pub use temperature_commands::*;
+pub use timer_commands::*;
+pub use trigger_commands::*;
+pub use undo_commands::*;
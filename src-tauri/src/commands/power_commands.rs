@@ -4,6 +4,7 @@
 //! power on/off operations, toggle functionality, and power state querying.
 
 use crate::error::AppError;
+use crate::undo::ChangeSource;
 use crate::AppState;
 use tauri::State;
 
@@ -13,6 +14,7 @@ pub async fn device_power_toggle(
     state: State<'_, AppState>,
     serial_number: String,
 ) -> Result<bool, AppError> {
+    let before = state.device_states(Some(&serial_number)).await;
     let manager = state.device_manager.lock().await;
 
     let handle = manager.get_device_handle(&serial_number)?;
@@ -31,6 +33,10 @@ pub async fn device_power_toggle(
         error_type: "PowerControlError".to_string(),
     })?;
 
+    drop(manager);
+    state.record_change(ChangeSource::Ui, "Power", before).await;
+    state.notify_manual_change(&serial_number).await;
+
     Ok(new_state)
 }
 
@@ -41,6 +47,7 @@ pub async fn set_device_power(
     serial_number: String,
     power_on: bool,
 ) -> Result<(), AppError> {
+    let before = state.device_states(Some(&serial_number)).await;
     let manager = state.device_manager.lock().await;
 
     let handle = manager.get_device_handle(&serial_number)?;
@@ -50,5 +57,9 @@ pub async fn set_device_power(
         error_type: "PowerControlError".to_string(),
     })?;
 
+    drop(manager);
+    state.record_change(ChangeSource::Ui, "Power", before).await;
+    state.notify_manual_change(&serial_number).await;
+
     Ok(())
 }
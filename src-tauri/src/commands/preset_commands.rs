@@ -0,0 +1,167 @@
+//! Preset related Tauri commands.
+//!
+//! This module contains the commands for managing the lighting presets kept in
+//! the configuration and for applying them to devices.
+use crate::commands::camera_commands::validate_lighting_state;
+use crate::config::{Preset, PresetsConfig};
+use crate::error::AppError;
+use crate::undo::ChangeSource;
+use crate::AppState;
+use tauri::{AppHandle, State};
+
+/// Get the presets, in display order.
+#[tauri::command]
+pub async fn get_presets(state: State<'_, AppState>) -> Result<Vec<Preset>, AppError> {
+    Ok(state.config_manager.get_config().presets.presets)
+}
+
+/// Create a preset at the end of the list.
+///
+/// A preset without an ID is given one derived from its name. Returns the
+/// preset as saved.
+#[tauri::command]
+pub async fn create_preset(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    mut preset: Preset,
+) -> Result<Preset, AppError> {
+    validate_preset(&preset)?;
+
+    preset.id = preset.id.trim().to_string();
+    let presets = state.config_manager.modify(|config| {
+        let presets = &mut config.presets;
+        if preset.id.is_empty() {
+            preset.id = presets.unique_id(&preset.name);
+        } else if presets.find(&preset.id).is_some() {
+            return Err(AppError {
+                message: format!("Preset {} already exists", preset.id),
+                error_type: "ValidationError".to_string(),
+            });
+        }
+        presets.presets.push(preset.clone());
+        Ok(presets.clone())
+    })?;
+
+    presets_saved(&app, &state, presets).await;
+    Ok(preset)
+}
+
+/// Update the preset with the same ID.
+#[tauri::command]
+pub async fn update_preset(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    preset: Preset,
+) -> Result<(), AppError> {
+    validate_preset(&preset)?;
+
+    let presets = state.config_manager.modify(|config| {
+        let presets = &mut config.presets;
+        let Some(existing) = presets.presets.iter_mut().find(|p| p.id == preset.id) else {
+            return Err(not_found(&preset.id));
+        };
+        *existing = preset;
+        Ok(presets.clone())
+    })?;
+
+    presets_saved(&app, &state, presets).await;
+    Ok(())
+}
+
+/// Delete a preset.
+#[tauri::command]
+pub async fn delete_preset(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<(), AppError> {
+    let presets = state.config_manager.modify(|config| {
+        let presets = &mut config.presets;
+        let count = presets.presets.len();
+        presets.presets.retain(|preset| preset.id != id);
+        if presets.presets.len() == count {
+            return Err(not_found(&id));
+        }
+        Ok(presets.clone())
+    })?;
+
+    presets_saved(&app, &state, presets).await;
+    Ok(())
+}
+
+/// Reorder the presets.
+///
+/// `ids` must list every preset ID exactly once, in the new order.
+#[tauri::command]
+pub async fn reorder_presets(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    ids: Vec<String>,
+) -> Result<(), AppError> {
+    let presets = state.config_manager.modify(|config| {
+        let presets = &mut config.presets;
+        if ids.len() != presets.presets.len() {
+            return Err(AppError {
+                message: "The new order must list every preset once".to_string(),
+                error_type: "ValidationError".to_string(),
+            });
+        }
+
+        let mut reordered = Vec::with_capacity(ids.len());
+        for id in &ids {
+            let Some(index) = presets.presets.iter().position(|preset| &preset.id == id) else {
+                return Err(AppError {
+                    message: "The new order must list every preset once".to_string(),
+                    error_type: "ValidationError".to_string(),
+                });
+            };
+            reordered.push(presets.presets.swap_remove(index));
+        }
+        presets.presets = reordered;
+        Ok(presets.clone())
+    })?;
+
+    presets_saved(&app, &state, presets).await;
+    Ok(())
+}
+
+/// Apply a preset to a device, or to all devices when no serial number is
+/// given.
+#[tauri::command]
+pub async fn apply_preset(
+    state: State<'_, AppState>,
+    id: String,
+    serial_number: Option<String>,
+) -> Result<(), AppError> {
+    state
+        .apply_preset(&id, serial_number.as_deref(), ChangeSource::Ui)
+        .await
+}
+
+/// Validate a preset's name and lighting values
+fn validate_preset(preset: &Preset) -> Result<(), AppError> {
+    if preset.name.trim().is_empty() {
+        return Err(AppError {
+            message: "Preset name must not be empty".to_string(),
+            error_type: "ValidationError".to_string(),
+        });
+    }
+    validate_lighting_state(&preset.lighting_state(), &preset.name)
+}
+
+/// Error for a preset ID that does not exist
+fn not_found(id: &str) -> AppError {
+    AppError {
+        message: format!("Preset {id} does not exist"),
+        error_type: "ValidationError".to_string(),
+    }
+}
+
+/// Match devices against the saved presets and show them in the tray
+async fn presets_saved(app: &AppHandle, state: &AppState, presets: PresetsConfig) {
+    state.set_presets(presets.presets).await;
+
+    if let Err(e) = crate::tray::refresh_tray_menu(app).await {
+        eprintln!("Failed to update the tray menu: {e}");
+    }
+}
@@ -0,0 +1,183 @@
+//! Profile related Tauri commands.
+//!
+//! This module contains the commands for managing profiles, named sets of
+//! settings switched as a whole, and for switching between them.
+use crate::commands::camera_commands::validate_lighting_state;
+use crate::config::{LightingState, Profile, ProfilesConfig};
+use crate::error::AppError;
+use crate::undo::ChangeSource;
+use crate::AppState;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Runtime, State};
+
+/// The profiles and the active one
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfilesStatus {
+    /// ID of the active profile, if any
+    pub active: Option<String>,
+    /// Profiles, in display order
+    pub profiles: Vec<Profile>,
+}
+
+/// Get the profiles and the active one.
+#[tauri::command]
+pub async fn get_profiles(state: State<'_, AppState>) -> Result<ProfilesStatus, AppError> {
+    let profiles = state.config_manager.get_config().profiles;
+    Ok(ProfilesStatus {
+        active: profiles.active,
+        profiles: profiles.profiles,
+    })
+}
+
+/// Create a profile from the current settings and lights.
+///
+/// `lighting_state` is applied to every connected device when switching to
+/// the profile. Returns the profile as saved.
+#[tauri::command]
+pub async fn create_profile(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    name: String,
+    lighting_state: LightingState,
+) -> Result<Profile, AppError> {
+    validate_name(&name)?;
+    validate_lighting_state(&lighting_state, name.trim())?;
+
+    let devices = state.capture_devices().await?;
+    let config = state.config_manager.get_config();
+    let mut profiles = config.profiles;
+    let profile = Profile {
+        id: profiles.unique_id(&name),
+        name: name.trim().to_string(),
+        lighting_state,
+        auto_toggle: config.auto_toggle,
+        devices,
+    };
+    profiles.profiles.push(profile.clone());
+
+    save_profiles(&app, &state, profiles).await?;
+    Ok(profile)
+}
+
+/// Create a copy of a profile under a new name.
+///
+/// Returns the copy as saved.
+#[tauri::command]
+pub async fn duplicate_profile(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: String,
+    name: String,
+) -> Result<Profile, AppError> {
+    validate_name(&name)?;
+
+    let mut profiles = state.config_manager.get_config().profiles;
+    let Some(original) = profiles.find(&id) else {
+        return Err(not_found(&id));
+    };
+    let profile = Profile {
+        id: profiles.unique_id(&name),
+        name: name.trim().to_string(),
+        ..original.clone()
+    };
+    profiles.profiles.push(profile.clone());
+
+    save_profiles(&app, &state, profiles).await?;
+    Ok(profile)
+}
+
+/// Switch to a profile.
+///
+/// Its settings become the current ones, the lights are put back the way
+/// they were kept in it, and its lighting state is applied to every
+/// connected device. Emits `profile-changed` with the profile.
+#[tauri::command]
+pub async fn switch_profile(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<Profile, AppError> {
+    let result = state.switch_profile(&id, ChangeSource::Ui).await;
+    profile_switched(&app, result.as_ref().ok()).await;
+    result
+}
+
+/// Delete a profile.
+///
+/// Deleting the active profile leaves the current settings as they are,
+/// with no profile active.
+#[tauri::command]
+pub async fn delete_profile(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<(), AppError> {
+    let mut profiles = state.config_manager.get_config().profiles;
+    let count = profiles.profiles.len();
+    profiles.profiles.retain(|profile| profile.id != id);
+    if profiles.profiles.len() == count {
+        return Err(not_found(&id));
+    }
+    if profiles.active.as_deref() == Some(id.as_str()) {
+        profiles.active = None;
+    }
+
+    save_profiles(&app, &state, profiles).await
+}
+
+/// Tell the frontend about a switch to a profile and show it in the tray.
+///
+/// Also used by the tray, so the window stays in line with it.
+pub(crate) async fn profile_switched<R: Runtime>(app: &AppHandle<R>, profile: Option<&Profile>) {
+    if let Some(profile) = profile {
+        if let Err(e) = app.emit("profile-changed", profile) {
+            eprintln!("Failed to emit profile-changed: {e}");
+        }
+    }
+    let _ = app.emit("device-refresh", ());
+
+    if let Err(e) = crate::tray::refresh_tray_menu(app).await {
+        eprintln!("Failed to update the tray menu: {e}");
+    }
+}
+
+/// Validate a profile name
+fn validate_name(name: &str) -> Result<(), AppError> {
+    if name.trim().is_empty() {
+        return Err(AppError {
+            message: "Profile name must not be empty".to_string(),
+            error_type: "ValidationError".to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Error for a profile ID that does not exist
+fn not_found(id: &str) -> AppError {
+    AppError {
+        message: format!("Profile {id} does not exist"),
+        error_type: "ValidationError".to_string(),
+    }
+}
+
+/// Save the profiles and show them in the tray
+async fn save_profiles(
+    app: &AppHandle,
+    state: &AppState,
+    profiles: ProfilesConfig,
+) -> Result<(), AppError> {
+    state
+        .config_manager
+        .update_profiles_config(profiles)
+        .map_err(|e| AppError {
+            message: format!("Failed to update config: {e}"),
+            error_type: "ConfigError".to_string(),
+        })?;
+
+    if let Err(e) = crate::tray::refresh_tray_menu(app).await {
+        eprintln!("Failed to update the tray menu: {e}");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,66 @@
+//! Rule related Tauri commands.
+//!
+//! This module contains the commands for managing the rules that combine
+//! conditions into lighting actions and for explaining their effect on each
+//! device.
+use crate::commands::camera_commands::validate_lighting_state;
+use crate::config::{LightingState, Rule, RulesConfig};
+use crate::error::AppError;
+use crate::triggers::rules::DeviceRulesExplanation;
+use crate::AppState;
+use tauri::State;
+
+/// Get the configured rules.
+#[tauri::command]
+pub async fn get_rules(state: State<'_, AppState>) -> Result<Vec<Rule>, AppError> {
+    Ok(state.config_manager.get_config().rules.rules)
+}
+
+/// Replace the rules.
+///
+/// The rules are saved and take effect immediately.
+#[tauri::command]
+pub async fn update_rules(state: State<'_, AppState>, rules: Vec<Rule>) -> Result<(), AppError> {
+    for rule in &rules {
+        let label = format!("Rule \"{}\"", rule.name);
+        if rule.action == LightingState::default() {
+            return Err(AppError {
+                message: format!("{label} must change at least one setting"),
+                error_type: "ValidationError".to_string(),
+            });
+        }
+        validate_lighting_state(&rule.action, &label)?;
+    }
+
+    state
+        .config_manager
+        .update_rules_config(RulesConfig {
+            rules: rules.clone(),
+        })
+        .map_err(|e| AppError {
+            message: format!("Failed to update config: {e}"),
+            error_type: "ConfigError".to_string(),
+        })?;
+
+    state.trigger_engine.lock().await.set_rules(rules).await;
+
+    Ok(())
+}
+
+/// Explain, for every device, which rules match and which of them control
+/// its settings.
+#[tauri::command]
+pub async fn explain_rules(
+    state: State<'_, AppState>,
+) -> Result<Vec<DeviceRulesExplanation>, AppError> {
+    state
+        .trigger_engine
+        .lock()
+        .await
+        .explain_rules()
+        .await
+        .map_err(|e| AppError {
+            message: format!("Failed to explain rules: {e}"),
+            error_type: "DeviceError".to_string(),
+        })
+}
@@ -0,0 +1,132 @@
+//! Scene related Tauri commands.
+//!
+//! This module contains the commands for capturing the current state of
+//! several devices as a scene, managing the saved scenes and applying them.
+use crate::config::{Scene, ScenesConfig};
+use crate::error::AppError;
+use crate::scenes::{self, SceneApplied};
+use crate::AppState;
+use std::time::Duration;
+use tauri::State;
+
+/// Longest fade when applying a scene, in milliseconds
+const MAX_FADE_MS: u64 = 60_000;
+
+/// Get the saved scenes, in display order.
+#[tauri::command]
+pub async fn get_scenes(state: State<'_, AppState>) -> Result<Vec<Scene>, AppError> {
+    Ok(state.config_manager.get_config().scenes.scenes)
+}
+
+/// Capture the current state of the connected devices as a new scene.
+///
+/// Captures all connected devices, or only those in `serial_numbers` if
+/// given. Returns the scene as saved.
+#[tauri::command]
+pub async fn capture_scene(
+    state: State<'_, AppState>,
+    name: String,
+    serial_numbers: Option<Vec<String>>,
+) -> Result<Scene, AppError> {
+    if name.trim().is_empty() {
+        return Err(AppError {
+            message: "Scene name must not be empty".to_string(),
+            error_type: "ValidationError".to_string(),
+        });
+    }
+
+    let devices = scenes::capture_devices(state.device_manager.as_ref(), serial_numbers.as_deref())
+        .await
+        .map_err(|e| AppError {
+            message: format!("Failed to capture scene: {e}"),
+            error_type: "DeviceError".to_string(),
+        })?;
+    if devices.is_empty() {
+        return Err(AppError {
+            message: "No connected devices to capture".to_string(),
+            error_type: "ValidationError".to_string(),
+        });
+    }
+
+    let mut config = state.config_manager.get_config().scenes;
+    let scene = Scene {
+        id: config.unique_id(&name),
+        name: name.trim().to_string(),
+        devices,
+    };
+    config.scenes.push(scene.clone());
+    save_scenes(&state, config)?;
+
+    Ok(scene)
+}
+
+/// Update the scene with the same ID, e.g. to rename it.
+#[tauri::command]
+pub async fn update_scene(state: State<'_, AppState>, scene: Scene) -> Result<(), AppError> {
+    if scene.name.trim().is_empty() {
+        return Err(AppError {
+            message: "Scene name must not be empty".to_string(),
+            error_type: "ValidationError".to_string(),
+        });
+    }
+
+    let mut config = state.config_manager.get_config().scenes;
+    let Some(existing) = config.scenes.iter_mut().find(|s| s.id == scene.id) else {
+        return Err(not_found(&scene.id));
+    };
+    *existing = scene;
+
+    save_scenes(&state, config)
+}
+
+/// Delete a scene.
+#[tauri::command]
+pub async fn delete_scene(state: State<'_, AppState>, id: String) -> Result<(), AppError> {
+    let mut config = state.config_manager.get_config().scenes;
+    let count = config.scenes.len();
+    config.scenes.retain(|scene| scene.id != id);
+    if config.scenes.len() == count {
+        return Err(not_found(&id));
+    }
+
+    save_scenes(&state, config)
+}
+
+/// Apply a scene to its connected devices, optionally fading over `fade_ms`.
+///
+/// Reports which devices were changed and which were not connected.
+#[tauri::command]
+pub async fn apply_scene(
+    state: State<'_, AppState>,
+    id: String,
+    fade_ms: Option<u64>,
+) -> Result<SceneApplied, AppError> {
+    let fade_ms = fade_ms.unwrap_or(0);
+    if fade_ms > MAX_FADE_MS {
+        return Err(AppError {
+            message: format!("Fade must be at most {} seconds", MAX_FADE_MS / 1000),
+            error_type: "ValidationError".to_string(),
+        });
+    }
+
+    state.apply_scene(&id, Duration::from_millis(fade_ms)).await
+}
+
+/// Error for a scene ID that does not exist
+fn not_found(id: &str) -> AppError {
+    AppError {
+        message: format!("Scene {id} does not exist"),
+        error_type: "ValidationError".to_string(),
+    }
+}
+
+/// Save the scenes
+fn save_scenes(state: &AppState, scenes: ScenesConfig) -> Result<(), AppError> {
+    state
+        .config_manager
+        .update_scenes_config(scenes)
+        .map_err(|e| AppError {
+            message: format!("Failed to update config: {e}"),
+            error_type: "ConfigError".to_string(),
+        })
+}
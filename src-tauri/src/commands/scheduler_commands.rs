@@ -0,0 +1,55 @@
+//! Scheduler related Tauri commands.
+//!
+//! This module contains the commands for managing the jobs that run lighting
+//! actions at cron-style times and for reporting when they run next.
+use crate::commands::camera_commands::validate_lighting_state;
+use crate::config::{ScheduledJob, SchedulerConfig};
+use crate::error::AppError;
+use crate::scheduler::cron::CronSchedule;
+use crate::scheduler::ScheduledJobStatus;
+use crate::AppState;
+use tauri::State;
+
+/// Get the scheduled jobs.
+#[tauri::command]
+pub async fn get_scheduled_jobs(state: State<'_, AppState>) -> Result<Vec<ScheduledJob>, AppError> {
+    Ok(state.config_manager.get_config().scheduler.jobs)
+}
+
+/// Replace the scheduled jobs.
+///
+/// The jobs are saved and take effect immediately.
+#[tauri::command]
+pub async fn update_scheduled_jobs(
+    state: State<'_, AppState>,
+    jobs: Vec<ScheduledJob>,
+) -> Result<(), AppError> {
+    for job in &jobs {
+        CronSchedule::parse(&job.schedule).map_err(|e| AppError {
+            message: format!("Job \"{}\" has an invalid schedule: {e}", job.name),
+            error_type: "ValidationError".to_string(),
+        })?;
+        validate_lighting_state(&job.action, &format!("\"{}\"", job.name))?;
+    }
+
+    state
+        .config_manager
+        .update_scheduler_config(SchedulerConfig { jobs: jobs.clone() })
+        .map_err(|e| AppError {
+            message: format!("Failed to update config: {e}"),
+            error_type: "ConfigError".to_string(),
+        })?;
+
+    state.job_scheduler.lock().await.set_jobs(jobs);
+
+    Ok(())
+}
+
+/// Get the next and last run of every scheduled job, in the order of
+/// [`get_scheduled_jobs`].
+#[tauri::command]
+pub async fn get_scheduled_job_status(
+    state: State<'_, AppState>,
+) -> Result<Vec<ScheduledJobStatus>, AppError> {
+    Ok(state.job_scheduler.lock().await.status())
+}
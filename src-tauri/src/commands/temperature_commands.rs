@@ -5,6 +5,7 @@
 //! with proper 100K step validation as required by Litra devices.
 
 use crate::error::AppError;
+use crate::undo::ChangeSource;
 use crate::AppState;
 use tauri::State;
 
@@ -43,6 +44,7 @@ pub async fn set_device_temperature(
     serial_number: String,
     kelvin: u16,
 ) -> Result<(), AppError> {
+    let before = state.device_states(Some(&serial_number)).await;
     let manager = state.device_manager.lock().await;
 
     let handle = manager.get_device_handle(&serial_number)?;
@@ -74,6 +76,13 @@ pub async fn set_device_temperature(
             error_type: "TemperatureControlError".to_string(),
         })?;
 
+    drop(manager);
+    state
+        .record_change(ChangeSource::Ui, "Temperature", before)
+        .await;
+    state.notify_manual_change(&serial_number).await;
+    state.pause_circadian_schedule().await;
+
     Ok(())
 }
 
@@ -123,6 +132,7 @@ pub async fn set_temperature_in_kelvin(
     serial_number: String,
     kelvin: u16,
 ) -> Result<(), AppError> {
+    let before = state.device_states(Some(&serial_number)).await;
     let manager = state.device_manager.lock().await;
 
     let handle = manager.get_device_handle(&serial_number)?;
@@ -134,5 +144,12 @@ pub async fn set_temperature_in_kelvin(
             error_type: "TemperatureControlError".to_string(),
         })?;
 
+    drop(manager);
+    state
+        .record_change(ChangeSource::Ui, "Temperature", before)
+        .await;
+    state.notify_manual_change(&serial_number).await;
+    state.pause_circadian_schedule().await;
+
     Ok(())
 }
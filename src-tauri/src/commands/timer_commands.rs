@@ -0,0 +1,102 @@
+//! Timer related Tauri commands.
+//!
+//! This module contains the commands for setting sleep timers that switch
+//! lights off after a while, listing the running timers and configuring the
+//! maximum on-time.
+use crate::config::MaxOnTimeConfig;
+use crate::error::AppError;
+use crate::power::timers::TimerInfo;
+use crate::AppState;
+use std::time::Duration;
+use tauri::State;
+
+/// Switch a device off after the given number of minutes.
+///
+/// Without a serial number the timer switches all devices off. A timer
+/// already set for the same device is replaced.
+#[tauri::command]
+pub async fn set_sleep_timer(
+    state: State<'_, AppState>,
+    serial_number: Option<String>,
+    minutes: u32,
+) -> Result<(), AppError> {
+    if minutes == 0 {
+        return Err(AppError {
+            message: "Sleep timer must be at least one minute".to_string(),
+            error_type: "ValidationError".to_string(),
+        });
+    }
+
+    if let Some(serial_number) = &serial_number {
+        state
+            .device_manager
+            .lock()
+            .await
+            .get_device_info(serial_number)?;
+    }
+
+    state
+        .timer_manager
+        .lock()
+        .await
+        .set_sleep_timer(serial_number, Duration::from_secs(u64::from(minutes) * 60));
+
+    Ok(())
+}
+
+/// Cancel the sleep timer of a device, or the one for all devices.
+///
+/// Returns whether a timer was set.
+#[tauri::command]
+pub async fn cancel_sleep_timer(
+    state: State<'_, AppState>,
+    serial_number: Option<String>,
+) -> Result<bool, AppError> {
+    Ok(state
+        .timer_manager
+        .lock()
+        .await
+        .cancel_sleep_timer(serial_number))
+}
+
+/// Get the running sleep timers and maximum on-time countdowns.
+#[tauri::command]
+pub async fn get_timers(state: State<'_, AppState>) -> Result<Vec<TimerInfo>, AppError> {
+    Ok(state.timer_manager.lock().await.timers())
+}
+
+/// Get the maximum on-time configuration.
+#[tauri::command]
+pub async fn get_max_on_time_config(
+    state: State<'_, AppState>,
+) -> Result<MaxOnTimeConfig, AppError> {
+    Ok(state.config_manager.get_config().max_on_time)
+}
+
+/// Update the maximum on-time configuration.
+///
+/// The configuration is saved and takes effect immediately.
+#[tauri::command]
+pub async fn update_max_on_time_config(
+    state: State<'_, AppState>,
+    config: MaxOnTimeConfig,
+) -> Result<(), AppError> {
+    if config.max_on_minutes == 0 {
+        return Err(AppError {
+            message: "Maximum on-time must be at least one minute".to_string(),
+            error_type: "ValidationError".to_string(),
+        });
+    }
+
+    state
+        .config_manager
+        .update_max_on_time_config(config.clone())
+        .map_err(|e| AppError {
+            message: format!("Failed to update config: {e}"),
+            error_type: "ConfigError".to_string(),
+        })?;
+
+    state.timer_manager.lock().await.set_max_on_time(config);
+
+    Ok(())
+}
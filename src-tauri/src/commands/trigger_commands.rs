@@ -0,0 +1,160 @@
+//! Trigger related Tauri commands.
+//!
+//! This module contains the commands for inspecting trigger signals and
+//! managing the bindings that map them to lighting actions, including the
+//! built-in session lock, idle and display sleep behaviours.
+use crate::commands::camera_commands::validate_lighting_state;
+use crate::config::{
+    DisplaySleepConfig, IdleConfig, SessionLockConfig, TriggerBinding, TriggersConfig,
+};
+use crate::error::AppError;
+use crate::triggers::SignalValue;
+use crate::AppState;
+use std::collections::BTreeMap;
+use tauri::State;
+
+/// Get the configured trigger bindings.
+#[tauri::command]
+pub async fn get_trigger_bindings(
+    state: State<'_, AppState>,
+) -> Result<Vec<TriggerBinding>, AppError> {
+    Ok(state.config_manager.get_config().triggers.bindings)
+}
+
+/// Replace the trigger bindings.
+///
+/// The bindings are saved and take effect immediately; bindings currently
+/// applied are released first.
+#[tauri::command]
+pub async fn update_trigger_bindings(
+    state: State<'_, AppState>,
+    bindings: Vec<TriggerBinding>,
+) -> Result<(), AppError> {
+    for binding in &bindings {
+        if binding.source.is_empty() {
+            return Err(AppError {
+                message: format!("Trigger binding \"{}\" has no source", binding.name),
+                error_type: "ValidationError".to_string(),
+            });
+        }
+        validate_lighting_state(&binding.action, &format!("\"{}\"", binding.name))?;
+    }
+
+    state
+        .config_manager
+        .update_triggers_config(TriggersConfig { bindings })
+        .map_err(|e| AppError {
+            message: format!("Failed to update config: {e}"),
+            error_type: "ConfigError".to_string(),
+        })?;
+
+    state.reload_trigger_bindings().await;
+
+    Ok(())
+}
+
+/// Get the session lock configuration.
+#[tauri::command]
+pub async fn get_session_lock_config(
+    state: State<'_, AppState>,
+) -> Result<SessionLockConfig, AppError> {
+    Ok(state.config_manager.get_config().session_lock)
+}
+
+/// Update the session lock configuration.
+///
+/// The configuration is saved and takes effect immediately.
+#[tauri::command]
+pub async fn update_session_lock_config(
+    state: State<'_, AppState>,
+    config: SessionLockConfig,
+) -> Result<(), AppError> {
+    state
+        .config_manager
+        .update_session_lock_config(config)
+        .map_err(|e| AppError {
+            message: format!("Failed to update config: {e}"),
+            error_type: "ConfigError".to_string(),
+        })?;
+
+    state.reload_trigger_bindings().await;
+
+    Ok(())
+}
+
+/// Get the latest value of every trigger signal, keyed by source ID.
+#[tauri::command]
+pub async fn get_trigger_signals(
+    state: State<'_, AppState>,
+) -> Result<BTreeMap<String, SignalValue>, AppError> {
+    Ok(state.trigger_engine.lock().await.get_signals().await)
+}
+
+/// Get the idle configuration.
+#[tauri::command]
+pub async fn get_idle_config(state: State<'_, AppState>) -> Result<IdleConfig, AppError> {
+    Ok(state.config_manager.get_config().idle)
+}
+
+/// Update the idle configuration.
+///
+/// The configuration is saved and takes effect immediately.
+#[tauri::command]
+pub async fn update_idle_config(
+    state: State<'_, AppState>,
+    config: IdleConfig,
+) -> Result<(), AppError> {
+    for binding in config.bindings() {
+        validate_lighting_state(&binding.action, &binding.name)?;
+    }
+    if config.dim_after_minutes > 0
+        && config.off_after_minutes > 0
+        && config.off_after_minutes <= config.dim_after_minutes
+    {
+        return Err(AppError {
+            message: "Lights must be switched off later than they are dimmed".to_string(),
+            error_type: "ValidationError".to_string(),
+        });
+    }
+
+    state
+        .config_manager
+        .update_idle_config(config)
+        .map_err(|e| AppError {
+            message: format!("Failed to update config: {e}"),
+            error_type: "ConfigError".to_string(),
+        })?;
+
+    state.reload_trigger_bindings().await;
+
+    Ok(())
+}
+
+/// Get the display sleep configuration.
+#[tauri::command]
+pub async fn get_display_sleep_config(
+    state: State<'_, AppState>,
+) -> Result<DisplaySleepConfig, AppError> {
+    Ok(state.config_manager.get_config().display_sleep)
+}
+
+/// Update the display sleep configuration.
+///
+/// The configuration is saved and takes effect immediately.
+#[tauri::command]
+pub async fn update_display_sleep_config(
+    state: State<'_, AppState>,
+    config: DisplaySleepConfig,
+) -> Result<(), AppError> {
+    state
+        .config_manager
+        .update_display_sleep_config(config)
+        .map_err(|e| AppError {
+            message: format!("Failed to update config: {e}"),
+            error_type: "ConfigError".to_string(),
+        })?;
+
+    state.reload_trigger_bindings().await;
+
+    Ok(())
+}
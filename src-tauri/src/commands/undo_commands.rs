@@ -0,0 +1,61 @@
+//! Undo related Tauri commands.
+//!
+//! This module contains the commands for looking up the recorded changes to
+//! the lights and for undoing and redoing them.
+use crate::error::AppError;
+use crate::undo::{ChangeSource, ChangeStep, USER_SOURCES};
+use crate::AppState;
+use serde::Serialize;
+use tauri::State;
+
+/// Changes that can be undone and redone
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeHistoryStatus {
+    /// Changes that can be undone, latest first
+    pub undo: Vec<ChangeStep>,
+    /// Changes that can be redone, next first
+    pub redo: Vec<ChangeStep>,
+}
+
+/// Get the changes that can be undone and redone, optionally only those of a
+/// device.
+#[tauri::command]
+pub async fn get_change_history(
+    state: State<'_, AppState>,
+    serial_number: Option<String>,
+) -> Result<ChangeHistoryStatus, AppError> {
+    let history = state.change_history.lock().unwrap();
+    Ok(ChangeHistoryStatus {
+        undo: history.undo_steps(serial_number.as_deref()),
+        redo: history.redo_steps(serial_number.as_deref()),
+    })
+}
+
+/// Undo the latest change to the lights from one of `sources`, by default
+/// the UI and the tray.
+///
+/// Returns the change undone, or nothing if there was none.
+#[tauri::command]
+pub async fn undo_last_change(
+    state: State<'_, AppState>,
+    sources: Option<Vec<ChangeSource>>,
+) -> Result<Option<ChangeStep>, AppError> {
+    state
+        .undo_last_change(sources.as_deref().unwrap_or(USER_SOURCES))
+        .await
+}
+
+/// Redo the latest undone change to the lights from one of `sources`, by
+/// default the UI and the tray.
+///
+/// Returns the change redone, or nothing if there was none.
+#[tauri::command]
+pub async fn redo_change(
+    state: State<'_, AppState>,
+    sources: Option<Vec<ChangeSource>>,
+) -> Result<Option<ChangeStep>, AppError> {
+    state
+        .redo_change(sources.as_deref().unwrap_or(USER_SOURCES))
+        .await
+}
@@ -1,11 +1,18 @@
-//! Configuration management for camera auto-toggle
+//! Application configuration
 //!
-//! This module handles all configuration persistence using the `confy` crate
-//! with TOML format. It provides hot-reload support and type-safe configuration.
+//! Holds the settings of everything the app does on its own: camera
+//! auto-toggle, trigger bindings and the session lock, idle and display sleep
+//! triggers, rules, presets, scenes and profiles, the calendar, the circadian
+//! schedule, scheduled jobs, the maximum on-time and the device history.
+//!
+//! The configuration is persisted as TOML using the `confy` crate. The
+//! [`ConfigManager`] keeps it in memory and saves every change to disk.
 
-use chrono::{DateTime, Utc};
+use crate::device::{DeviceInfo, DeviceState};
+use chrono::{DateTime, NaiveTime, Utc, Weekday};
 use confy;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 
@@ -15,33 +22,204 @@ pub const CONFIG_FILE_NAME: &str = "config";
 /// Application name
 pub const APP_NAME: &str = "litra-control";
 
+/// Directory for application data such as history files.
+///
+/// Follows the XDG base directory specification: `$XDG_DATA_HOME/litra-control`,
+/// falling back to `~/.local/share/litra-control`.
+pub fn data_dir() -> PathBuf {
+    std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share"))
+        })
+        .unwrap_or_else(std::env::temp_dir)
+        .join(APP_NAME)
+}
+
 /// Main configuration structure for the entire application
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
 pub struct LitraConfig {
     /// Auto-toggle specific configuration
     pub auto_toggle: AutoToggleConfig,
+    /// Trigger bindings
+    pub triggers: TriggersConfig,
+    /// Lights-off while the session is locked
+    pub session_lock: SessionLockConfig,
+    /// Dimming and lights-off while the user is idle
+    pub idle: IdleConfig,
+    /// Lights-off while the displays are asleep
+    pub display_sleep: DisplaySleepConfig,
+    /// Lights-off after a maximum on-time
+    pub max_on_time: MaxOnTimeConfig,
+    /// Color temperature following the sun
+    pub circadian: CircadianConfig,
+    /// Lighting actions run at scheduled times
+    pub scheduler: SchedulerConfig,
+    /// Rules combining conditions into lighting actions
+    pub rules: RulesConfig,
+    /// Lights switched on ahead of calendar meetings
+    pub calendar: CalendarConfig,
+    /// Lighting presets
+    pub presets: PresetsConfig,
+    /// Captured multi-device scenes
+    pub scenes: ScenesConfig,
+    /// Recording of device state over time
+    pub history: HistoryConfig,
+    /// Named profiles of settings switched as a whole
+    pub profiles: ProfilesConfig,
     /// Device state tracking
     pub device_states: DeviceStates,
 }
 
+impl LitraConfig {
+    /// All trigger bindings to run: the user's bindings followed by those
+    /// of the built-in behaviours
+    pub fn trigger_bindings(&self) -> Vec<TriggerBinding> {
+        let mut bindings = self.triggers.bindings.clone();
+        bindings.push(self.auto_toggle.binding());
+        bindings.push(self.session_lock.binding());
+        bindings.extend(self.idle.bindings());
+        bindings.push(self.display_sleep.binding());
+        bindings
+    }
+
+    /// Switch to a profile, taking its settings over as the current ones.
+    ///
+    /// `devices` is the current state of the lights, kept in the profile
+    /// being left so switching back to it restores them. Returns the
+    /// profile, or `None` if there is none with the ID.
+    pub fn switch_profile(&mut self, id: &str, devices: Vec<SceneDevice>) -> Option<Profile> {
+        self.profiles.find(id)?;
+        if let Some(active) = self.profiles.active_mut() {
+            active.devices = devices;
+        }
+
+        let profile = self.profiles.find(id)?.clone();
+        self.auto_toggle = profile.auto_toggle.clone();
+        self.profiles.active = Some(profile.id.clone());
+        Some(profile)
+    }
+
+    /// Copy the current settings a profile holds into the active profile,
+    /// so changes made while it is active are kept in it
+    fn sync_active_profile(&mut self) {
+        let auto_toggle = self.auto_toggle.clone();
+        if let Some(profile) = self.profiles.active_mut() {
+            profile.auto_toggle = auto_toggle;
+        }
+    }
+}
+
 /// Configuration for the camera auto-toggle functionality
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct AutoToggleConfig {
     /// Whether auto-toggle is enabled
     pub enabled: bool,
+    /// Capture devices whose use starts a session
+    pub activity_source: ActivitySource,
     /// Device selection strategy
     pub strategy: AutoToggleStrategy,
-    /// Debounce delay in milliseconds
-    pub debounce_ms: u64,
+    /// How long a camera must stay open before lights are switched on, in
+    /// milliseconds
+    pub on_delay_ms: u64,
+    /// How long lights stay on after the last camera closes, in milliseconds
+    #[serde(alias = "debounceMs")]
+    pub off_grace_ms: u64,
+    /// Lighting state applied to controlled devices while a camera is in use
+    pub in_call_state: LightingState,
+    /// How to treat devices the user changes by hand during a session
+    pub override_policy: OverridePolicy,
 }
 
 impl Default for AutoToggleConfig {
     fn default() -> Self {
         Self {
             enabled: false,
+            activity_source: ActivitySource::default(),
             strategy: AutoToggleStrategy::default(),
-            debounce_ms: 3000,
+            on_delay_ms: 1000,
+            off_grace_ms: 3000,
+            in_call_state: LightingState {
+                power_on: Some(true),
+                ..LightingState::default()
+            },
+            override_policy: OverridePolicy::default(),
+        }
+    }
+}
+
+/// Capture devices that drive auto-toggle
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ActivitySource {
+    /// Cameras (`/dev/video*`)
+    #[default]
+    Camera,
+    /// Microphones (ALSA capture streams)
+    Microphone,
+    /// Either a camera or a microphone
+    CameraOrMicrophone,
+}
+
+/// Target lighting state for one or more devices
+///
+/// Fields left unset are not changed when the state is applied.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LightingState {
+    /// Power state to switch to
+    pub power_on: Option<bool>,
+    /// Brightness as a percentage of the device's range (0-100)
+    pub brightness_percentage: Option<u8>,
+    /// Color temperature in Kelvin, in 100 K steps
+    pub temperature_kelvin: Option<u16>,
+    /// ID of a preset whose brightness and temperature are applied where
+    /// this state leaves them unset
+    pub preset: Option<String>,
+}
+
+impl LightingState {
+    /// The state with the brightness and temperature of its preset filled in
+    /// where it leaves them unset.
+    ///
+    /// A preset that does not exist sets nothing.
+    pub fn resolve(&self, presets: &[Preset]) -> LightingState {
+        let preset = self
+            .preset
+            .as_deref()
+            .and_then(|id| presets.iter().find(|preset| preset.id == id));
+        LightingState {
+            power_on: self.power_on,
+            brightness_percentage: self
+                .brightness_percentage
+                .or(preset.map(|preset| preset.brightness_percentage)),
+            temperature_kelvin: self
+                .temperature_kelvin
+                .or(preset.map(|preset| preset.temperature_kelvin)),
+            preset: None,
+        }
+    }
+}
+
+/// Name of the binding auto-toggle runs as
+pub const AUTO_TOGGLE_BINDING_NAME: &str = "Camera auto-toggle";
+
+impl AutoToggleConfig {
+    /// Binding applying the in-call state to the selected devices while a
+    /// camera session is active and restoring them when it ends
+    pub fn binding(&self) -> TriggerBinding {
+        TriggerBinding {
+            name: AUTO_TOGGLE_BINDING_NAME.to_string(),
+            enabled: self.enabled,
+            source: CAMERA_SOURCE_ID.to_string(),
+            condition: SignalCondition::IsTrue,
+            devices: self.strategy.devices(),
+            action: self.in_call_state.clone(),
+            restore_on_release: true,
+            override_policy: self.override_policy,
         }
     }
 }
@@ -60,6 +238,787 @@ pub enum AutoToggleStrategy {
     },
 }
 
+impl AutoToggleStrategy {
+    /// The devices the strategy selects
+    pub fn devices(&self) -> DeviceSet {
+        match self {
+            AutoToggleStrategy::AllDevices => DeviceSet::AllDevices,
+            AutoToggleStrategy::SelectedDevice { serial_number } => DeviceSet::Devices {
+                serial_numbers: vec![serial_number.clone()],
+            },
+        }
+    }
+}
+
+/// Policies for devices changed by hand while a binding holds them, such as
+/// during a camera session
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum OverridePolicy {
+    /// Stop controlling the device while the binding stays applied and leave
+    /// it as the user set it when the binding is released
+    #[default]
+    RespectOverride,
+    /// Keep controlling the device: the binding's action is applied again
+    /// right away and the device is restored when the binding is released
+    Reassert,
+}
+
+/// ID of the camera trigger source
+pub const CAMERA_SOURCE_ID: &str = "camera";
+
+/// ID of the microphone trigger source
+pub const MICROPHONE_SOURCE_ID: &str = "microphone";
+
+/// ID of the session lock trigger source
+pub const SESSION_LOCK_SOURCE_ID: &str = "sessionLocked";
+
+/// ID of the idle trigger source
+pub const IDLE_SOURCE_ID: &str = "idleSeconds";
+
+/// ID of the display trigger source
+pub const DISPLAYS_SOURCE_ID: &str = "displaysOff";
+
+/// Value of a trigger signal
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum SignalValue {
+    /// A condition that is either met or not, e.g. camera in use
+    Bool(bool),
+    /// A measured value, e.g. seconds since the last input
+    Number(f64),
+}
+
+/// Configuration for the trigger subsystem
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TriggersConfig {
+    /// Bindings from trigger signals to lighting actions
+    pub bindings: Vec<TriggerBinding>,
+}
+
+/// Binding from a trigger signal to a lighting action
+///
+/// The action is applied when the condition starts to hold for the source's
+/// signal. When it stops holding, the devices are optionally restored to the
+/// state they had before.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TriggerBinding {
+    /// Display name of the binding
+    pub name: String,
+    /// Whether the binding is active
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// ID of the trigger source whose signal is watched (e.g. `camera`)
+    pub source: String,
+    /// Condition on the signal value
+    pub condition: SignalCondition,
+    /// Devices the action applies to
+    #[serde(default)]
+    pub devices: DeviceSet,
+    /// Lighting state applied while the condition holds
+    pub action: LightingState,
+    /// Whether devices are restored once the condition stops holding
+    #[serde(default = "default_true")]
+    pub restore_on_release: bool,
+    /// How devices changed by hand while the binding is applied are treated
+    #[serde(default)]
+    pub override_policy: OverridePolicy,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Condition on a trigger signal value
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum SignalCondition {
+    /// A boolean signal is true
+    IsTrue,
+    /// A boolean signal is false
+    IsFalse,
+    /// A valued signal is above the threshold
+    Above { threshold: f64 },
+    /// A valued signal is below the threshold
+    Below { threshold: f64 },
+}
+
+impl SignalCondition {
+    /// Check whether a signal value satisfies the condition
+    pub fn matches(&self, value: &SignalValue) -> bool {
+        match (self, value) {
+            (SignalCondition::IsTrue, SignalValue::Bool(value)) => *value,
+            (SignalCondition::IsFalse, SignalValue::Bool(value)) => !*value,
+            (SignalCondition::Above { threshold }, SignalValue::Number(value)) => value > threshold,
+            (SignalCondition::Below { threshold }, SignalValue::Number(value)) => value < threshold,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for SignalCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignalCondition::IsTrue => write!(f, "is true"),
+            SignalCondition::IsFalse => write!(f, "is false"),
+            SignalCondition::Above { threshold } => write!(f, "above {threshold}"),
+            SignalCondition::Below { threshold } => write!(f, "below {threshold}"),
+        }
+    }
+}
+
+/// Set of devices an action applies to
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DeviceSet {
+    /// All connected devices
+    #[default]
+    AllDevices,
+    /// The devices with the given serial numbers
+    Devices {
+        #[serde(rename = "serialNumbers")]
+        serial_numbers: Vec<String>,
+    },
+}
+
+/// Configuration for switching lights off while the session is locked
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SessionLockConfig {
+    /// Whether lights are switched off on lock
+    pub enabled: bool,
+    /// Devices switched off
+    pub devices: DeviceSet,
+}
+
+impl SessionLockConfig {
+    /// Binding switching the devices off while the session is locked and
+    /// restoring them on unlock
+    pub fn binding(&self) -> TriggerBinding {
+        lights_off_binding(
+            "Session lock",
+            self.enabled,
+            SESSION_LOCK_SOURCE_ID,
+            &self.devices,
+        )
+    }
+}
+
+/// Configuration for switching lights off while all displays are asleep
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DisplaySleepConfig {
+    /// Whether lights are switched off with the displays
+    pub enabled: bool,
+    /// Devices switched off
+    pub devices: DeviceSet,
+}
+
+impl DisplaySleepConfig {
+    /// Binding switching the devices off while all displays are off and
+    /// restoring them when one wakes
+    pub fn binding(&self) -> TriggerBinding {
+        lights_off_binding(
+            "Display sleep",
+            self.enabled,
+            DISPLAYS_SOURCE_ID,
+            &self.devices,
+        )
+    }
+}
+
+/// Binding switching devices off while a boolean signal is true
+fn lights_off_binding(
+    name: &str,
+    enabled: bool,
+    source: &str,
+    devices: &DeviceSet,
+) -> TriggerBinding {
+    TriggerBinding {
+        name: name.to_string(),
+        enabled,
+        source: source.to_string(),
+        condition: SignalCondition::IsTrue,
+        devices: devices.clone(),
+        action: LightingState {
+            power_on: Some(false),
+            ..LightingState::default()
+        },
+        restore_on_release: true,
+        override_policy: OverridePolicy::default(),
+    }
+}
+
+/// Configuration for switching lights off after they have been on for too long
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MaxOnTimeConfig {
+    /// Whether lights are switched off after the maximum on-time
+    pub enabled: bool,
+    /// Minutes a light may stay on without a break
+    pub max_on_minutes: u32,
+    /// Devices the limit applies to
+    pub devices: DeviceSet,
+}
+
+impl Default for MaxOnTimeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_on_minutes: 240,
+            devices: DeviceSet::default(),
+        }
+    }
+}
+
+/// What to do about scheduled runs missed while the system was asleep
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum CatchUpPolicy {
+    /// Drop missed runs
+    Skip,
+    /// Run once for the missed runs if the latest of them is at most this
+    /// many minutes late
+    RunIfWithin { minutes: u32 },
+    /// Always run once for the missed runs
+    Always,
+}
+
+impl Default for CatchUpPolicy {
+    fn default() -> Self {
+        Self::RunIfWithin { minutes: 60 }
+    }
+}
+
+/// A lighting action run at the times of a cron expression
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledJob {
+    /// Name shown to the user
+    pub name: String,
+    /// Whether the job runs
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// When the job runs, as a five-field cron expression in local time,
+    /// e.g. `55 8 * * mon-fri`
+    pub schedule: String,
+    /// Devices the action applies to
+    #[serde(default)]
+    pub devices: DeviceSet,
+    /// Lighting state applied
+    pub action: LightingState,
+    /// What to do about runs missed while the system was asleep
+    #[serde(default)]
+    pub catch_up: CatchUpPolicy,
+}
+
+/// Configuration for scheduled lighting actions
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(default)]
+pub struct SchedulerConfig {
+    /// Scheduled jobs
+    pub jobs: Vec<ScheduledJob>,
+}
+
+/// A rule applying a lighting state to devices while its condition holds.
+///
+/// When several matching rules control the same setting of a device, the
+/// one with the highest priority wins, and the earlier one on a tie. Devices
+/// go back to the state they had before the first rule applied once no rule
+/// controls them any more.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Rule {
+    /// Display name of the rule
+    pub name: String,
+    /// Whether the rule is active
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Priority over other rules, higher winning
+    #[serde(default)]
+    pub priority: i32,
+    /// Condition under which the rule applies
+    pub condition: RuleCondition,
+    /// Devices the action applies to
+    #[serde(default)]
+    pub devices: DeviceSet,
+    /// Lighting state applied while the condition holds
+    pub action: LightingState,
+}
+
+/// Condition of a rule
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum RuleCondition {
+    /// Every condition holds
+    All(Vec<RuleCondition>),
+    /// At least one condition holds
+    Any(Vec<RuleCondition>),
+    /// The condition does not hold
+    Not(Box<RuleCondition>),
+    /// The local time is from `start` up to `end`, spanning midnight if
+    /// `end` is earlier and the whole day if they are equal
+    TimeWindow { start: NaiveTime, end: NaiveTime },
+    /// The local day is one of the given weekdays
+    Weekdays { days: Vec<Weekday> },
+    /// A trigger signal (e.g. `camera`, `microphone`, `sessionLocked` or
+    /// `idleSeconds`) satisfies a condition
+    Signal {
+        source: String,
+        condition: SignalCondition,
+    },
+    /// A device is connected
+    DeviceConnected {
+        #[serde(rename = "serialNumber")]
+        serial_number: String,
+    },
+}
+
+/// Configuration for rules
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(default)]
+pub struct RulesConfig {
+    /// Rules, in order
+    pub rules: Vec<Rule>,
+}
+
+/// Configuration for recording device state over time
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HistoryConfig {
+    /// Whether device state is recorded
+    pub enabled: bool,
+    /// Days recorded samples are kept for
+    pub retention_days: u32,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            retention_days: 30,
+        }
+    }
+}
+
+/// Configuration for switching lights on ahead of meetings in a local
+/// iCalendar file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CalendarConfig {
+    /// Whether lights are switched on ahead of meetings
+    pub enabled: bool,
+    /// Path of an `.ics` file, or of a directory of them such as a
+    /// vdirsyncer collection
+    pub path: PathBuf,
+    /// Minutes before a meeting starts that lights are switched on
+    pub lead_minutes: u32,
+    /// Only include meetings with a video call link
+    pub require_video_link: bool,
+    /// Only include meetings whose title, description or location contains
+    /// one of these words, if any are given
+    pub keywords: Vec<String>,
+    /// Devices switched on
+    pub devices: DeviceSet,
+    /// Lighting state applied ahead of and during a meeting
+    pub action: LightingState,
+}
+
+impl Default for CalendarConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: PathBuf::new(),
+            lead_minutes: 5,
+            require_video_link: true,
+            keywords: Vec::new(),
+            devices: DeviceSet::default(),
+            action: LightingState {
+                power_on: Some(true),
+                ..LightingState::default()
+            },
+        }
+    }
+}
+
+/// A named brightness and color temperature
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Preset {
+    /// Unique ID other settings refer to the preset by
+    pub id: String,
+    /// Display name
+    pub name: String,
+    /// What the preset is good for
+    #[serde(default)]
+    pub description: String,
+    /// Brightness as a percentage of the device's range (0-100)
+    pub brightness_percentage: u8,
+    /// Color temperature in Kelvin, in 100 K steps
+    pub temperature_kelvin: u16,
+}
+
+impl Preset {
+    fn new(id: &str, name: &str, description: &str, brightness: u8, temperature: u16) -> Self {
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            description: description.to_string(),
+            brightness_percentage: brightness,
+            temperature_kelvin: temperature,
+        }
+    }
+
+    /// The lighting state the preset applies, leaving power unchanged
+    pub fn lighting_state(&self) -> LightingState {
+        LightingState {
+            power_on: None,
+            brightness_percentage: Some(self.brightness_percentage),
+            temperature_kelvin: Some(self.temperature_kelvin),
+            preset: None,
+        }
+    }
+
+    /// Whether a device is at the brightness and temperature applying the
+    /// preset to it would set, whether it is on or not
+    ///
+    /// A temperature outside the device's limits is compared clamped to
+    /// them, as applying the preset would set it.
+    pub fn matches(&self, device: &DeviceInfo) -> bool {
+        let state = device.state();
+        device.state_with(&state, &self.lighting_state()) == state
+    }
+}
+
+/// Configuration for lighting presets
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct PresetsConfig {
+    /// Presets, in display order
+    pub presets: Vec<Preset>,
+}
+
+impl Default for PresetsConfig {
+    fn default() -> Self {
+        Self {
+            presets: vec![
+                Preset::new(
+                    "cozy-daylight",
+                    "Cozy Daylight",
+                    "A soft, balanced daylight effect. Good for a natural look on video calls.",
+                    60,
+                    5200,
+                ),
+                Preset::new(
+                    "natural-balance",
+                    "Natural Balance",
+                    "Slightly more neutral/white, for accurate color representation.",
+                    70,
+                    5600,
+                ),
+                Preset::new(
+                    "cool-blue",
+                    "Cool Blue",
+                    "A higher color temperature for a crisp, energetic effect.",
+                    75,
+                    6500,
+                ),
+                Preset::new(
+                    "warm-candlelight",
+                    "Warm Candlelight",
+                    "A lower color temperature for a warmer, softer, ambient feel.",
+                    40,
+                    3000,
+                ),
+                Preset::new(
+                    "bright-companionship",
+                    "Bright Companionship",
+                    "A brighter, focused setting for maximum illumination.",
+                    100,
+                    5000,
+                ),
+            ],
+        }
+    }
+}
+
+impl PresetsConfig {
+    /// Find a preset by ID
+    pub fn find(&self, id: &str) -> Option<&Preset> {
+        self.presets.iter().find(|preset| preset.id == id)
+    }
+
+    /// An ID derived from a name that no preset uses yet
+    pub fn unique_id(&self, name: &str) -> String {
+        unique_id(name, "preset", |id| self.find(id).is_some())
+    }
+}
+
+/// The state of one device in a scene
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SceneDevice {
+    /// Serial number identifying the device
+    pub serial_number: String,
+    /// Device model, shown while the device is not connected
+    #[serde(default)]
+    pub device_type: String,
+    /// State the device is put in
+    pub state: DeviceState,
+}
+
+/// A named snapshot of several devices' state
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Scene {
+    /// Unique ID other settings refer to the scene by
+    pub id: String,
+    /// Display name
+    pub name: String,
+    /// State of each device in the scene
+    #[serde(default)]
+    pub devices: Vec<SceneDevice>,
+}
+
+/// Configuration for multi-device scenes
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(default)]
+pub struct ScenesConfig {
+    /// Scenes, in display order
+    pub scenes: Vec<Scene>,
+}
+
+impl ScenesConfig {
+    /// Find a scene by ID
+    pub fn find(&self, id: &str) -> Option<&Scene> {
+        self.scenes.iter().find(|scene| scene.id == id)
+    }
+
+    /// An ID derived from a name that no scene uses yet
+    pub fn unique_id(&self, name: &str) -> String {
+        unique_id(name, "scene", |id| self.find(id).is_some())
+    }
+}
+
+/// A named set of settings switched as a whole
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    /// Unique ID
+    pub id: String,
+    /// Display name
+    pub name: String,
+    /// Lighting state applied to every connected device when switching to
+    /// the profile, over the state kept for it in `devices`
+    #[serde(default)]
+    pub lighting_state: LightingState,
+    /// Camera auto-toggle configuration
+    #[serde(default)]
+    pub auto_toggle: AutoToggleConfig,
+    /// State of each light, kept when the profile is created and when
+    /// switching away from it, and restored when switching to it
+    #[serde(default)]
+    pub devices: Vec<SceneDevice>,
+}
+
+/// Configuration for profiles
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ProfilesConfig {
+    /// ID of the active profile, kept across restarts
+    pub active: Option<String>,
+    /// Profiles, in display order
+    pub profiles: Vec<Profile>,
+}
+
+impl ProfilesConfig {
+    /// Find a profile by ID
+    pub fn find(&self, id: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|profile| profile.id == id)
+    }
+
+    /// An ID derived from a name that no profile uses yet
+    pub fn unique_id(&self, name: &str) -> String {
+        unique_id(name, "profile", |id| self.find(id).is_some())
+    }
+
+    /// The active profile, if any
+    fn active_mut(&mut self) -> Option<&mut Profile> {
+        let active = self.active.as_deref()?;
+        self.profiles
+            .iter_mut()
+            .find(|profile| profile.id == active)
+    }
+}
+
+/// Lowercase, dash-separated ID derived from a name, numbered to differ from
+/// the IDs `taken` reports, and `fallback` for names without letters or digits
+fn unique_id(name: &str, fallback: &str, taken: impl Fn(&str) -> bool) -> String {
+    let mut base = String::new();
+    for c in name.trim().chars() {
+        if c.is_alphanumeric() {
+            base.extend(c.to_lowercase());
+        } else if !base.is_empty() && !base.ends_with('-') {
+            base.push('-');
+        }
+    }
+    let base = match base.trim_end_matches('-') {
+        "" => fallback.to_string(),
+        base => base.to_string(),
+    };
+
+    let mut id = base.clone();
+    let mut suffix = 2;
+    while taken(&id) {
+        id = format!("{base}-{suffix}");
+        suffix += 1;
+    }
+    id
+}
+
+/// Event of the solar day an anchor point is placed relative to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DayEvent {
+    /// Sunrise
+    Sunrise,
+    /// Sun at its highest
+    SolarNoon,
+    /// Sunset
+    Sunset,
+}
+
+/// Point of the circadian schedule the lighting is interpolated between
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CircadianAnchor {
+    /// Event the anchor is placed relative to
+    pub event: DayEvent,
+    /// Minutes after the event, negative for before it
+    #[serde(default)]
+    pub offset_minutes: i32,
+    /// Color temperature at the anchor, in Kelvin
+    pub temperature_kelvin: u16,
+    /// Brightness at the anchor, as a percentage (0-100); brightness is only
+    /// changed between anchors that both set it
+    #[serde(default)]
+    pub brightness_percentage: Option<u8>,
+}
+
+impl CircadianAnchor {
+    fn new(event: DayEvent, offset_minutes: i32, temperature_kelvin: u16) -> Self {
+        Self {
+            event,
+            offset_minutes,
+            temperature_kelvin,
+            brightness_percentage: None,
+        }
+    }
+}
+
+/// Configuration for a color temperature schedule following the sun
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CircadianConfig {
+    /// Whether the schedule is active
+    pub enabled: bool,
+    /// Latitude sunrise and sunset are computed for, in degrees north
+    pub latitude: f64,
+    /// Longitude sunrise and sunset are computed for, in degrees east
+    pub longitude: f64,
+    /// Points the lighting is interpolated between
+    pub anchors: Vec<CircadianAnchor>,
+    /// Devices following the schedule
+    pub devices: DeviceSet,
+}
+
+impl Default for CircadianConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            latitude: 51.48,
+            longitude: 0.0,
+            anchors: vec![
+                CircadianAnchor::new(DayEvent::Sunrise, -60, 2700),
+                CircadianAnchor::new(DayEvent::Sunrise, 60, 4500),
+                CircadianAnchor::new(DayEvent::SolarNoon, 0, 6500),
+                CircadianAnchor::new(DayEvent::Sunset, -60, 4500),
+                CircadianAnchor::new(DayEvent::Sunset, 30, 2700),
+            ],
+            devices: DeviceSet::default(),
+        }
+    }
+}
+
+/// Configuration for dimming and switching lights off while the user is idle
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct IdleConfig {
+    /// Whether the idle policy is active
+    pub enabled: bool,
+    /// Minutes of idle time before lights are dimmed, 0 to never dim
+    pub dim_after_minutes: u32,
+    /// Brightness lights are dimmed to, as a percentage (0-100)
+    pub dim_brightness_percentage: u8,
+    /// Minutes of idle time before lights are switched off, 0 to never
+    /// switch them off
+    pub off_after_minutes: u32,
+    /// Devices the policy applies to
+    pub devices: DeviceSet,
+}
+
+impl Default for IdleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dim_after_minutes: 5,
+            dim_brightness_percentage: 10,
+            off_after_minutes: 15,
+            devices: DeviceSet::default(),
+        }
+    }
+}
+
+impl IdleConfig {
+    /// Bindings dimming and then switching off the devices as idle time
+    /// grows, restoring them on activity
+    pub fn bindings(&self) -> Vec<TriggerBinding> {
+        let binding = |name: &str, minutes: u32, action: LightingState| TriggerBinding {
+            name: name.to_string(),
+            enabled: self.enabled && minutes > 0,
+            source: IDLE_SOURCE_ID.to_string(),
+            condition: SignalCondition::Above {
+                threshold: f64::from(minutes) * 60.0,
+            },
+            devices: self.devices.clone(),
+            action,
+            restore_on_release: true,
+            override_policy: OverridePolicy::default(),
+        };
+
+        vec![
+            binding(
+                "Idle dim",
+                self.dim_after_minutes,
+                LightingState {
+                    brightness_percentage: Some(self.dim_brightness_percentage),
+                    ..LightingState::default()
+                },
+            ),
+            binding(
+                "Idle off",
+                self.off_after_minutes,
+                LightingState {
+                    power_on: Some(false),
+                    ..LightingState::default()
+                },
+            ),
+        ]
+    }
+}
+
 /// Device state tracking for persistence
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct DeviceStates {
@@ -89,14 +1048,40 @@ impl ConfigManager {
     }
 
     /// Update the configuration and save to disk
+    ///
+    /// The active profile takes over the new auto-toggle configuration.
     pub fn update_config(&self, new_config: LitraConfig) -> Result<(), Box<dyn std::error::Error>> {
+        self.modify(|config| {
+            *config = new_config;
+            Ok(())
+        })
+    }
+
+    /// Change the configuration and save it to disk
+    ///
+    /// The change is made under the write lock, so changes made at the same
+    /// time cannot overwrite each other. Nothing is changed if `change` fails
+    /// or the configuration cannot be saved. The active profile takes over the
+    /// new auto-toggle configuration.
+    pub fn modify<T, E>(
+        &self,
+        change: impl FnOnce(&mut LitraConfig) -> Result<T, E>,
+    ) -> Result<T, E>
+    where
+        E: From<confy::ConfyError>,
+    {
+        let mut current = self.config.write().expect("Failed to write config");
+        let mut config = current.clone();
+        let value = change(&mut config)?;
+        config.sync_active_profile();
+
         // Save to disk
-        confy::store(APP_NAME, Some(CONFIG_FILE_NAME), &new_config)?;
+        confy::store(APP_NAME, Some(CONFIG_FILE_NAME), &config)?;
 
         // Update in-memory config
-        *self.config.write().expect("Failed to write config") = new_config;
+        *current = config;
 
-        Ok(())
+        Ok(value)
     }
 
     /// Update only the auto-toggle configuration
@@ -104,9 +1089,155 @@ impl ConfigManager {
         &self,
         auto_toggle: AutoToggleConfig,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let mut config = self.get_config();
-        config.auto_toggle = auto_toggle;
-        self.update_config(config)
+        self.modify(|config| {
+            config.auto_toggle = auto_toggle;
+            Ok(())
+        })
+    }
+
+    /// Update only the trigger configuration
+    pub fn update_triggers_config(
+        &self,
+        triggers: TriggersConfig,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.modify(|config| {
+            config.triggers = triggers;
+            Ok(())
+        })
+    }
+
+    /// Update only the session lock configuration
+    pub fn update_session_lock_config(
+        &self,
+        session_lock: SessionLockConfig,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.modify(|config| {
+            config.session_lock = session_lock;
+            Ok(())
+        })
+    }
+
+    /// Update only the idle configuration
+    pub fn update_idle_config(&self, idle: IdleConfig) -> Result<(), Box<dyn std::error::Error>> {
+        self.modify(|config| {
+            config.idle = idle;
+            Ok(())
+        })
+    }
+
+    /// Update only the maximum on-time configuration
+    pub fn update_max_on_time_config(
+        &self,
+        max_on_time: MaxOnTimeConfig,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.modify(|config| {
+            config.max_on_time = max_on_time;
+            Ok(())
+        })
+    }
+
+    /// Update only the scheduled jobs
+    pub fn update_scheduler_config(
+        &self,
+        scheduler: SchedulerConfig,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.modify(|config| {
+            config.scheduler = scheduler;
+            Ok(())
+        })
+    }
+
+    /// Update only the scenes
+    pub fn update_scenes_config(
+        &self,
+        scenes: ScenesConfig,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.modify(|config| {
+            config.scenes = scenes;
+            Ok(())
+        })
+    }
+
+    /// Update only the history configuration
+    pub fn update_history_config(
+        &self,
+        history: HistoryConfig,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.modify(|config| {
+            config.history = history;
+            Ok(())
+        })
+    }
+
+    /// Update only the calendar configuration
+    pub fn update_calendar_config(
+        &self,
+        calendar: CalendarConfig,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.modify(|config| {
+            config.calendar = calendar;
+            Ok(())
+        })
+    }
+
+    /// Update only the rules
+    pub fn update_rules_config(
+        &self,
+        rules: RulesConfig,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.modify(|config| {
+            config.rules = rules;
+            Ok(())
+        })
+    }
+
+    /// Update only the circadian schedule configuration
+    pub fn update_circadian_config(
+        &self,
+        circadian: CircadianConfig,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.modify(|config| {
+            config.circadian = circadian;
+            Ok(())
+        })
+    }
+
+    /// Update only the display sleep configuration
+    pub fn update_display_sleep_config(
+        &self,
+        display_sleep: DisplaySleepConfig,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.modify(|config| {
+            config.display_sleep = display_sleep;
+            Ok(())
+        })
+    }
+
+    /// Update only the profiles
+    pub fn update_profiles_config(
+        &self,
+        profiles: ProfilesConfig,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.modify(|config| {
+            config.profiles = profiles;
+            Ok(())
+        })
+    }
+
+    /// Switch to a profile and save, returning the profile
+    ///
+    /// `devices` is the current state of the lights, kept in the profile
+    /// being left.
+    pub fn switch_profile(
+        &self,
+        id: &str,
+        devices: Vec<SceneDevice>,
+    ) -> Result<Profile, Box<dyn std::error::Error>> {
+        self.modify(|config| {
+            config
+                .switch_profile(id, devices)
+                .ok_or_else(|| format!("Profile {id} does not exist").into())
+        })
     }
 
     /// Update only the device states
@@ -114,9 +1245,10 @@ impl ConfigManager {
         &self,
         device_states: DeviceStates,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let mut config = self.get_config();
-        config.device_states = device_states;
-        self.update_config(config)
+        self.modify(|config| {
+            config.device_states = device_states;
+            Ok(())
+        })
     }
 
     /// Get the configuration file path
@@ -140,11 +1272,228 @@ mod tests {
 
         assert_eq!(config.auto_toggle.enabled, deserialized.auto_toggle.enabled);
         assert_eq!(
-            config.auto_toggle.debounce_ms,
-            deserialized.auto_toggle.debounce_ms
+            config.auto_toggle.on_delay_ms,
+            deserialized.auto_toggle.on_delay_ms
+        );
+        assert_eq!(
+            config.auto_toggle.off_grace_ms,
+            deserialized.auto_toggle.off_grace_ms
+        );
+    }
+
+    #[test]
+    fn test_in_call_state_serialization() {
+        let mut config = LitraConfig::default();
+        config.auto_toggle.in_call_state = LightingState {
+            power_on: Some(true),
+            brightness_percentage: Some(70),
+            temperature_kelvin: Some(5600),
+            preset: None,
+        };
+
+        let serialized = toml::to_string(&config).unwrap();
+        let deserialized: LitraConfig = toml::from_str(&serialized).unwrap();
+
+        assert_eq!(
+            config.auto_toggle.in_call_state,
+            deserialized.auto_toggle.in_call_state
+        );
+    }
+
+    #[test]
+    fn test_config_without_in_call_state_loads() {
+        let serialized = r#"
+            [auto_toggle]
+            enabled = true
+            strategy = "allDevices"
+            debounceMs = 2000
+
+            [device_states]
+            auto_toggle_controlled = []
+        "#;
+        let config: LitraConfig = toml::from_str(serialized).unwrap();
+
+        assert!(config.auto_toggle.enabled);
+        // The old debounce delay carries over as the turn-off grace period
+        assert_eq!(config.auto_toggle.off_grace_ms, 2000);
+        assert_eq!(config.auto_toggle.on_delay_ms, 1000);
+        assert_eq!(config.auto_toggle.in_call_state.power_on, Some(true));
+        assert_eq!(config.auto_toggle.in_call_state.brightness_percentage, None);
+        assert_eq!(config.auto_toggle.activity_source, ActivitySource::Camera);
+    }
+
+    #[test]
+    fn test_activity_source_serialization() {
+        let mut config = LitraConfig::default();
+        config.auto_toggle.activity_source = ActivitySource::CameraOrMicrophone;
+
+        let serialized = toml::to_string(&config).unwrap();
+        assert!(serialized.contains(r#"activitySource = "cameraOrMicrophone""#));
+
+        let deserialized: LitraConfig = toml::from_str(&serialized).unwrap();
+        assert_eq!(
+            deserialized.auto_toggle.activity_source,
+            ActivitySource::CameraOrMicrophone
+        );
+    }
+
+    #[test]
+    fn test_circadian_config_serialization() {
+        let mut config = LitraConfig::default();
+        config.circadian.latitude = 40.71;
+        config.circadian.longitude = -74.01;
+        config.circadian.anchors[2].brightness_percentage = Some(80);
+
+        let serialized = toml::to_string(&config).unwrap();
+        assert!(serialized.contains(r#"event = "solarNoon""#));
+        let deserialized: LitraConfig = toml::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.circadian, config.circadian);
+
+        // Missing fields fall back to the default schedule
+        let partial: LitraConfig = toml::from_str("[circadian]\nenabled = true\n").unwrap();
+        assert!(partial.circadian.enabled);
+        assert_eq!(
+            partial.circadian.anchors,
+            CircadianConfig::default().anchors
         );
     }
 
+    #[test]
+    fn test_trigger_binding_serialization() {
+        let config = LitraConfig {
+            triggers: TriggersConfig {
+                bindings: vec![TriggerBinding {
+                    name: "Dim when idle".to_string(),
+                    enabled: true,
+                    source: "idle".to_string(),
+                    condition: SignalCondition::Above { threshold: 300.0 },
+                    devices: DeviceSet::Devices {
+                        serial_numbers: vec!["ABC123".to_string()],
+                    },
+                    action: LightingState {
+                        brightness_percentage: Some(10),
+                        ..LightingState::default()
+                    },
+                    restore_on_release: true,
+                    override_policy: OverridePolicy::Reassert,
+                }],
+            },
+            ..LitraConfig::default()
+        };
+
+        let serialized = toml::to_string(&config).unwrap();
+        let deserialized: LitraConfig = toml::from_str(&serialized).unwrap();
+
+        assert_eq!(deserialized.triggers, config.triggers);
+    }
+
+    #[test]
+    fn test_session_lock_binding() {
+        let mut config = LitraConfig::default();
+        let session_lock = |config: &LitraConfig| {
+            config
+                .trigger_bindings()
+                .into_iter()
+                .find(|binding| binding.source == SESSION_LOCK_SOURCE_ID)
+                .unwrap()
+        };
+        assert!(!session_lock(&config).enabled);
+
+        config.session_lock = SessionLockConfig {
+            enabled: true,
+            devices: DeviceSet::Devices {
+                serial_numbers: vec!["ABC123".to_string()],
+            },
+        };
+        let binding = session_lock(&config);
+        assert!(binding.enabled);
+        assert_eq!(binding.action.power_on, Some(false));
+        assert_eq!(binding.devices, config.session_lock.devices);
+        assert!(binding.restore_on_release);
+    }
+
+    #[test]
+    fn test_auto_toggle_binding() {
+        let mut config = LitraConfig::default();
+        config.auto_toggle = AutoToggleConfig {
+            enabled: true,
+            strategy: AutoToggleStrategy::SelectedDevice {
+                serial_number: "ABC123".to_string(),
+            },
+            override_policy: OverridePolicy::Reassert,
+            ..AutoToggleConfig::default()
+        };
+
+        let binding = config
+            .trigger_bindings()
+            .into_iter()
+            .find(|binding| binding.name == AUTO_TOGGLE_BINDING_NAME)
+            .unwrap();
+        assert!(binding.enabled);
+        assert_eq!(binding.source, CAMERA_SOURCE_ID);
+        assert_eq!(binding.condition, SignalCondition::IsTrue);
+        assert_eq!(
+            binding.devices,
+            DeviceSet::Devices {
+                serial_numbers: vec!["ABC123".to_string()],
+            }
+        );
+        assert_eq!(binding.action, config.auto_toggle.in_call_state);
+        assert_eq!(binding.override_policy, OverridePolicy::Reassert);
+        assert!(binding.restore_on_release);
+    }
+
+    #[test]
+    fn test_display_sleep_binding() {
+        let mut config = LitraConfig::default();
+        config.display_sleep.enabled = true;
+
+        let binding = config
+            .trigger_bindings()
+            .into_iter()
+            .find(|binding| binding.source == DISPLAYS_SOURCE_ID)
+            .unwrap();
+        assert!(binding.enabled);
+        assert_eq!(binding.condition, SignalCondition::IsTrue);
+        assert_eq!(binding.action.power_on, Some(false));
+        assert!(binding.restore_on_release);
+    }
+
+    #[test]
+    fn test_idle_bindings() {
+        let mut idle = IdleConfig {
+            enabled: true,
+            ..IdleConfig::default()
+        };
+        let bindings = idle.bindings();
+        assert!(bindings.iter().all(|binding| binding.enabled));
+        assert_eq!(
+            bindings[0].condition,
+            SignalCondition::Above { threshold: 300.0 }
+        );
+        assert_eq!(bindings[0].action.brightness_percentage, Some(10));
+        assert_eq!(
+            bindings[1].condition,
+            SignalCondition::Above { threshold: 900.0 }
+        );
+        assert_eq!(bindings[1].action.power_on, Some(false));
+
+        // A zero threshold disables that step only
+        idle.dim_after_minutes = 0;
+        let bindings = idle.bindings();
+        assert!(!bindings[0].enabled);
+        assert!(bindings[1].enabled);
+    }
+
+    #[test]
+    fn test_signal_conditions() {
+        assert!(SignalCondition::IsTrue.matches(&SignalValue::Bool(true)));
+        assert!(SignalCondition::IsFalse.matches(&SignalValue::Bool(false)));
+        assert!(SignalCondition::Above { threshold: 1.0 }.matches(&SignalValue::Number(2.0)));
+        assert!(!SignalCondition::Below { threshold: 1.0 }.matches(&SignalValue::Number(2.0)));
+        assert!(!SignalCondition::IsTrue.matches(&SignalValue::Number(1.0)));
+    }
+
     #[test]
     fn test_strategy_serialization() {
         let strategies = vec![
@@ -170,4 +1519,212 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_rules_serialization() {
+        let json = r#"{
+            "name": "Evening calls",
+            "priority": 10,
+            "condition": {"all": [
+                {"signal": {"source": "camera", "condition": "isTrue"}},
+                {"timeWindow": {"start": "18:00", "end": "23:30"}},
+                {"not": {"weekdays": {"days": ["sat", "Sun"]}}},
+                {"deviceConnected": {"serialNumber": "ABC123"}}
+            ]},
+            "action": {"temperatureKelvin": 2700}
+        }"#;
+        let rule: Rule = serde_json::from_str(json).unwrap();
+        assert!(rule.enabled);
+        assert_eq!(rule.devices, DeviceSet::AllDevices);
+        let RuleCondition::All(conditions) = &rule.condition else {
+            panic!("Expected an all condition");
+        };
+        assert_eq!(
+            conditions[1],
+            RuleCondition::TimeWindow {
+                start: NaiveTime::from_hms_opt(18, 0, 0).unwrap(),
+                end: NaiveTime::from_hms_opt(23, 30, 0).unwrap(),
+            }
+        );
+
+        let mut config = LitraConfig::default();
+        config.rules.rules.push(rule);
+        let serialized = toml::to_string(&config).unwrap();
+        let deserialized: LitraConfig = toml::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.rules, config.rules);
+    }
+
+    #[test]
+    fn test_presets() {
+        let presets = PresetsConfig::default();
+        assert_eq!(
+            presets.find("warm-candlelight").unwrap().lighting_state(),
+            LightingState {
+                power_on: None,
+                brightness_percentage: Some(40),
+                temperature_kelvin: Some(3000),
+                preset: None,
+            }
+        );
+        assert_eq!(
+            presets.unique_id("  Late Night: Reading! "),
+            "late-night-reading"
+        );
+        assert_eq!(presets.unique_id("Cool Blue"), "cool-blue-2");
+        assert_eq!(presets.unique_id("***"), "preset");
+
+        // Matching uses the lumens the preset sets on the device's range
+        let cozy = presets.find("cozy-daylight").unwrap();
+        let glow = DeviceInfo {
+            brightness_lumens: 158,
+            temperature_kelvin: 5200,
+            ..DeviceInfo::disconnected("ABC".to_string(), "Litra Glow".to_string())
+        };
+        assert!(cozy.matches(&glow));
+        assert!(!cozy.matches(&DeviceInfo {
+            brightness_lumens: 157,
+            ..glow.clone()
+        }));
+        let beam = DeviceInfo {
+            min_brightness_lumens: 30,
+            max_brightness_lumens: 400,
+            ..glow.clone()
+        };
+        assert!(!cozy.matches(&beam));
+        assert!(cozy.matches(&DeviceInfo {
+            brightness_lumens: 252,
+            ..beam
+        }));
+
+        // A temperature the device cannot reach matches the nearest it can
+        let cool = presets.find("cool-blue").unwrap();
+        let warm_only = DeviceInfo {
+            brightness_lumens: 192,
+            temperature_kelvin: 6000,
+            max_temperature_kelvin: 6000,
+            ..glow.clone()
+        };
+        assert!(cool.matches(&warm_only));
+        assert!(!cool.matches(&DeviceInfo {
+            temperature_kelvin: 5900,
+            ..warm_only
+        }));
+
+        // Presets missing from older configs are the built-in ones, while an
+        // emptied list stays empty
+        let partial: LitraConfig = toml::from_str("[idle]\nenabled = true\n").unwrap();
+        assert_eq!(partial.presets, presets);
+        let emptied: LitraConfig = toml::from_str("[presets]\npresets = []\n").unwrap();
+        assert!(emptied.presets.presets.is_empty());
+    }
+
+    #[test]
+    fn test_scenes_serialization() {
+        let mut config = LitraConfig::default();
+        config.scenes.scenes.push(Scene {
+            id: "streaming".to_string(),
+            name: "Streaming".to_string(),
+            devices: vec![
+                SceneDevice {
+                    serial_number: "KEY".to_string(),
+                    device_type: "Litra Beam".to_string(),
+                    state: DeviceState {
+                        is_on: true,
+                        brightness_lumens: 320,
+                        temperature_kelvin: 5600,
+                    },
+                },
+                SceneDevice {
+                    serial_number: "BACK".to_string(),
+                    device_type: "Litra Glow".to_string(),
+                    state: DeviceState {
+                        is_on: false,
+                        brightness_lumens: 100,
+                        temperature_kelvin: 4000,
+                    },
+                },
+            ],
+        });
+
+        let serialized = toml::to_string(&config).unwrap();
+        let deserialized: LitraConfig = toml::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.scenes, config.scenes);
+        assert_eq!(config.scenes.unique_id("Streaming"), "streaming-2");
+        assert_eq!(config.scenes.unique_id(""), "scene");
+    }
+
+    #[test]
+    fn test_profiles() {
+        let light = |brightness_lumens| SceneDevice {
+            serial_number: "KEY".to_string(),
+            device_type: "Litra Glow".to_string(),
+            state: DeviceState {
+                is_on: true,
+                brightness_lumens,
+                temperature_kelvin: 5000,
+            },
+        };
+
+        let mut config = LitraConfig::default();
+        config.auto_toggle.enabled = true;
+        config.profiles.profiles = vec![
+            Profile {
+                id: "office-calls".to_string(),
+                name: "Office calls".to_string(),
+                lighting_state: LightingState {
+                    power_on: Some(true),
+                    brightness_percentage: Some(80),
+                    temperature_kelvin: Some(5000),
+                    preset: None,
+                },
+                auto_toggle: config.auto_toggle.clone(),
+                devices: vec![light(100)],
+            },
+            Profile {
+                id: "evening-streaming".to_string(),
+                name: "Evening streaming".to_string(),
+                lighting_state: LightingState::default(),
+                auto_toggle: AutoToggleConfig::default(),
+                devices: Vec::new(),
+            },
+        ];
+        config.profiles.active = Some("office-calls".to_string());
+
+        let serialized = toml::to_string(&config).unwrap();
+        let mut config: LitraConfig = toml::from_str(&serialized).unwrap();
+        assert_eq!(config.profiles.active.as_deref(), Some("office-calls"));
+        assert_eq!(config.profiles.profiles.len(), 2);
+        assert_eq!(config.profiles.unique_id("Office calls"), "office-calls-2");
+
+        // Changes made while a profile is active are kept in it, and so are
+        // the lights as they were when switching away from it
+        config.auto_toggle.off_grace_ms = 10000;
+        config.sync_active_profile();
+        let evening = config
+            .switch_profile("evening-streaming", vec![light(200)])
+            .unwrap();
+        assert_eq!(evening.name, "Evening streaming");
+        assert!(evening.devices.is_empty());
+        assert!(!config.auto_toggle.enabled);
+
+        let office = config
+            .switch_profile("office-calls", vec![light(50)])
+            .unwrap();
+        assert_eq!(office.auto_toggle.off_grace_ms, 10000);
+        assert_eq!(office.devices, [light(200)]);
+        assert!(config.auto_toggle.enabled);
+        assert_eq!(config.profiles.active.as_deref(), Some("office-calls"));
+        assert_eq!(
+            config.profiles.find("evening-streaming").unwrap().devices,
+            [light(50)]
+        );
+
+        // Switching to a missing profile keeps the lights of the active one
+        assert!(config.switch_profile("missing", Vec::new()).is_none());
+        assert_eq!(config.profiles.active.as_deref(), Some("office-calls"));
+        assert_eq!(
+            config.profiles.find("office-calls").unwrap().devices,
+            [light(200)]
+        );
+    }
 }
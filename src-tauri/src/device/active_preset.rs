@@ -0,0 +1,112 @@
+//! Tracking of the preset each device is at.
+use super::types::DeviceInfo;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Change of the preset a device is at, sent to the frontend
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivePresetChange {
+    /// Serial number of the device
+    pub serial_number: String,
+    /// ID of the preset the device is now at, if any
+    pub preset_id: Option<String>,
+}
+
+/// Remembers the active preset of each device to report when it changes
+#[derive(Debug, Default)]
+pub struct ActivePresetTracker {
+    /// Active preset of each connected device
+    active: BTreeMap<String, Option<String>>,
+}
+
+impl ActivePresetTracker {
+    /// Take in the current devices and return the changes since the last
+    /// update.
+    ///
+    /// Devices no longer connected are reported as at no preset.
+    pub fn update(&mut self, devices: &[DeviceInfo]) -> Vec<ActivePresetChange> {
+        let mut changes = Vec::new();
+        let mut active = BTreeMap::new();
+
+        for device in devices.iter().filter(|device| device.is_connected) {
+            let previous = self.active.remove(&device.serial_number).flatten();
+            if previous != device.active_preset {
+                changes.push(ActivePresetChange {
+                    serial_number: device.serial_number.clone(),
+                    preset_id: device.active_preset.clone(),
+                });
+            }
+            active.insert(device.serial_number.clone(), device.active_preset.clone());
+        }
+
+        for (serial_number, previous) in std::mem::take(&mut self.active) {
+            if previous.is_some() {
+                changes.push(ActivePresetChange {
+                    serial_number,
+                    preset_id: None,
+                });
+            }
+        }
+
+        self.active = active;
+        changes
+    }
+}
+
+/// ID of the preset all connected devices are at, if they agree
+pub fn common_preset(devices: &[DeviceInfo]) -> Option<&str> {
+    let mut presets = devices
+        .iter()
+        .filter(|device| device.is_connected)
+        .map(|device| device.active_preset.as_deref());
+    let first = presets.next()??;
+    presets.all(|preset| preset == Some(first)).then_some(first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(serial_number: &str, active_preset: Option<&str>) -> DeviceInfo {
+        DeviceInfo {
+            is_connected: true,
+            active_preset: active_preset.map(str::to_string),
+            ..DeviceInfo::disconnected(serial_number.to_string(), "Litra Glow".to_string())
+        }
+    }
+
+    fn change(serial_number: &str, preset_id: Option<&str>) -> ActivePresetChange {
+        ActivePresetChange {
+            serial_number: serial_number.to_string(),
+            preset_id: preset_id.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_reports_changes() {
+        let mut tracker = ActivePresetTracker::default();
+
+        // Devices at no preset are not news when first seen
+        let devices = [device("KEY", Some("cool-blue")), device("FILL", None)];
+        assert_eq!(tracker.update(&devices), [change("KEY", Some("cool-blue"))]);
+        assert_eq!(common_preset(&devices), None);
+
+        let devices = [
+            device("KEY", Some("cool-blue")),
+            device("FILL", Some("cool-blue")),
+        ];
+        assert_eq!(
+            tracker.update(&devices),
+            [change("FILL", Some("cool-blue"))]
+        );
+        assert_eq!(common_preset(&devices), Some("cool-blue"));
+
+        // A device going away leaves its preset
+        let changes = tracker.update(&[device("FILL", Some("cool-blue"))]);
+        assert_eq!(changes, [change("KEY", None)]);
+        assert!(tracker
+            .update(&[device("FILL", Some("cool-blue"))])
+            .is_empty());
+    }
+}
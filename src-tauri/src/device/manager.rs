@@ -1,5 +1,6 @@
 //! Device manager implementation for handling Litra device operations.
-use super::types::DeviceInfo;
+use super::types::{DeviceInfo, DeviceState};
+use crate::config::{LightingState, Preset};
 use crate::error::{device_communication_error, device_not_found_error, AppError, AppResult};
 use litra::Litra;
 
@@ -10,6 +11,9 @@ use litra::Litra;
 pub struct DeviceManager {
     /// Litra context for device communication
     context: Litra,
+    /// Presets matched against the device state and referred to by lighting
+    /// states
+    presets: Vec<Preset>,
 }
 
 impl DeviceManager {
@@ -21,7 +25,16 @@ impl DeviceManager {
     /// cannot be initialized.
     pub fn new() -> AppResult<Self> {
         let context = Litra::new().map_err(AppError::from)?;
-        Ok(DeviceManager { context })
+        Ok(DeviceManager {
+            context,
+            presets: Vec::new(),
+        })
+    }
+
+    /// Sets the presets reported as active in device information and
+    /// resolved in the lighting states applied.
+    pub fn set_presets(&mut self, presets: Vec<Preset>) {
+        self.presets = presets;
     }
 
     /// Refreshes the internal device list.
@@ -34,6 +47,15 @@ impl DeviceManager {
             .map_err(AppError::from)
     }
 
+    /// Reopens the Litra context.
+    ///
+    /// Device handles go stale when the devices lose power, e.g. across a
+    /// system suspend, so the context is created anew rather than refreshed.
+    pub fn reconnect(&mut self) -> AppResult<()> {
+        self.context = Litra::new().map_err(AppError::from)?;
+        Ok(())
+    }
+
     /// Retrieves detailed information for a specific device.
     ///
     /// # Arguments
@@ -131,7 +153,7 @@ impl DeviceManager {
             0
         };
 
-        Ok(DeviceInfo {
+        let mut info = DeviceInfo {
             serial_number: device_serial.to_string(),
             device_type,
             is_connected: true,
@@ -143,7 +165,98 @@ impl DeviceManager {
             max_brightness_lumens: max_brightness,
             min_temperature_kelvin: min_temperature,
             max_temperature_kelvin: max_temperature,
-        })
+            active_preset: None,
+        };
+        info.active_preset = self
+            .presets
+            .iter()
+            .find(|preset| preset.matches(&info))
+            .map(|preset| preset.id.clone());
+
+        Ok(info)
+    }
+
+    /// Restores a device to a previously captured state.
+    ///
+    /// # Arguments
+    ///
+    /// * `serial_number` - The serial number of the device to update
+    /// * `state` - The state to restore
+    ///
+    /// # Returns
+    ///
+    /// Returns an error if the device is not found or cannot be updated.
+    pub fn apply_device_state(&self, serial_number: &str, state: &DeviceState) -> AppResult<()> {
+        let handle = self.get_device_handle(serial_number)?;
+
+        handle
+            .set_brightness_in_lumen(state.brightness_lumens)
+            .map_err(|e| device_communication_error(&format!("Failed to set brightness: {e}")))?;
+
+        handle
+            .set_temperature_in_kelvin(state.temperature_kelvin)
+            .map_err(|e| device_communication_error(&format!("Failed to set temperature: {e}")))?;
+
+        handle
+            .set_on(state.is_on)
+            .map_err(|e| device_communication_error(&format!("Failed to set power state: {e}")))
+    }
+
+    /// Applies a lighting state to a device.
+    ///
+    /// Only the fields set in `state` are changed, with a preset it refers to
+    /// filling in its brightness and temperature. A temperature outside the
+    /// device's limits is clamped to them. A device being switched on is
+    /// powered up first, and one being switched off is powered down last, so
+    /// the new brightness and temperature are in place without a visible jump.
+    ///
+    /// # Arguments
+    ///
+    /// * `serial_number` - The serial number of the device to update
+    /// * `state` - The lighting state to apply
+    ///
+    /// # Returns
+    ///
+    /// Returns an error if the device is not found or cannot be updated.
+    pub fn apply_lighting_state(
+        &self,
+        serial_number: &str,
+        state: &LightingState,
+    ) -> AppResult<()> {
+        let state = &state.resolve(&self.presets);
+        let info = self.get_device_info(serial_number)?;
+        let handle = self.get_device_handle(serial_number)?;
+        let target = info.state_with(&info.state(), state);
+
+        if state.power_on == Some(true) {
+            handle.set_on(true).map_err(|e| {
+                device_communication_error(&format!("Failed to set power state: {e}"))
+            })?;
+        }
+
+        if state.brightness_percentage.is_some() {
+            handle
+                .set_brightness_in_lumen(target.brightness_lumens)
+                .map_err(|e| {
+                    device_communication_error(&format!("Failed to set brightness: {e}"))
+                })?;
+        }
+
+        if state.temperature_kelvin.is_some() {
+            handle
+                .set_temperature_in_kelvin(target.temperature_kelvin)
+                .map_err(|e| {
+                    device_communication_error(&format!("Failed to set temperature: {e}"))
+                })?;
+        }
+
+        if state.power_on == Some(false) {
+            handle.set_on(false).map_err(|e| {
+                device_communication_error(&format!("Failed to set power state: {e}"))
+            })?;
+        }
+
+        Ok(())
     }
 
     /// Finds a device by serial number and returns a handle to it.
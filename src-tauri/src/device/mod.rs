@@ -2,8 +2,11 @@
 ///
 /// This module provides comprehensive device management functionality including
 /// device discovery, state management, and communication with Litra devices.
+pub mod active_preset;
 pub mod manager;
+pub mod sink;
 pub mod types;
 
+pub use active_preset::{common_preset, ActivePresetTracker};
 pub use manager::DeviceManager;
-pub use types::DeviceInfo;
+pub use types::{DeviceInfo, DeviceState};
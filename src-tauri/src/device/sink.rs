@@ -0,0 +1,210 @@
+//! Device sinks
+//!
+//! Automation reads and changes device state through the [`DeviceSink`]
+//! trait, so it can run against the real device manager or an in-memory fake
+//! in tests.
+
+use super::{DeviceInfo, DeviceManager, DeviceState};
+use crate::config::LightingState;
+use async_trait::async_trait;
+use tokio::sync::Mutex;
+
+/// Result type for device sink operations
+pub type DeviceSinkResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Target for the device reads and changes made by automation
+#[async_trait]
+pub trait DeviceSink: Send + Sync {
+    /// Get information for all connected devices
+    async fn get_all_devices(&self) -> DeviceSinkResult<Vec<DeviceInfo>>;
+
+    /// Get information for a single device
+    async fn get_device_info(&self, serial_number: &str) -> DeviceSinkResult<DeviceInfo>;
+
+    /// Apply a lighting state to a device
+    async fn apply_lighting_state(
+        &self,
+        serial_number: &str,
+        state: &LightingState,
+    ) -> DeviceSinkResult<()>;
+
+    /// Restore a device to a previously captured state
+    async fn apply_device_state(
+        &self,
+        serial_number: &str,
+        state: &DeviceState,
+    ) -> DeviceSinkResult<()>;
+
+    /// Reopen the connection to the devices, e.g. after a system resume
+    async fn reconnect(&self) -> DeviceSinkResult<()>;
+}
+
+#[async_trait]
+impl DeviceSink for Mutex<DeviceManager> {
+    async fn get_all_devices(&self) -> DeviceSinkResult<Vec<DeviceInfo>> {
+        Ok(self.lock().await.get_all_devices()?)
+    }
+
+    async fn get_device_info(&self, serial_number: &str) -> DeviceSinkResult<DeviceInfo> {
+        Ok(self.lock().await.get_device_info(serial_number)?)
+    }
+
+    async fn apply_lighting_state(
+        &self,
+        serial_number: &str,
+        state: &LightingState,
+    ) -> DeviceSinkResult<()> {
+        Ok(self
+            .lock()
+            .await
+            .apply_lighting_state(serial_number, state)?)
+    }
+
+    async fn apply_device_state(
+        &self,
+        serial_number: &str,
+        state: &DeviceState,
+    ) -> DeviceSinkResult<()> {
+        Ok(self.lock().await.apply_device_state(serial_number, state)?)
+    }
+
+    async fn reconnect(&self) -> DeviceSinkResult<()> {
+        Ok(self.lock().await.reconnect()?)
+    }
+}
+
+/// A change made through a [`RecordingDeviceSink`]
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SinkCall {
+    /// A lighting state was applied to the device
+    ApplyLightingState(String),
+    /// The device was restored to a captured state
+    ApplyDeviceState(String, DeviceState),
+    /// The connection to the devices was reopened
+    Reconnect,
+}
+
+/// In-memory device sink recording every change, for tests
+#[cfg(test)]
+pub struct RecordingDeviceSink {
+    /// Simulated devices
+    devices: std::sync::Mutex<Vec<DeviceInfo>>,
+    /// Presets resolved in the lighting states applied
+    presets: std::sync::Mutex<Vec<crate::config::Preset>>,
+    /// Changes made so far
+    calls: std::sync::Mutex<Vec<SinkCall>>,
+}
+
+#[cfg(test)]
+impl RecordingDeviceSink {
+    /// Create a sink with switched-off devices of the given serial numbers
+    pub fn new(serial_numbers: &[&str]) -> Self {
+        use crate::test_support::INITIAL_STATE;
+
+        let devices = serial_numbers
+            .iter()
+            .map(|serial_number| DeviceInfo {
+                is_connected: true,
+                is_on: INITIAL_STATE.is_on,
+                brightness_lumens: INITIAL_STATE.brightness_lumens,
+                brightness_percentage: 40,
+                temperature_kelvin: INITIAL_STATE.temperature_kelvin,
+                ..DeviceInfo::disconnected(serial_number.to_string(), "Litra Glow".to_string())
+            })
+            .collect();
+
+        Self {
+            devices: std::sync::Mutex::new(devices),
+            presets: std::sync::Mutex::new(Vec::new()),
+            calls: std::sync::Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Set the presets resolved in the lighting states applied, like
+    /// [`DeviceManager::set_presets`]
+    pub fn set_presets(&self, presets: Vec<crate::config::Preset>) {
+        *self.presets.lock().unwrap() = presets;
+    }
+
+    /// Current state of a device
+    pub fn device_state(&self, serial_number: &str) -> DeviceState {
+        self.find(serial_number)
+            .expect("Unknown test device")
+            .state()
+    }
+
+    /// Change a device behind automation's back, like its own button would
+    pub fn set_device_state(&self, serial_number: &str, state: DeviceState) {
+        let mut devices = self.devices.lock().unwrap();
+        let device = devices
+            .iter_mut()
+            .find(|device| device.serial_number == serial_number)
+            .expect("Unknown test device");
+        device.is_on = state.is_on;
+        device.brightness_lumens = state.brightness_lumens;
+        device.temperature_kelvin = state.temperature_kelvin;
+    }
+
+    /// All changes made so far
+    pub fn calls(&self) -> Vec<SinkCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    fn find(&self, serial_number: &str) -> Option<DeviceInfo> {
+        self.devices
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|device| device.serial_number == serial_number)
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl DeviceSink for RecordingDeviceSink {
+    async fn get_all_devices(&self) -> DeviceSinkResult<Vec<DeviceInfo>> {
+        Ok(self.devices.lock().unwrap().clone())
+    }
+
+    async fn get_device_info(&self, serial_number: &str) -> DeviceSinkResult<DeviceInfo> {
+        self.find(serial_number)
+            .ok_or_else(|| format!("Device {serial_number} not found").into())
+    }
+
+    async fn apply_lighting_state(
+        &self,
+        serial_number: &str,
+        state: &LightingState,
+    ) -> DeviceSinkResult<()> {
+        let state = state.resolve(&self.presets.lock().unwrap());
+        let device = self.get_device_info(serial_number).await?;
+
+        self.set_device_state(serial_number, device.state_with(&device.state(), &state));
+        self.calls
+            .lock()
+            .unwrap()
+            .push(SinkCall::ApplyLightingState(serial_number.to_string()));
+        Ok(())
+    }
+
+    async fn apply_device_state(
+        &self,
+        serial_number: &str,
+        state: &DeviceState,
+    ) -> DeviceSinkResult<()> {
+        self.get_device_info(serial_number).await?;
+        self.set_device_state(serial_number, *state);
+        self.calls.lock().unwrap().push(SinkCall::ApplyDeviceState(
+            serial_number.to_string(),
+            *state,
+        ));
+        Ok(())
+    }
+
+    async fn reconnect(&self) -> DeviceSinkResult<()> {
+        self.calls.lock().unwrap().push(SinkCall::Reconnect);
+        Ok(())
+    }
+}
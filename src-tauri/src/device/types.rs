@@ -1,4 +1,5 @@
 /// Type definitions for device information and state.
+use crate::config::LightingState;
 use serde::{Deserialize, Serialize};
 
 /// Complete device information structure for frontend communication.
@@ -39,6 +40,26 @@ pub struct DeviceInfo {
 
     /// Maximum color temperature supported (typically 6500K)
     pub max_temperature_kelvin: u16,
+
+    /// ID of the preset matching the current brightness and temperature
+    #[serde(default)]
+    pub active_preset: Option<String>,
+}
+
+/// Snapshot of the adjustable state of a device.
+///
+/// Used to put a device back exactly the way it was after it has been
+/// changed automatically.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceState {
+    /// Power state (on/off)
+    pub is_on: bool,
+
+    /// Brightness level in lumens
+    pub brightness_lumens: u16,
+
+    /// Color temperature in Kelvin
+    pub temperature_kelvin: u16,
 }
 
 impl DeviceInfo {
@@ -56,6 +77,16 @@ impl DeviceInfo {
             max_brightness_lumens: 250,
             min_temperature_kelvin: 2700,
             max_temperature_kelvin: 6500,
+            active_preset: None,
+        }
+    }
+
+    /// Returns a snapshot of the current adjustable state.
+    pub fn state(&self) -> DeviceState {
+        DeviceState {
+            is_on: self.is_on,
+            brightness_lumens: self.brightness_lumens,
+            temperature_kelvin: self.temperature_kelvin,
         }
     }
 
@@ -79,6 +110,32 @@ impl DeviceInfo {
         self.min_brightness_lumens + ((range as f64 * percentage as f64 / 100.0) as u16)
     }
 
+    /// Clamps a temperature to device limits, rounded to a 100 K increment.
+    pub fn clamp_temperature(&self, kelvin: u16) -> u16 {
+        (kelvin.saturating_add(50) / 100 * 100)
+            .clamp(self.min_temperature_kelvin, self.max_temperature_kelvin)
+    }
+
+    /// Returns `from` with the fields set in a lighting state applied, the
+    /// way the device manager writes them to this device.
+    ///
+    /// The lighting state must have its preset resolved already.
+    pub fn state_with(&self, from: &DeviceState, state: &LightingState) -> DeviceState {
+        DeviceState {
+            is_on: state.power_on.unwrap_or(from.is_on),
+            brightness_lumens: state
+                .brightness_percentage
+                .map_or(from.brightness_lumens, |percentage| {
+                    self.calculate_lumens_from_percentage(percentage)
+                }),
+            temperature_kelvin: state
+                .temperature_kelvin
+                .map_or(from.temperature_kelvin, |kelvin| {
+                    self.clamp_temperature(kelvin)
+                }),
+        }
+    }
+
     /// Validates if the given brightness in lumens is within device limits.
     pub fn is_valid_brightness(&self, lumens: u16) -> bool {
         lumens >= self.min_brightness_lumens && lumens <= self.max_brightness_lumens
@@ -0,0 +1,289 @@
+//! Device state history
+//!
+//! Samples the power, brightness and temperature of every device at a fixed
+//! interval and keeps the changes on disk, so past lighting can be looked up
+//! and exported. Old samples are downsampled and expired ones deleted
+//! according to the retention configured. The devices read at every sample
+//! are also published, so other parts of the app can follow them without
+//! polling the devices themselves.
+
+pub mod store;
+
+use crate::config::{self, HistoryConfig};
+use crate::device::sink::DeviceSink;
+use crate::device::DeviceInfo;
+use crate::task::BackgroundTask;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeSet;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use store::{HistorySample, HistoryStore};
+use tokio::sync::{watch, Notify};
+
+/// Name of the directory holding the sample files, under the data directory
+pub const HISTORY_DIR_NAME: &str = "device_history";
+
+/// Time between two samples of the devices
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Time between two compactions of the stored samples
+const COMPACT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Global state type for the device history recorder
+pub type DeviceHistoryRecorderState = Arc<tokio::sync::Mutex<DeviceHistoryRecorder>>;
+
+/// Recorder sampling device state into the history
+pub struct DeviceHistoryRecorder {
+    /// Sink used to read devices
+    sink: Arc<dyn DeviceSink>,
+    /// Stored samples, shared with the recorder task
+    store: Arc<Mutex<HistoryStore>>,
+    /// Configuration shared with the recorder task
+    config: Arc<Mutex<HistoryConfig>>,
+    /// Wakes the recorder task when the configuration changes
+    changed: Arc<Notify>,
+    /// Devices read at the latest sample
+    devices: watch::Sender<Vec<DeviceInfo>>,
+    /// Recorder task, while running
+    task: Option<BackgroundTask>,
+}
+
+impl DeviceHistoryRecorder {
+    /// Create a new, stopped recorder keeping its samples in the data
+    /// directory
+    pub fn new(sink: Arc<dyn DeviceSink>) -> Self {
+        Self::with_dir(sink, config::data_dir().join(HISTORY_DIR_NAME))
+    }
+
+    /// Create a new, stopped recorder keeping its samples in `dir`
+    pub fn with_dir(sink: Arc<dyn DeviceSink>, dir: PathBuf) -> Self {
+        Self {
+            sink,
+            store: Arc::new(Mutex::new(HistoryStore::new(dir))),
+            config: Arc::new(Mutex::new(HistoryConfig::default())),
+            changed: Arc::new(Notify::new()),
+            devices: watch::Sender::new(Vec::new()),
+            task: None,
+        }
+    }
+
+    /// Start recording with the given configuration
+    pub fn start(&mut self, config: HistoryConfig) {
+        if self.is_running() {
+            return;
+        }
+        self.set_config(config);
+
+        let task = RecorderTask {
+            sink: self.sink.clone(),
+            store: self.store.clone(),
+            config: self.config.clone(),
+            changed: self.changed.clone(),
+            devices: self.devices.clone(),
+            seen: BTreeSet::new(),
+        };
+        self.task = Some(BackgroundTask::spawn(|stop| task.run(stop)));
+    }
+
+    /// Stop recording
+    pub async fn stop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.stop().await;
+        }
+    }
+
+    /// Whether the recorder task is running
+    pub fn is_running(&self) -> bool {
+        self.task.as_ref().is_some_and(BackgroundTask::is_running)
+    }
+
+    /// Replace the configuration of the running recorder
+    pub fn set_config(&mut self, config: HistoryConfig) {
+        *self.config.lock().unwrap() = config;
+        self.changed.notify_one();
+    }
+
+    /// Subscribe to the devices read at every sample.
+    ///
+    /// Devices are read while the recorder runs, even with recording
+    /// disabled.
+    pub fn subscribe(&self) -> watch::Receiver<Vec<DeviceInfo>> {
+        self.devices.subscribe()
+    }
+
+    /// Samples of a device between `from` and `to`, starting with its state
+    /// at `from` if known
+    pub fn query(
+        &self,
+        serial_number: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> io::Result<Vec<HistorySample>> {
+        self.store.lock().unwrap().query(serial_number, from, to)
+    }
+}
+
+/// Samples as CSV, one row per sample with the time in RFC 3339 format
+pub fn to_csv(samples: &[HistorySample]) -> String {
+    let mut csv = String::from("time,connected,power,brightness_lumens,temperature_kelvin\n");
+    for sample in samples {
+        let time = sample.time.to_rfc3339();
+        match sample.state {
+            Some(state) => csv.push_str(&format!(
+                "{time},true,{},{},{}\n",
+                if state.is_on { "on" } else { "off" },
+                state.brightness_lumens,
+                state.temperature_kelvin
+            )),
+            None => csv.push_str(&format!("{time},false,,,\n")),
+        }
+    }
+    csv
+}
+
+/// Background task sampling the devices
+struct RecorderTask {
+    sink: Arc<dyn DeviceSink>,
+    store: Arc<Mutex<HistoryStore>>,
+    config: Arc<Mutex<HistoryConfig>>,
+    changed: Arc<Notify>,
+    devices: watch::Sender<Vec<DeviceInfo>>,
+    /// Devices sampled since the task started, recorded as not connected
+    /// once they disappear
+    seen: BTreeSet<String>,
+}
+
+impl RecorderTask {
+    async fn run(mut self, mut stop: watch::Receiver<bool>) {
+        let mut sample = tokio::time::interval(SAMPLE_INTERVAL);
+        let mut compact = tokio::time::interval(COMPACT_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = sample.tick() => self.sample().await,
+                _ = compact.tick() => self.compact(),
+                _ = self.changed.notified() => self.compact(),
+                _ = stop.changed() => break,
+            }
+        }
+    }
+
+    /// Publish the devices and record the current state of every one
+    async fn sample(&mut self) {
+        let devices = match self.sink.get_all_devices().await {
+            Ok(devices) => devices,
+            Err(e) => {
+                eprintln!("Failed to sample devices for the history: {e}");
+                return;
+            }
+        };
+        self.devices.send_replace(devices.clone());
+        if !self.config.lock().unwrap().enabled {
+            return;
+        }
+
+        let time = Utc::now();
+        let mut samples: Vec<(String, Option<_>)> = self
+            .seen
+            .iter()
+            .filter(|serial| !devices.iter().any(|d| &d.serial_number == *serial))
+            .map(|serial| (serial.clone(), None))
+            .collect();
+        for device in devices {
+            self.seen.insert(device.serial_number.clone());
+            let state = device.is_connected.then(|| device.state());
+            samples.push((device.serial_number, state));
+        }
+
+        let mut store = self.store.lock().unwrap();
+        for (serial_number, state) in samples {
+            if let Err(e) = store.record(&serial_number, HistorySample { time, state }) {
+                eprintln!("Failed to record history of device {serial_number}: {e}");
+            }
+        }
+    }
+
+    /// Downsample and expire old samples
+    fn compact(&self) {
+        let retention_days = self.config.lock().unwrap().retention_days;
+        if let Err(e) = self
+            .store
+            .lock()
+            .unwrap()
+            .compact(Utc::now(), retention_days)
+        {
+            eprintln!("Failed to compact the device history: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::sink::RecordingDeviceSink;
+    use crate::device::DeviceState;
+    use crate::test_support::TempDir;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_records_changes() {
+        let dir = TempDir::new("device-history-recorder");
+        let sink = Arc::new(RecordingDeviceSink::new(&["ABC"]));
+        let mut recorder = DeviceHistoryRecorder::with_dir(sink.clone(), dir.path().to_path_buf());
+
+        let start = Utc::now() - chrono::Duration::seconds(1);
+        let initial = sink.device_state("ABC");
+        recorder.start(HistoryConfig::default());
+        tokio::time::sleep(SAMPLE_INTERVAL / 2).await;
+        let lit = DeviceState {
+            is_on: true,
+            brightness_lumens: 180,
+            temperature_kelvin: 5000,
+        };
+        sink.set_device_state("ABC", lit);
+        tokio::time::sleep(SAMPLE_INTERVAL).await;
+        recorder.stop().await;
+
+        let samples = recorder
+            .query("ABC", start, Utc::now() + chrono::Duration::seconds(1))
+            .unwrap();
+        let states: Vec<_> = samples.iter().map(|sample| sample.state).collect();
+        assert_eq!(states, [Some(initial), Some(lit)]);
+
+        let csv = to_csv(&samples);
+        assert!(csv.starts_with("time,connected,power,brightness_lumens,temperature_kelvin\n"));
+        assert!(csv.lines().nth(2).unwrap().ends_with(",true,on,180,5000"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_publishes_devices_with_recording_disabled() {
+        let dir = TempDir::new("device-history-disabled");
+        let sink = Arc::new(RecordingDeviceSink::new(&["ABC"]));
+        let mut recorder = DeviceHistoryRecorder::with_dir(sink.clone(), dir.path().to_path_buf());
+        let mut devices = recorder.subscribe();
+
+        let start = Utc::now() - chrono::Duration::seconds(1);
+        recorder.start(HistoryConfig {
+            enabled: false,
+            ..HistoryConfig::default()
+        });
+        devices.changed().await.unwrap();
+        assert_eq!(devices.borrow_and_update()[0].serial_number, "ABC");
+
+        let lit = DeviceState {
+            is_on: true,
+            brightness_lumens: 180,
+            temperature_kelvin: 5000,
+        };
+        sink.set_device_state("ABC", lit);
+        devices.changed().await.unwrap();
+        assert_eq!(devices.borrow_and_update()[0].state(), lit);
+        recorder.stop().await;
+
+        let samples = recorder
+            .query("ABC", start, Utc::now() + chrono::Duration::seconds(1))
+            .unwrap();
+        assert!(samples.is_empty());
+    }
+}
@@ -0,0 +1,404 @@
+//! On-disk storage of device state samples
+//!
+//! Samples are kept in one text file per device and day, one line per
+//! sample: the Unix time followed by the power state, brightness in lumens
+//! and temperature in Kelvin, or `-` while the device was not connected. Only
+//! changes are written, so a steady light costs a line a day. Older days are
+//! downsampled and expired days deleted by [`HistoryStore::compact`].
+
+use crate::device::DeviceState;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// Age after which a day is downsampled
+const DOWNSAMPLE_AFTER: Duration = Duration::days(7);
+
+/// Samples of a downsampled day are at least this far apart
+const DOWNSAMPLE_BUCKET_SECS: i64 = 300;
+
+/// Extension of the daily sample files
+const FILE_EXTENSION: &str = "log";
+
+/// State of a device at a point in time
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct HistorySample {
+    /// When the device entered this state
+    pub time: DateTime<Utc>,
+    /// Device state, `None` while the device was not connected
+    pub state: Option<DeviceState>,
+}
+
+impl HistorySample {
+    /// The sample as a line of a sample file
+    fn to_line(self) -> String {
+        match self.state {
+            Some(state) => format!(
+                "{} {} {} {}",
+                self.time.timestamp(),
+                u8::from(state.is_on),
+                state.brightness_lumens,
+                state.temperature_kelvin
+            ),
+            None => format!("{} -", self.time.timestamp()),
+        }
+    }
+
+    /// Parse a line of a sample file
+    fn from_line(line: &str) -> Option<Self> {
+        let mut fields = line.split_whitespace();
+        let time = DateTime::from_timestamp(fields.next()?.parse().ok()?, 0)?;
+        let state = match fields.next()? {
+            "-" => None,
+            is_on => Some(DeviceState {
+                is_on: is_on == "1",
+                brightness_lumens: fields.next()?.parse().ok()?,
+                temperature_kelvin: fields.next()?.parse().ok()?,
+            }),
+        };
+        Some(Self { time, state })
+    }
+}
+
+/// Daily sample files of all devices
+#[derive(Debug)]
+pub struct HistoryStore {
+    /// Directory holding a subdirectory of daily files per device
+    dir: PathBuf,
+    /// Latest sample written for each device
+    latest: HashMap<String, HistorySample>,
+}
+
+impl HistoryStore {
+    /// Create a store keeping its files in `dir`
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            latest: HashMap::new(),
+        }
+    }
+
+    /// Record the state of a device.
+    ///
+    /// The sample is only written if the state changed, or if it is the
+    /// first one of a day so every daily file starts with the full state.
+    pub fn record(&mut self, serial_number: &str, sample: HistorySample) -> io::Result<()> {
+        let latest = match self.latest.get(serial_number) {
+            Some(latest) => Some(*latest),
+            None => self.last_before(serial_number, sample.time)?,
+        };
+        if latest.is_some_and(|latest| {
+            latest.state == sample.state && latest.time.date_naive() == sample.time.date_naive()
+        }) {
+            return Ok(());
+        }
+
+        let path = self.day_path(serial_number, sample.time.date_naive());
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", sample.to_line())?;
+
+        self.latest.insert(serial_number.to_string(), sample);
+        Ok(())
+    }
+
+    /// Samples of a device between `from` and `to`.
+    ///
+    /// The state at `from` is included as the first sample, if known.
+    pub fn query(
+        &self,
+        serial_number: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> io::Result<Vec<HistorySample>> {
+        let mut samples = Vec::new();
+        if let Some(initial) = self.last_before(serial_number, from)? {
+            samples.push(HistorySample {
+                time: from,
+                ..initial
+            });
+        }
+
+        for date in self.dates(serial_number)? {
+            if date < from.date_naive() || date > to.date_naive() {
+                continue;
+            }
+            samples.extend(
+                self.read_day(serial_number, date)?
+                    .into_iter()
+                    .filter(|sample| sample.time >= from && sample.time <= to),
+            );
+        }
+        Ok(samples)
+    }
+
+    /// Downsample days older than a week and delete days older than
+    /// `retention_days`
+    pub fn compact(&self, now: DateTime<Utc>, retention_days: u32) -> io::Result<()> {
+        let expired = (now - Duration::days(i64::from(retention_days))).date_naive();
+        let downsample = (now - DOWNSAMPLE_AFTER).date_naive();
+
+        for serial_number in self.serial_numbers()? {
+            for date in self.dates(&serial_number)? {
+                let path = self.day_path(&serial_number, date);
+                if date < expired {
+                    fs::remove_file(path)?;
+                } else if date < downsample {
+                    let samples = self.read_day(&serial_number, date)?;
+                    let downsampled = downsampled(&samples);
+                    if downsampled.len() < samples.len() {
+                        let contents: String = downsampled
+                            .iter()
+                            .map(|sample| sample.to_line() + "\n")
+                            .collect();
+                        fs::write(path, contents)?;
+                    }
+                }
+            }
+
+            let dir = self.dir.join(file_name(&serial_number));
+            if fs::read_dir(&dir)?.next().is_none() {
+                fs::remove_dir(dir)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Latest sample of a device before `time`, looking back through the
+    /// daily files
+    fn last_before(
+        &self,
+        serial_number: &str,
+        time: DateTime<Utc>,
+    ) -> io::Result<Option<HistorySample>> {
+        for date in self.dates(serial_number)?.into_iter().rev() {
+            if date > time.date_naive() {
+                continue;
+            }
+            let last = self
+                .read_day(serial_number, date)?
+                .into_iter()
+                .rfind(|sample| sample.time < time);
+            if last.is_some() {
+                return Ok(last);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Dates a device has a daily file for, oldest first
+    fn dates(&self, serial_number: &str) -> io::Result<Vec<NaiveDate>> {
+        let dir = self.dir.join(file_name(serial_number));
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut dates: Vec<NaiveDate> = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != FILE_EXTENSION {
+                    return None;
+                }
+                path.file_stem()?.to_str()?.parse().ok()
+            })
+            .collect();
+        dates.sort();
+        Ok(dates)
+    }
+
+    /// Devices with a directory of daily files
+    fn serial_numbers(&self) -> io::Result<Vec<String>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        Ok(entries
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .collect())
+    }
+
+    /// Samples in a daily file, oldest first
+    fn read_day(&self, serial_number: &str, date: NaiveDate) -> io::Result<Vec<HistorySample>> {
+        let contents = fs::read_to_string(self.day_path(serial_number, date))?;
+        Ok(contents
+            .lines()
+            .filter_map(HistorySample::from_line)
+            .collect())
+    }
+
+    /// Path of a device's daily file
+    fn day_path(&self, serial_number: &str, date: NaiveDate) -> PathBuf {
+        self.dir
+            .join(file_name(serial_number))
+            .join(format!("{date}.{FILE_EXTENSION}"))
+    }
+}
+
+/// A serial number made safe to use as a file name
+fn file_name(serial_number: &str) -> String {
+    serial_number
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// The last sample of every downsampling bucket, keeping the day's first
+/// sample so the day still starts with the full state
+fn downsampled(samples: &[HistorySample]) -> Vec<HistorySample> {
+    let bucket = |sample: &HistorySample| sample.time.timestamp() / DOWNSAMPLE_BUCKET_SECS;
+
+    let Some((first, rest)) = samples.split_first() else {
+        return Vec::new();
+    };
+    let mut result = vec![*first];
+    let mut latest_per_bucket: Vec<HistorySample> = Vec::new();
+    for sample in rest {
+        match latest_per_bucket.last_mut() {
+            Some(last) if bucket(last) == bucket(sample) => *last = *sample,
+            _ => latest_per_bucket.push(*sample),
+        }
+    }
+    result.extend(latest_per_bucket);
+    result.dedup_by(|later, earlier| later.state == earlier.state);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use chrono::TimeZone;
+
+    fn on(brightness_lumens: u16) -> Option<DeviceState> {
+        Some(DeviceState {
+            is_on: true,
+            brightness_lumens,
+            temperature_kelvin: 4000,
+        })
+    }
+
+    fn at(day: u32, hour: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 3, day, hour, min, 0).unwrap()
+    }
+
+    /// Store in a fresh temp dir, deleted when the returned guard is dropped
+    fn temp_store(name: &str) -> (TempDir, HistoryStore) {
+        let dir = TempDir::new(&format!("device-history-{name}"));
+        let store = HistoryStore::new(dir.path().to_path_buf());
+        (dir, store)
+    }
+
+    #[test]
+    fn test_record_and_query() {
+        let (_dir, mut store) = temp_store("query");
+        let samples = [
+            (at(1, 9, 0), on(100)),
+            (at(1, 9, 1), on(100)),
+            (at(1, 10, 0), on(200)),
+            (at(1, 18, 0), None),
+            (at(2, 9, 0), None),
+            (at(2, 9, 30), on(150)),
+        ];
+        for (time, state) in samples {
+            store
+                .record("ABC/1", HistorySample { time, state })
+                .unwrap();
+        }
+
+        // Unchanged states are only written again on a new day
+        let day_one = store.read_day("ABC/1", at(1, 0, 0).date_naive()).unwrap();
+        assert_eq!(day_one.len(), 3);
+        assert_eq!(
+            store.read_day("ABC/1", at(2, 0, 0).date_naive()).unwrap()[0],
+            HistorySample {
+                time: at(2, 9, 0),
+                state: None
+            }
+        );
+
+        // The state at the start of the range comes first
+        let queried = store.query("ABC/1", at(1, 12, 0), at(2, 12, 0)).unwrap();
+        let expected = [
+            (at(1, 12, 0), on(200)),
+            (at(1, 18, 0), None),
+            (at(2, 9, 0), None),
+            (at(2, 9, 30), on(150)),
+        ];
+        assert_eq!(
+            queried,
+            expected.map(|(time, state)| HistorySample { time, state })
+        );
+
+        // A new store picks up where the files left off
+        let mut reopened = HistoryStore::new(store.dir.clone());
+        reopened
+            .record(
+                "ABC/1",
+                HistorySample {
+                    time: at(2, 10, 0),
+                    state: on(150),
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            reopened
+                .read_day("ABC/1", at(2, 0, 0).date_naive())
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_compact() {
+        let (_dir, mut store) = temp_store("compact");
+        for minute in 0..30 {
+            let sample = HistorySample {
+                time: at(1, 9, minute),
+                state: on(100 + minute as u16),
+            };
+            store.record("ABC", sample).unwrap();
+        }
+        store
+            .record(
+                "ABC",
+                HistorySample {
+                    time: at(20, 9, 0),
+                    state: on(100),
+                },
+            )
+            .unwrap();
+
+        // Kept as they are until a week old
+        store.compact(at(5, 0, 0), 30).unwrap();
+        assert_eq!(
+            store
+                .read_day("ABC", at(1, 0, 0).date_naive())
+                .unwrap()
+                .len(),
+            30
+        );
+
+        // Then reduced to the first sample and one per five minutes
+        store.compact(at(10, 0, 0), 30).unwrap();
+        let downsampled = store.read_day("ABC", at(1, 0, 0).date_naive()).unwrap();
+        let brightness: Vec<_> = downsampled
+            .iter()
+            .map(|sample| sample.state.unwrap().brightness_lumens)
+            .collect();
+        assert_eq!(brightness, [100, 104, 109, 114, 119, 124, 129]);
+
+        // And deleted once past the retention
+        store.compact(at(25, 0, 0), 14).unwrap();
+        assert_eq!(store.dates("ABC").unwrap(), [at(20, 0, 0).date_naive()]);
+    }
+}
@@ -46,6 +46,16 @@ impl From<std::io::Error> for AppError {
     }
 }
 
+/// Converts configuration save errors to application errors.
+impl From<confy::ConfyError> for AppError {
+    fn from(error: confy::ConfyError) -> Self {
+        AppError {
+            message: format!("Failed to update config: {error}"),
+            error_type: "ConfigError".to_string(),
+        }
+    }
+}
+
 /// Convenience type alias for Results that may contain application errors.
 pub type AppResult<T> = Result<T, AppError>;
 
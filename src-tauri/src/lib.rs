@@ -3,24 +3,59 @@
 //! This application provides a comprehensive interface for managing Litra devices,
 //! including power control, brightness adjustment, and temperature settings.
 
+mod calendar;
 mod camera_monitor;
+mod circadian;
 mod cli;
 mod commands;
 pub mod config;
 mod device;
+mod device_history;
 mod error;
+mod logind;
+mod power;
+mod scenes;
+mod scheduler;
+mod task;
+#[cfg(test)]
+mod test_support;
 mod tray;
+mod triggers;
+mod undo;
 
 pub use commands::*;
 pub use device::{DeviceInfo, DeviceManager};
 pub use error::{AppError, AppResult};
-use tauri::Manager;
-use tokio::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{Mutex, Notify, OnceCell};
 
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::camera_monitor::{CameraMonitor, CameraMonitorState};
-use crate::config::ConfigManager;
+use crate::calendar::{CalendarWatcher, CalendarWatcherState};
+use crate::camera_monitor::events::{
+    AutoToggleAction, AutoToggleApplied, MonitorEvent, AUTO_TOGGLE_APPLIED_EVENT,
+};
+use crate::camera_monitor::{CameraMonitor, CameraMonitorResult, CameraMonitorState};
+use crate::circadian::{CircadianScheduler, CircadianSchedulerState};
+use crate::config::{
+    AutoToggleConfig, ConfigManager, Preset, Profile, SceneDevice, AUTO_TOGGLE_BINDING_NAME,
+};
+use crate::device::sink::DeviceSink;
+use crate::device::{common_preset, ActivePresetTracker, DeviceState};
+use crate::device_history::{DeviceHistoryRecorder, DeviceHistoryRecorderState};
+use crate::power::{SleepWatcher, SleepWatcherState, TimerManager, TimerManagerState};
+use crate::scenes::SceneApplied;
+use crate::scheduler::{JobScheduler, JobSchedulerState};
+use crate::triggers::camera::CameraTriggerSource;
+use crate::triggers::displays::DisplayTriggerSource;
+use crate::triggers::engine::BindingAction;
+use crate::triggers::idle::IdleTriggerSource;
+use crate::triggers::microphone::MicrophoneTriggerSource;
+use crate::triggers::session_lock::SessionLockTriggerSource;
+use crate::triggers::{TriggerEngine, TriggerEngineState, TriggerSource};
+use crate::undo::{ChangeHistoryState, ChangeRecordingSink, ChangeSource, ChangeStep};
 
 /// The application state.
 ///
@@ -33,6 +68,26 @@ pub struct AppState {
     pub config_manager: Arc<ConfigManager>,
     /// The camera monitor.
     pub camera_monitor: CameraMonitorState,
+    /// The trigger engine.
+    pub trigger_engine: TriggerEngineState,
+    /// The sleep watcher.
+    pub sleep_watcher: SleepWatcherState,
+    /// The sleep timers and maximum on-time.
+    pub timer_manager: TimerManagerState,
+    /// The circadian color temperature schedule.
+    pub circadian_scheduler: CircadianSchedulerState,
+    /// The scheduled lighting jobs.
+    pub job_scheduler: JobSchedulerState,
+    /// The meeting pre-warm from the calendar.
+    pub calendar_watcher: CalendarWatcherState,
+    /// The recorder of device state over time.
+    pub history_recorder: DeviceHistoryRecorderState,
+    /// The changes that can be undone and redone.
+    pub change_history: ChangeHistoryState,
+    /// Wakes the active preset check after a manual change.
+    active_preset_check: Notify,
+    /// Connection to the system bus, made on first use.
+    system_bus: OnceCell<zbus::Connection>,
 }
 
 /// The application state constructor.
@@ -46,20 +101,665 @@ impl Default for AppState {
 
 impl AppState {
     pub fn new() -> Self {
-        let device_manager = Arc::new(Mutex::new(
-            DeviceManager::new().expect("Failed to initialize device manager"),
-        ));
-
         let config_manager = ConfigManager::new().expect("Failed to initialize config manager");
 
+        let mut device_manager = DeviceManager::new().expect("Failed to initialize device manager");
+        device_manager.set_presets(config_manager.get_config().presets.presets);
+        let device_manager = Arc::new(Mutex::new(device_manager));
+
+        // Changes made by automation are recorded so they can be undone
+        let change_history = ChangeHistoryState::default();
+        let automation = |description: &str| -> Arc<dyn DeviceSink> {
+            Arc::new(ChangeRecordingSink::new(
+                device_manager.clone(),
+                change_history.clone(),
+                ChangeSource::Automation,
+                description,
+            ))
+        };
+
+        let camera_monitor = Arc::new(Mutex::new(CameraMonitor::new()));
+        let trigger_engine = Arc::new(Mutex::new(TriggerEngine::new(automation("Trigger"))));
+
         Self {
             device_manager: device_manager.clone(),
             config_manager: Arc::new(config_manager),
-            camera_monitor: Arc::new(Mutex::new(CameraMonitor::new(device_manager))),
+            camera_monitor: camera_monitor.clone(),
+            trigger_engine: trigger_engine.clone(),
+            sleep_watcher: Arc::new(Mutex::new(SleepWatcher::new(
+                automation("System sleep"),
+                camera_monitor.clone(),
+            ))),
+            timer_manager: Arc::new(Mutex::new(TimerManager::new(
+                automation("Timer"),
+                trigger_engine.clone(),
+            ))),
+            circadian_scheduler: Arc::new(Mutex::new(CircadianScheduler::new(
+                automation("Circadian schedule"),
+                camera_monitor,
+            ))),
+            job_scheduler: Arc::new(Mutex::new(JobScheduler::new(
+                automation("Scheduled job"),
+                trigger_engine.clone(),
+            ))),
+            calendar_watcher: Arc::new(Mutex::new(CalendarWatcher::new(
+                automation("Calendar"),
+                trigger_engine,
+            ))),
+            history_recorder: Arc::new(Mutex::new(DeviceHistoryRecorder::new(
+                device_manager.clone(),
+            ))),
+            change_history,
+            active_preset_check: Notify::new(),
+            system_bus: OnceCell::new(),
+        }
+    }
+
+    /// Reports a user-initiated device change to the trigger engine.
+    ///
+    /// Must not be called while holding the device manager lock.
+    pub async fn notify_manual_change(&self, serial_number: &str) {
+        self.trigger_engine
+            .lock()
+            .await
+            .notify_manual_change(serial_number)
+            .await;
+        self.active_preset_check.notify_one();
+    }
+
+    /// Replaces the presets, matching devices against the new ones and
+    /// resolving the actions that refer to them with the new values.
+    pub async fn set_presets(&self, presets: Vec<Preset>) {
+        self.device_manager
+            .lock()
+            .await
+            .set_presets(presets.clone());
+        self.trigger_engine.lock().await.set_presets(presets).await;
+        self.active_preset_check.notify_one();
+    }
+
+    /// Brings the camera monitor in line with an auto-toggle configuration.
+    ///
+    /// Starts monitoring when auto-toggle is enabled, stops it (restoring any
+    /// lights it changed) when disabled, and passes the configuration to a
+    /// monitor that is already running. The auto-toggle binding is reloaded,
+    /// so a session in progress gets the new in-call state and devices.
+    pub async fn apply_auto_toggle_config(
+        &self,
+        config: AutoToggleConfig,
+    ) -> CameraMonitorResult<()> {
+        let mut monitor = self.camera_monitor.lock().await;
+
+        let result = if !config.enabled {
+            monitor.stop_monitoring().await
+        } else if monitor.is_monitoring()
+            && monitor.activity_source() == Some(config.activity_source)
+        {
+            monitor.update_config(config);
+            Ok(())
+        } else {
+            // A different activity source needs a new event source, so restart
+            match monitor.stop_monitoring().await {
+                Ok(()) => monitor.start_monitoring(config).await,
+                Err(e) => Err(e),
+            }
+        };
+        drop(monitor);
+
+        self.reload_trigger_bindings().await;
+        result
+    }
+
+    /// Starts the trigger engine with the configured bindings and rules.
+    pub async fn start_triggers(&self) {
+        let system_bus = self.system_bus().await;
+        let camera_monitor = self.camera_monitor.lock().await;
+
+        let mut sources: Vec<Box<dyn TriggerSource>> = vec![
+            Box::new(CameraTriggerSource::new(&camera_monitor)),
+            Box::new(MicrophoneTriggerSource::new()),
+            Box::new(DisplayTriggerSource::new()),
+        ];
+        match system_bus {
+            Ok(connection) => {
+                sources.push(Box::new(SessionLockTriggerSource::new(connection.clone())));
+                sources.push(Box::new(IdleTriggerSource::new(
+                    connection,
+                    &camera_monitor,
+                )));
+            }
+            Err(e) => eprintln!("Failed to connect to the system bus: {e}"),
+        }
+        drop(camera_monitor);
+
+        let config = self.config_manager.get_config();
+
+        let mut trigger_engine = self.trigger_engine.lock().await;
+        trigger_engine
+            .set_presets(config.presets.presets.clone())
+            .await;
+        trigger_engine
+            .start(config.trigger_bindings(), config.rules.rules, sources)
+            .await;
+    }
+
+    /// Starts switching lights off around system sleep.
+    pub async fn start_sleep_watcher(&self) {
+        match self.system_bus().await {
+            Ok(connection) => self.sleep_watcher.lock().await.start(connection),
+            Err(e) => eprintln!("Failed to connect to the system bus: {e}"),
+        }
+    }
+
+    /// Starts running sleep timers and the configured maximum on-time.
+    pub async fn start_timers(&self) {
+        let max_on_time = self.config_manager.get_config().max_on_time;
+        self.timer_manager.lock().await.start(max_on_time);
+    }
+
+    /// Starts following the configured circadian schedule.
+    pub async fn start_circadian_schedule(&self) {
+        let circadian = self.config_manager.get_config().circadian;
+        self.circadian_scheduler.lock().await.start(circadian);
+    }
+
+    /// Starts running the scheduled jobs.
+    pub async fn start_job_scheduler(&self) {
+        let jobs = self.config_manager.get_config().scheduler.jobs;
+        self.job_scheduler.lock().await.start(jobs);
+    }
+
+    /// Starts switching lights on ahead of calendar meetings.
+    pub async fn start_calendar_watcher(&self) {
+        let calendar = self.config_manager.get_config().calendar;
+        self.calendar_watcher.lock().await.start(calendar);
+    }
+
+    /// Starts recording device state over time.
+    pub async fn start_history_recorder(&self) {
+        let history = self.config_manager.get_config().history;
+        self.history_recorder.lock().await.start(history);
+    }
+
+    /// Pauses the circadian schedule after the user adjusted a light by hand.
+    pub async fn pause_circadian_schedule(&self) {
+        self.circadian_scheduler.lock().await.pause();
+    }
+
+    /// Applies a preset to one device, or to every connected device.
+    ///
+    /// The change counts as a manual one, so the camera monitor and the
+    /// circadian schedule leave the lights alone afterwards. It is recorded
+    /// as a single step that can be undone.
+    pub async fn apply_preset(
+        &self,
+        id: &str,
+        serial_number: Option<&str>,
+        source: ChangeSource,
+    ) -> AppResult<()> {
+        let presets = self.config_manager.get_config().presets;
+        let Some(preset) = presets.find(id) else {
+            return Err(AppError {
+                message: format!("Preset {id} does not exist"),
+                error_type: "ValidationError".to_string(),
+            });
+        };
+        let lighting_state = preset.lighting_state();
+        let before = self.device_states(serial_number).await;
+
+        let manager = self.device_manager.lock().await;
+        let serial_numbers = match serial_number {
+            Some(serial_number) => vec![serial_number.to_string()],
+            None => manager
+                .get_all_devices()?
+                .into_iter()
+                .filter(|device| device.is_connected)
+                .map(|device| device.serial_number)
+                .collect(),
+        };
+        let mut result = Ok(());
+        let mut applied = Vec::new();
+        for serial_number in serial_numbers {
+            match manager.apply_lighting_state(&serial_number, &lighting_state) {
+                Ok(()) => applied.push(serial_number),
+                Err(e) => result = Err(e),
+            }
+        }
+        drop(manager);
+
+        self.record_change(source, format!("Preset {}", preset.name), before)
+            .await;
+        for serial_number in &applied {
+            self.notify_manual_change(serial_number).await;
+        }
+        if !applied.is_empty() {
+            self.pause_circadian_schedule().await;
+        }
+
+        result
+    }
+
+    /// Applies a scene to its connected devices, fading over `fade`.
+    ///
+    /// Like presets, scenes count as a manual change and are undone as a
+    /// single step.
+    pub async fn apply_scene(&self, id: &str, fade: Duration) -> AppResult<SceneApplied> {
+        let Some(scene) = self.config_manager.get_config().scenes.find(id).cloned() else {
+            return Err(AppError {
+                message: format!("Scene {id} does not exist"),
+                error_type: "ValidationError".to_string(),
+            });
+        };
+        let before = self.device_states(None).await;
+
+        let applied = scenes::apply_scene(self.device_manager.as_ref(), &scene, fade)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Failed to apply scene {}: {e}", scene.name),
+                error_type: "DeviceError".to_string(),
+            })?;
+
+        self.record_change(ChangeSource::Ui, format!("Scene {}", scene.name), before)
+            .await;
+        for serial_number in &applied.applied {
+            self.notify_manual_change(serial_number).await;
+        }
+        if !applied.applied.is_empty() {
+            self.pause_circadian_schedule().await;
+        }
+
+        Ok(applied)
+    }
+
+    /// Switches to a profile.
+    ///
+    /// The lights are kept in the profile being left. Camera monitoring is
+    /// brought in line with the new profile's auto-toggle configuration, and
+    /// each connected device is put back in the state kept for it, with the
+    /// profile's lighting state applied over that, as a single step that can
+    /// be undone.
+    pub async fn switch_profile(&self, id: &str, source: ChangeSource) -> AppResult<Profile> {
+        let devices = self.capture_devices().await?;
+        let profile = self
+            .config_manager
+            .switch_profile(id, devices)
+            .map_err(|e| AppError {
+                message: format!("Failed to switch profile: {e}"),
+                error_type: "ConfigError".to_string(),
+            })?;
+
+        self.apply_auto_toggle_config(profile.auto_toggle.clone())
+            .await
+            .map_err(|e| AppError {
+                message: format!("Failed to apply camera config: {e}"),
+                error_type: "CameraMonitorError".to_string(),
+            })?;
+
+        let presets = self.config_manager.get_config().presets.presets;
+        let lighting_state = profile.lighting_state.resolve(&presets);
+
+        let manager = self.device_manager.lock().await;
+        let devices: Vec<_> = manager
+            .get_all_devices()?
+            .into_iter()
+            .filter(|device| device.is_connected)
+            .collect();
+        let before: Vec<_> = devices
+            .iter()
+            .map(|device| (device.serial_number.clone(), device.state()))
+            .collect();
+
+        let mut result = Ok(());
+        let mut applied = Vec::new();
+        for device in &devices {
+            let kept = profile
+                .devices
+                .iter()
+                .find(|kept| kept.serial_number == device.serial_number)
+                .map_or(device.state(), |kept| kept.state);
+            let target = device.state_with(&kept, &lighting_state);
+            if target == device.state() {
+                continue;
+            }
+            match manager.apply_device_state(&device.serial_number, &target) {
+                Ok(()) => applied.push(device.serial_number.clone()),
+                Err(e) => result = Err(e),
+            }
+        }
+        drop(manager);
+
+        self.record_change(source, format!("Profile {}", profile.name), before)
+            .await;
+        for serial_number in &applied {
+            self.notify_manual_change(serial_number).await;
+        }
+        if !applied.is_empty() {
+            self.pause_circadian_schedule().await;
+        }
+
+        result.map(|()| profile)
+    }
+
+    /// Captures the state of every connected device, to keep in a profile.
+    pub async fn capture_devices(&self) -> AppResult<Vec<SceneDevice>> {
+        scenes::capture_devices(self.device_manager.as_ref(), None)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Failed to read the lights: {e}"),
+                error_type: "DeviceError".to_string(),
+            })
+    }
+
+    /// Reads the state of a device, or of every connected device.
+    ///
+    /// Taken before a change to record it with [`AppState::record_change`].
+    /// Devices that cannot be read are left out. Must not be called while
+    /// holding the device manager lock.
+    pub async fn device_states(&self, serial_number: Option<&str>) -> Vec<(String, DeviceState)> {
+        let manager = self.device_manager.lock().await;
+        let devices = match serial_number {
+            Some(serial_number) => manager.get_device_info(serial_number).map(|d| vec![d]),
+            None => manager.get_all_devices(),
+        };
+
+        devices
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|device| device.is_connected)
+            .map(|device| (device.serial_number.clone(), device.state()))
+            .collect()
+    }
+
+    /// Records a change of the devices in `before` so it can be undone.
+    ///
+    /// Must not be called while holding the device manager lock.
+    pub async fn record_change(
+        &self,
+        source: ChangeSource,
+        description: impl Into<String>,
+        before: Vec<(String, DeviceState)>,
+    ) {
+        let manager = self.device_manager.lock().await;
+        let after: Vec<_> = before
+            .iter()
+            .filter_map(|(serial_number, _)| {
+                let device = manager.get_device_info(serial_number).ok()?;
+                Some((serial_number.clone(), device.state()))
+            })
+            .collect();
+        drop(manager);
+
+        self.change_history.lock().unwrap().record(ChangeStep::new(
+            source,
+            description,
+            before,
+            &after,
+        ));
+    }
+
+    /// Undoes the latest change from one of `sources`, returning it, or
+    /// `None` if there is nothing to undo.
+    ///
+    /// Devices no longer connected are skipped. If the change cannot be
+    /// undone it is the next one undone.
+    pub async fn undo_last_change(
+        &self,
+        sources: &[ChangeSource],
+    ) -> AppResult<Option<ChangeStep>> {
+        let Some(step) = self.change_history.lock().unwrap().pop_undo(sources) else {
+            return Ok(None);
+        };
+
+        match self.restore(&step, true).await {
+            Ok(()) => {
+                self.change_history.lock().unwrap().push_redo(step.clone());
+                Ok(Some(step))
+            }
+            Err(e) => {
+                self.change_history.lock().unwrap().push_undo(step);
+                Err(e)
+            }
+        }
+    }
+
+    /// Redoes the latest undone change from one of `sources`, returning it,
+    /// or `None` if there is nothing to redo.
+    pub async fn redo_change(&self, sources: &[ChangeSource]) -> AppResult<Option<ChangeStep>> {
+        let Some(step) = self.change_history.lock().unwrap().pop_redo(sources) else {
+            return Ok(None);
+        };
+
+        match self.restore(&step, false).await {
+            Ok(()) => {
+                self.change_history.lock().unwrap().push_undo(step.clone());
+                Ok(Some(step))
+            }
+            Err(e) => {
+                self.change_history.lock().unwrap().push_redo(step);
+                Err(e)
+            }
+        }
+    }
+
+    /// Puts the devices of a change back to their state before it, or
+    /// forward to their state after it.
+    ///
+    /// If a device fails, the devices already changed are put back so the
+    /// change applies to all devices or none. Like other manual changes, the
+    /// camera monitor and the circadian schedule leave the lights alone
+    /// afterwards.
+    async fn restore(&self, step: &ChangeStep, undo: bool) -> AppResult<()> {
+        let manager = self.device_manager.lock().await;
+        let connected: Vec<_> = manager
+            .get_all_devices()?
+            .into_iter()
+            .filter(|device| device.is_connected)
+            .map(|device| device.serial_number)
+            .collect();
+
+        let mut result = Ok(());
+        let mut restored = Vec::new();
+        for change in step
+            .changes
+            .iter()
+            .filter(|change| connected.contains(&change.serial_number))
+        {
+            let (target, current) = if undo {
+                (change.before, change.after)
+            } else {
+                (change.after, change.before)
+            };
+            if let Err(e) = manager.apply_device_state(&change.serial_number, &target) {
+                result = Err(e);
+                break;
+            }
+            restored.push((change.serial_number.as_str(), current));
+        }
+
+        if result.is_err() {
+            for (serial_number, state) in restored.drain(..) {
+                if let Err(e) = manager.apply_device_state(serial_number, &state) {
+                    eprintln!("Failed to put device {serial_number} back: {e}");
+                }
+            }
+        }
+        drop(manager);
+
+        for (serial_number, _) in &restored {
+            self.notify_manual_change(serial_number).await;
+        }
+        if !restored.is_empty() {
+            self.pause_circadian_schedule().await;
+        }
+
+        result
+    }
+
+    /// Returns the system bus connection, connecting on first use.
+    async fn system_bus(&self) -> zbus::Result<zbus::Connection> {
+        self.system_bus
+            .get_or_try_init(logind::system_bus)
+            .await
+            .cloned()
+    }
+
+    /// Reloads the trigger bindings from the configuration.
+    pub async fn reload_trigger_bindings(&self) {
+        let bindings = self.config_manager.get_config().trigger_bindings();
+        self.trigger_engine
+            .lock()
+            .await
+            .set_bindings(bindings)
+            .await;
+    }
+
+    /// Runs the shutdown sequence before the application exits.
+    ///
+    /// Stops the sleep watcher, the timers, the schedules, the calendar
+    /// watcher and the history recorder, then the trigger engine, so lights
+    /// they changed, auto-toggle's among them, are restored, and finally the
+    /// camera monitor.
+    pub async fn shutdown(&self) {
+        println!("Shutting down");
+
+        self.sleep_watcher.lock().await.stop().await;
+        self.timer_manager.lock().await.stop().await;
+        self.circadian_scheduler.lock().await.stop().await;
+        self.job_scheduler.lock().await.stop().await;
+        self.calendar_watcher.lock().await.stop().await;
+        self.history_recorder.lock().await.stop().await;
+        self.trigger_engine.lock().await.stop().await;
+
+        if let Err(e) = self.camera_monitor.lock().await.stop_monitoring().await {
+            eprintln!("Failed to stop camera monitoring: {e}");
         }
     }
 }
 
+/// Forwards camera monitor events to the frontend as Tauri events.
+fn forward_camera_events(app: &AppHandle) {
+    let state = app.state::<AppState>();
+    let mut events = tauri::async_runtime::block_on(state.camera_monitor.lock()).subscribe();
+    let app = app.clone();
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(skipped)) => {
+                    eprintln!("Dropped {skipped} camera monitor events");
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+
+            let result = match &event {
+                MonitorEvent::SessionStarted(session) | MonitorEvent::SessionEnded(session) => {
+                    app.emit(event.name(), session)
+                }
+            };
+            if let Err(e) = result {
+                eprintln!("Failed to emit {}: {e}", event.name());
+            }
+        }
+    });
+}
+
+/// Forwards the changes the auto-toggle binding makes to the frontend, and
+/// records the devices it switched in the camera session.
+fn forward_auto_toggle_changes(app: &AppHandle) {
+    let state = app.state::<AppState>();
+    let mut events = tauri::async_runtime::block_on(state.trigger_engine.lock()).subscribe();
+    let app = app.clone();
+
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<AppState>();
+
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(skipped)) => {
+                    eprintln!("Dropped {skipped} trigger binding events");
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            if event.binding != AUTO_TOGGLE_BINDING_NAME {
+                continue;
+            }
+
+            let action = match event.action {
+                BindingAction::Applied => {
+                    state
+                        .camera_monitor
+                        .lock()
+                        .await
+                        .record_affected_devices(event.serial_numbers.clone());
+                    AutoToggleAction::InCallState
+                }
+                BindingAction::Restored => AutoToggleAction::Restore,
+            };
+            let applied = AutoToggleApplied {
+                action,
+                serial_numbers: event.serial_numbers,
+            };
+            if let Err(e) = app.emit(AUTO_TOGGLE_APPLIED_EVENT, applied) {
+                eprintln!("Failed to emit {AUTO_TOGGLE_APPLIED_EVENT}: {e}");
+            }
+        }
+    });
+}
+
+/// Emits `active-preset-changed` when the preset a device is at changes.
+///
+/// Devices are checked right after manual changes, and otherwise follow the
+/// devices the history recorder reads, catching changes made by automation
+/// or the device's own buttons without polling the devices again. The tray
+/// menu is rebuilt to show the preset all lights are at.
+fn forward_active_preset_changes(app: &AppHandle) {
+    let state = app.state::<AppState>();
+    let mut sampled = tauri::async_runtime::block_on(state.history_recorder.lock()).subscribe();
+    let app = app.clone();
+
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<AppState>();
+        let mut tracker = ActivePresetTracker::default();
+        let mut shown_preset = None;
+
+        loop {
+            let devices = tokio::select! {
+                changed = sampled.changed() => match changed {
+                    Ok(()) => sampled.borrow_and_update().clone(),
+                    Err(_) => break,
+                },
+                _ = state.active_preset_check.notified() => {
+                    match state.device_manager.lock().await.get_all_devices() {
+                        Ok(devices) => devices,
+                        Err(e) => {
+                            eprintln!("Failed to check active presets: {e}");
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            for change in tracker.update(&devices) {
+                if let Err(e) = app.emit("active-preset-changed", &change) {
+                    eprintln!("Failed to emit active-preset-changed: {e}");
+                }
+            }
+
+            let common = common_preset(&devices).map(str::to_string);
+            if common != shown_preset {
+                shown_preset = common;
+                if let Err(e) = tray::refresh_tray_menu(&app).await {
+                    eprintln!("Failed to update the tray menu: {e}");
+                }
+            }
+        }
+    });
+}
+
 /// Initializes and runs the Tauri application.
 ///
 /// This function sets up the application state, registers command handlers,
@@ -82,6 +782,46 @@ pub fn run() {
                     .expect("Failed to setup tray");
             });
 
+            // Publish camera activity to the frontend
+            forward_camera_events(app.app_handle());
+            forward_auto_toggle_changes(app.app_handle());
+
+            // Publish the preset each device is at
+            forward_active_preset_changes(app.app_handle());
+
+            // Monitoring is owned by the backend, so it runs even when the
+            // app starts minimized without a window
+            let state = app.state::<AppState>();
+            let auto_toggle = state.config_manager.get_config().auto_toggle;
+            if auto_toggle.enabled {
+                if let Err(e) =
+                    tauri::async_runtime::block_on(state.apply_auto_toggle_config(auto_toggle))
+                {
+                    eprintln!("Failed to start camera monitoring: {e}");
+                }
+            }
+
+            // Start applying trigger bindings
+            tauri::async_runtime::block_on(state.start_triggers());
+
+            // Follow the system into sleep and back
+            tauri::async_runtime::block_on(state.start_sleep_watcher());
+
+            // Run sleep timers and the maximum on-time
+            tauri::async_runtime::block_on(state.start_timers());
+
+            // Follow the circadian schedule
+            tauri::async_runtime::block_on(state.start_circadian_schedule());
+
+            // Run scheduled jobs
+            tauri::async_runtime::block_on(state.start_job_scheduler());
+
+            // Switch lights on ahead of meetings
+            tauri::async_runtime::block_on(state.start_calendar_watcher());
+
+            // Record device state over time
+            tauri::async_runtime::block_on(state.start_history_recorder());
+
             // Handle CLI args
             if let Err(e) = crate::cli::handle_cli_args(app) {
                 eprintln!("Error handling CLI args: {e}");
@@ -109,9 +849,61 @@ pub fn run() {
             is_camera_monitoring,
             get_camera_device_count,
             get_controlled_devices,
+            get_camera_history,
+            list_cameras,
             debug_camera_system,
             update_camera_config,
             get_camera_config,
+            get_trigger_bindings,
+            update_trigger_bindings,
+            get_trigger_signals,
+            get_session_lock_config,
+            update_session_lock_config,
+            get_idle_config,
+            update_idle_config,
+            get_display_sleep_config,
+            update_display_sleep_config,
+            set_sleep_timer,
+            cancel_sleep_timer,
+            get_timers,
+            get_max_on_time_config,
+            update_max_on_time_config,
+            get_circadian_config,
+            update_circadian_config,
+            get_circadian_status,
+            resume_circadian_schedule,
+            get_scheduled_jobs,
+            update_scheduled_jobs,
+            get_scheduled_job_status,
+            get_rules,
+            update_rules,
+            explain_rules,
+            get_calendar_config,
+            update_calendar_config,
+            get_calendar_status,
+            get_presets,
+            create_preset,
+            update_preset,
+            delete_preset,
+            reorder_presets,
+            apply_preset,
+            get_scenes,
+            capture_scene,
+            update_scene,
+            delete_scene,
+            apply_scene,
+            get_history_config,
+            update_history_config,
+            query_device_history,
+            export_device_history_csv,
+            get_change_history,
+            undo_last_change,
+            redo_change,
+            get_profiles,
+            create_profile,
+            duplicate_profile,
+            switch_profile,
+            delete_profile,
         ])
         .on_window_event(|window, event| {
             // Handle window close to minimize to tray instead
@@ -120,6 +912,11 @@ pub fn run() {
                 let _ = window.hide();
             }
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                tauri::async_runtime::block_on(app.state::<AppState>().shutdown());
+            }
+        });
 }
@@ -0,0 +1,75 @@
+//! systemd-logind integration
+//!
+//! D-Bus proxies for the parts of `org.freedesktop.login1` the app follows,
+//! such as the lock and idle state of the user's session and system sleep.
+
+#[cfg(test)]
+pub mod stub;
+
+use zbus::zvariant::{OwnedFd, OwnedObjectPath};
+use zbus::Connection;
+
+/// Session ID logind resolves to the caller's session
+const AUTO_SESSION_ID: &str = "auto";
+
+/// logind manager object
+#[zbus::proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+pub trait Manager {
+    /// Get the object path of a session by its ID
+    fn get_session(&self, session_id: &str) -> zbus::Result<OwnedObjectPath>;
+
+    /// Take an inhibitor lock, held until the returned descriptor is closed
+    fn inhibit(&self, what: &str, who: &str, why: &str, mode: &str) -> zbus::Result<OwnedFd>;
+
+    /// Emitted with `start` set before the system sleeps, and cleared after
+    /// it resumes
+    #[zbus(signal)]
+    fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+}
+
+/// logind session object
+#[zbus::proxy(
+    interface = "org.freedesktop.login1.Session",
+    default_service = "org.freedesktop.login1"
+)]
+pub trait Session {
+    /// Emitted when the session is asked to lock its screen
+    #[zbus(signal)]
+    fn lock(&self) -> zbus::Result<()>;
+
+    /// Emitted when the session is asked to unlock its screen
+    #[zbus(signal)]
+    fn unlock(&self) -> zbus::Result<()>;
+
+    /// Whether the session's screen is locked, as reported by the desktop
+    #[zbus(property)]
+    fn locked_hint(&self) -> zbus::Result<bool>;
+
+    /// Whether the session is idle, as reported by the desktop
+    #[zbus(property)]
+    fn idle_hint(&self) -> zbus::Result<bool>;
+
+    /// When the session last became idle or active, in microseconds since
+    /// the Unix epoch
+    #[zbus(property)]
+    fn idle_since_hint(&self) -> zbus::Result<u64>;
+}
+
+/// Connect to the system bus
+pub async fn system_bus() -> zbus::Result<Connection> {
+    Connection::system().await
+}
+
+/// Get a proxy for the session the app runs in
+pub async fn current_session(connection: &Connection) -> zbus::Result<SessionProxy<'static>> {
+    let path = ManagerProxy::new(connection)
+        .await?
+        .get_session(AUTO_SESSION_ID)
+        .await?;
+
+    SessionProxy::builder(connection).path(path)?.build().await
+}
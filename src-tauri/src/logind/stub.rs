@@ -0,0 +1,313 @@
+//! Stub logind service on a private bus, for tests
+//!
+//! [`PrivateBus`] runs a `dbus-daemon` of its own so tests never touch the
+//! real system bus, and [`StubLogind`] serves the logind objects on it and
+//! lets tests drive them.
+//!
+//! Tests using the stub need `dbus-daemon` installed, so they are ignored by
+//! default and run with `cargo test -- --ignored`. They fail rather than pass
+//! when the daemon cannot be started.
+
+use std::io::{BufRead, BufReader, ErrorKind, Read};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zbus::object_server::{InterfaceRef, SignalEmitter};
+use zbus::zvariant::{OwnedFd, OwnedObjectPath};
+use zbus::{connection, fdo, Connection};
+
+/// Object path of the stub's only session
+pub const SESSION_PATH: &str = "/org/freedesktop/login1/session/c1";
+
+/// Bus configuration allowing everything, for a private test bus
+const BUS_CONFIG: &str = r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-Bus Bus Configuration 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+<busconfig>
+  <type>session</type>
+  <listen>unix:tmpdir=/tmp</listen>
+  <auth>EXTERNAL</auth>
+  <policy context="default">
+    <allow send_destination="*" eavesdrop="true"/>
+    <allow eavesdrop="true"/>
+    <allow own="*"/>
+  </policy>
+</busconfig>
+"#;
+
+/// A `dbus-daemon` private to a test, stopped when dropped
+pub struct PrivateBus {
+    /// The daemon process
+    daemon: Child,
+    /// Configuration file of the daemon
+    config_path: PathBuf,
+    /// Address clients connect to
+    address: String,
+}
+
+impl PrivateBus {
+    /// Start a private bus.
+    ///
+    /// Panics if `dbus-daemon` is not installed or does not come up.
+    pub fn start(name: &str) -> Self {
+        let config_path = std::env::temp_dir().join(format!(
+            "litra-control-bus-{name}-{}.conf",
+            std::process::id()
+        ));
+        if let Err(e) = std::fs::write(&config_path, BUS_CONFIG) {
+            panic!("Failed to write private bus config: {e}");
+        }
+
+        let mut daemon = match Command::new("dbus-daemon")
+            .arg(format!("--config-file={}", config_path.display()))
+            .args(["--nofork", "--print-address=1"])
+            .stdout(Stdio::piped())
+            .spawn()
+        {
+            Ok(daemon) => daemon,
+            Err(e) => {
+                let _ = std::fs::remove_file(&config_path);
+                panic!("Failed to run dbus-daemon, which logind tests need: {e}");
+            }
+        };
+
+        // Stops the daemon and removes its config if starting fails below
+        let stdout = daemon.stdout.take();
+        let mut bus = Self {
+            daemon,
+            config_path,
+            address: String::new(),
+        };
+
+        let mut address = String::new();
+        let read = match stdout {
+            Some(stdout) => BufReader::new(stdout).read_line(&mut address),
+            None => Ok(0),
+        };
+        match read {
+            Ok(_) if address.trim().is_empty() => {
+                panic!("dbus-daemon exited without printing a bus address")
+            }
+            Ok(_) => {}
+            Err(e) => panic!("Failed to read the private bus address: {e}"),
+        }
+
+        bus.address = address.trim().to_string();
+        bus
+    }
+
+    /// Connect a new client to the bus
+    pub async fn connect(&self) -> Connection {
+        connection::Builder::address(self.address.as_str())
+            .expect("Invalid bus address")
+            .build()
+            .await
+            .expect("Failed to connect to private bus")
+    }
+}
+
+impl Drop for PrivateBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+        let _ = std::fs::remove_file(&self.config_path);
+    }
+}
+
+/// Inhibitor locks handed out by the stub, as the stub's end of a socket
+/// pair whose other end went to the client
+type Inhibitors = Arc<Mutex<Vec<UnixStream>>>;
+
+/// Stub of the logind manager object
+struct StubManager {
+    /// Inhibitor locks handed out so far
+    inhibitors: Inhibitors,
+}
+
+#[zbus::interface(name = "org.freedesktop.login1.Manager")]
+impl StubManager {
+    async fn get_session(&self, session_id: &str) -> fdo::Result<OwnedObjectPath> {
+        if session_id != "auto" {
+            return Err(fdo::Error::Failed(format!("No session '{session_id}'")));
+        }
+        Ok(OwnedObjectPath::try_from(SESSION_PATH).expect("Valid session path"))
+    }
+
+    async fn inhibit(
+        &self,
+        what: &str,
+        _who: &str,
+        _why: &str,
+        mode: &str,
+    ) -> fdo::Result<OwnedFd> {
+        if what != "sleep" || mode != "delay" {
+            return Err(fdo::Error::NotSupported(format!(
+                "{mode} inhibitor for {what}"
+            )));
+        }
+
+        let (kept, handed_out) =
+            UnixStream::pair().map_err(|e| fdo::Error::Failed(e.to_string()))?;
+        kept.set_nonblocking(true)
+            .map_err(|e| fdo::Error::Failed(e.to_string()))?;
+        self.inhibitors.lock().unwrap().push(kept);
+
+        Ok(std::os::fd::OwnedFd::from(handed_out).into())
+    }
+
+    #[zbus(signal)]
+    async fn prepare_for_sleep(emitter: &SignalEmitter<'_>, start: bool) -> zbus::Result<()>;
+}
+
+/// Stub of a logind session object
+#[derive(Default)]
+struct StubSession {
+    /// Value of the `LockedHint` property
+    locked_hint: bool,
+    /// Value of the `IdleHint` property
+    idle_hint: bool,
+    /// Value of the `IdleSinceHint` property
+    idle_since_hint: u64,
+}
+
+#[zbus::interface(name = "org.freedesktop.login1.Session")]
+impl StubSession {
+    #[zbus(signal)]
+    async fn lock(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn unlock(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn locked_hint(&self) -> bool {
+        self.locked_hint
+    }
+
+    #[zbus(property)]
+    fn idle_hint(&self) -> bool {
+        self.idle_hint
+    }
+
+    #[zbus(property)]
+    fn idle_since_hint(&self) -> u64 {
+        self.idle_since_hint
+    }
+}
+
+/// Stub logind service serving one session on a private bus
+pub struct StubLogind {
+    /// Connection owning the logind name
+    connection: Connection,
+    /// Inhibitor locks handed out so far
+    inhibitors: Inhibitors,
+}
+
+impl StubLogind {
+    /// Start serving the stub on the given bus
+    pub async fn start(bus: &PrivateBus) -> Self {
+        let inhibitors = Inhibitors::default();
+        let manager = StubManager {
+            inhibitors: inhibitors.clone(),
+        };
+        let connection = connection::Builder::address(bus.address.as_str())
+            .expect("Invalid bus address")
+            .name("org.freedesktop.login1")
+            .expect("Invalid service name")
+            .serve_at("/org/freedesktop/login1", manager)
+            .expect("Invalid manager path")
+            .serve_at(SESSION_PATH, StubSession::default())
+            .expect("Invalid session path")
+            .build()
+            .await
+            .expect("Failed to start stub logind");
+
+        Self {
+            connection,
+            inhibitors,
+        }
+    }
+
+    /// Emit the manager's `PrepareForSleep` signal
+    pub async fn prepare_for_sleep(&self, start: bool) {
+        let manager = self
+            .connection
+            .object_server()
+            .interface::<_, StubManager>("/org/freedesktop/login1")
+            .await
+            .expect("Manager object is served");
+        StubManager::prepare_for_sleep(manager.signal_emitter(), start)
+            .await
+            .expect("Failed to emit PrepareForSleep");
+    }
+
+    /// Number of inhibitor locks whose holder has not released them
+    pub fn held_inhibitors(&self) -> usize {
+        let mut inhibitors = self.inhibitors.lock().unwrap();
+        // A released lock reads as end of file on the stub's end
+        inhibitors.retain_mut(
+            |kept| matches!(kept.read(&mut [0; 1]), Err(e) if e.kind() == ErrorKind::WouldBlock),
+        );
+        inhibitors.len()
+    }
+
+    /// Emit the session's `Lock` signal
+    pub async fn lock(&self) {
+        StubSession::lock(self.session().await.signal_emitter())
+            .await
+            .expect("Failed to emit Lock");
+    }
+
+    /// Emit the session's `Unlock` signal
+    pub async fn unlock(&self) {
+        StubSession::unlock(self.session().await.signal_emitter())
+            .await
+            .expect("Failed to emit Unlock");
+    }
+
+    /// Change the session's `LockedHint` property
+    pub async fn set_locked_hint(&self, locked: bool) {
+        let session = self.session().await;
+        session.get_mut().await.locked_hint = locked;
+        session
+            .get()
+            .await
+            .locked_hint_changed(session.signal_emitter())
+            .await
+            .expect("Failed to emit LockedHint change");
+    }
+
+    /// Mark the session idle for the given time, or active if `None`
+    pub async fn set_idle(&self, idle_for: Option<Duration>) {
+        let now = SystemTime::now();
+        let since = idle_for.map_or(now, |idle_for| now - idle_for);
+        let since = since.duration_since(UNIX_EPOCH).unwrap().as_micros() as u64;
+
+        let session = self.session().await;
+        {
+            let mut session = session.get_mut().await;
+            session.idle_hint = idle_for.is_some();
+            session.idle_since_hint = since;
+        }
+
+        let emitter = session.signal_emitter();
+        let session = session.get().await;
+        session
+            .idle_since_hint_changed(emitter)
+            .await
+            .expect("Failed to emit IdleSinceHint change");
+        session
+            .idle_hint_changed(emitter)
+            .await
+            .expect("Failed to emit IdleHint change");
+    }
+
+    /// The served session object
+    async fn session(&self) -> InterfaceRef<StubSession> {
+        self.connection
+            .object_server()
+            .interface(SESSION_PATH)
+            .await
+            .expect("Session object is served")
+    }
+}
@@ -0,0 +1,22 @@
+//! Power management
+//!
+//! Reacts to system power events so the lights follow the machine into
+//! suspend and come back with it, and switches lights off on timers.
+
+pub mod sleep;
+pub mod timers;
+
+pub use sleep::SleepWatcher;
+pub use timers::TimerManager;
+
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Global state type for the sleep watcher
+pub type SleepWatcherState = Arc<Mutex<SleepWatcher>>;
+
+/// Global state type for the timer manager
+pub type TimerManagerState = Arc<Mutex<TimerManager>>;
+
+/// Result type for power management operations
+pub type PowerResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
@@ -0,0 +1,349 @@
+//! Suspend and resume handling
+//!
+//! Holds a logind delay inhibitor so lights can be switched off before the
+//! system sleeps. Their state is saved to a file first, so it survives even
+//! if the app does not. After resume the device handles are reopened, the
+//! lights are restored and the camera monitor reconciles its sessions.
+
+use crate::camera_monitor::CameraMonitorState;
+use crate::config::{self, LightingState};
+use crate::device::sink::DeviceSink;
+use crate::device::DeviceState;
+use crate::logind::ManagerProxy;
+use crate::power::PowerResult;
+use crate::task::BackgroundTask;
+use futures_util::StreamExt;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use zbus::zvariant::OwnedFd;
+use zbus::Connection;
+
+/// Name of the file holding the state of the lights switched off for sleep
+pub const SLEEP_STATE_FILE_NAME: &str = "sleep_state.json";
+
+/// Name the inhibitor lock is taken under
+const INHIBITOR_WHO: &str = "Litra Control";
+
+/// Reason given for the inhibitor lock
+const INHIBITOR_WHY: &str = "Switch lights off before sleep";
+
+/// How often restoring the lights is attempted after resume, as devices can
+/// take a moment to reappear on the bus
+const RESTORE_ATTEMPTS: usize = 5;
+
+/// Delay between attempts to restore the lights
+const RESTORE_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Watcher switching lights off around system sleep
+pub struct SleepWatcher {
+    /// Sink used to read and change devices
+    sink: Arc<dyn DeviceSink>,
+    /// Camera monitor to reconcile after resume
+    camera_monitor: CameraMonitorState,
+    /// File the state of the lights is saved to before sleep
+    state_path: PathBuf,
+    /// Watcher task, while running
+    task: Option<BackgroundTask>,
+}
+
+impl SleepWatcher {
+    /// Create a new, stopped sleep watcher
+    pub fn new(sink: Arc<dyn DeviceSink>, camera_monitor: CameraMonitorState) -> Self {
+        Self::with_state_path(
+            sink,
+            camera_monitor,
+            config::data_dir().join(SLEEP_STATE_FILE_NAME),
+        )
+    }
+
+    /// Create a new, stopped sleep watcher saving state to the given file
+    pub fn with_state_path(
+        sink: Arc<dyn DeviceSink>,
+        camera_monitor: CameraMonitorState,
+        state_path: PathBuf,
+    ) -> Self {
+        Self {
+            sink,
+            camera_monitor,
+            state_path,
+            task: None,
+        }
+    }
+
+    /// Start watching for sleep through logind on the given bus.
+    ///
+    /// Lights left off by a sleep the app did not see the end of are restored
+    /// first.
+    pub fn start(&mut self, connection: Connection) {
+        if self.is_running() {
+            return;
+        }
+
+        let task = SleepTask {
+            sink: self.sink.clone(),
+            camera_monitor: self.camera_monitor.clone(),
+            state_path: self.state_path.clone(),
+        };
+        self.task = Some(BackgroundTask::spawn(|stop| async move {
+            if let Err(e) = task.run(connection, stop).await {
+                eprintln!("Sleep watcher error: {e}");
+            }
+        }));
+    }
+
+    /// Stop watching, releasing the inhibitor lock
+    pub async fn stop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.stop().await;
+        }
+    }
+
+    /// Check if the watcher is running
+    pub fn is_running(&self) -> bool {
+        self.task.as_ref().is_some_and(BackgroundTask::is_running)
+    }
+}
+
+/// State owned by the watcher task
+struct SleepTask {
+    /// Sink used to read and change devices
+    sink: Arc<dyn DeviceSink>,
+    /// Camera monitor to reconcile after resume
+    camera_monitor: CameraMonitorState,
+    /// File the state of the lights is saved to before sleep
+    state_path: PathBuf,
+}
+
+impl SleepTask {
+    /// Follow sleep and resume until stopped
+    async fn run(self, connection: Connection, mut stop: watch::Receiver<bool>) -> PowerResult<()> {
+        self.restore_lights().await;
+
+        let manager = ManagerProxy::new(&connection).await?;
+        let mut sleep_signals = manager.receive_prepare_for_sleep().await?;
+        let mut inhibitor = take_inhibitor(&manager).await;
+
+        loop {
+            let signal = tokio::select! {
+                signal = sleep_signals.next() => signal,
+                _ = stop.changed() => break,
+            };
+            let Some(signal) = signal else {
+                break;
+            };
+
+            if signal.args()?.start {
+                println!("System is going to sleep");
+                self.prepare_for_sleep().await;
+                // Closing the lock lets the system go ahead
+                drop(inhibitor.take());
+            } else {
+                println!("System resumed");
+                self.resume().await;
+                inhibitor = take_inhibitor(&manager).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Save the state of the lights that are on and switch them off
+    async fn prepare_for_sleep(&self) {
+        let devices = match self.sink.get_all_devices().await {
+            Ok(devices) => devices,
+            Err(e) => {
+                eprintln!("Failed to get devices before sleep: {e}");
+                return;
+            }
+        };
+
+        let lit: BTreeMap<String, DeviceState> = devices
+            .iter()
+            .filter(|device| device.is_connected && device.is_on)
+            .map(|device| (device.serial_number.clone(), device.state()))
+            .collect();
+        save_states(&self.state_path, &lit);
+
+        let off = LightingState {
+            power_on: Some(false),
+            ..LightingState::default()
+        };
+        for serial_number in lit.keys() {
+            if let Err(e) = self.sink.apply_lighting_state(serial_number, &off).await {
+                eprintln!("Failed to switch off {serial_number} before sleep: {e}");
+            }
+        }
+    }
+
+    /// Reopen the devices, restore the lights and reconcile camera sessions
+    async fn resume(&self) {
+        for attempt in 1..=RESTORE_ATTEMPTS {
+            if let Err(e) = self.sink.reconnect().await {
+                eprintln!("Failed to reconnect devices after resume: {e}");
+            }
+            if self.restore_lights().await {
+                break;
+            }
+            if attempt < RESTORE_ATTEMPTS {
+                tokio::time::sleep(RESTORE_RETRY_INTERVAL).await;
+            }
+        }
+
+        self.camera_monitor.lock().await.reconcile_now();
+    }
+
+    /// Restore the lights saved before sleep.
+    ///
+    /// Lights that cannot be restored yet stay saved. Returns whether all
+    /// lights were restored.
+    async fn restore_lights(&self) -> bool {
+        let mut remaining = load_states(&self.state_path);
+        if remaining.is_empty() {
+            return true;
+        }
+
+        let mut restored = Vec::new();
+        for (serial_number, state) in &remaining {
+            match self.sink.apply_device_state(serial_number, state).await {
+                Ok(()) => restored.push(serial_number.clone()),
+                Err(e) => eprintln!("Failed to restore {serial_number} after sleep: {e}"),
+            }
+        }
+        for serial_number in restored {
+            remaining.remove(&serial_number);
+        }
+
+        save_states(&self.state_path, &remaining);
+        remaining.is_empty()
+    }
+}
+
+/// Take a delay inhibitor lock for sleep, if logind grants one
+async fn take_inhibitor(manager: &ManagerProxy<'_>) -> Option<OwnedFd> {
+    match manager
+        .inhibit("sleep", INHIBITOR_WHO, INHIBITOR_WHY, "delay")
+        .await
+    {
+        Ok(inhibitor) => Some(inhibitor),
+        Err(e) => {
+            eprintln!("Failed to take sleep inhibitor: {e}");
+            None
+        }
+    }
+}
+
+/// Read the saved light states, empty if there are none
+fn load_states(path: &Path) -> BTreeMap<String, DeviceState> {
+    match fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+            eprintln!("Failed to parse sleep state {}: {e}", path.display());
+            BTreeMap::new()
+        }),
+        Err(_) => BTreeMap::new(),
+    }
+}
+
+/// Save the light states, removing the file when there are none
+fn save_states(path: &Path, states: &BTreeMap<String, DeviceState>) {
+    if states.is_empty() {
+        let _ = fs::remove_file(path);
+        return;
+    }
+
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| {
+            let contents = serde_json::to_string_pretty(states)?;
+            fs::write(path, contents)
+        });
+    if let Err(e) = result {
+        eprintln!("Failed to save sleep state {}: {e}", path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::sink::{RecordingDeviceSink, SinkCall};
+    use crate::logind::stub::{PrivateBus, StubLogind};
+    use crate::test_support::{
+        key_light_sink, test_monitor, wait_until, TempDir, FILL_LIGHT, KEY_LIGHT, LIT_STATE,
+    };
+    use tokio::sync::Mutex;
+
+    fn sleep_task(sink: Arc<RecordingDeviceSink>, state_path: PathBuf) -> SleepTask {
+        SleepTask {
+            camera_monitor: Arc::new(Mutex::new(test_monitor())),
+            sink,
+            state_path,
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs dbus-daemon, run with --ignored"]
+    async fn test_lights_off_for_sleep_and_restored_on_resume() {
+        let bus = PrivateBus::start("sleep");
+        let logind = StubLogind::start(&bus).await;
+
+        let sink = key_light_sink(&[FILL_LIGHT]);
+        let fill_state = sink.device_state(FILL_LIGHT);
+        let dir = TempDir::new("sleep");
+        let path = dir.join("states.json");
+
+        let task = sleep_task(sink.clone(), path.clone());
+        let (stop_tx, stop_rx) = watch::channel(false);
+        let connection = bus.connect().await;
+        let handle = tokio::spawn(async move { task.run(connection, stop_rx).await });
+
+        // The inhibitor is taken once the watcher is subscribed
+        wait_until("inhibitor taken", || logind.held_inhibitors() == 1).await;
+
+        logind.prepare_for_sleep(true).await;
+        wait_until("inhibitor released", || logind.held_inhibitors() == 0).await;
+        assert!(!sink.device_state(KEY_LIGHT).is_on);
+        assert_eq!(load_states(&path).get(KEY_LIGHT), Some(&LIT_STATE));
+        // Lights that were off are left alone
+        assert!(!load_states(&path).contains_key(FILL_LIGHT));
+
+        logind.prepare_for_sleep(false).await;
+        wait_until("inhibitor taken again", || logind.held_inhibitors() == 1).await;
+        assert!(sink.calls().contains(&SinkCall::Reconnect));
+        assert_eq!(sink.device_state(KEY_LIGHT), LIT_STATE);
+        assert_eq!(sink.device_state(FILL_LIGHT), fill_state);
+        assert!(!path.exists());
+
+        stop_tx.send(true).unwrap();
+        handle.await.unwrap().unwrap();
+        wait_until("inhibitor released on stop", || {
+            logind.held_inhibitors() == 0
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_saved_lights_are_restored() {
+        let sink = Arc::new(RecordingDeviceSink::new(&[KEY_LIGHT]));
+        let dir = TempDir::new("sleep");
+        let path = dir.join("states.json");
+        save_states(
+            &path,
+            &BTreeMap::from([
+                (KEY_LIGHT.to_string(), LIT_STATE),
+                ("GONE001".to_string(), LIT_STATE),
+            ]),
+        );
+
+        let task = sleep_task(sink.clone(), path.clone());
+        assert!(!task.restore_lights().await);
+        assert_eq!(sink.device_state(KEY_LIGHT), LIT_STATE);
+
+        // Devices that are not back yet stay saved for the next attempt
+        let remaining = load_states(&path);
+        assert_eq!(remaining.keys().collect::<Vec<_>>(), vec!["GONE001"]);
+    }
+}
@@ -0,0 +1,492 @@
+//! Sleep timers and maximum on-time
+//!
+//! A sleep timer switches one device, or all of them, off once its time is
+//! up. The maximum on-time switches off lights that have been on for too long
+//! without a break; camera sessions restart the count, so lights are never
+//! switched off during a call. Timers live in the backend and keep running
+//! while the window is hidden.
+
+use crate::camera_monitor::strategies::create_device_set_selector;
+use crate::config::{LightingState, MaxOnTimeConfig, CAMERA_SOURCE_ID};
+use crate::device::sink::DeviceSink;
+use crate::task::BackgroundTask;
+use crate::triggers::TriggerEngineState;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{watch, Notify};
+use tokio::time::Instant;
+
+/// Interval between checks of how long lights have been on
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Kind of a running timer
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum TimerKind {
+    /// Sleep timer set by the user
+    SleepTimer,
+    /// Maximum on-time of a light that is on
+    MaxOnTime,
+}
+
+/// A running timer, as reported to the frontend
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TimerInfo {
+    /// Kind of the timer
+    pub kind: TimerKind,
+    /// Device switched off when the timer fires, `None` for all devices
+    pub serial_number: Option<String>,
+    /// Seconds until the timer fires
+    pub remaining_seconds: u64,
+}
+
+/// Timers shared with the timer task
+#[derive(Default)]
+struct TimerState {
+    /// When each sleep timer fires, by device (`None` for all devices)
+    sleep_timers: BTreeMap<Option<String>, Instant>,
+    /// Since when each light has been seen on, for the maximum on-time
+    on_since: BTreeMap<String, Instant>,
+    /// Maximum on-time configuration
+    max_on_time: MaxOnTimeConfig,
+}
+
+impl TimerState {
+    /// When the maximum on-time of a light seen on since `since` runs out
+    fn on_time_deadline(&self, since: Instant) -> Instant {
+        since + Duration::from_secs(u64::from(self.max_on_time.max_on_minutes) * 60)
+    }
+}
+
+/// Manager running sleep timers and the maximum on-time
+pub struct TimerManager {
+    /// Sink used to read and change devices
+    sink: Arc<dyn DeviceSink>,
+    /// Trigger engine told about lights switched off, and whose camera
+    /// signal holds off the maximum on-time
+    trigger_engine: TriggerEngineState,
+    /// Timers shared with the timer task
+    state: Arc<Mutex<TimerState>>,
+    /// Wakes the timer task when the timers change
+    changed: Arc<Notify>,
+    /// Timer task, while running
+    task: Option<BackgroundTask>,
+}
+
+impl TimerManager {
+    /// Create a new, stopped timer manager
+    pub fn new(sink: Arc<dyn DeviceSink>, trigger_engine: TriggerEngineState) -> Self {
+        Self {
+            sink,
+            trigger_engine,
+            state: Arc::new(Mutex::new(TimerState::default())),
+            changed: Arc::new(Notify::new()),
+            task: None,
+        }
+    }
+
+    /// Start running timers with the given maximum on-time configuration
+    pub fn start(&mut self, max_on_time: MaxOnTimeConfig) {
+        if self.is_running() {
+            return;
+        }
+        self.set_max_on_time(max_on_time);
+
+        let task = TimerTask {
+            sink: self.sink.clone(),
+            trigger_engine: self.trigger_engine.clone(),
+            state: self.state.clone(),
+            changed: self.changed.clone(),
+        };
+        self.task = Some(BackgroundTask::spawn(|stop| task.run(stop)));
+    }
+
+    /// Stop running timers
+    pub async fn stop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.stop().await;
+        }
+    }
+
+    /// Check if timers are running
+    pub fn is_running(&self) -> bool {
+        self.task.as_ref().is_some_and(BackgroundTask::is_running)
+    }
+
+    /// Switch a device, or all devices if `None`, off after the given time,
+    /// replacing any sleep timer already set for it
+    pub fn set_sleep_timer(&self, serial_number: Option<String>, duration: Duration) {
+        self.state
+            .lock()
+            .unwrap()
+            .sleep_timers
+            .insert(serial_number, Instant::now() + duration);
+        self.changed.notify_one();
+    }
+
+    /// Cancel the sleep timer of a device, or the one for all devices if
+    /// `None`, returning whether one was set
+    pub fn cancel_sleep_timer(&self, serial_number: Option<String>) -> bool {
+        let cancelled = self
+            .state
+            .lock()
+            .unwrap()
+            .sleep_timers
+            .remove(&serial_number)
+            .is_some();
+        self.changed.notify_one();
+        cancelled
+    }
+
+    /// Replace the maximum on-time configuration
+    pub fn set_max_on_time(&self, config: MaxOnTimeConfig) {
+        let mut state = self.state.lock().unwrap();
+        if !config.enabled || config.devices != state.max_on_time.devices {
+            state.on_since.clear();
+        }
+        state.max_on_time = config;
+        drop(state);
+        self.changed.notify_one();
+    }
+
+    /// Get the running timers, soonest first
+    pub fn timers(&self) -> Vec<TimerInfo> {
+        let state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let remaining = |deadline: Instant| deadline.saturating_duration_since(now).as_secs();
+
+        let sleep_timers = state
+            .sleep_timers
+            .iter()
+            .map(|(serial_number, deadline)| TimerInfo {
+                kind: TimerKind::SleepTimer,
+                serial_number: serial_number.clone(),
+                remaining_seconds: remaining(*deadline),
+            });
+        let on_times = state
+            .on_since
+            .iter()
+            .map(|(serial_number, since)| TimerInfo {
+                kind: TimerKind::MaxOnTime,
+                serial_number: Some(serial_number.clone()),
+                remaining_seconds: remaining(state.on_time_deadline(*since)),
+            });
+
+        let mut timers: Vec<TimerInfo> = sleep_timers.chain(on_times).collect();
+        timers.sort_by_key(|timer| timer.remaining_seconds);
+        timers
+    }
+}
+
+/// State owned by the timer task
+struct TimerTask {
+    /// Sink used to read and change devices
+    sink: Arc<dyn DeviceSink>,
+    /// Trigger engine told about lights switched off
+    trigger_engine: TriggerEngineState,
+    /// Timers shared with the manager
+    state: Arc<Mutex<TimerState>>,
+    /// Notified when the timers change
+    changed: Arc<Notify>,
+}
+
+impl TimerTask {
+    /// Fire timers as they run out until stopped
+    async fn run(self, mut stop: watch::Receiver<bool>) {
+        let mut next_poll = Instant::now();
+
+        loop {
+            self.fire_sleep_timers().await;
+            if Instant::now() >= next_poll || self.on_time_expired() {
+                self.check_on_time().await;
+                next_poll = Instant::now() + POLL_INTERVAL;
+            }
+
+            let wake_at = self
+                .next_deadline()
+                .map_or(next_poll, |at| at.min(next_poll));
+            tokio::select! {
+                _ = tokio::time::sleep_until(wake_at) => {}
+                _ = self.changed.notified() => {}
+                _ = stop.changed() => break,
+            }
+        }
+    }
+
+    /// Earliest time a sleep timer fires or a maximum on-time runs out
+    fn next_deadline(&self) -> Option<Instant> {
+        let state = self.state.lock().unwrap();
+        let on_times = state
+            .on_since
+            .values()
+            .map(|since| state.on_time_deadline(*since));
+        state.sleep_timers.values().copied().chain(on_times).min()
+    }
+
+    /// Whether a light has reached its maximum on-time
+    fn on_time_expired(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        let now = Instant::now();
+        state
+            .on_since
+            .values()
+            .any(|since| state.on_time_deadline(*since) <= now)
+    }
+
+    /// Switch off the devices of sleep timers that ran out
+    async fn fire_sleep_timers(&self) {
+        let due: Vec<Option<String>> = {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+            let due = state
+                .sleep_timers
+                .iter()
+                .filter(|(_, deadline)| **deadline <= now)
+                .map(|(serial_number, _)| serial_number.clone())
+                .collect::<Vec<_>>();
+            for serial_number in &due {
+                state.sleep_timers.remove(serial_number);
+            }
+            due
+        };
+
+        for serial_number in due {
+            match serial_number {
+                Some(serial_number) => {
+                    println!("Sleep timer for {serial_number} ran out");
+                    self.switch_off(&serial_number).await;
+                }
+                None => {
+                    println!("Sleep timer for all devices ran out");
+                    let devices = match self.sink.get_all_devices().await {
+                        Ok(devices) => devices,
+                        Err(e) => {
+                            eprintln!("Failed to get devices for sleep timer: {e}");
+                            continue;
+                        }
+                    };
+                    for device in devices.iter().filter(|device| device.is_connected) {
+                        self.switch_off(&device.serial_number).await;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Track how long lights have been on and switch off those that reached
+    /// the maximum on-time
+    async fn check_on_time(&self) {
+        let config = self.state.lock().unwrap().max_on_time.clone();
+        if !config.enabled {
+            return;
+        }
+
+        let devices = match self.sink.get_all_devices().await {
+            Ok(devices) => devices,
+            Err(e) => {
+                eprintln!("Failed to get devices for maximum on-time: {e}");
+                return;
+            }
+        };
+        let session_active = self
+            .trigger_engine
+            .lock()
+            .await
+            .signal_is_true(CAMERA_SOURCE_ID)
+            .await;
+        let selector = create_device_set_selector(&config.devices);
+
+        let expired: Vec<String> = {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+            state.on_since.retain(|serial_number, _| {
+                devices.iter().any(|device| {
+                    &device.serial_number == serial_number
+                        && device.is_on
+                        && selector.should_control_device(device)
+                })
+            });
+
+            let mut expired = Vec::new();
+            for device in &devices {
+                if !device.is_on || !selector.should_control_device(device) {
+                    continue;
+                }
+                let serial_number = device.serial_number.clone();
+                // A call restarts the count
+                let since = if session_active {
+                    state.on_since.insert(serial_number, now);
+                    now
+                } else {
+                    *state.on_since.entry(serial_number).or_insert(now)
+                };
+                if state.on_time_deadline(since) <= now {
+                    expired.push(device.serial_number.clone());
+                }
+            }
+            for serial_number in &expired {
+                state.on_since.remove(serial_number);
+            }
+            expired
+        };
+
+        for serial_number in expired {
+            println!("{serial_number} reached its maximum on-time");
+            self.switch_off(&serial_number).await;
+        }
+    }
+
+    /// Switch a device off and tell the trigger engine, like a manual change
+    async fn switch_off(&self, serial_number: &str) {
+        let off = LightingState {
+            power_on: Some(false),
+            ..LightingState::default()
+        };
+        if let Err(e) = self.sink.apply_lighting_state(serial_number, &off).await {
+            eprintln!("Failed to switch off {serial_number}: {e}");
+            return;
+        }
+
+        self.trigger_engine
+            .lock()
+            .await
+            .notify_manual_change(serial_number)
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera_monitor::source::ScriptedEventSource;
+    use crate::config::AutoToggleConfig;
+    use crate::device::sink::RecordingDeviceSink;
+    use crate::test_support::{key_light_sink, test_monitor, KEY_LIGHT, LIT_STATE};
+    use crate::triggers::camera::CameraTriggerSource;
+    use crate::triggers::TriggerEngine;
+
+    const DESK_LIGHT: &str = "DESK001";
+
+    fn minutes(minutes: u64) -> Duration {
+        Duration::from_secs(minutes * 60)
+    }
+
+    /// Start a timer manager over two lit devices
+    fn start_manager(max_on_time: MaxOnTimeConfig) -> (TimerManager, Arc<RecordingDeviceSink>) {
+        let sink = key_light_sink(&[DESK_LIGHT]);
+        sink.set_device_state(DESK_LIGHT, LIT_STATE);
+        let engine = TriggerEngine::new(sink.clone());
+
+        let mut manager =
+            TimerManager::new(sink.clone(), Arc::new(tokio::sync::Mutex::new(engine)));
+        manager.start(max_on_time);
+        (manager, sink)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sleep_timer_switches_device_off() {
+        let (mut manager, sink) = start_manager(MaxOnTimeConfig::default());
+
+        manager.set_sleep_timer(Some(KEY_LIGHT.to_string()), minutes(30));
+        manager.set_sleep_timer(None, minutes(60));
+        assert_eq!(
+            manager.timers(),
+            vec![
+                TimerInfo {
+                    kind: TimerKind::SleepTimer,
+                    serial_number: Some(KEY_LIGHT.to_string()),
+                    remaining_seconds: 30 * 60,
+                },
+                TimerInfo {
+                    kind: TimerKind::SleepTimer,
+                    serial_number: None,
+                    remaining_seconds: 60 * 60,
+                },
+            ]
+        );
+
+        tokio::time::sleep(minutes(30) + Duration::from_secs(1)).await;
+        assert!(!sink.device_state(KEY_LIGHT).is_on);
+        assert!(sink.device_state(DESK_LIGHT).is_on);
+        assert_eq!(manager.timers().len(), 1);
+
+        assert!(manager.cancel_sleep_timer(None));
+        tokio::time::sleep(minutes(60)).await;
+        assert!(sink.device_state(DESK_LIGHT).is_on);
+        assert!(manager.timers().is_empty());
+
+        manager.stop().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_max_on_time_switches_lights_off() {
+        let (mut manager, sink) = start_manager(MaxOnTimeConfig {
+            enabled: true,
+            max_on_minutes: 60,
+            ..MaxOnTimeConfig::default()
+        });
+
+        tokio::time::sleep(minutes(59)).await;
+        assert!(sink.device_state(KEY_LIGHT).is_on);
+        assert_eq!(manager.timers().len(), 2);
+        assert!(manager
+            .timers()
+            .iter()
+            .all(|timer| timer.kind == TimerKind::MaxOnTime && timer.remaining_seconds == 60));
+
+        tokio::time::sleep(minutes(2)).await;
+        assert!(!sink.device_state(KEY_LIGHT).is_on);
+        assert!(!sink.device_state(DESK_LIGHT).is_on);
+        assert!(manager.timers().is_empty());
+
+        manager.stop().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_max_on_time_waits_for_camera_session() {
+        let sink = key_light_sink(&[]);
+        let mut monitor = test_monitor();
+        let mut engine = TriggerEngine::new(sink.clone());
+        engine
+            .start(
+                Vec::new(),
+                Vec::new(),
+                vec![Box::new(CameraTriggerSource::new(&monitor))],
+            )
+            .await;
+        monitor
+            .start_monitoring_with_source(
+                AutoToggleConfig {
+                    enabled: true,
+                    ..AutoToggleConfig::default()
+                },
+                Box::new(ScriptedEventSource::new(Vec::new()).with_open_count(1)),
+            )
+            .unwrap();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert!(monitor.is_session_active());
+
+        let mut manager =
+            TimerManager::new(sink.clone(), Arc::new(tokio::sync::Mutex::new(engine)));
+        manager.start(MaxOnTimeConfig {
+            enabled: true,
+            max_on_minutes: 60,
+            ..MaxOnTimeConfig::default()
+        });
+
+        tokio::time::sleep(minutes(120)).await;
+        assert!(sink.device_state(KEY_LIGHT).is_on);
+
+        // The count starts over once the call is over
+        monitor.stop_monitoring().await.unwrap();
+        tokio::time::sleep(minutes(30)).await;
+        assert!(sink.device_state(KEY_LIGHT).is_on);
+        tokio::time::sleep(minutes(31)).await;
+        assert!(!sink.device_state(KEY_LIGHT).is_on);
+
+        manager.stop().await;
+    }
+}
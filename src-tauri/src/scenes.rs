@@ -0,0 +1,303 @@
+//! Multi-device scenes
+//!
+//! A scene holds the power, brightness and temperature of several devices,
+//! captured from their live state. Applying a scene changes all of its
+//! connected devices together, optionally fading between the current and the
+//! new state, and puts every device back the way it was if one of them cannot
+//! be changed. Devices of the scene that are not connected are skipped.
+
+use crate::config::{Scene, SceneDevice};
+use crate::device::sink::{DeviceSink, DeviceSinkResult};
+use crate::device::{DeviceInfo, DeviceState};
+use serde::Serialize;
+use std::time::Duration;
+
+/// Time between two steps of a fade
+const FADE_STEP: Duration = Duration::from_millis(100);
+
+/// Result of applying a scene
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SceneApplied {
+    /// Serial numbers of the devices changed
+    pub applied: Vec<String>,
+    /// Serial numbers of the scene's devices that are not connected
+    pub missing: Vec<String>,
+}
+
+/// Capture the state of connected devices
+///
+/// Captures all connected devices, or only those in `serial_numbers` if given.
+pub async fn capture_devices(
+    sink: &dyn DeviceSink,
+    serial_numbers: Option<&[String]>,
+) -> DeviceSinkResult<Vec<SceneDevice>> {
+    Ok(sink
+        .get_all_devices()
+        .await?
+        .into_iter()
+        .filter(|device| device.is_connected)
+        .filter(|device| {
+            serial_numbers.is_none_or(|serials| serials.contains(&device.serial_number))
+        })
+        .map(|device| SceneDevice {
+            state: device.state(),
+            serial_number: device.serial_number,
+            device_type: device.device_type,
+        })
+        .collect())
+}
+
+/// A device changed by a scene
+struct Transition {
+    /// Serial number of the device
+    serial_number: String,
+    /// State before the scene was applied, restored on failure
+    original: DeviceState,
+    /// Start of the fade
+    from: DeviceState,
+    /// End of the fade
+    to: DeviceState,
+    /// State powering the device off at the end of a fade out, so the
+    /// target's brightness and temperature are then written while it is off
+    switch_off: Option<DeviceState>,
+    /// State applied once the fade is over
+    target: DeviceState,
+}
+
+impl Transition {
+    fn new(device: &DeviceInfo, state: DeviceState, fade: bool) -> Self {
+        let original = device.state();
+        let target = DeviceState {
+            is_on: state.is_on,
+            brightness_lumens: state
+                .brightness_lumens
+                .clamp(device.min_brightness_lumens, device.max_brightness_lumens),
+            temperature_kelvin: state
+                .temperature_kelvin
+                .clamp(device.min_temperature_kelvin, device.max_temperature_kelvin),
+        };
+
+        // Lights being switched on fade in from their lowest brightness, and
+        // lights being switched off fade out to it
+        let lowest = |state: DeviceState| DeviceState {
+            is_on: true,
+            brightness_lumens: device.min_brightness_lumens,
+            ..state
+        };
+        let from = if original.is_on {
+            original
+        } else {
+            lowest(original)
+        };
+        let to = if target.is_on { target } else { lowest(target) };
+        let switch_off =
+            (fade && original.is_on && !target.is_on).then_some(DeviceState { is_on: false, ..to });
+
+        Self {
+            serial_number: device.serial_number.clone(),
+            original,
+            from,
+            to,
+            switch_off,
+            target,
+        }
+    }
+
+    /// State at `step` of `steps` fade steps
+    fn at(&self, step: u32, steps: u32) -> DeviceState {
+        let lerp = |from: u16, to: u16| {
+            let delta = (f64::from(to) - f64::from(from)) * f64::from(step) / f64::from(steps);
+            (f64::from(from) + delta).round() as u16
+        };
+        DeviceState {
+            is_on: true,
+            brightness_lumens: lerp(self.from.brightness_lumens, self.to.brightness_lumens),
+            // Devices only take temperatures in steps of 100 K
+            temperature_kelvin: lerp(self.from.temperature_kelvin, self.to.temperature_kelvin)
+                .div_ceil(100)
+                * 100,
+        }
+    }
+
+    /// Whether the device is lit during the fade
+    fn fades(&self) -> bool {
+        self.original.is_on || self.target.is_on
+    }
+}
+
+/// Apply a scene to its connected devices
+///
+/// With a non-zero `fade`, brightness and temperature move gradually over
+/// that time. If a device cannot be changed, all devices are put back in
+/// their previous state and the error is returned.
+pub async fn apply_scene(
+    sink: &dyn DeviceSink,
+    scene: &Scene,
+    fade: Duration,
+) -> DeviceSinkResult<SceneApplied> {
+    let devices = sink.get_all_devices().await?;
+    let steps = (fade.as_millis() / FADE_STEP.as_millis()) as u32;
+
+    let mut result = SceneApplied::default();
+    let mut transitions = Vec::new();
+    for scene_device in &scene.devices {
+        match devices
+            .iter()
+            .find(|device| device.serial_number == scene_device.serial_number)
+            .filter(|device| device.is_connected)
+        {
+            Some(device) => {
+                transitions.push(Transition::new(device, scene_device.state, steps > 1));
+                result.applied.push(device.serial_number.clone());
+            }
+            None => result.missing.push(scene_device.serial_number.clone()),
+        }
+    }
+
+    if let Err(e) = run_transitions(sink, &transitions, steps).await {
+        for transition in &transitions {
+            if let Err(e) = sink
+                .apply_device_state(&transition.serial_number, &transition.original)
+                .await
+            {
+                eprintln!(
+                    "Failed to restore device {} after a failed scene: {e}",
+                    transition.serial_number
+                );
+            }
+        }
+        return Err(e);
+    }
+
+    Ok(result)
+}
+
+/// Step all devices through their fade together, then apply the targets
+async fn run_transitions(
+    sink: &dyn DeviceSink,
+    transitions: &[Transition],
+    steps: u32,
+) -> DeviceSinkResult<()> {
+    for step in 1..steps {
+        for transition in transitions.iter().filter(|t| t.fades()) {
+            sink.apply_device_state(&transition.serial_number, &transition.at(step, steps))
+                .await?;
+        }
+        tokio::time::sleep(FADE_STEP).await;
+    }
+
+    for transition in transitions {
+        if let Some(switch_off) = &transition.switch_off {
+            sink.apply_device_state(&transition.serial_number, switch_off)
+                .await?;
+        }
+        sink.apply_device_state(&transition.serial_number, &transition.target)
+            .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::sink::{RecordingDeviceSink, SinkCall};
+
+    fn state(is_on: bool, brightness_lumens: u16, temperature_kelvin: u16) -> DeviceState {
+        DeviceState {
+            is_on,
+            brightness_lumens,
+            temperature_kelvin,
+        }
+    }
+
+    fn scene(devices: &[(&str, DeviceState)]) -> Scene {
+        Scene {
+            id: "test".to_string(),
+            name: "Test".to_string(),
+            devices: devices
+                .iter()
+                .map(|(serial_number, state)| SceneDevice {
+                    serial_number: serial_number.to_string(),
+                    device_type: "Litra Glow".to_string(),
+                    state: *state,
+                })
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_capture_and_apply() {
+        let sink = RecordingDeviceSink::new(&["KEY", "FILL", "BACK"]);
+        sink.set_device_state("KEY", state(true, 200, 5600));
+        sink.set_device_state("FILL", state(true, 90, 5000));
+
+        let captured = capture_devices(&sink, None).await.unwrap();
+        assert_eq!(captured.len(), 3);
+        assert_eq!(captured[0].state, state(true, 200, 5600));
+        let only_fill = capture_devices(&sink, Some(&["FILL".to_string()]))
+            .await
+            .unwrap();
+        assert_eq!(only_fill.len(), 1);
+
+        // Devices not connected are skipped, limits are respected
+        let scene = scene(&[
+            ("KEY", state(true, 400, 6000)),
+            ("BACK", state(false, 100, 4000)),
+            ("GONE", state(true, 100, 4000)),
+        ]);
+        let applied = apply_scene(&sink, &scene, Duration::ZERO).await.unwrap();
+        assert_eq!(applied.applied, ["KEY", "BACK"]);
+        assert_eq!(applied.missing, ["GONE"]);
+        assert_eq!(sink.device_state("KEY"), state(true, 250, 6000));
+        assert_eq!(sink.device_state("FILL"), state(true, 90, 5000));
+        assert_eq!(sink.device_state("BACK"), state(false, 100, 4000));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fade() {
+        let sink = RecordingDeviceSink::new(&["KEY", "BACK"]);
+        sink.set_device_state("BACK", state(true, 200, 3000));
+
+        let scene = scene(&[
+            ("KEY", state(true, 220, 5000)),
+            ("BACK", state(false, 200, 3000)),
+        ]);
+        apply_scene(&sink, &scene, Duration::from_millis(400))
+            .await
+            .unwrap();
+
+        let key: Vec<_> = sink
+            .calls()
+            .into_iter()
+            .filter_map(|call| match call {
+                SinkCall::ApplyDeviceState(serial, state) if serial == "KEY" => Some(state),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            key,
+            [
+                state(true, 70, 4300),
+                state(true, 120, 4500),
+                state(true, 170, 4800),
+                state(true, 220, 5000),
+            ]
+        );
+        // The back light fades out before switching off, then takes the
+        // scene's brightness while off
+        let back: Vec<_> = sink
+            .calls()
+            .into_iter()
+            .filter_map(|call| match call {
+                SinkCall::ApplyDeviceState(serial, state) if serial == "BACK" => Some(state),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            back[back.len() - 2..],
+            [state(false, 20, 3000), state(false, 200, 3000)]
+        );
+        assert_eq!(sink.device_state("BACK"), state(false, 200, 3000));
+    }
+}
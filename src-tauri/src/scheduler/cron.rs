@@ -0,0 +1,280 @@
+//! Cron expressions
+//!
+//! Parses the classic five-field cron syntax (`minute hour day-of-month month
+//! day-of-week`) with lists, ranges, steps, month and weekday names and the
+//! `@daily`-style shorthands, and finds the next time an expression matches.
+//! As in cron, a day matches when either day field does if both are
+//! restricted.
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Timelike};
+
+/// How many days ahead to look for a match, covering leap days
+const SEARCH_DAYS: i64 = 366 * 8;
+
+/// Names accepted in the month field, from January
+const MONTH_NAMES: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+/// Names accepted in the day-of-week field, from Sunday
+const WEEKDAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// A parsed cron expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    /// Matching minutes, as bits 0-59
+    minutes: u64,
+    /// Matching hours, as bits 0-23
+    hours: u64,
+    /// Matching days of the month, as bits 1-31
+    days_of_month: u64,
+    /// Matching months, as bits 1-12
+    months: u64,
+    /// Matching days of the week, as bits 0-6 from Sunday
+    days_of_week: u64,
+    /// Whether the day-of-month field was restricted
+    day_of_month_restricted: bool,
+    /// Whether the day-of-week field was restricted
+    day_of_week_restricted: bool,
+}
+
+impl CronSchedule {
+    /// Parse a cron expression
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let expression = expression.trim();
+        let expanded = match expression {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            _ => expression,
+        };
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            return Err(format!(
+                "Expected 5 fields (minute hour day month weekday) in \"{expression}\""
+            ));
+        };
+
+        let mut days_of_week = parse_field(day_of_week, "weekday", 0, 7, &WEEKDAY_NAMES, 0)?;
+        // Both 0 and 7 are Sunday
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week & !(1 << 7)) | 1;
+        }
+
+        Ok(Self {
+            minutes: parse_field(minute, "minute", 0, 59, &[], 0)?,
+            hours: parse_field(hour, "hour", 0, 23, &[], 0)?,
+            days_of_month: parse_field(day_of_month, "day", 1, 31, &[], 0)?,
+            months: parse_field(month, "month", 1, 12, &MONTH_NAMES, 1)?,
+            days_of_week,
+            day_of_month_restricted: !day_of_month.starts_with('*'),
+            day_of_week_restricted: !day_of_week.starts_with('*'),
+        })
+    }
+
+    /// The first matching minute strictly after the given time, in its time
+    /// zone.
+    ///
+    /// Local times skipped by a daylight saving change are passed over, and
+    /// repeated ones match the first time round.
+    pub fn next_after<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let timezone = after.timezone();
+        let mut cursor = after.naive_local();
+
+        loop {
+            let naive = self.next_naive_after(cursor)?;
+            if let Some(time) = timezone.from_local_datetime(&naive).earliest() {
+                if time > *after {
+                    return Some(time);
+                }
+            }
+            cursor = naive;
+        }
+    }
+
+    /// The first matching minute strictly after a local time
+    fn next_naive_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let start = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+
+        for day_offset in 0..SEARCH_DAYS {
+            let date = start.date() + Duration::days(day_offset);
+            if !self.matches_date(date) {
+                continue;
+            }
+
+            let first_day = day_offset == 0;
+            for hour in 0..24 {
+                if !has_bit(self.hours, hour) || (first_day && hour < start.hour()) {
+                    continue;
+                }
+                for minute in 0..60 {
+                    let too_early = first_day && hour == start.hour() && minute < start.minute();
+                    if has_bit(self.minutes, minute) && !too_early {
+                        return date.and_hms_opt(hour, minute, 0);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Whether the day and month fields match a date
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        if !has_bit(self.months, date.month()) {
+            return false;
+        }
+
+        let day_of_month = has_bit(self.days_of_month, date.day());
+        let day_of_week = has_bit(self.days_of_week, date.weekday().num_days_from_sunday());
+        if self.day_of_month_restricted && self.day_of_week_restricted {
+            day_of_month || day_of_week
+        } else {
+            day_of_month && day_of_week
+        }
+    }
+}
+
+fn has_bit(bits: u64, value: u32) -> bool {
+    bits & (1 << value) != 0
+}
+
+/// Parse one field into a bit set of the values it matches.
+///
+/// `names` are accepted in place of numbers, the first standing for
+/// `first_name`.
+fn parse_field(
+    field: &str,
+    label: &str,
+    min: u32,
+    max: u32,
+    names: &[&str],
+    first_name: u32,
+) -> Result<u64, String> {
+    let value = |text: &str| -> Result<u32, String> {
+        let lower = text.to_ascii_lowercase();
+        let value = match names.iter().position(|name| *name == lower) {
+            Some(index) => index as u32 + first_name,
+            None => text
+                .parse()
+                .map_err(|_| format!("Invalid {label} \"{text}\""))?,
+        };
+        if !(min..=max).contains(&value) {
+            return Err(format!("{label} {value} is out of range {min}-{max}"));
+        }
+        Ok(value)
+    };
+
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("Invalid {label} step \"{step}\""))?;
+                (range, step)
+            }
+            None => (part, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (value(start)?, value(end)?)
+        } else {
+            let start = value(range)?;
+            // "5/15" runs from 5 to the end of the range
+            (start, if part.contains('/') { max } else { start })
+        };
+        if start > end {
+            return Err(format!("Invalid {label} range \"{range}\""));
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+
+    Ok(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn next(expression: &str, after: DateTime<Utc>) -> DateTime<Utc> {
+        CronSchedule::parse(expression)
+            .unwrap()
+            .next_after(&after)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_next_after() {
+        // Friday 2024-06-07 after 08:55, weekdays only
+        let weekdays = "55 8 * * mon-fri";
+        assert_eq!(next(weekdays, at(2024, 6, 7, 8, 0)), at(2024, 6, 7, 8, 55));
+        assert_eq!(
+            next(weekdays, at(2024, 6, 7, 8, 55)),
+            at(2024, 6, 10, 8, 55)
+        );
+
+        assert_eq!(
+            next("*/15 * * * *", at(2024, 6, 7, 8, 50)),
+            at(2024, 6, 7, 9, 0)
+        );
+        assert_eq!(
+            next("0 9-17/4 * * *", at(2024, 6, 7, 9, 0)),
+            at(2024, 6, 7, 13, 0)
+        );
+        assert_eq!(
+            next("@daily", at(2024, 12, 31, 12, 0)),
+            at(2025, 1, 1, 0, 0)
+        );
+        assert_eq!(
+            next("0 12 29 feb *", at(2025, 1, 1, 0, 0)),
+            at(2028, 2, 29, 12, 0)
+        );
+
+        // Either day field matches when both are restricted
+        assert_eq!(
+            next("0 0 1 * sun", at(2024, 6, 1, 12, 0)),
+            at(2024, 6, 2, 0, 0)
+        );
+        assert_eq!(
+            next("0 0 * * 7", at(2024, 6, 1, 12, 0)),
+            at(2024, 6, 2, 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for expression in [
+            "",
+            "* * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * foo *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+        ] {
+            assert!(
+                CronSchedule::parse(expression).is_err(),
+                "{expression:?} should not parse"
+            );
+        }
+        assert!(CronSchedule::parse("0,30 8-18 * JAN-Jun Mon,Wed,Fri").is_ok());
+    }
+}
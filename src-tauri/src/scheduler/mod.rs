@@ -0,0 +1,417 @@
+//! Scheduled lighting actions
+//!
+//! Runs the configured jobs at the times of their cron expressions. The wall
+//! clock is checked at least once a minute, so runs that fell while the
+//! system was asleep are noticed soon after resume and handled by the job's
+//! catch-up policy. Runs missed while the app was not running are not caught
+//! up.
+
+pub mod cron;
+
+use crate::camera_monitor::strategies::create_device_set_selector;
+use crate::config::{CatchUpPolicy, ScheduledJob};
+use crate::device::sink::DeviceSink;
+use crate::task::BackgroundTask;
+use crate::triggers::TriggerEngineState;
+use chrono::{DateTime, Local, TimeZone};
+use cron::CronSchedule;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{watch, Notify};
+
+/// Longest the scheduler sleeps before checking the wall clock again
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How late a run may start and still count as on time rather than missed
+const ON_TIME_TOLERANCE: chrono::Duration = chrono::Duration::minutes(2);
+
+/// Most missed runs looked through to find the latest one
+const MAX_MISSED_RUNS: usize = 100_000;
+
+/// Global state type for the job scheduler
+pub type JobSchedulerState = Arc<tokio::sync::Mutex<JobScheduler>>;
+
+/// Run times of a job, as reported to the frontend
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledJobStatus {
+    /// Name of the job
+    pub name: String,
+    /// When the job runs next, if it is enabled
+    pub next_run: Option<DateTime<Local>>,
+    /// When the job last ran since the app started
+    pub last_run: Option<DateTime<Local>>,
+}
+
+/// A configured job with its parsed schedule
+struct Job {
+    /// Job configuration
+    config: ScheduledJob,
+    /// Parsed schedule, `None` if the expression is invalid
+    schedule: Option<CronSchedule>,
+    /// When the job last ran
+    last_run: Option<DateTime<Local>>,
+}
+
+impl Job {
+    fn new(config: ScheduledJob) -> Self {
+        let schedule = CronSchedule::parse(&config.schedule)
+            .map_err(|e| eprintln!("Invalid schedule for job {}: {e}", config.name))
+            .ok();
+        Self {
+            config,
+            schedule,
+            last_run: None,
+        }
+    }
+
+    /// The job's schedule, if it is enabled and valid
+    fn active_schedule(&self) -> Option<&CronSchedule> {
+        self.schedule.as_ref().filter(|_| self.config.enabled)
+    }
+}
+
+/// Scheduler running the configured jobs
+pub struct JobScheduler {
+    /// Sink used to read and change devices
+    sink: Arc<dyn DeviceSink>,
+    /// Trigger engine told about devices the jobs change
+    trigger_engine: TriggerEngineState,
+    /// Jobs shared with the scheduler task
+    jobs: Arc<Mutex<Vec<Job>>>,
+    /// Wakes the scheduler task when the jobs change
+    changed: Arc<Notify>,
+    /// Scheduler task, while running
+    task: Option<BackgroundTask>,
+}
+
+impl JobScheduler {
+    /// Create a new, stopped job scheduler
+    pub fn new(sink: Arc<dyn DeviceSink>, trigger_engine: TriggerEngineState) -> Self {
+        Self {
+            sink,
+            trigger_engine,
+            jobs: Arc::new(Mutex::new(Vec::new())),
+            changed: Arc::new(Notify::new()),
+            task: None,
+        }
+    }
+
+    /// Start running the given jobs
+    pub fn start(&mut self, jobs: Vec<ScheduledJob>) {
+        if self.is_running() {
+            return;
+        }
+        self.set_jobs(jobs);
+
+        let task = SchedulerTask {
+            sink: self.sink.clone(),
+            trigger_engine: self.trigger_engine.clone(),
+            jobs: self.jobs.clone(),
+            changed: self.changed.clone(),
+            last_check: Local::now(),
+        };
+        self.task = Some(BackgroundTask::spawn(|stop| task.run(stop)));
+    }
+
+    /// Stop running jobs
+    pub async fn stop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.stop().await;
+        }
+    }
+
+    /// Check if the scheduler is running
+    pub fn is_running(&self) -> bool {
+        self.task.as_ref().is_some_and(BackgroundTask::is_running)
+    }
+
+    /// Replace the jobs, keeping the last run of jobs that did not change
+    pub fn set_jobs(&self, configs: Vec<ScheduledJob>) {
+        let mut jobs = self.jobs.lock().unwrap();
+        let new_jobs = configs
+            .into_iter()
+            .map(|config| {
+                let last_run = jobs
+                    .iter()
+                    .find(|job| job.config == config)
+                    .and_then(|job| job.last_run);
+                Job {
+                    last_run,
+                    ..Job::new(config)
+                }
+            })
+            .collect();
+        *jobs = new_jobs;
+        drop(jobs);
+        self.changed.notify_one();
+    }
+
+    /// Get the next and last run of every job, in configuration order
+    pub fn status(&self) -> Vec<ScheduledJobStatus> {
+        let now = Local::now();
+        self.jobs
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|job| ScheduledJobStatus {
+                name: job.config.name.clone(),
+                next_run: job
+                    .active_schedule()
+                    .and_then(|schedule| schedule.next_after(&now)),
+                last_run: job.last_run,
+            })
+            .collect()
+    }
+}
+
+/// State owned by the scheduler task
+struct SchedulerTask {
+    /// Sink used to read and change devices
+    sink: Arc<dyn DeviceSink>,
+    /// Trigger engine told about devices the jobs change
+    trigger_engine: TriggerEngineState,
+    /// Jobs shared with the scheduler
+    jobs: Arc<Mutex<Vec<Job>>>,
+    /// Notified when the jobs change
+    changed: Arc<Notify>,
+    /// Wall clock time up to which runs have been handled
+    last_check: DateTime<Local>,
+}
+
+impl SchedulerTask {
+    /// Run jobs as they fall due until stopped
+    async fn run(mut self, mut stop: watch::Receiver<bool>) {
+        loop {
+            let now = Local::now();
+            self.run_due_jobs(now).await;
+
+            // The monotonic clock may stand still during suspend, so never
+            // sleep long without looking at the wall clock
+            let next_run = self
+                .jobs
+                .lock()
+                .unwrap()
+                .iter()
+                .filter_map(|job| job.active_schedule()?.next_after(&now))
+                .min();
+            let sleep = next_run
+                .and_then(|next_run| (next_run - now).to_std().ok())
+                .map_or(CHECK_INTERVAL, |until| until.min(CHECK_INTERVAL));
+
+            tokio::select! {
+                _ = tokio::time::sleep(sleep) => {}
+                _ = self.changed.notified() => {}
+                _ = stop.changed() => break,
+            }
+        }
+    }
+
+    /// Run the jobs that fell due since the last check
+    async fn run_due_jobs(&mut self, now: DateTime<Local>) {
+        let due: Vec<ScheduledJob> = {
+            let mut jobs = self.jobs.lock().unwrap();
+            jobs.iter_mut()
+                .filter_map(|job| {
+                    let schedule = job.active_schedule()?;
+                    let run = due_run(schedule, job.config.catch_up, &self.last_check, &now)?;
+                    if run.missed {
+                        println!("Catching up on missed run of job {}", job.config.name);
+                    }
+                    job.last_run = Some(now);
+                    Some(job.config.clone())
+                })
+                .collect()
+        };
+        self.last_check = now;
+
+        for job in due {
+            self.run_job(&job).await;
+        }
+    }
+
+    /// Apply a job's action to its devices
+    async fn run_job(&self, job: &ScheduledJob) {
+        println!("Running scheduled job {}", job.name);
+        let devices = match self.sink.get_all_devices().await {
+            Ok(devices) => devices,
+            Err(e) => {
+                eprintln!("Failed to get devices for job {}: {e}", job.name);
+                return;
+            }
+        };
+        let selector = create_device_set_selector(&job.devices);
+
+        let mut changed = Vec::new();
+        for device in devices {
+            if !selector.should_control_device(&device) {
+                continue;
+            }
+            match self
+                .sink
+                .apply_lighting_state(&device.serial_number, &job.action)
+                .await
+            {
+                Ok(()) => changed.push(device.serial_number),
+                Err(e) => eprintln!(
+                    "Failed to apply job {} to {}: {e}",
+                    job.name, device.serial_number
+                ),
+            }
+        }
+
+        // Like a change by hand, so a camera session does not undo it
+        let engine = self.trigger_engine.lock().await;
+        for serial_number in changed {
+            engine.notify_manual_change(&serial_number).await;
+        }
+    }
+}
+
+/// A run that fell due
+#[derive(Debug, Clone, PartialEq)]
+struct DueRun<Tz: TimeZone> {
+    /// When the run was scheduled; the latest if several fell due
+    at: DateTime<Tz>,
+    /// Whether it is late enough to count as missed
+    missed: bool,
+}
+
+/// The run of a schedule to make for the time between two checks, if any.
+///
+/// Several runs falling due at once are collapsed into the latest. Runs more
+/// than a couple of minutes late count as missed and are made or dropped as
+/// the catch-up policy says.
+fn due_run<Tz: TimeZone>(
+    schedule: &CronSchedule,
+    catch_up: CatchUpPolicy,
+    last_check: &DateTime<Tz>,
+    now: &DateTime<Tz>,
+) -> Option<DueRun<Tz>> {
+    let mut latest = None;
+    let mut cursor = last_check.clone();
+    for _ in 0..MAX_MISSED_RUNS {
+        match schedule.next_after(&cursor) {
+            Some(run) if run <= *now => {
+                cursor = run.clone();
+                latest = Some(run);
+            }
+            _ => break,
+        }
+    }
+
+    let at = latest?;
+    let lateness = now.clone() - at.clone();
+    if lateness <= ON_TIME_TOLERANCE {
+        return Some(DueRun { at, missed: false });
+    }
+
+    let catch_up = match catch_up {
+        CatchUpPolicy::Skip => false,
+        CatchUpPolicy::RunIfWithin { minutes } => {
+            lateness <= chrono::Duration::minutes(i64::from(minutes))
+        }
+        CatchUpPolicy::Always => true,
+    };
+    catch_up.then_some(DueRun { at, missed: true })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{DeviceSet, LightingState};
+    use crate::device::sink::RecordingDeviceSink;
+    use crate::test_support::KEY_LIGHT;
+    use crate::triggers::TriggerEngine;
+    use chrono::Utc;
+
+    const DESK_LIGHT: &str = "DESK001";
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, day, hour, minute, 0).unwrap()
+    }
+
+    fn due(catch_up: CatchUpPolicy, from: DateTime<Utc>, to: DateTime<Utc>) -> Option<DueRun<Utc>> {
+        let weekdays = CronSchedule::parse("55 8 * * mon-fri").unwrap();
+        due_run(&weekdays, catch_up, &from, &to)
+    }
+
+    #[test]
+    fn test_due_run_on_time() {
+        let within_hour = CatchUpPolicy::default();
+
+        assert_eq!(due(within_hour, at(7, 8, 54), at(7, 8, 54)), None);
+        assert_eq!(
+            due(within_hour, at(7, 8, 54), at(7, 8, 55)),
+            Some(DueRun {
+                at: at(7, 8, 55),
+                missed: false,
+            })
+        );
+        // Already handled by the previous check
+        assert_eq!(due(within_hour, at(7, 8, 55), at(7, 8, 56)), None);
+    }
+
+    #[test]
+    fn test_due_run_catch_up_policies() {
+        // Asleep from Friday evening until Monday 09:30
+        let (slept, woke) = (at(7, 18, 0), at(10, 9, 30));
+        let missed = Some(DueRun {
+            at: at(10, 8, 55),
+            missed: true,
+        });
+
+        assert_eq!(due(CatchUpPolicy::default(), slept, woke), missed);
+        assert_eq!(due(CatchUpPolicy::Skip, slept, woke), None);
+        assert_eq!(
+            due(CatchUpPolicy::RunIfWithin { minutes: 30 }, slept, woke),
+            None
+        );
+
+        // Woken on Monday evening, after a week of missed runs
+        let (slept, woke) = (at(3, 18, 0), at(10, 20, 0));
+        assert_eq!(due(CatchUpPolicy::default(), slept, woke), None);
+        assert_eq!(due(CatchUpPolicy::Always, slept, woke), missed);
+    }
+
+    #[tokio::test]
+    async fn test_due_job_applies_action() {
+        let sink = Arc::new(RecordingDeviceSink::new(&[KEY_LIGHT, DESK_LIGHT]));
+        let engine = TriggerEngine::new(sink.clone());
+        let scheduler = JobScheduler::new(sink.clone(), Arc::new(tokio::sync::Mutex::new(engine)));
+        scheduler.set_jobs(vec![ScheduledJob {
+            name: "Desk light on".to_string(),
+            enabled: true,
+            schedule: "* * * * *".to_string(),
+            devices: DeviceSet::Devices {
+                serial_numbers: vec![DESK_LIGHT.to_string()],
+            },
+            action: LightingState {
+                power_on: Some(true),
+                temperature_kelvin: Some(5600),
+                ..LightingState::default()
+            },
+            catch_up: CatchUpPolicy::Skip,
+        }]);
+
+        let now = Local::now();
+        let mut task = SchedulerTask {
+            sink: scheduler.sink.clone(),
+            trigger_engine: scheduler.trigger_engine.clone(),
+            jobs: scheduler.jobs.clone(),
+            changed: scheduler.changed.clone(),
+            last_check: now - chrono::Duration::seconds(61),
+        };
+        task.run_due_jobs(now).await;
+
+        let desk = sink.device_state(DESK_LIGHT);
+        assert!(desk.is_on);
+        assert_eq!(desk.temperature_kelvin, 5600);
+        assert!(!sink.device_state(KEY_LIGHT).is_on);
+
+        let status = scheduler.status();
+        assert_eq!(status[0].last_run, Some(now));
+        assert!(status[0].next_run.is_some_and(|next_run| next_run > now));
+    }
+}
@@ -0,0 +1,92 @@
+//! Background tasks
+//!
+//! Watchers, schedules and monitors run as tokio tasks told to stop through a
+//! watch channel. Stopping gives them a few seconds to clean up, such as
+//! putting lights back, before they are aborted.
+
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+/// How long to wait for a task to finish when stopping it
+pub const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Handle of tasks running until told to stop
+pub struct BackgroundTask {
+    /// Channel for telling the tasks to stop
+    stop_tx: watch::Sender<bool>,
+    /// Handles of the tasks, in the order they were spawned
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl BackgroundTask {
+    /// Spawn a task, giving it the receiver that tells it to stop
+    pub fn spawn<F>(run: impl FnOnce(watch::Receiver<bool>) -> F) -> Self
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let (stop_tx, _) = watch::channel(false);
+        let mut task = Self {
+            stop_tx,
+            handles: Vec::new(),
+        };
+        task.spawn_alongside(run);
+        task
+    }
+
+    /// Spawn another task stopped together with the others
+    pub fn spawn_alongside<F>(&mut self, run: impl FnOnce(watch::Receiver<bool>) -> F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let stop_rx = self.stop_tx.subscribe();
+        self.handles.push(tokio::spawn(run(stop_rx)));
+    }
+
+    /// Tell the tasks to stop and wait for them, aborting those that take
+    /// longer than [`STOP_TIMEOUT`]
+    pub async fn stop(self) {
+        let _ = self.stop_tx.send(true);
+        for mut handle in self.handles {
+            if tokio::time::timeout(STOP_TIMEOUT, &mut handle)
+                .await
+                .is_err()
+            {
+                handle.abort();
+            }
+        }
+    }
+
+    /// Check if any of the tasks is still running
+    pub fn is_running(&self) -> bool {
+        self.handles.iter().any(|handle| !handle.is_finished())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stop_waits_for_cleanup_then_aborts() {
+        let cleaned_up = Arc::new(AtomicBool::new(false));
+        let mut task = BackgroundTask::spawn({
+            let cleaned_up = cleaned_up.clone();
+            |mut stop| async move {
+                let _ = stop.changed().await;
+                cleaned_up.store(true, Ordering::SeqCst);
+            }
+        });
+        // Ignores the stop signal
+        task.spawn_alongside(|_stop| std::future::pending());
+        assert!(task.is_running());
+
+        let started = tokio::time::Instant::now();
+        task.stop().await;
+        assert!(cleaned_up.load(Ordering::SeqCst));
+        assert!(started.elapsed() >= STOP_TIMEOUT);
+    }
+}
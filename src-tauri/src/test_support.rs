@@ -0,0 +1,106 @@
+//! Shared fixtures for tests
+//!
+//! Tests drive devices through a [`RecordingDeviceSink`], and find files they
+//! write in a [`TempDir`].
+
+use crate::camera_monitor::history::{SessionHistory, MAX_HISTORY_ENTRIES};
+use crate::camera_monitor::CameraMonitor;
+use crate::device::sink::RecordingDeviceSink;
+use crate::device::DeviceState;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Serial number of the light most tests act on
+pub const KEY_LIGHT: &str = "KEY001";
+
+/// Serial number of a second light, for tests acting on several
+pub const FILL_LIGHT: &str = "FILL001";
+
+/// State the lights of a [`RecordingDeviceSink`] start in
+pub const INITIAL_STATE: DeviceState = DeviceState {
+    is_on: false,
+    brightness_lumens: 100,
+    temperature_kelvin: 4000,
+};
+
+/// State of a light that is on, away from [`INITIAL_STATE`]
+pub const LIT_STATE: DeviceState = DeviceState {
+    is_on: true,
+    brightness_lumens: 200,
+    temperature_kelvin: 5000,
+};
+
+/// Sink with the key light on in [`LIT_STATE`] and the `others` off
+pub fn key_light_sink(others: &[&str]) -> Arc<RecordingDeviceSink> {
+    let mut serial_numbers = vec![KEY_LIGHT];
+    serial_numbers.extend_from_slice(others);
+    let sink = Arc::new(RecordingDeviceSink::new(&serial_numbers));
+    sink.set_device_state(KEY_LIGHT, LIT_STATE);
+    sink
+}
+
+/// Stopped camera monitor keeping its session history in memory
+pub fn test_monitor() -> CameraMonitor {
+    CameraMonitor::with_history(SessionHistory::in_memory(MAX_HISTORY_ENTRIES))
+}
+
+/// Wait until `check` holds, failing after a few seconds
+pub async fn wait_until(what: &str, check: impl Fn() -> bool) {
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    while !check() {
+        assert!(tokio::time::Instant::now() < deadline, "Timed out: {what}");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+/// Fake `/proc/asound` tree with the given capture substream status files,
+/// keyed by their path under the root
+pub fn fake_asound(name: &str, substreams: &[(&str, &str)]) -> TempDir {
+    let root = TempDir::new(&format!("asound-{name}"));
+
+    for (path, status) in substreams {
+        let dir = root.join(path);
+        std::fs::create_dir_all(&dir).expect("Failed to create substream dir");
+        std::fs::write(dir.join("status"), status).expect("Failed to write substream status");
+    }
+    root
+}
+
+/// Empty directory under the system temp directory, deleted when dropped
+/// even if the test fails
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Create a directory unique to this test run and `name`
+    pub fn new(name: &str) -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "litra-control-{name}-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).expect("Failed to create temp dir");
+        Self { path }
+    }
+
+    /// Path of the directory itself
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Path of an entry in the directory
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.path.join(name)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
@@ -3,11 +3,22 @@
 //! This module handles all system tray functionality including menu creation,
 //! event handling, and device power control from the tray menu.
 
+use crate::commands::profile_commands::profile_switched;
+use crate::config::{Preset, ProfilesConfig};
+use crate::device::common_preset;
+use crate::undo::ChangeSource;
 use crate::{refresh_devices, AppState, DeviceInfo};
-use tauri::menu::{MenuBuilder, MenuItemBuilder, SubmenuBuilder};
+use std::time::Duration;
+use tauri::menu::{CheckMenuItemBuilder, MenuBuilder, MenuItemBuilder, SubmenuBuilder};
 use tauri::tray::TrayIconBuilder;
 use tauri::{AppHandle, Emitter, Manager, Runtime};
 
+/// Sleep timer durations offered in the tray, in minutes
+const SLEEP_TIMER_MINUTES: [u64; 4] = [15, 30, 60, 120];
+
+/// ID of the tray icon, used to replace its menu
+const TRAY_ID: &str = "main";
+
 /// Initialize the system tray with menu and event handlers
 pub async fn setup_tray<R: Runtime>(app: &AppHandle<R>) -> Result<(), Box<dyn std::error::Error>> {
     // Get device manager to check for devices
@@ -20,11 +31,13 @@ pub async fn setup_tray<R: Runtime>(app: &AppHandle<R>) -> Result<(), Box<dyn st
         .get_all_devices()
         .unwrap_or_default();
 
+    let config = app_state.config_manager.get_config();
+
     // Build the tray menu
-    let menu = build_tray_menu(app, &devices)?;
+    let menu = build_tray_menu(app, &devices, &config.presets.presets, &config.profiles)?;
 
     // Create system tray
-    let _tray = TrayIconBuilder::new()
+    let _tray = TrayIconBuilder::with_id(TRAY_ID)
         .tooltip("Litra Control")
         .menu(&menu)
         .on_menu_event(move |app, event| {
@@ -42,10 +55,38 @@ pub async fn setup_tray<R: Runtime>(app: &AppHandle<R>) -> Result<(), Box<dyn st
     Ok(())
 }
 
+/// Rebuild the tray menu after the devices, presets or profiles changed
+pub async fn refresh_tray_menu<R: Runtime>(
+    app: &AppHandle<R>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return Ok(());
+    };
+
+    let app_state = app.state::<AppState>();
+    let devices = app_state
+        .device_manager
+        .lock()
+        .await
+        .get_all_devices()
+        .unwrap_or_default();
+    let config = app_state.config_manager.get_config();
+
+    tray.set_menu(Some(build_tray_menu(
+        app,
+        &devices,
+        &config.presets.presets,
+        &config.profiles,
+    )?))?;
+    Ok(())
+}
+
 /// Build the tray menu with device-specific options
 fn build_tray_menu<R: Runtime>(
     app: &AppHandle<R>,
     devices: &[DeviceInfo],
+    presets: &[Preset],
+    profiles: &ProfilesConfig,
 ) -> Result<tauri::menu::Menu<R>, Box<dyn std::error::Error>> {
     let show_hide = MenuItemBuilder::with_id("show_hide", "Show/Hide").build(app)?;
     let separator1 = tauri::menu::PredefinedMenuItem::separator(app)?;
@@ -74,6 +115,55 @@ fn build_tray_menu<R: Runtime>(
 
     let power_menu = power_submenu.build()?;
 
+    // Create presets submenu applying a preset to all devices, checking the
+    // one they are all at
+    let active_preset = common_preset(devices);
+    let mut presets_submenu = SubmenuBuilder::new(app, "Presets");
+    for preset in presets {
+        let preset_item =
+            CheckMenuItemBuilder::with_id(format!("preset_{}", preset.id), &preset.name)
+                .checked(active_preset == Some(preset.id.as_str()))
+                .build(app)?;
+        presets_submenu = presets_submenu.item(&preset_item);
+    }
+    let presets_menu = presets_submenu.enabled(!presets.is_empty()).build()?;
+
+    // Create profiles submenu switching profile, checking the active one
+    let mut profiles_submenu = SubmenuBuilder::new(app, "Profiles");
+    for profile in &profiles.profiles {
+        let profile_item =
+            CheckMenuItemBuilder::with_id(format!("profile_{}", profile.id), &profile.name)
+                .checked(profiles.active.as_deref() == Some(profile.id.as_str()))
+                .build(app)?;
+        profiles_submenu = profiles_submenu.item(&profile_item);
+    }
+    let profiles_menu = profiles_submenu
+        .enabled(!profiles.profiles.is_empty())
+        .build()?;
+
+    // Create sleep timer submenu switching all devices off
+    let mut sleep_timer_submenu = SubmenuBuilder::new(app, "Sleep Timer");
+    for minutes in SLEEP_TIMER_MINUTES {
+        let label = if minutes < 60 {
+            format!("Off in {minutes} Minutes")
+        } else {
+            format!(
+                "Off in {} Hour{}",
+                minutes / 60,
+                if minutes == 60 { "" } else { "s" }
+            )
+        };
+        let timer_item =
+            MenuItemBuilder::with_id(format!("sleep_timer_{minutes}"), label).build(app)?;
+        sleep_timer_submenu = sleep_timer_submenu.item(&timer_item);
+    }
+    let cancel_timer =
+        MenuItemBuilder::with_id("sleep_timer_cancel", "Cancel Sleep Timer").build(app)?;
+    let sleep_timer_menu = sleep_timer_submenu
+        .separator()
+        .item(&cancel_timer)
+        .build()?;
+
     let refresh_devices =
         MenuItemBuilder::with_id("refresh_devices", "Refresh Devices").build(app)?;
 
@@ -85,6 +175,9 @@ fn build_tray_menu<R: Runtime>(
             &show_hide,
             &separator1,
             &power_menu,
+            &presets_menu,
+            &profiles_menu,
+            &sleep_timer_menu,
             &refresh_devices,
             &separator2,
             &quit,
@@ -112,6 +205,46 @@ async fn handle_tray_menu_event<R: Runtime>(app: &AppHandle<R>, event_id: &str)
                 .expect("Failed to refresh devices");
             let _ = app.emit("device-refresh", ());
         }
+        "sleep_timer_cancel" => {
+            app.state::<AppState>()
+                .timer_manager
+                .lock()
+                .await
+                .cancel_sleep_timer(None);
+            let _ = app.emit("timers-changed", ());
+        }
+        event_id if event_id.starts_with("sleep_timer_") => {
+            if let Ok(minutes) = event_id["sleep_timer_".len()..].parse::<u64>() {
+                app.state::<AppState>()
+                    .timer_manager
+                    .lock()
+                    .await
+                    .set_sleep_timer(None, Duration::from_secs(minutes * 60));
+                let _ = app.emit("timers-changed", ());
+            }
+        }
+        event_id if event_id.starts_with("preset_") => {
+            let preset_id = event_id.strip_prefix("preset_").unwrap();
+            if let Err(e) = app
+                .state::<AppState>()
+                .apply_preset(preset_id, None, ChangeSource::Tray)
+                .await
+            {
+                eprintln!("Failed to apply preset {preset_id}: {e}");
+            }
+            let _ = app.emit("device-refresh", ());
+        }
+        event_id if event_id.starts_with("profile_") => {
+            let profile_id = event_id.strip_prefix("profile_").unwrap();
+            let result = app
+                .state::<AppState>()
+                .switch_profile(profile_id, ChangeSource::Tray)
+                .await;
+            if let Err(e) = &result {
+                eprintln!("Failed to switch to profile {profile_id}: {e}");
+            }
+            profile_switched(app, result.as_ref().ok()).await;
+        }
         event_id if event_id.starts_with("power_") => {
             let serial_number = event_id.strip_prefix("power_").unwrap();
             toggle_device_power(app, serial_number).await;
@@ -123,29 +256,54 @@ async fn handle_tray_menu_event<R: Runtime>(app: &AppHandle<R>, event_id: &str)
 /// Toggle power for all connected devices
 async fn toggle_all_devices<R: Runtime>(app: &AppHandle<R>) {
     let app_state = app.state::<AppState>();
-    let dm = app_state.device_manager.lock().await;
-    if let Ok(devices) = dm.get_all_devices() {
-        for device in devices {
-            // Get device handle and toggle power
-            if let Ok(handle) = dm.get_device_handle(&device.serial_number) {
-                let _ = handle.set_on(!device.is_on);
+    let before = app_state.device_states(None).await;
+    let mut toggled = Vec::new();
+    {
+        let dm = app_state.device_manager.lock().await;
+        if let Ok(devices) = dm.get_all_devices() {
+            for device in devices {
+                // Get device handle and toggle power
+                if let Ok(handle) = dm.get_device_handle(&device.serial_number) {
+                    if handle.set_on(!device.is_on).is_ok() {
+                        toggled.push(device.serial_number);
+                    }
+                }
             }
         }
     }
 
+    app_state
+        .record_change(ChangeSource::Tray, "Toggle all devices", before)
+        .await;
+    for serial_number in toggled {
+        app_state.notify_manual_change(&serial_number).await;
+    }
+
     let _ = app.emit("device-refresh", ());
 }
 
 /// Toggle power for a specific device
 async fn toggle_device_power<R: Runtime>(app: &AppHandle<R>, serial_number: &str) {
     let app_state = app.state::<AppState>();
-    let dm = app_state.device_manager.lock().await;
+    let before = app_state.device_states(Some(serial_number)).await;
+    let toggled = {
+        let dm = app_state.device_manager.lock().await;
 
-    // Get device handle
-    if let Ok(handle) = dm.get_device_handle(serial_number) {
-        if let Ok(is_on) = handle.is_on() {
-            let _ = handle.set_on(!is_on);
-        }
+        // Get device handle
+        dm.get_device_handle(serial_number)
+            .and_then(|handle| {
+                let is_on = handle.is_on()?;
+                handle.set_on(!is_on)?;
+                Ok(())
+            })
+            .is_ok()
+    };
+
+    if toggled {
+        app_state
+            .record_change(ChangeSource::Tray, "Power", before)
+            .await;
+        app_state.notify_manual_change(serial_number).await;
     }
 
     let _ = app.emit("device-refresh", ());
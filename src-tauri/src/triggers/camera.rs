@@ -0,0 +1,283 @@
+//! Camera trigger source
+//!
+//! Publishes whether a camera session is active, as decided by the camera
+//! monitor with its turn-on delay and turn-off grace period applied.
+//!
+//! Camera auto-toggle is the built-in binding on this signal (see
+//! [`AutoToggleConfig::binding`](crate::config::AutoToggleConfig::binding)),
+//! applying the in-call state while a session is active. The signal only
+//! changes while monitoring is running.
+
+use crate::camera_monitor::events::MonitorEvent;
+use crate::camera_monitor::CameraMonitor;
+use crate::config::CAMERA_SOURCE_ID;
+use crate::triggers::{SignalSender, SignalValue, TriggerResult, TriggerSource};
+use async_trait::async_trait;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::watch;
+
+/// Trigger source following camera sessions
+pub struct CameraTriggerSource {
+    /// Events from the camera monitor
+    events: broadcast::Receiver<MonitorEvent>,
+    /// Whether a session was active when the source was created
+    session_active: bool,
+}
+
+impl CameraTriggerSource {
+    /// Create a camera trigger source following the given monitor.
+    ///
+    /// The signal only changes while the monitor is running.
+    pub fn new(monitor: &CameraMonitor) -> Self {
+        Self {
+            events: monitor.subscribe(),
+            session_active: monitor.is_session_active(),
+        }
+    }
+}
+
+#[async_trait]
+impl TriggerSource for CameraTriggerSource {
+    fn id(&self) -> &str {
+        CAMERA_SOURCE_ID
+    }
+
+    async fn run(
+        mut self: Box<Self>,
+        signals: SignalSender,
+        mut stop: watch::Receiver<bool>,
+    ) -> TriggerResult<()> {
+        signals.send(SignalValue::Bool(self.session_active));
+
+        loop {
+            let event = tokio::select! {
+                event = self.events.recv() => event,
+                _ = stop.changed() => break,
+            };
+
+            let session_active = match event {
+                Ok(MonitorEvent::SessionStarted(_)) => true,
+                Ok(MonitorEvent::SessionEnded(_)) => false,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+
+            if !signals.send(SignalValue::Bool(session_active)) {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera_monitor::source::{CameraEvent, ScriptStep, ScriptedEventSource};
+    use crate::config::{
+        AutoToggleConfig, LightingState, OverridePolicy, AUTO_TOGGLE_BINDING_NAME,
+    };
+    use crate::device::sink::{RecordingDeviceSink, SinkCall};
+    use crate::device::DeviceState;
+    use crate::test_support::{test_monitor, FILL_LIGHT, INITIAL_STATE, KEY_LIGHT};
+    use crate::triggers::engine::{BindingAction, DEVICE_POLL_INTERVAL};
+    use crate::triggers::TriggerEngine;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::time::sleep;
+
+    fn opened(node: &str) -> ScriptStep {
+        ScriptStep::Event(CameraEvent::Opened(node.to_string()))
+    }
+
+    fn closed(node: &str) -> ScriptStep {
+        ScriptStep::Event(CameraEvent::Closed(node.to_string()))
+    }
+
+    fn test_config(on_delay_ms: u64, off_grace_ms: u64) -> AutoToggleConfig {
+        AutoToggleConfig {
+            enabled: true,
+            on_delay_ms,
+            off_grace_ms,
+            in_call_state: LightingState {
+                power_on: Some(true),
+                brightness_percentage: Some(100),
+                temperature_kelvin: Some(5000),
+                preset: None,
+            },
+            ..AutoToggleConfig::default()
+        }
+    }
+
+    /// Start a monitor replaying `source` and an engine running auto-toggle
+    /// on its sessions
+    async fn start_auto_toggle(
+        config: AutoToggleConfig,
+        source: ScriptedEventSource,
+    ) -> (CameraMonitor, TriggerEngine, Arc<RecordingDeviceSink>) {
+        let sink = Arc::new(RecordingDeviceSink::new(&[KEY_LIGHT, FILL_LIGHT]));
+        let mut monitor = test_monitor();
+        let mut engine = TriggerEngine::new(sink.clone());
+        engine
+            .start(
+                vec![config.binding()],
+                Vec::new(),
+                vec![Box::new(CameraTriggerSource::new(&monitor))],
+            )
+            .await;
+        monitor
+            .start_monitoring_with_source(config, Box::new(source))
+            .unwrap();
+        (monitor, engine, sink)
+    }
+
+    /// Number of times the key light was restored
+    fn restorations(sink: &RecordingDeviceSink) -> usize {
+        sink.calls()
+            .iter()
+            .filter(
+                |call| matches!(call, SinkCall::ApplyDeviceState(serial, _) if serial == KEY_LIGHT),
+            )
+            .count()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_session_applies_and_restores_state() {
+        let source =
+            ScriptedEventSource::new(vec![(0, opened("video0")), (5000, closed("video0"))]);
+        let (_monitor, engine, sink) = start_auto_toggle(test_config(1000, 3000), source).await;
+        let mut events = engine.subscribe();
+
+        sleep(Duration::from_millis(2000)).await;
+        let state = sink.device_state(KEY_LIGHT);
+        assert!(state.is_on);
+        assert_eq!(state.temperature_kelvin, 5000);
+        assert_eq!(
+            engine.bound_devices(AUTO_TOGGLE_BINDING_NAME).await,
+            vec![KEY_LIGHT, FILL_LIGHT]
+        );
+
+        sleep(Duration::from_millis(7000)).await;
+        assert_eq!(sink.device_state(KEY_LIGHT), INITIAL_STATE);
+        assert_eq!(sink.device_state(FILL_LIGHT), INITIAL_STATE);
+        assert!(engine
+            .bound_devices(AUTO_TOGGLE_BINDING_NAME)
+            .await
+            .is_empty());
+
+        let actions: Vec<BindingAction> = std::iter::from_fn(|| events.try_recv().ok())
+            .map(|event| {
+                assert_eq!(event.binding, AUTO_TOGGLE_BINDING_NAME);
+                assert_eq!(event.serial_numbers, vec![KEY_LIGHT, FILL_LIGHT]);
+                event.action
+            })
+            .collect();
+        assert_eq!(
+            actions,
+            vec![BindingAction::Applied, BindingAction::Restored]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_overlapping_sessions_restore_once() {
+        let source = ScriptedEventSource::new(vec![
+            (0, opened("video0")),
+            (2000, opened("video2")),
+            (4000, closed("video0")),
+            (10000, closed("video2")),
+        ]);
+        let (_monitor, _engine, sink) = start_auto_toggle(test_config(500, 1000), source).await;
+
+        // First camera closed while the second is still open
+        sleep(Duration::from_millis(8000)).await;
+        assert!(sink.device_state(KEY_LIGHT).is_on);
+        assert_eq!(restorations(&sink), 0);
+
+        sleep(Duration::from_millis(4000)).await;
+        assert_eq!(sink.device_state(KEY_LIGHT), INITIAL_STATE);
+        assert_eq!(restorations(&sink), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stop_while_active_restores_devices() {
+        let source = ScriptedEventSource::new(vec![(0, opened("video0"))]);
+        let (mut monitor, _engine, sink) = start_auto_toggle(test_config(0, 3000), source).await;
+
+        sleep(Duration::from_millis(1000)).await;
+        assert!(sink.device_state(KEY_LIGHT).is_on);
+
+        monitor.stop_monitoring().await.unwrap();
+        sleep(Duration::from_millis(100)).await;
+        assert_eq!(sink.device_state(KEY_LIGHT), INITIAL_STATE);
+        assert_eq!(sink.device_state(FILL_LIGHT), INITIAL_STATE);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_config_update_applies_to_running_session() {
+        let source =
+            ScriptedEventSource::new(vec![(0, opened("video0")), (2000, closed("video0"))]);
+        let (monitor, engine, sink) = start_auto_toggle(test_config(0, 30000), source).await;
+
+        sleep(Duration::from_millis(1000)).await;
+        assert_eq!(sink.device_state(KEY_LIGHT).temperature_kelvin, 5000);
+
+        let mut config = test_config(0, 1000);
+        config.in_call_state.temperature_kelvin = Some(3000);
+        engine.set_bindings(vec![config.binding()]).await;
+        monitor.update_config(config);
+        assert_eq!(sink.device_state(KEY_LIGHT).temperature_kelvin, 3000);
+
+        // The session still ends with the state from before the call
+        sleep(Duration::from_millis(2500)).await;
+        assert_eq!(sink.device_state(KEY_LIGHT), INITIAL_STATE);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_manual_override_is_respected() {
+        let source =
+            ScriptedEventSource::new(vec![(0, opened("video0")), (5000, closed("video0"))]);
+        let (_monitor, engine, sink) = start_auto_toggle(test_config(0, 1000), source).await;
+
+        sleep(Duration::from_millis(1000)).await;
+        let dimmed = DeviceState {
+            brightness_lumens: 20,
+            ..sink.device_state(KEY_LIGHT)
+        };
+        sink.set_device_state(KEY_LIGHT, dimmed);
+        engine.notify_manual_change(KEY_LIGHT).await;
+
+        sleep(Duration::from_millis(7000)).await;
+        assert_eq!(sink.device_state(KEY_LIGHT), dimmed);
+        assert_eq!(sink.device_state(FILL_LIGHT), INITIAL_STATE);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_manual_change_is_reasserted() {
+        let source =
+            ScriptedEventSource::new(vec![(0, opened("video0")), (10000, closed("video0"))]);
+        let config = AutoToggleConfig {
+            override_policy: OverridePolicy::Reassert,
+            ..test_config(0, 1000)
+        };
+        let (_monitor, engine, sink) = start_auto_toggle(config, source).await;
+
+        sleep(Duration::from_millis(1000)).await;
+        let in_call = sink.device_state(KEY_LIGHT);
+        assert!(in_call.is_on);
+
+        // Switched off from the UI while the same camera stays open
+        sink.set_device_state(KEY_LIGHT, INITIAL_STATE);
+        engine.notify_manual_change(KEY_LIGHT).await;
+        assert_eq!(sink.device_state(KEY_LIGHT), in_call);
+
+        // Switched off with the device's own button
+        sink.set_device_state(KEY_LIGHT, INITIAL_STATE);
+        sleep(DEVICE_POLL_INTERVAL + Duration::from_millis(500)).await;
+        assert_eq!(sink.device_state(KEY_LIGHT), in_call);
+
+        // The session still ends with the state from before the call
+        sleep(Duration::from_millis(10000)).await;
+        assert_eq!(sink.device_state(KEY_LIGHT), INITIAL_STATE);
+    }
+}
@@ -0,0 +1,191 @@
+//! Display trigger source
+//!
+//! Publishes whether every connected display is off, from the DRM connector
+//! state in sysfs (`/sys/class/drm/card*-*/status` and `dpms`). The kernel
+//! offers no change notifications for these files, so they are polled.
+
+use crate::config::DISPLAYS_SOURCE_ID;
+use crate::triggers::{SignalSender, SignalValue, TriggerResult, TriggerSource};
+use async_trait::async_trait;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Sysfs directory with the DRM devices and connectors
+pub const DRM_PATH: &str = "/sys/class/drm";
+
+/// Interval between polls of the connector state
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Trigger source following whether the displays are asleep
+pub struct DisplayTriggerSource {
+    /// Root of the DRM sysfs tree
+    root: PathBuf,
+}
+
+impl DisplayTriggerSource {
+    /// Create a display trigger source reading `/sys/class/drm`
+    pub fn new() -> Self {
+        Self::with_root(PathBuf::from(DRM_PATH))
+    }
+
+    /// Create a display trigger source reading the given DRM tree
+    pub fn with_root(root: PathBuf) -> Self {
+        Self { root }
+    }
+}
+
+#[async_trait]
+impl TriggerSource for DisplayTriggerSource {
+    fn id(&self) -> &str {
+        DISPLAYS_SOURCE_ID
+    }
+
+    async fn run(
+        self: Box<Self>,
+        signals: SignalSender,
+        mut stop: watch::Receiver<bool>,
+    ) -> TriggerResult<()> {
+        let mut last_off = None;
+
+        loop {
+            let off = all_displays_off(&self.root);
+            if last_off != Some(off) {
+                if !signals.send(SignalValue::Bool(off)) {
+                    break;
+                }
+                last_off = Some(off);
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                _ = stop.changed() => break,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether there are connected displays and all of them are off.
+///
+/// Without any connected display there is nothing to follow, so the lights
+/// are left alone.
+fn all_displays_off(root: &Path) -> bool {
+    let mut powered = connectors(root)
+        .into_iter()
+        .filter(|connector| read_trimmed(&connector.join("status")).as_deref() == Some("connected"))
+        .map(|connector| read_trimmed(&connector.join("dpms")).as_deref() == Some("On"))
+        .peekable();
+
+    powered.peek().is_some() && powered.all(|on| !on)
+}
+
+/// Connector directories, named `card<N>-<connector>`
+fn connectors(root: &Path) -> Vec<PathBuf> {
+    fs::read_dir(root)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| {
+                    let name = entry.file_name();
+                    let name = name.to_string_lossy();
+                    name.strip_prefix("card")
+                        .and_then(|rest| rest.split_once('-'))
+                        .is_some_and(|(index, _)| {
+                            !index.is_empty() && index.chars().all(|c| c.is_ascii_digit())
+                        })
+                })
+                .map(|entry| entry.path())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Read a sysfs attribute without its trailing newline
+fn read_trimmed(path: &Path) -> Option<String> {
+    fs::read_to_string(path)
+        .ok()
+        .map(|contents| contents.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DisplaySleepConfig;
+    use crate::test_support::{key_light_sink, TempDir, KEY_LIGHT, LIT_STATE};
+    use crate::triggers::TriggerEngine;
+
+    /// Build a fake DRM tree with the given connectors
+    fn fake_drm(name: &str, connectors: &[(&str, &str, &str)]) -> TempDir {
+        let root = TempDir::new(&format!("drm-{name}"));
+        fs::create_dir_all(root.join("card0")).unwrap();
+
+        for (connector, status, dpms) in connectors {
+            set_connector(root.path(), connector, status, dpms);
+        }
+        root
+    }
+
+    fn set_connector(root: &Path, connector: &str, status: &str, dpms: &str) {
+        let dir = root.join(connector);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("status"), format!("{status}\n")).unwrap();
+        fs::write(dir.join("dpms"), format!("{dpms}\n")).unwrap();
+    }
+
+    #[test]
+    fn test_all_displays_off() {
+        let root = fake_drm(
+            "states",
+            &[
+                ("card0-DP-1", "connected", "Off"),
+                ("card0-HDMI-A-1", "disconnected", "On"),
+            ],
+        );
+        assert!(all_displays_off(root.path()));
+
+        set_connector(root.path(), "card0-DP-2", "connected", "On");
+        assert!(!all_displays_off(root.path()));
+
+        set_connector(root.path(), "card0-DP-2", "connected", "Standby");
+        assert!(all_displays_off(root.path()));
+
+        // Nothing connected means nothing to follow
+        set_connector(root.path(), "card0-DP-1", "disconnected", "Off");
+        set_connector(root.path(), "card0-DP-2", "disconnected", "Off");
+        assert!(!all_displays_off(root.path()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_display_sleep_turns_lights_off_and_wake_restores() {
+        let root = fake_drm("binding", &[("card0-DP-1", "connected", "On")]);
+
+        let sink = key_light_sink(&[]);
+        let binding = DisplaySleepConfig {
+            enabled: true,
+            ..DisplaySleepConfig::default()
+        }
+        .binding();
+
+        let mut engine = TriggerEngine::new(sink.clone());
+        let source = DisplayTriggerSource::with_root(root.path().to_path_buf());
+        engine
+            .start(vec![binding], Vec::new(), vec![Box::new(source)])
+            .await;
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(sink.device_state(KEY_LIGHT), LIT_STATE);
+
+        set_connector(root.path(), "card0-DP-1", "connected", "Off");
+        tokio::time::sleep(POLL_INTERVAL).await;
+        assert!(!sink.device_state(KEY_LIGHT).is_on);
+
+        set_connector(root.path(), "card0-DP-1", "connected", "On");
+        tokio::time::sleep(POLL_INTERVAL).await;
+        assert_eq!(sink.device_state(KEY_LIGHT), LIT_STATE);
+
+        engine.stop().await;
+    }
+}
@@ -0,0 +1,1020 @@
+//! Trigger engine
+//!
+//! Runs the trigger sources, keeps the latest value of every signal and
+//! applies the configured bindings when their conditions start or stop
+//! holding. Devices a binding changed are checked every
+//! [`DEVICE_POLL_INTERVAL`] for changes made outside the app, which are
+//! treated like changes by hand under the binding's override policy.
+//! Rules are evaluated after the bindings, whenever a signal changes and at
+//! least every [`RULE_CHECK_INTERVAL`] for their time conditions.
+
+use crate::camera_monitor::strategies::create_device_set_selector;
+use crate::config::{LightingState, OverridePolicy, Preset, Rule, TriggerBinding};
+use crate::device::sink::DeviceSink;
+use crate::device::DeviceState;
+use crate::task::BackgroundTask;
+use crate::triggers::rules::{DeviceRulesExplanation, RuleContext};
+use crate::triggers::{Signal, SignalSender, SignalValue, TriggerResult, TriggerSource};
+use chrono::Local;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, Mutex};
+
+/// How often rules are evaluated without a signal changing
+pub const RULE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Interval between checking devices changed by bindings for changes made
+/// outside the app, such as with the device's own button
+pub const DEVICE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Number of events buffered for slow subscribers
+const EVENT_CHANNEL_CAPACITY: usize = 32;
+
+/// Device states to restore, by serial number
+pub type Snapshots = Vec<(String, DeviceState)>;
+
+/// What a binding did to devices
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindingAction {
+    /// The binding's action was applied
+    Applied,
+    /// The devices were restored to their state from before
+    Restored,
+}
+
+/// Devices changed by a binding in one step
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BindingEvent {
+    /// Name of the binding
+    pub binding: String,
+    /// What was done to the devices
+    pub action: BindingAction,
+    /// Serial numbers of the changed devices
+    pub serial_numbers: Vec<String>,
+}
+
+/// A device changed by an applied binding
+#[derive(Debug, Clone)]
+struct BoundDevice {
+    /// Serial number of the device
+    serial_number: String,
+    /// State to restore when the binding is released
+    snapshot: DeviceState,
+}
+
+/// Bindings and signal values shared with the engine task
+struct EngineState {
+    /// Configured bindings
+    bindings: Vec<TriggerBinding>,
+    /// Devices held by each binding currently applied, by binding index
+    applied: Vec<Option<Vec<BoundDevice>>>,
+    /// State the engine last left each bound device in, to tell changes
+    /// made outside the app from its own
+    expected: BTreeMap<String, DeviceState>,
+    /// Latest value of every signal
+    values: BTreeMap<String, SignalValue>,
+    /// Configured rules
+    rules: Vec<Rule>,
+    /// Presets rule actions may refer to
+    presets: Vec<Preset>,
+    /// Devices controlled by rules, by serial number
+    ruled: BTreeMap<String, RuledDevice>,
+    /// Channel for publishing binding events
+    events: broadcast::Sender<BindingEvent>,
+}
+
+/// A device controlled by rules
+struct RuledDevice {
+    /// State of the device before the first rule applied
+    snapshot: DeviceState,
+    /// Settings the rules applied
+    state: LightingState,
+}
+
+/// Engine applying trigger bindings
+pub struct TriggerEngine {
+    /// Sink used to read and change devices
+    sink: Arc<dyn DeviceSink>,
+    /// State shared with the engine task
+    state: Arc<Mutex<EngineState>>,
+    /// Channel for publishing binding events
+    events: broadcast::Sender<BindingEvent>,
+    /// Tasks running the engine and the sources, while running
+    task: Option<BackgroundTask>,
+}
+
+impl TriggerEngine {
+    /// Create a new, stopped trigger engine
+    pub fn new(sink: Arc<dyn DeviceSink>) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+
+        Self {
+            sink,
+            state: Arc::new(Mutex::new(EngineState::new(events.clone()))),
+            events,
+            task: None,
+        }
+    }
+
+    /// Start the engine with the given bindings, rules and sources
+    pub async fn start(
+        &mut self,
+        bindings: Vec<TriggerBinding>,
+        rules: Vec<Rule>,
+        sources: Vec<Box<dyn TriggerSource>>,
+    ) {
+        if self.is_running() {
+            return;
+        }
+
+        {
+            let mut state = self.state.lock().await;
+            state.values.clear();
+            state.rules = rules;
+            state.set_bindings(bindings, &*self.sink).await;
+        }
+
+        let (signal_tx, mut signal_rx) = mpsc::unbounded_channel::<Signal>();
+
+        let state = self.state.clone();
+        let sink = self.sink.clone();
+        let mut task = BackgroundTask::spawn(move |mut stop_rx| async move {
+            let mut rule_checks = tokio::time::interval_at(
+                tokio::time::Instant::now() + RULE_CHECK_INTERVAL,
+                RULE_CHECK_INTERVAL,
+            );
+            let mut device_checks = tokio::time::interval_at(
+                tokio::time::Instant::now() + DEVICE_POLL_INTERVAL,
+                DEVICE_POLL_INTERVAL,
+            );
+            loop {
+                let signal = tokio::select! {
+                    signal = signal_rx.recv() => signal,
+                    _ = rule_checks.tick() => {
+                        state.lock().await.evaluate_rules(&*sink).await;
+                        continue;
+                    }
+                    _ = device_checks.tick() => {
+                        state.lock().await.detect_external_changes(&*sink).await;
+                        continue;
+                    }
+                    _ = stop_rx.changed() => break,
+                };
+                let Some(signal) = signal else {
+                    break;
+                };
+
+                state.lock().await.handle_signal(signal, &*sink).await;
+            }
+        });
+
+        for source in sources {
+            let id = source.id().to_string();
+            let signals = SignalSender::new(&id, signal_tx.clone());
+            println!("Starting trigger source: {id}");
+            task.spawn_alongside(move |stop_rx| async move {
+                if let Err(e) = source.run(signals, stop_rx).await {
+                    eprintln!("Trigger source {id} failed: {e}");
+                }
+            });
+        }
+        drop(signal_tx);
+
+        self.task = Some(task);
+    }
+
+    /// Stop the engine, restoring devices of bindings still applied and
+    /// those controlled by rules
+    pub async fn stop(&mut self) {
+        let Some(task) = self.task.take() else {
+            return;
+        };
+
+        task.stop().await;
+
+        let mut state = self.state.lock().await;
+        state.release_rules(&*self.sink).await;
+        state.release_all(&*self.sink).await;
+    }
+
+    /// Check if the engine is running
+    pub fn is_running(&self) -> bool {
+        self.task.is_some()
+    }
+
+    /// Replace the bindings.
+    ///
+    /// Applied bindings that are unchanged stay applied. One changed while
+    /// applied keeps the states to restore if its condition still holds,
+    /// with its new action applied; the others are released. The new
+    /// bindings are then evaluated against the latest signal values.
+    pub async fn set_bindings(&self, bindings: Vec<TriggerBinding>) {
+        self.state
+            .lock()
+            .await
+            .set_bindings(bindings, &*self.sink)
+            .await;
+    }
+
+    /// Get the latest value of every signal
+    pub async fn get_signals(&self) -> BTreeMap<String, SignalValue> {
+        self.state.lock().await.values.clone()
+    }
+
+    /// Check if a source's signal is currently `true`
+    pub async fn signal_is_true(&self, source: &str) -> bool {
+        self.state.lock().await.values.get(source) == Some(&SignalValue::Bool(true))
+    }
+
+    /// Serial numbers of the devices an applied binding holds, by its name
+    pub async fn bound_devices(&self, name: &str) -> Vec<String> {
+        let state = self.state.lock().await;
+        state
+            .bindings
+            .iter()
+            .zip(&state.applied)
+            .filter(|(binding, _)| binding.name == name)
+            .filter_map(|(_, applied)| applied.as_ref())
+            .flatten()
+            .map(|device| device.serial_number.clone())
+            .collect()
+    }
+
+    /// Subscribe to the changes bindings make to devices
+    pub fn subscribe(&self) -> broadcast::Receiver<BindingEvent> {
+        self.events.subscribe()
+    }
+
+    /// Report a change the user made to a device.
+    ///
+    /// Applied bindings holding the device treat it according to their
+    /// override policy.
+    pub async fn notify_manual_change(&self, serial_number: &str) {
+        self.state
+            .lock()
+            .await
+            .handle_manual_change(serial_number, &*self.sink)
+            .await;
+    }
+
+    /// Have the applied binding on a source's signal put devices back to
+    /// the given states when it is released.
+    ///
+    /// Used for devices changed before the binding applied by something
+    /// that lets go of them while it holds, such as lights switched on ahead
+    /// of a meeting that runs into a camera session, so the binding restores
+    /// the state from before that change rather than the one it found.
+    /// Gives the states back if no binding restoring its devices is applied
+    /// on the signal.
+    pub async fn restore_on_release(
+        &self,
+        source: &str,
+        snapshots: Snapshots,
+    ) -> Result<(), Snapshots> {
+        self.state.lock().await.adopt_snapshots(source, snapshots)
+    }
+
+    /// Replace the rules.
+    ///
+    /// Devices are moved to the settings of the new rules straight away, and
+    /// those no rule controls any more are restored.
+    pub async fn set_rules(&self, rules: Vec<Rule>) {
+        let mut state = self.state.lock().await;
+        state.rules = rules;
+        state.evaluate_rules(&*self.sink).await;
+    }
+
+    /// Replace the presets rule actions can refer to.
+    ///
+    /// Devices are moved to the settings the rules now resolve to.
+    pub async fn set_presets(&self, presets: Vec<Preset>) {
+        let mut state = self.state.lock().await;
+        state.presets = presets;
+        state.evaluate_rules(&*self.sink).await;
+    }
+
+    /// Explain which rules control every device and why
+    pub async fn explain_rules(&self) -> TriggerResult<Vec<DeviceRulesExplanation>> {
+        let state = self.state.lock().await;
+        let devices = self.sink.get_all_devices().await?;
+        let context = RuleContext {
+            now: Local::now().naive_local(),
+            signals: &state.values,
+            devices: &devices,
+            presets: &state.presets,
+        };
+        Ok(context.explain_rules(&state.rules))
+    }
+}
+
+impl EngineState {
+    /// Create an empty state publishing binding events on `events`
+    fn new(events: broadcast::Sender<BindingEvent>) -> Self {
+        Self {
+            bindings: Vec::new(),
+            applied: Vec::new(),
+            expected: BTreeMap::new(),
+            values: BTreeMap::new(),
+            rules: Vec::new(),
+            presets: Vec::new(),
+            ruled: BTreeMap::new(),
+            events,
+        }
+    }
+
+    /// Record a new signal value and apply the bindings it affects
+    async fn handle_signal(&mut self, signal: Signal, sink: &dyn DeviceSink) {
+        if self.values.get(&signal.source) == Some(&signal.value) {
+            return;
+        }
+
+        println!("Trigger signal {}: {:?}", signal.source, signal.value);
+        self.values.insert(signal.source, signal.value);
+        self.evaluate(sink).await;
+        self.evaluate_rules(sink).await;
+    }
+
+    /// Restore every device controlled by rules
+    async fn release_rules(&mut self, sink: &dyn DeviceSink) {
+        for (serial_number, ruled) in std::mem::take(&mut self.ruled) {
+            if let Err(e) = sink
+                .apply_device_state(&serial_number, &ruled.snapshot)
+                .await
+            {
+                eprintln!("Failed to restore state of {serial_number}: {e}");
+                continue;
+            }
+            self.expected.insert(serial_number, ruled.snapshot);
+        }
+    }
+
+    /// Move every device to the settings the rules resolve to, restoring
+    /// devices no rule controls any more
+    async fn evaluate_rules(&mut self, sink: &dyn DeviceSink) {
+        if self.rules.is_empty() && self.ruled.is_empty() {
+            return;
+        }
+
+        let devices = match sink.get_all_devices().await {
+            Ok(devices) => devices,
+            Err(e) => {
+                eprintln!("Failed to get devices for rules: {e}");
+                return;
+            }
+        };
+        let resolved = RuleContext {
+            now: Local::now().naive_local(),
+            signals: &self.values,
+            devices: &devices,
+            presets: &self.presets,
+        }
+        .resolve(&self.rules);
+
+        let released: Vec<String> = self
+            .ruled
+            .keys()
+            .filter(|serial_number| !resolved.contains_key(*serial_number))
+            .cloned()
+            .collect();
+        for serial_number in released {
+            if let Some(ruled) = self.ruled.remove(&serial_number) {
+                println!("Rules released {serial_number}");
+                if let Err(e) = sink
+                    .apply_device_state(&serial_number, &ruled.snapshot)
+                    .await
+                {
+                    eprintln!("Failed to restore state of {serial_number}: {e}");
+                    continue;
+                }
+                self.expected.insert(serial_number, ruled.snapshot);
+            }
+        }
+
+        for (serial_number, state) in resolved {
+            let Some(device) = devices.iter().find(|d| d.serial_number == serial_number) else {
+                continue;
+            };
+            let snapshot = match self.ruled.get(&serial_number) {
+                Some(ruled) if ruled.state == state => continue,
+                Some(ruled) => ruled.snapshot,
+                None => device.state(),
+            };
+
+            println!("Rules apply {state:?} to {serial_number}");
+            let target = device.state_with(&snapshot, &state);
+            match sink.apply_device_state(&serial_number, &target).await {
+                Ok(()) => {
+                    self.expected.insert(serial_number.clone(), target);
+                    self.ruled
+                        .insert(serial_number, RuledDevice { snapshot, state });
+                }
+                Err(e) => eprintln!("Failed to apply rules to {serial_number}: {e}"),
+            }
+        }
+    }
+
+    /// Switch to new bindings, releasing applied ones that changed
+    async fn set_bindings(&mut self, bindings: Vec<TriggerBinding>, sink: &dyn DeviceSink) {
+        let old_bindings = std::mem::take(&mut self.bindings);
+        let old_applied = std::mem::take(&mut self.applied);
+        let mut applied: Vec<Option<Vec<BoundDevice>>> = bindings.iter().map(|_| None).collect();
+
+        for (binding, devices) in old_bindings.iter().zip(old_applied).rev() {
+            let Some(devices) = devices else {
+                continue;
+            };
+            let unclaimed = |index: usize| applied[index].is_none();
+            let same =
+                (0..bindings.len()).find(|&index| unclaimed(index) && bindings[index] == *binding);
+            let renewed = same.or_else(|| {
+                (0..bindings.len()).find(|&index| {
+                    unclaimed(index)
+                        && bindings[index].name == binding.name
+                        && self.holds(&bindings[index])
+                })
+            });
+
+            match renewed {
+                Some(index) if Some(index) == same => applied[index] = Some(devices),
+                Some(index) => {
+                    let devices = self.reapply_binding(&bindings[index], devices, sink).await;
+                    applied[index] = Some(devices);
+                }
+                None => self.release_binding(binding, devices, sink).await,
+            }
+        }
+
+        self.applied = applied;
+        self.bindings = bindings;
+        self.evaluate(sink).await;
+    }
+
+    /// Whether a binding is enabled and its condition holds
+    fn holds(&self, binding: &TriggerBinding) -> bool {
+        binding.enabled
+            && self
+                .values
+                .get(&binding.source)
+                .is_some_and(|value| binding.condition.matches(value))
+    }
+
+    /// Apply bindings whose condition started holding and release those whose
+    /// condition stopped holding.
+    ///
+    /// Releases happen first and in reverse order, so devices changed by
+    /// stacked bindings end up in the state from before the first of them.
+    async fn evaluate(&mut self, sink: &dyn DeviceSink) {
+        let holds: Vec<bool> = self
+            .bindings
+            .iter()
+            .map(|binding| self.holds(binding))
+            .collect();
+
+        for index in (0..self.bindings.len()).rev() {
+            if !holds[index] {
+                if let Some(devices) = self.applied[index].take() {
+                    let binding = self.bindings[index].clone();
+                    self.release_binding(&binding, devices, sink).await;
+                }
+            }
+        }
+
+        for index in 0..self.bindings.len() {
+            if holds[index] && self.applied[index].is_none() {
+                let binding = self.bindings[index].clone();
+                let devices = self.apply_binding(&binding, sink).await;
+                self.applied[index] = Some(devices);
+            }
+        }
+    }
+
+    /// Release every binding currently applied, in reverse order
+    async fn release_all(&mut self, sink: &dyn DeviceSink) {
+        for index in (0..self.bindings.len()).rev() {
+            if let Some(devices) = self.applied[index].take() {
+                let binding = self.bindings[index].clone();
+                self.release_binding(&binding, devices, sink).await;
+            }
+        }
+    }
+
+    /// Apply a binding's action, returning the devices it changed
+    async fn apply_binding(
+        &mut self,
+        binding: &TriggerBinding,
+        sink: &dyn DeviceSink,
+    ) -> Vec<BoundDevice> {
+        self.reapply_binding(binding, Vec::new(), sink).await
+    }
+
+    /// Apply a binding's action to the devices it selects now.
+    ///
+    /// Devices already in `devices` keep the state to restore, those no
+    /// longer selected are restored. Returns the devices the binding holds.
+    async fn reapply_binding(
+        &mut self,
+        binding: &TriggerBinding,
+        devices: Vec<BoundDevice>,
+        sink: &dyn DeviceSink,
+    ) -> Vec<BoundDevice> {
+        println!("Applying trigger binding: {}", binding.name);
+
+        let connected = match sink.get_all_devices().await {
+            Ok(connected) => connected,
+            Err(e) => {
+                eprintln!("Failed to get devices for {}: {e}", binding.name);
+                return devices;
+            }
+        };
+        let selector = create_device_set_selector(&binding.devices);
+
+        let (mut held, deselected): (Vec<BoundDevice>, Vec<BoundDevice>) =
+            devices.into_iter().partition(|device| {
+                connected.iter().any(|info| {
+                    info.serial_number == device.serial_number
+                        && selector.should_control_device(info)
+                })
+            });
+        if !deselected.is_empty() {
+            self.release_binding(binding, deselected, sink).await;
+        }
+
+        let mut changed = Vec::new();
+        for device in connected {
+            if !selector.should_control_device(&device) {
+                continue;
+            }
+
+            if let Err(e) = sink
+                .apply_lighting_state(&device.serial_number, &binding.action)
+                .await
+            {
+                eprintln!(
+                    "Failed to apply {} to {}: {e}",
+                    binding.name, device.serial_number
+                );
+                continue;
+            }
+
+            self.note_applied(&device.serial_number, sink).await;
+            if !held
+                .iter()
+                .any(|held| held.serial_number == device.serial_number)
+            {
+                held.push(BoundDevice {
+                    serial_number: device.serial_number.clone(),
+                    snapshot: device.state(),
+                });
+            }
+            changed.push(device.serial_number);
+        }
+
+        publish(&self.events, binding, BindingAction::Applied, changed);
+        held
+    }
+
+    /// Release a binding, restoring its devices if configured to
+    async fn release_binding(
+        &mut self,
+        binding: &TriggerBinding,
+        devices: Vec<BoundDevice>,
+        sink: &dyn DeviceSink,
+    ) {
+        println!("Releasing trigger binding: {}", binding.name);
+
+        if !binding.restore_on_release {
+            return;
+        }
+
+        let mut restored = Vec::new();
+        for device in devices {
+            if let Err(e) = sink
+                .apply_device_state(&device.serial_number, &device.snapshot)
+                .await
+            {
+                eprintln!("Failed to restore state of {}: {e}", device.serial_number);
+                continue;
+            }
+            self.expected
+                .insert(device.serial_number.clone(), device.snapshot);
+            restored.push(device.serial_number);
+        }
+
+        publish(&self.events, binding, BindingAction::Restored, restored);
+    }
+
+    /// Remember the state a device is in after the engine changed it
+    async fn note_applied(&mut self, serial_number: &str, sink: &dyn DeviceSink) {
+        match sink.get_device_info(serial_number).await {
+            Ok(info) => {
+                self.expected
+                    .insert(serial_number.to_string(), info.state());
+            }
+            Err(_) => {
+                self.expected.remove(serial_number);
+            }
+        }
+    }
+
+    /// Apply the override policy of the bindings holding a device the user
+    /// changed by hand
+    async fn handle_manual_change(&mut self, serial_number: &str, sink: &dyn DeviceSink) {
+        for index in 0..self.bindings.len() {
+            let Some(devices) = &mut self.applied[index] else {
+                continue;
+            };
+            if !devices
+                .iter()
+                .any(|device| device.serial_number == serial_number)
+            {
+                continue;
+            }
+
+            let binding = self.bindings[index].clone();
+            match binding.override_policy {
+                OverridePolicy::RespectOverride => {
+                    // Leave the device as the user set it from now on
+                    devices.retain(|device| device.serial_number != serial_number);
+                    println!(
+                        "Device {serial_number} manually overridden, released from {}",
+                        binding.name
+                    );
+                }
+                OverridePolicy::Reassert => {
+                    println!(
+                        "Device {serial_number} changed manually, reasserting {}",
+                        binding.name
+                    );
+                    if let Err(e) = sink
+                        .apply_lighting_state(serial_number, &binding.action)
+                        .await
+                    {
+                        eprintln!(
+                            "Failed to reassert {} on {serial_number}: {e}",
+                            binding.name
+                        );
+                        continue;
+                    }
+                    self.note_applied(serial_number, sink).await;
+                    publish(
+                        &self.events,
+                        &binding,
+                        BindingAction::Applied,
+                        vec![serial_number.to_string()],
+                    );
+                }
+            }
+        }
+    }
+
+    /// Check the devices bindings hold for changes made outside the app
+    async fn detect_external_changes(&mut self, sink: &dyn DeviceSink) {
+        let bound: BTreeSet<String> = self
+            .applied
+            .iter()
+            .flatten()
+            .flatten()
+            .map(|device| device.serial_number.clone())
+            .collect();
+        self.expected
+            .retain(|serial_number, _| bound.contains(serial_number));
+
+        let mut changed = Vec::new();
+        for serial_number in bound {
+            let Ok(info) = sink.get_device_info(&serial_number).await else {
+                continue;
+            };
+            // Only report each change once
+            match self.expected.insert(serial_number.clone(), info.state()) {
+                Some(expected) if expected != info.state() => changed.push(serial_number),
+                _ => {}
+            }
+        }
+
+        for serial_number in changed {
+            self.handle_manual_change(&serial_number, sink).await;
+        }
+    }
+
+    /// Have the first applied binding on `source` that restores its devices
+    /// put them back to `snapshots` when released
+    fn adopt_snapshots(&mut self, source: &str, snapshots: Snapshots) -> Result<(), Snapshots> {
+        let Some(devices) = self
+            .bindings
+            .iter()
+            .zip(self.applied.iter_mut())
+            .find(|(binding, applied)| {
+                binding.source == source && binding.restore_on_release && applied.is_some()
+            })
+            .and_then(|(_, applied)| applied.as_mut())
+        else {
+            return Err(snapshots);
+        };
+
+        for (serial_number, snapshot) in snapshots {
+            match devices
+                .iter_mut()
+                .find(|device| device.serial_number == serial_number)
+            {
+                Some(device) => device.snapshot = snapshot,
+                None => devices.push(BoundDevice {
+                    serial_number,
+                    snapshot,
+                }),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Publish the devices a binding just changed, if any
+fn publish(
+    events: &broadcast::Sender<BindingEvent>,
+    binding: &TriggerBinding,
+    action: BindingAction,
+    serial_numbers: Vec<String>,
+) {
+    if serial_numbers.is_empty() {
+        return;
+    }
+
+    let _ = events.send(BindingEvent {
+        binding: binding.name.clone(),
+        action,
+        serial_numbers,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{DeviceSet, RuleCondition, SignalCondition};
+    use crate::device::sink::RecordingDeviceSink;
+    use crate::test_support::{FILL_LIGHT, INITIAL_STATE, KEY_LIGHT};
+    use async_trait::async_trait;
+    use tokio::sync::watch;
+    use tokio::time::sleep;
+
+    /// Trigger source publishing a timed script of values
+    struct ScriptedSource {
+        id: &'static str,
+        script: Vec<(u64, SignalValue)>,
+    }
+
+    #[async_trait]
+    impl TriggerSource for ScriptedSource {
+        fn id(&self) -> &str {
+            self.id
+        }
+
+        async fn run(
+            self: Box<Self>,
+            signals: SignalSender,
+            _stop: watch::Receiver<bool>,
+        ) -> TriggerResult<()> {
+            let mut elapsed = 0;
+            for (at_ms, value) in self.script {
+                sleep(Duration::from_millis(at_ms - elapsed)).await;
+                elapsed = at_ms;
+                signals.send(value);
+            }
+            Ok(())
+        }
+    }
+
+    fn binding(source: &str, condition: SignalCondition, devices: DeviceSet) -> TriggerBinding {
+        TriggerBinding {
+            name: format!("{source} binding"),
+            enabled: true,
+            source: source.to_string(),
+            condition,
+            devices,
+            action: LightingState {
+                power_on: Some(true),
+                temperature_kelvin: Some(6000),
+                ..LightingState::default()
+            },
+            restore_on_release: true,
+            override_policy: OverridePolicy::RespectOverride,
+        }
+    }
+
+    async fn start_engine(
+        bindings: Vec<TriggerBinding>,
+        sources: Vec<ScriptedSource>,
+    ) -> (TriggerEngine, Arc<RecordingDeviceSink>) {
+        let sink = Arc::new(RecordingDeviceSink::new(&[KEY_LIGHT, FILL_LIGHT]));
+        let mut engine = TriggerEngine::new(sink.clone());
+        engine
+            .start(
+                bindings,
+                Vec::new(),
+                sources
+                    .into_iter()
+                    .map(|source| Box::new(source) as Box<dyn TriggerSource>)
+                    .collect(),
+            )
+            .await;
+        (engine, sink)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_boolean_binding_applies_and_restores() {
+        let source = ScriptedSource {
+            id: "camera",
+            script: vec![
+                (0, SignalValue::Bool(false)),
+                (1000, SignalValue::Bool(true)),
+                (3000, SignalValue::Bool(false)),
+            ],
+        };
+        let (engine, sink) = start_engine(
+            vec![binding(
+                "camera",
+                SignalCondition::IsTrue,
+                DeviceSet::AllDevices,
+            )],
+            vec![source],
+        )
+        .await;
+
+        sleep(Duration::from_millis(2000)).await;
+        assert!(sink.device_state(KEY_LIGHT).is_on);
+        assert!(sink.device_state(FILL_LIGHT).is_on);
+        assert_eq!(
+            engine.get_signals().await.get("camera"),
+            Some(&SignalValue::Bool(true))
+        );
+
+        sleep(Duration::from_millis(2000)).await;
+        assert_eq!(sink.device_state(KEY_LIGHT), INITIAL_STATE);
+        assert_eq!(sink.device_state(FILL_LIGHT), INITIAL_STATE);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_valued_binding_on_device_set() {
+        let source = ScriptedSource {
+            id: "idle",
+            script: vec![
+                (0, SignalValue::Number(10.0)),
+                (1000, SignalValue::Number(400.0)),
+            ],
+        };
+        let devices = DeviceSet::Devices {
+            serial_numbers: vec![FILL_LIGHT.to_string()],
+        };
+        let (_engine, sink) = start_engine(
+            vec![binding(
+                "idle",
+                SignalCondition::Above { threshold: 300.0 },
+                devices,
+            )],
+            vec![source],
+        )
+        .await;
+
+        sleep(Duration::from_millis(500)).await;
+        assert!(!sink.device_state(FILL_LIGHT).is_on);
+
+        sleep(Duration::from_millis(1000)).await;
+        assert!(sink.device_state(FILL_LIGHT).is_on);
+        assert_eq!(sink.device_state(KEY_LIGHT), INITIAL_STATE);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_removing_or_stopping_releases_bindings() {
+        let source = ScriptedSource {
+            id: "camera",
+            script: vec![(0, SignalValue::Bool(true))],
+        };
+        let camera_binding = binding("camera", SignalCondition::IsTrue, DeviceSet::AllDevices);
+        let (mut engine, sink) = start_engine(vec![camera_binding.clone()], vec![source]).await;
+
+        sleep(Duration::from_millis(100)).await;
+        assert!(sink.device_state(KEY_LIGHT).is_on);
+
+        engine.set_bindings(Vec::new()).await;
+        assert_eq!(sink.device_state(KEY_LIGHT), INITIAL_STATE);
+
+        engine.set_bindings(vec![camera_binding]).await;
+        assert!(sink.device_state(KEY_LIGHT).is_on);
+
+        engine.stop().await;
+        assert!(!engine.is_running());
+        assert_eq!(sink.device_state(KEY_LIGHT), INITIAL_STATE);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_disabled_binding_is_ignored() {
+        let source = ScriptedSource {
+            id: "camera",
+            script: vec![(0, SignalValue::Bool(true))],
+        };
+        let mut disabled = binding("camera", SignalCondition::IsTrue, DeviceSet::AllDevices);
+        disabled.enabled = false;
+        let (_engine, sink) = start_engine(vec![disabled], vec![source]).await;
+
+        sleep(Duration::from_millis(100)).await;
+        assert_eq!(sink.device_state(KEY_LIGHT), INITIAL_STATE);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stacked_bindings_restore_original_state() {
+        let source = ScriptedSource {
+            id: "idle",
+            script: vec![
+                (0, SignalValue::Number(400.0)),
+                (1000, SignalValue::Number(1000.0)),
+                (2000, SignalValue::Number(0.0)),
+            ],
+        };
+        let dim = binding(
+            "idle",
+            SignalCondition::Above { threshold: 300.0 },
+            DeviceSet::AllDevices,
+        );
+        let mut off = binding(
+            "idle",
+            SignalCondition::Above { threshold: 900.0 },
+            DeviceSet::AllDevices,
+        );
+        off.action = LightingState {
+            power_on: Some(false),
+            ..LightingState::default()
+        };
+        let (_engine, sink) = start_engine(vec![dim, off], vec![source]).await;
+
+        sleep(Duration::from_millis(1500)).await;
+        assert!(!sink.device_state(KEY_LIGHT).is_on);
+        assert_eq!(sink.device_state(KEY_LIGHT).temperature_kelvin, 6000);
+
+        // Both release at once and the first binding's snapshot wins
+        sleep(Duration::from_millis(1000)).await;
+        assert_eq!(sink.device_state(KEY_LIGHT), INITIAL_STATE);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rules_apply_and_restore() {
+        let source = ScriptedSource {
+            id: "camera",
+            script: vec![
+                (0, SignalValue::Bool(true)),
+                (1000, SignalValue::Bool(false)),
+            ],
+        };
+        let (engine, sink) = start_engine(Vec::new(), vec![source]).await;
+        let camera_rule = Rule {
+            name: "Camera".to_string(),
+            enabled: true,
+            priority: 0,
+            condition: RuleCondition::Signal {
+                source: "camera".to_string(),
+                condition: SignalCondition::IsTrue,
+            },
+            devices: DeviceSet::AllDevices,
+            action: LightingState {
+                power_on: Some(true),
+                temperature_kelvin: Some(3000),
+                ..LightingState::default()
+            },
+        };
+        let dim_key = Rule {
+            name: "Dim key".to_string(),
+            priority: 5,
+            condition: RuleCondition::All(Vec::new()),
+            devices: DeviceSet::Devices {
+                serial_numbers: vec![KEY_LIGHT.to_string()],
+            },
+            action: LightingState {
+                brightness_percentage: Some(0),
+                temperature_kelvin: Some(6500),
+                ..LightingState::default()
+            },
+            ..camera_rule.clone()
+        };
+
+        sleep(Duration::from_millis(100)).await;
+        engine.set_rules(vec![camera_rule, dim_key]).await;
+        let key = sink.device_state(KEY_LIGHT);
+        assert!(key.is_on);
+        assert_eq!(key.temperature_kelvin, 6500);
+        assert_eq!(key.brightness_lumens, 20);
+        assert_eq!(sink.device_state(FILL_LIGHT).temperature_kelvin, 3000);
+
+        // The camera rule releases, the dimming rule still holds
+        sleep(Duration::from_millis(1000)).await;
+        assert_eq!(sink.device_state(FILL_LIGHT), INITIAL_STATE);
+        assert_eq!(
+            sink.device_state(KEY_LIGHT),
+            DeviceState {
+                brightness_lumens: 20,
+                temperature_kelvin: 6500,
+                ..INITIAL_STATE
+            }
+        );
+
+        engine.set_rules(Vec::new()).await;
+        assert_eq!(sink.device_state(KEY_LIGHT), INITIAL_STATE);
+    }
+}
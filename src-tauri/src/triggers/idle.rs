@@ -0,0 +1,208 @@
+//! Idle trigger source
+//!
+//! Publishes how many seconds the user's session has been idle, following
+//! logind's `IdleHint` and `IdleSinceHint`. While a camera session is active
+//! the user is in a call rather than away, so the source reports no idle
+//! time and idle bindings stand down.
+
+use crate::camera_monitor::events::MonitorEvent;
+use crate::camera_monitor::CameraMonitor;
+use crate::config::IDLE_SOURCE_ID;
+use crate::logind::{self, SessionProxy};
+use crate::triggers::{SignalSender, SignalValue, TriggerResult, TriggerSource};
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::watch;
+use zbus::Connection;
+
+/// Interval between updates of the idle time while idle
+const UPDATE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Trigger source following the session idle time
+pub struct IdleTriggerSource {
+    /// Bus logind is reached on
+    connection: Connection,
+    /// Events from the camera monitor
+    camera_events: broadcast::Receiver<MonitorEvent>,
+    /// Whether a camera session is active
+    camera_active: bool,
+}
+
+impl IdleTriggerSource {
+    /// Create an idle trigger source talking to logind on the given bus and
+    /// standing down during sessions of the given camera monitor
+    pub fn new(connection: Connection, monitor: &CameraMonitor) -> Self {
+        Self {
+            connection,
+            camera_events: monitor.subscribe(),
+            camera_active: monitor.is_session_active(),
+        }
+    }
+}
+
+#[async_trait]
+impl TriggerSource for IdleTriggerSource {
+    fn id(&self) -> &str {
+        IDLE_SOURCE_ID
+    }
+
+    async fn run(
+        mut self: Box<Self>,
+        signals: SignalSender,
+        mut stop: watch::Receiver<bool>,
+    ) -> TriggerResult<()> {
+        let session = logind::current_session(&self.connection).await?;
+        let mut hints = session.receive_idle_hint_changed().await;
+        let mut since_hints = session.receive_idle_since_hint_changed().await;
+        let mut updates = tokio::time::interval(UPDATE_INTERVAL);
+
+        loop {
+            let idle_seconds = if self.camera_active {
+                0.0
+            } else {
+                idle_seconds(&session).await?
+            };
+            if !signals.send(SignalValue::Number(idle_seconds)) {
+                break;
+            }
+
+            tokio::select! {
+                _ = updates.tick() => {}
+                Some(_) = hints.next() => {}
+                Some(_) = since_hints.next() => {}
+                event = self.camera_events.recv() => match event {
+                    Ok(MonitorEvent::SessionStarted(_)) => self.camera_active = true,
+                    Ok(MonitorEvent::SessionEnded(_)) => self.camera_active = false,
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                },
+                _ = stop.changed() => break,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Seconds the session has been idle, 0 while it is active
+async fn idle_seconds(session: &SessionProxy<'_>) -> zbus::Result<f64> {
+    if !session.idle_hint().await? {
+        return Ok(0.0);
+    }
+
+    let since = UNIX_EPOCH + Duration::from_micros(session.idle_since_hint().await?);
+    Ok(SystemTime::now()
+        .duration_since(since)
+        .unwrap_or_default()
+        .as_secs_f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera_monitor::source::ScriptedEventSource;
+    use crate::config::{AutoToggleConfig, DeviceSet, IdleConfig};
+    use crate::device::sink::RecordingDeviceSink;
+    use crate::logind::stub::{PrivateBus, StubLogind};
+    use crate::test_support::{key_light_sink, test_monitor, wait_until, KEY_LIGHT, LIT_STATE};
+    use crate::triggers::TriggerEngine;
+    use std::sync::Arc;
+
+    fn minutes(minutes: u64) -> Duration {
+        Duration::from_secs(minutes * 60)
+    }
+
+    /// Start an engine running the idle policy for the key light
+    async fn start_engine(
+        bus: &PrivateBus,
+        sink: Arc<RecordingDeviceSink>,
+        monitor: &CameraMonitor,
+    ) -> TriggerEngine {
+        let idle = IdleConfig {
+            enabled: true,
+            devices: DeviceSet::Devices {
+                serial_numbers: vec![KEY_LIGHT.to_string()],
+            },
+            ..IdleConfig::default()
+        };
+        let source = IdleTriggerSource::new(bus.connect().await, monitor);
+
+        let mut engine = TriggerEngine::new(sink);
+        engine
+            .start(idle.bindings(), Vec::new(), vec![Box::new(source)])
+            .await;
+        engine
+    }
+
+    #[tokio::test]
+    #[ignore = "needs dbus-daemon, run with --ignored"]
+    async fn test_idle_dims_then_switches_off_and_activity_restores() {
+        let bus = PrivateBus::start("idle");
+        let logind = StubLogind::start(&bus).await;
+
+        let sink = key_light_sink(&[]);
+        let monitor = test_monitor();
+        let mut engine = start_engine(&bus, sink.clone(), &monitor).await;
+
+        logind.set_idle(Some(minutes(6))).await;
+        wait_until("lights dimmed", || {
+            let state = sink.device_state(KEY_LIGHT);
+            state.is_on && state.brightness_lumens < LIT_STATE.brightness_lumens
+        })
+        .await;
+
+        logind.set_idle(Some(minutes(16))).await;
+        wait_until("lights off", || !sink.device_state(KEY_LIGHT).is_on).await;
+
+        logind.set_idle(None).await;
+        wait_until("lights restored", || {
+            sink.device_state(KEY_LIGHT) == LIT_STATE
+        })
+        .await;
+
+        engine.stop().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs dbus-daemon, run with --ignored"]
+    async fn test_idle_stands_down_during_camera_session() {
+        let bus = PrivateBus::start("idle-camera");
+        let logind = StubLogind::start(&bus).await;
+
+        let sink = key_light_sink(&[]);
+        let mut monitor = test_monitor();
+        let auto_toggle = AutoToggleConfig {
+            enabled: true,
+            ..AutoToggleConfig::default()
+        };
+        monitor
+            .start_monitoring_with_source(
+                auto_toggle,
+                Box::new(ScriptedEventSource::new(Vec::new()).with_open_count(1)),
+            )
+            .unwrap();
+        wait_until("camera session active", || monitor.is_session_active()).await;
+
+        let mut engine = start_engine(&bus, sink.clone(), &monitor).await;
+        logind.set_idle(Some(minutes(20))).await;
+
+        // Give the source time to publish, then check nothing happened
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(
+            engine.get_signals().await.get(IDLE_SOURCE_ID),
+            Some(&SignalValue::Number(0.0))
+        );
+        assert_eq!(sink.device_state(KEY_LIGHT), LIT_STATE);
+
+        // Once the call is over the idle time counts again
+        monitor.stop_monitoring().await.unwrap();
+        wait_until("lights off after call", || {
+            !sink.device_state(KEY_LIGHT).is_on
+        })
+        .await;
+
+        engine.stop().await;
+    }
+}
@@ -0,0 +1,107 @@
+//! Microphone trigger source
+//!
+//! Publishes whether any ALSA capture stream is recording. Unlike the camera
+//! source this does not depend on the camera monitor, so bindings can react
+//! to microphone use whatever the auto-toggle settings are.
+
+use crate::camera_monitor::alsa;
+use crate::config::MICROPHONE_SOURCE_ID;
+use crate::triggers::{SignalSender, SignalValue, TriggerResult, TriggerSource};
+use async_trait::async_trait;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Interval between polls of the capture stream status
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Trigger source following microphone use
+pub struct MicrophoneTriggerSource {
+    /// Root of the asound procfs tree
+    root: PathBuf,
+}
+
+impl MicrophoneTriggerSource {
+    /// Create a microphone trigger source reading `/proc/asound`
+    pub fn new() -> Self {
+        Self::with_root(PathBuf::from(alsa::ASOUND_PATH))
+    }
+
+    /// Create a microphone trigger source reading the given asound tree
+    pub fn with_root(root: PathBuf) -> Self {
+        Self { root }
+    }
+}
+
+#[async_trait]
+impl TriggerSource for MicrophoneTriggerSource {
+    fn id(&self) -> &str {
+        MICROPHONE_SOURCE_ID
+    }
+
+    async fn run(
+        self: Box<Self>,
+        signals: SignalSender,
+        mut stop: watch::Receiver<bool>,
+    ) -> TriggerResult<()> {
+        let mut last_active = None;
+
+        loop {
+            let active = !alsa::active_capture_streams(&self.root).is_empty();
+            if last_active != Some(active) {
+                if !signals.send(SignalValue::Bool(active)) {
+                    break;
+                }
+                last_active = Some(active);
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                _ = stop.changed() => break,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fake_asound;
+    use crate::triggers::Signal;
+    use std::fs;
+    use tokio::sync::mpsc;
+
+    /// Capture substream of the fake asound tree
+    const SUBSTREAM: &str = "card0/pcm0c/sub0";
+
+    #[tokio::test(start_paused = true)]
+    async fn test_signal_follows_capture_stream() {
+        let root = fake_asound("trigger", &[(SUBSTREAM, "closed\n")]);
+        let status = root.join(SUBSTREAM).join("status");
+
+        let (signal_tx, mut signal_rx) = mpsc::unbounded_channel();
+        let (stop_tx, stop_rx) = watch::channel(false);
+        let source = MicrophoneTriggerSource::with_root(root.path().to_path_buf());
+        let signals = SignalSender::new(MICROPHONE_SOURCE_ID, signal_tx);
+        let handle = tokio::spawn(Box::new(source).run(signals, stop_rx));
+
+        let signal = |value| Signal {
+            source: MICROPHONE_SOURCE_ID.to_string(),
+            value: SignalValue::Bool(value),
+        };
+        assert_eq!(signal_rx.recv().await, Some(signal(false)));
+
+        fs::write(&status, "state: RUNNING\n").unwrap();
+        assert_eq!(signal_rx.recv().await, Some(signal(true)));
+
+        fs::write(&status, "closed\n").unwrap();
+        assert_eq!(signal_rx.recv().await, Some(signal(false)));
+
+        stop_tx.send(true).unwrap();
+        handle.await.unwrap().unwrap();
+        // Only changes are published
+        assert!(signal_rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,31 @@
+//! Trigger subsystem
+//!
+//! Trigger sources watch something on the system, such as camera or microphone
+//! use, the session lock, idle time or display power, and publish a named
+//! signal that is either a boolean or a value.
+//! Bindings from the configuration map signals to lighting actions on a set of
+//! devices; the [`TriggerEngine`] evaluates them and applies the actions.
+//! It also evaluates [`rules`], which combine signals with other conditions
+//! and resolve conflicts between each other by priority.
+
+pub mod camera;
+pub mod displays;
+pub mod engine;
+pub mod idle;
+pub mod microphone;
+pub mod rules;
+pub mod session_lock;
+pub mod signal;
+
+pub use crate::config::SignalValue;
+pub use engine::TriggerEngine;
+pub use signal::{Signal, SignalSender, TriggerSource};
+
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Global state type for the trigger engine
+pub type TriggerEngineState = Arc<Mutex<TriggerEngine>>;
+
+/// Result type for trigger operations
+pub type TriggerResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
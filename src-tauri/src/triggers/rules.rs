@@ -0,0 +1,560 @@
+//! Rules
+//!
+//! Rules combine conditions on the time, the day, trigger signals and
+//! connected devices into lighting actions. This module decides which rules
+//! hold, resolves conflicts between them for every device and explains the
+//! outcome; the [`TriggerEngine`](super::TriggerEngine) applies it.
+
+use crate::camera_monitor::strategies::create_device_set_selector;
+use crate::config::{LightingState, Preset, Rule, RuleCondition};
+use crate::device::{DeviceInfo, DeviceState};
+use crate::triggers::SignalValue;
+use chrono::{Datelike, NaiveDateTime, NaiveTime};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Facts rules are evaluated against
+pub struct RuleContext<'a> {
+    /// Local date and time
+    pub now: NaiveDateTime,
+    /// Latest value of every signal
+    pub signals: &'a BTreeMap<String, SignalValue>,
+    /// Known devices
+    pub devices: &'a [DeviceInfo],
+    /// Presets rule actions may refer to
+    pub presets: &'a [Preset],
+}
+
+/// Setting of a device a rule can control
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum LightingField {
+    /// Power state
+    Power,
+    /// Brightness
+    Brightness,
+    /// Color temperature
+    Temperature,
+}
+
+/// How a condition evaluated
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConditionExplanation {
+    /// Readable form of the condition
+    pub description: String,
+    /// Whether the condition holds
+    pub holds: bool,
+    /// Explanations of nested conditions
+    pub children: Vec<ConditionExplanation>,
+}
+
+/// What a rule did to a device
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum RuleOutcome {
+    /// The rule is disabled
+    Disabled,
+    /// The rule's condition does not hold
+    NotMatched,
+    /// The rule controls some settings of the device
+    Applied { fields: Vec<LightingField> },
+    /// The rule's condition holds but its action sets nothing
+    NoAction,
+    /// Every setting of the rule is controlled by a rule with precedence
+    Overridden { by: String },
+}
+
+/// Why a rule does or does not affect a device
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleExplanation {
+    /// Name of the rule
+    pub name: String,
+    /// Priority of the rule
+    pub priority: i32,
+    /// Outcome for the device
+    pub outcome: RuleOutcome,
+    /// How the rule's condition evaluated
+    pub condition: ConditionExplanation,
+}
+
+/// Why a device is in its current state
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceRulesExplanation {
+    /// Serial number of the device
+    pub serial_number: String,
+    /// Current state of the device
+    pub current: DeviceState,
+    /// Settings the rules control, unset where no rule does
+    pub state: LightingState,
+    /// Summary of which rule controls what
+    pub summary: String,
+    /// Rules that select the device, in order of precedence
+    pub rules: Vec<RuleExplanation>,
+}
+
+impl RuleContext<'_> {
+    /// Whether a condition holds
+    pub fn holds(&self, condition: &RuleCondition) -> bool {
+        match condition {
+            RuleCondition::All(conditions) => conditions.iter().all(|c| self.holds(c)),
+            RuleCondition::Any(conditions) => conditions.iter().any(|c| self.holds(c)),
+            RuleCondition::Not(condition) => !self.holds(condition),
+            RuleCondition::TimeWindow { start, end } => in_window(self.now.time(), *start, *end),
+            RuleCondition::Weekdays { days } => days.contains(&self.now.weekday()),
+            RuleCondition::Signal { source, condition } => self
+                .signals
+                .get(source)
+                .is_some_and(|value| condition.matches(value)),
+            RuleCondition::DeviceConnected { serial_number } => self
+                .devices
+                .iter()
+                .any(|device| device.serial_number == *serial_number && device.is_connected),
+        }
+    }
+
+    /// Evaluate a condition, recording the result of every part of it
+    pub fn explain(&self, condition: &RuleCondition) -> ConditionExplanation {
+        let (description, children) = match condition {
+            RuleCondition::All(conditions) => (
+                "all of".to_string(),
+                conditions.iter().map(|c| self.explain(c)).collect(),
+            ),
+            RuleCondition::Any(conditions) => (
+                "any of".to_string(),
+                conditions.iter().map(|c| self.explain(c)).collect(),
+            ),
+            RuleCondition::Not(condition) => ("not".to_string(), vec![self.explain(condition)]),
+            RuleCondition::TimeWindow { start, end } => (
+                format!(
+                    "time between {} and {}",
+                    start.format("%H:%M"),
+                    end.format("%H:%M")
+                ),
+                Vec::new(),
+            ),
+            RuleCondition::Weekdays { days } => {
+                let days: Vec<String> = days.iter().map(|day| day.to_string()).collect();
+                (format!("weekday is {}", days.join(", ")), Vec::new())
+            }
+            RuleCondition::Signal { source, condition } => {
+                let value = match self.signals.get(source) {
+                    Some(SignalValue::Bool(value)) => value.to_string(),
+                    Some(SignalValue::Number(value)) => value.to_string(),
+                    None => "unknown".to_string(),
+                };
+                (
+                    format!("{source} {condition} (currently {value})"),
+                    Vec::new(),
+                )
+            }
+            RuleCondition::DeviceConnected { serial_number } => {
+                (format!("{serial_number} is connected"), Vec::new())
+            }
+        };
+
+        ConditionExplanation {
+            description,
+            holds: self.holds(condition),
+            children,
+        }
+    }
+
+    /// The settings the rules control on every device, leaving out devices
+    /// no rule controls
+    pub fn resolve(&self, rules: &[Rule]) -> BTreeMap<String, LightingState> {
+        self.devices
+            .iter()
+            .filter_map(|device| {
+                let state = self.resolve_device(rules, device).0;
+                (state != LightingState::default()).then(|| (device.serial_number.clone(), state))
+            })
+            .collect()
+    }
+
+    /// Explain the rules' effect on every device
+    pub fn explain_rules(&self, rules: &[Rule]) -> Vec<DeviceRulesExplanation> {
+        self.devices
+            .iter()
+            .map(|device| {
+                let (state, explanations) = self.resolve_device(rules, device);
+                DeviceRulesExplanation {
+                    serial_number: device.serial_number.clone(),
+                    current: device.state(),
+                    summary: summarize(&explanations),
+                    state,
+                    rules: explanations,
+                }
+            })
+            .collect()
+    }
+
+    /// Resolve the rules selecting a device.
+    ///
+    /// Every setting is taken from the matching rule with the highest
+    /// priority that sets it, the earlier rule winning a tie.
+    fn resolve_device(
+        &self,
+        rules: &[Rule],
+        device: &DeviceInfo,
+    ) -> (LightingState, Vec<RuleExplanation>) {
+        let mut selecting: Vec<&Rule> = rules
+            .iter()
+            .filter(|rule| create_device_set_selector(&rule.devices).should_control_device(device))
+            .collect();
+        // Stable, so ties keep the configured order
+        selecting.sort_by_key(|rule| std::cmp::Reverse(rule.priority));
+
+        let mut state = LightingState::default();
+        let mut owners: BTreeMap<&'static str, &str> = BTreeMap::new();
+        let mut explanations = Vec::new();
+        for rule in selecting {
+            let condition = self.explain(&rule.condition);
+            let outcome = if !rule.enabled {
+                RuleOutcome::Disabled
+            } else if !condition.holds {
+                RuleOutcome::NotMatched
+            } else {
+                let action = rule.action.resolve(self.presets);
+                let mut fields = Vec::new();
+                let mut overridden_by = None;
+                let mut claim = |field: LightingField, key: &'static str, set: bool| {
+                    if !set {
+                        return false;
+                    }
+                    match owners.get(key) {
+                        Some(owner) => {
+                            overridden_by.get_or_insert_with(|| owner.to_string());
+                            false
+                        }
+                        None => {
+                            owners.insert(key, &rule.name);
+                            fields.push(field);
+                            true
+                        }
+                    }
+                };
+                if claim(LightingField::Power, "power", action.power_on.is_some()) {
+                    state.power_on = action.power_on;
+                }
+                if claim(
+                    LightingField::Brightness,
+                    "brightness",
+                    action.brightness_percentage.is_some(),
+                ) {
+                    state.brightness_percentage = action.brightness_percentage;
+                }
+                if claim(
+                    LightingField::Temperature,
+                    "temperature",
+                    action.temperature_kelvin.is_some(),
+                ) {
+                    state.temperature_kelvin = action.temperature_kelvin;
+                }
+
+                match (fields.is_empty(), overridden_by) {
+                    (false, _) => RuleOutcome::Applied { fields },
+                    (true, Some(by)) => RuleOutcome::Overridden { by },
+                    (true, None) => RuleOutcome::NoAction,
+                }
+            };
+
+            explanations.push(RuleExplanation {
+                name: rule.name.clone(),
+                priority: rule.priority,
+                outcome,
+                condition,
+            });
+        }
+
+        (state, explanations)
+    }
+}
+
+/// Whether a time is in a window, which spans midnight if `end` is earlier
+/// than `start` and the whole day if they are equal
+fn in_window(time: NaiveTime, start: NaiveTime, end: NaiveTime) -> bool {
+    match start.cmp(&end) {
+        std::cmp::Ordering::Less => start <= time && time < end,
+        std::cmp::Ordering::Greater => time >= start || time < end,
+        std::cmp::Ordering::Equal => true,
+    }
+}
+
+/// Describe which rules control a device
+fn summarize(explanations: &[RuleExplanation]) -> String {
+    let controlling: Vec<String> = explanations
+        .iter()
+        .filter_map(|rule| match &rule.outcome {
+            RuleOutcome::Applied { fields } => {
+                let fields: Vec<&str> = fields
+                    .iter()
+                    .map(|field| match field {
+                        LightingField::Power => "power",
+                        LightingField::Brightness => "brightness",
+                        LightingField::Temperature => "temperature",
+                    })
+                    .collect();
+                Some(format!("{} set by \"{}\"", fields.join(" and "), rule.name))
+            }
+            _ => None,
+        })
+        .collect();
+
+    if controlling.is_empty() {
+        "No rule controls this device".to_string()
+    } else {
+        let summary = controlling.join("; ");
+        let mut chars = summary.chars();
+        chars
+            .next()
+            .map(|first| first.to_uppercase().chain(chars).collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{DeviceSet, SignalCondition};
+    use crate::test_support::{FILL_LIGHT, KEY_LIGHT};
+    use chrono::{NaiveDate, Weekday};
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn devices() -> Vec<DeviceInfo> {
+        [KEY_LIGHT, FILL_LIGHT]
+            .iter()
+            .map(|serial_number| DeviceInfo {
+                is_connected: true,
+                ..DeviceInfo::disconnected(serial_number.to_string(), "Litra Glow".to_string())
+            })
+            .collect()
+    }
+
+    fn rule(name: &str, priority: i32, condition: RuleCondition, action: LightingState) -> Rule {
+        Rule {
+            name: name.to_string(),
+            enabled: true,
+            priority,
+            condition,
+            devices: DeviceSet::AllDevices,
+            action,
+        }
+    }
+
+    fn camera_on() -> RuleCondition {
+        RuleCondition::Signal {
+            source: "camera".to_string(),
+            condition: SignalCondition::IsTrue,
+        }
+    }
+
+    #[test]
+    fn test_conditions() {
+        let signals = BTreeMap::from([
+            ("camera".to_string(), SignalValue::Bool(true)),
+            ("idleSeconds".to_string(), SignalValue::Number(30.0)),
+        ]);
+        let devices = devices();
+        // Friday evening
+        let context = RuleContext {
+            now: NaiveDate::from_ymd_opt(2024, 6, 7)
+                .unwrap()
+                .and_hms_opt(19, 30, 0)
+                .unwrap(),
+            signals: &signals,
+            devices: &devices,
+            presets: &[],
+        };
+
+        let evening = RuleCondition::TimeWindow {
+            start: time(18, 0),
+            end: time(23, 0),
+        };
+        let night = RuleCondition::TimeWindow {
+            start: time(22, 0),
+            end: time(6, 0),
+        };
+        let idle = RuleCondition::Signal {
+            source: "idleSeconds".to_string(),
+            condition: SignalCondition::Above { threshold: 600.0 },
+        };
+
+        assert!(context.holds(&RuleCondition::All(vec![camera_on(), evening.clone()])));
+        assert!(!context.holds(&night));
+        assert!(context.holds(&RuleCondition::Not(Box::new(night))));
+        assert!(!context.holds(&RuleCondition::Any(vec![idle.clone()])));
+        assert!(context.holds(&RuleCondition::Weekdays {
+            days: vec![Weekday::Fri, Weekday::Sat],
+        }));
+        assert!(context.holds(&RuleCondition::DeviceConnected {
+            serial_number: KEY_LIGHT.to_string(),
+        }));
+        assert!(!context.holds(&RuleCondition::DeviceConnected {
+            serial_number: "OTHER001".to_string(),
+        }));
+
+        let explanation = context.explain(&RuleCondition::All(vec![evening, idle]));
+        assert!(!explanation.holds);
+        assert!(explanation.children[0].holds);
+        assert_eq!(
+            explanation.children[1].description,
+            "idleSeconds above 600 (currently 30)"
+        );
+    }
+
+    #[test]
+    fn test_conflict_resolution() {
+        let signals = BTreeMap::from([("camera".to_string(), SignalValue::Bool(true))]);
+        let devices = devices();
+        let context = RuleContext {
+            now: NaiveDate::from_ymd_opt(2024, 6, 7)
+                .unwrap()
+                .and_hms_opt(19, 30, 0)
+                .unwrap(),
+            signals: &signals,
+            devices: &devices,
+            presets: &[],
+        };
+
+        let warm = rule(
+            "Warm",
+            0,
+            camera_on(),
+            LightingState {
+                power_on: Some(true),
+                temperature_kelvin: Some(2700),
+                ..LightingState::default()
+            },
+        );
+        let mut bright_key = rule(
+            "Bright key",
+            10,
+            camera_on(),
+            LightingState {
+                power_on: Some(true),
+                brightness_percentage: Some(90),
+                ..LightingState::default()
+            },
+        );
+        bright_key.devices = DeviceSet::Devices {
+            serial_numbers: vec![KEY_LIGHT.to_string()],
+        };
+        let mut off = rule(
+            "Off",
+            20,
+            camera_on(),
+            LightingState {
+                power_on: Some(false),
+                ..LightingState::default()
+            },
+        );
+        off.enabled = false;
+
+        let resolved = context.resolve(&[warm.clone(), bright_key.clone(), off]);
+        assert_eq!(
+            resolved[KEY_LIGHT],
+            LightingState {
+                power_on: Some(true),
+                brightness_percentage: Some(90),
+                temperature_kelvin: Some(2700),
+                preset: None,
+            }
+        );
+        assert_eq!(resolved[FILL_LIGHT].brightness_percentage, None);
+
+        // A rule whose every setting is taken is overridden
+        let mut overridden = warm.clone();
+        overridden.name = "Cool".to_string();
+        overridden.action.temperature_kelvin = Some(6500);
+        let explanations = context.explain_rules(&[warm, overridden, bright_key]);
+        let key = &explanations[0];
+        assert_eq!(key.rules[0].name, "Bright key");
+        assert_eq!(
+            key.rules[2].outcome,
+            RuleOutcome::Overridden {
+                by: "Bright key".to_string()
+            }
+        );
+        assert_eq!(
+            key.summary,
+            "Power and brightness set by \"Bright key\"; temperature set by \"Warm\""
+        );
+
+        // A matching rule that sets nothing is told apart from one that applies
+        let empty = rule("Empty", 0, camera_on(), LightingState::default());
+        let explanations = context.explain_rules(&[empty]);
+        assert_eq!(explanations[0].rules[0].outcome, RuleOutcome::NoAction);
+        assert_eq!(explanations[0].summary, "No rule controls this device");
+    }
+
+    #[test]
+    fn test_preset_actions() {
+        let signals = BTreeMap::from([("camera".to_string(), SignalValue::Bool(true))]);
+        let devices = devices();
+        let presets = [Preset {
+            id: "focus".to_string(),
+            name: "Focus".to_string(),
+            description: String::new(),
+            brightness_percentage: 80,
+            temperature_kelvin: 6000,
+        }];
+        let context = RuleContext {
+            now: NaiveDate::from_ymd_opt(2024, 6, 7)
+                .unwrap()
+                .and_hms_opt(19, 30, 0)
+                .unwrap(),
+            signals: &signals,
+            devices: &devices,
+            presets: &presets,
+        };
+
+        // The preset fills in the settings the rule leaves unset, and they
+        // compete with other rules like settings of the rule itself
+        let focus = rule(
+            "Focus",
+            10,
+            camera_on(),
+            LightingState {
+                power_on: Some(true),
+                temperature_kelvin: Some(5000),
+                preset: Some("focus".to_string()),
+                ..LightingState::default()
+            },
+        );
+        let dim = rule(
+            "Dim",
+            20,
+            camera_on(),
+            LightingState {
+                brightness_percentage: Some(30),
+                ..LightingState::default()
+            },
+        );
+        let resolved = context.resolve(&[focus.clone(), dim]);
+        assert_eq!(
+            resolved[KEY_LIGHT],
+            LightingState {
+                power_on: Some(true),
+                brightness_percentage: Some(30),
+                temperature_kelvin: Some(5000),
+                preset: None,
+            }
+        );
+
+        let resolved = context.resolve(std::slice::from_ref(&focus));
+        assert_eq!(resolved[KEY_LIGHT].brightness_percentage, Some(80));
+
+        // A preset that no longer exists sets nothing
+        let mut missing = focus;
+        missing.action.preset = Some("gone".to_string());
+        let resolved = context.resolve(&[missing]);
+        assert_eq!(resolved[KEY_LIGHT].brightness_percentage, None);
+        assert_eq!(resolved[KEY_LIGHT].temperature_kelvin, Some(5000));
+    }
+}
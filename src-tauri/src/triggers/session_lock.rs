@@ -0,0 +1,131 @@
+//! Session lock trigger source
+//!
+//! Publishes whether the user's session is locked, following logind's
+//! `Lock`/`Unlock` signals and the session's `LockedHint` property.
+
+use crate::config::SESSION_LOCK_SOURCE_ID;
+use crate::logind;
+use crate::triggers::{SignalSender, SignalValue, TriggerResult, TriggerSource};
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use tokio::sync::watch;
+use zbus::Connection;
+
+/// Trigger source following the session lock state
+pub struct SessionLockTriggerSource {
+    /// Bus logind is reached on
+    connection: Connection,
+}
+
+impl SessionLockTriggerSource {
+    /// Create a session lock trigger source talking to logind on the given bus
+    pub fn new(connection: Connection) -> Self {
+        Self { connection }
+    }
+}
+
+#[async_trait]
+impl TriggerSource for SessionLockTriggerSource {
+    fn id(&self) -> &str {
+        SESSION_LOCK_SOURCE_ID
+    }
+
+    async fn run(
+        self: Box<Self>,
+        signals: SignalSender,
+        mut stop: watch::Receiver<bool>,
+    ) -> TriggerResult<()> {
+        let session = logind::current_session(&self.connection).await?;
+
+        // Subscribe before reading the initial state so no change is missed
+        let mut locks = session.receive_lock().await?;
+        let mut unlocks = session.receive_unlock().await?;
+        let mut hints = session.receive_locked_hint_changed().await;
+
+        signals.send(SignalValue::Bool(session.locked_hint().await?));
+
+        loop {
+            let locked = tokio::select! {
+                Some(_) = locks.next() => true,
+                Some(_) = unlocks.next() => false,
+                Some(change) = hints.next() => match change.get().await {
+                    Ok(locked) => locked,
+                    Err(_) => continue,
+                },
+                _ = stop.changed() => break,
+                else => break,
+            };
+
+            if !signals.send(SignalValue::Bool(locked)) {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SessionLockConfig;
+    use crate::logind::stub::{PrivateBus, StubLogind};
+    use crate::test_support::{key_light_sink, wait_until, KEY_LIGHT, LIT_STATE};
+    use crate::triggers::TriggerEngine;
+    use std::time::Duration;
+
+    #[tokio::test]
+    #[ignore = "needs dbus-daemon, run with --ignored"]
+    async fn test_lock_turns_lights_off_and_unlock_restores() {
+        let bus = PrivateBus::start("session-lock");
+        let logind = StubLogind::start(&bus).await;
+
+        let sink = key_light_sink(&[]);
+        let binding = SessionLockConfig {
+            enabled: true,
+            ..SessionLockConfig::default()
+        }
+        .binding();
+
+        let mut engine = TriggerEngine::new(sink.clone());
+        let source = SessionLockTriggerSource::new(bus.connect().await);
+        engine
+            .start(vec![binding], Vec::new(), vec![Box::new(source)])
+            .await;
+
+        // The source publishes the initial state once it is subscribed
+        let mut subscribed = false;
+        for _ in 0..250 {
+            if !engine.get_signals().await.is_empty() {
+                subscribed = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(subscribed, "Session lock source did not start");
+
+        logind.lock().await;
+        wait_until("lights off on lock", || !sink.device_state(KEY_LIGHT).is_on).await;
+
+        logind.unlock().await;
+        wait_until("lights restored on unlock", || {
+            sink.device_state(KEY_LIGHT) == LIT_STATE
+        })
+        .await;
+
+        // Desktops that only set the hint are followed too
+        logind.set_locked_hint(true).await;
+        wait_until("lights off on locked hint", || {
+            !sink.device_state(KEY_LIGHT).is_on
+        })
+        .await;
+
+        logind.set_locked_hint(false).await;
+        wait_until("lights restored on cleared hint", || {
+            sink.device_state(KEY_LIGHT) == LIT_STATE
+        })
+        .await;
+
+        engine.stop().await;
+    }
+}
@@ -0,0 +1,60 @@
+//! Trigger signals and the trigger source trait
+
+use crate::config::SignalValue;
+use crate::triggers::TriggerResult;
+use async_trait::async_trait;
+use tokio::sync::{mpsc, watch};
+
+/// A signal value published by a trigger source
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signal {
+    /// ID of the source that published the signal
+    pub source: String,
+    /// New value of the signal
+    pub value: SignalValue,
+}
+
+/// Handle a trigger source publishes its signal through
+#[derive(Debug, Clone)]
+pub struct SignalSender {
+    /// ID of the source the sender belongs to
+    source: String,
+    /// Channel to the trigger engine
+    tx: mpsc::UnboundedSender<Signal>,
+}
+
+impl SignalSender {
+    /// Create a sender publishing signals for the given source
+    pub fn new(source: &str, tx: mpsc::UnboundedSender<Signal>) -> Self {
+        Self {
+            source: source.to_string(),
+            tx,
+        }
+    }
+
+    /// Publish a new signal value.
+    ///
+    /// Returns `false` once the trigger engine is gone.
+    pub fn send(&self, value: SignalValue) -> bool {
+        self.tx
+            .send(Signal {
+                source: self.source.clone(),
+                value,
+            })
+            .is_ok()
+    }
+}
+
+/// Source of a trigger signal
+#[async_trait]
+pub trait TriggerSource: Send {
+    /// ID of the source, used by bindings to refer to its signal
+    fn id(&self) -> &str;
+
+    /// Watch the system and publish signal changes until `stop` is set
+    async fn run(
+        self: Box<Self>,
+        signals: SignalSender,
+        stop: watch::Receiver<bool>,
+    ) -> TriggerResult<()>;
+}
@@ -0,0 +1,519 @@
+//! Undo and redo of device state changes
+//!
+//! Changes made to the lights from the UI, the tray or automation are
+//! recorded with each device's state before and after them, so they can be
+//! undone and redone. A change to several devices at once is one step, and
+//! quick successive changes from the same place, like dragging a slider, are
+//! merged into one. Undo and redo pick the latest step from the sources asked
+//! for, so automation changing the lights in between does not get in the way
+//! of undoing a click.
+
+use crate::config::LightingState;
+use crate::device::sink::{DeviceSink, DeviceSinkResult};
+use crate::device::{DeviceInfo, DeviceState};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Most steps kept for each device
+pub const MAX_STEPS_PER_DEVICE: usize = 50;
+
+/// Longest time between two changes from the same place merged into one step
+const MERGE_WINDOW: Duration = Duration::from_secs(2);
+
+/// Global state type for the change history
+pub type ChangeHistoryState = Arc<Mutex<ChangeHistory>>;
+
+/// Where a change to the lights came from
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ChangeSource {
+    /// The application window
+    Ui,
+    /// The tray menu
+    Tray,
+    /// Camera monitoring, triggers, timers and schedules
+    Automation,
+}
+
+/// Sources of the changes made by the user, undone and redone unless
+/// others are asked for
+pub const USER_SOURCES: &[ChangeSource] = &[ChangeSource::Ui, ChangeSource::Tray];
+
+/// Change of a single device
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceChange {
+    /// Serial number of the device
+    pub serial_number: String,
+    /// State before the change
+    pub before: DeviceState,
+    /// State after the change
+    pub after: DeviceState,
+}
+
+/// Change to one or more devices, undone and redone as a whole
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeStep {
+    /// Where the change came from
+    pub source: ChangeSource,
+    /// What was changed, e.g. "Brightness" or "Preset Cool Blue"
+    pub description: String,
+    /// Time of the latest change in the step
+    pub time: DateTime<Utc>,
+    /// Devices changed
+    pub changes: Vec<DeviceChange>,
+}
+
+impl ChangeStep {
+    /// Step for the devices whose state differs between `before` and `after`
+    ///
+    /// Devices missing from `after` are left out.
+    pub fn new(
+        source: ChangeSource,
+        description: impl Into<String>,
+        before: Vec<(String, DeviceState)>,
+        after: &[(String, DeviceState)],
+    ) -> Self {
+        let changes = before
+            .into_iter()
+            .filter_map(|(serial_number, before)| {
+                let (_, after) = after.iter().find(|(serial, _)| *serial == serial_number)?;
+                Some(DeviceChange {
+                    serial_number,
+                    before,
+                    after: *after,
+                })
+            })
+            .collect();
+
+        Self {
+            source,
+            description: description.into(),
+            time: Utc::now(),
+            changes,
+        }
+    }
+
+    /// Whether a device was changed in this step
+    pub fn changes_device(&self, serial_number: &str) -> bool {
+        self.changes
+            .iter()
+            .any(|change| change.serial_number == serial_number)
+    }
+
+    /// Whether `next` continues this step
+    fn continued_by(&self, next: &ChangeStep) -> bool {
+        self.source == next.source
+            && self.description == next.description
+            && (next.time - self.time)
+                .to_std()
+                .is_ok_and(|elapsed| elapsed <= MERGE_WINDOW)
+    }
+
+    /// Take in a later step, keeping each device's earliest state before and
+    /// latest state after
+    fn merge(&mut self, next: ChangeStep) {
+        self.time = next.time;
+        for change in next.changes {
+            match self
+                .changes
+                .iter_mut()
+                .find(|existing| existing.serial_number == change.serial_number)
+            {
+                Some(existing) => existing.after = change.after,
+                None => self.changes.push(change),
+            }
+        }
+        self.changes.retain(|change| change.before != change.after);
+    }
+}
+
+/// Bounded undo and redo stacks of changes
+#[derive(Debug)]
+pub struct ChangeHistory {
+    /// Steps that can be undone, oldest first
+    undo: VecDeque<ChangeStep>,
+    /// Steps that can be redone, most recently undone last
+    redo: Vec<ChangeStep>,
+    /// Most steps kept for each device
+    capacity: usize,
+}
+
+impl Default for ChangeHistory {
+    fn default() -> Self {
+        Self::new(MAX_STEPS_PER_DEVICE)
+    }
+}
+
+impl ChangeHistory {
+    /// Create an empty history keeping up to `capacity` steps per device
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            capacity,
+        }
+    }
+
+    /// Record a new change
+    ///
+    /// Devices left as they were are ignored. A new change by the user can
+    /// no longer be followed by redoing changes by the user that were undone,
+    /// and likewise for automation.
+    pub fn record(&mut self, mut step: ChangeStep) {
+        step.changes.retain(|change| change.before != change.after);
+        if step.changes.is_empty() {
+            return;
+        }
+        let by_user = USER_SOURCES.contains(&step.source);
+        self.redo
+            .retain(|undone| USER_SOURCES.contains(&undone.source) != by_user);
+
+        match self.undo.back_mut() {
+            Some(last) if last.continued_by(&step) => {
+                last.merge(step);
+                if last.changes.is_empty() {
+                    self.undo.pop_back();
+                }
+            }
+            _ => self.push_undo(step),
+        }
+    }
+
+    /// Take the latest step from one of `sources` to undo
+    pub fn pop_undo(&mut self, sources: &[ChangeSource]) -> Option<ChangeStep> {
+        let index = self
+            .undo
+            .iter()
+            .rposition(|step| sources.contains(&step.source))?;
+        self.undo.remove(index)
+    }
+
+    /// Take the latest undone step from one of `sources` to redo
+    pub fn pop_redo(&mut self, sources: &[ChangeSource]) -> Option<ChangeStep> {
+        let index = self
+            .redo
+            .iter()
+            .rposition(|step| sources.contains(&step.source))?;
+        Some(self.redo.remove(index))
+    }
+
+    /// Put a step back onto the undo stack, after redoing it or failing to
+    /// undo it
+    pub fn push_undo(&mut self, step: ChangeStep) {
+        self.undo.push_back(step);
+        self.trim();
+    }
+
+    /// Put a step onto the redo stack, after undoing it or failing to redo it
+    pub fn push_redo(&mut self, step: ChangeStep) {
+        self.redo.push(step);
+    }
+
+    /// Steps that can be undone, latest first, optionally only those
+    /// changing a device
+    pub fn undo_steps(&self, serial_number: Option<&str>) -> Vec<ChangeStep> {
+        Self::filter(self.undo.iter().rev(), serial_number)
+    }
+
+    /// Steps that can be redone, next first, optionally only those changing
+    /// a device
+    pub fn redo_steps(&self, serial_number: Option<&str>) -> Vec<ChangeStep> {
+        Self::filter(self.redo.iter().rev(), serial_number)
+    }
+
+    fn filter<'a>(
+        steps: impl Iterator<Item = &'a ChangeStep>,
+        serial_number: Option<&str>,
+    ) -> Vec<ChangeStep> {
+        steps
+            .filter(|step| serial_number.is_none_or(|serial| step.changes_device(serial)))
+            .cloned()
+            .collect()
+    }
+
+    /// Forget the oldest changes of devices in more steps than the capacity
+    fn trim(&mut self) {
+        let Some(latest) = self.undo.back() else {
+            return;
+        };
+        let serial_numbers: Vec<_> = latest
+            .changes
+            .iter()
+            .map(|change| change.serial_number.clone())
+            .collect();
+
+        for serial_number in serial_numbers {
+            let steps = self
+                .undo
+                .iter()
+                .filter(|step| step.changes_device(&serial_number))
+                .count();
+            for _ in self.capacity..steps {
+                let Some(oldest) = self
+                    .undo
+                    .iter_mut()
+                    .find(|step| step.changes_device(&serial_number))
+                else {
+                    break;
+                };
+                oldest
+                    .changes
+                    .retain(|change| change.serial_number != serial_number);
+            }
+        }
+        self.undo.retain(|step| !step.changes.is_empty());
+    }
+}
+
+/// Device sink recording the changes made through it in the change history
+pub struct ChangeRecordingSink {
+    /// Sink making the changes
+    inner: Arc<dyn DeviceSink>,
+    /// History the changes are recorded in
+    history: ChangeHistoryState,
+    /// Where the changes come from
+    source: ChangeSource,
+    /// What makes the changes, e.g. "Camera monitor"
+    description: String,
+}
+
+impl ChangeRecordingSink {
+    /// Create a sink recording changes as coming from `source`
+    pub fn new(
+        inner: Arc<dyn DeviceSink>,
+        history: ChangeHistoryState,
+        source: ChangeSource,
+        description: impl Into<String>,
+    ) -> Self {
+        Self {
+            inner,
+            history,
+            source,
+            description: description.into(),
+        }
+    }
+
+    /// Record a device's change, if both its states could be read
+    async fn record(&self, serial_number: &str, before: Option<DeviceState>) {
+        let Some(before) = before else {
+            return;
+        };
+        let Ok(after) = self.inner.get_device_info(serial_number).await else {
+            return;
+        };
+
+        let serial_number = serial_number.to_string();
+        self.history.lock().unwrap().record(ChangeStep::new(
+            self.source,
+            self.description.clone(),
+            vec![(serial_number.clone(), before)],
+            &[(serial_number, after.state())],
+        ));
+    }
+}
+
+#[async_trait]
+impl DeviceSink for ChangeRecordingSink {
+    async fn get_all_devices(&self) -> DeviceSinkResult<Vec<DeviceInfo>> {
+        self.inner.get_all_devices().await
+    }
+
+    async fn get_device_info(&self, serial_number: &str) -> DeviceSinkResult<DeviceInfo> {
+        self.inner.get_device_info(serial_number).await
+    }
+
+    async fn apply_lighting_state(
+        &self,
+        serial_number: &str,
+        state: &LightingState,
+    ) -> DeviceSinkResult<()> {
+        let before = self.inner.get_device_info(serial_number).await.ok();
+        self.inner
+            .apply_lighting_state(serial_number, state)
+            .await?;
+        self.record(serial_number, before.map(|device| device.state()))
+            .await;
+        Ok(())
+    }
+
+    async fn apply_device_state(
+        &self,
+        serial_number: &str,
+        state: &DeviceState,
+    ) -> DeviceSinkResult<()> {
+        let before = self.inner.get_device_info(serial_number).await.ok();
+        self.inner.apply_device_state(serial_number, state).await?;
+        self.record(serial_number, before.map(|device| device.state()))
+            .await;
+        Ok(())
+    }
+
+    async fn reconnect(&self) -> DeviceSinkResult<()> {
+        self.inner.reconnect().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::sink::RecordingDeviceSink;
+
+    fn state(brightness_lumens: u16) -> DeviceState {
+        DeviceState {
+            is_on: true,
+            brightness_lumens,
+            temperature_kelvin: 4000,
+        }
+    }
+
+    fn step(description: &str, changes: &[(&str, u16, u16)]) -> ChangeStep {
+        let before = changes
+            .iter()
+            .map(|(serial, before, _)| (serial.to_string(), state(*before)))
+            .collect();
+        let after: Vec<_> = changes
+            .iter()
+            .map(|(serial, _, after)| (serial.to_string(), state(*after)))
+            .collect();
+        ChangeStep::new(ChangeSource::Ui, description, before, &after)
+    }
+
+    #[test]
+    fn test_undo_and_redo() {
+        let mut history = ChangeHistory::default();
+        history.record(step(
+            "Preset Cool Blue",
+            &[("KEY", 100, 200), ("FILL", 50, 200)],
+        ));
+        // Unchanged devices are not part of a step
+        history.record(step("Power", &[("KEY", 200, 200)]));
+        assert_eq!(history.undo_steps(None).len(), 1);
+
+        let undone = history.pop_undo(USER_SOURCES).unwrap();
+        assert_eq!(undone.changes.len(), 2);
+        history.push_redo(undone);
+        assert!(history.undo_steps(None).is_empty());
+        assert_eq!(history.redo_steps(Some("FILL")).len(), 1);
+
+        let redone = history.pop_redo(USER_SOURCES).unwrap();
+        history.push_undo(redone);
+        assert_eq!(history.undo_steps(Some("KEY")).len(), 1);
+
+        // A new change discards undone ones
+        let undone = history.pop_undo(USER_SOURCES).unwrap();
+        history.push_redo(undone);
+        history.record(step("Brightness", &[("KEY", 100, 120)]));
+        assert!(history.redo_steps(None).is_empty());
+        assert!(history.undo_steps(Some("FILL")).is_empty());
+    }
+
+    #[test]
+    fn test_undo_skips_automation() {
+        let mut history = ChangeHistory::default();
+        history.record(step("Preset Cool Blue", &[("KEY", 100, 200)]));
+        let automation = |before, after| ChangeStep {
+            source: ChangeSource::Automation,
+            ..step("Camera monitor", &[("KEY", before, after)])
+        };
+        history.record(automation(200, 220));
+
+        // The click is undone even though automation changed the light since
+        let undone = history.pop_undo(USER_SOURCES).unwrap();
+        assert_eq!(undone.description, "Preset Cool Blue");
+        history.push_redo(undone);
+        assert_eq!(history.undo_steps(None).len(), 1);
+
+        // Automation does not discard the undone click
+        history.record(automation(220, 240));
+        assert!(history.pop_redo(&[ChangeSource::Automation]).is_none());
+        let redone = history.pop_redo(USER_SOURCES).unwrap();
+        assert_eq!(redone.description, "Preset Cool Blue");
+
+        let undone = history.pop_undo(&[ChangeSource::Automation]).unwrap();
+        assert_eq!(undone.changes[0].after, state(240));
+    }
+
+    #[test]
+    fn test_merges_quick_changes() {
+        let mut history = ChangeHistory::default();
+        history.record(step("Brightness", &[("KEY", 100, 120)]));
+        history.record(step("Brightness", &[("KEY", 120, 150)]));
+        history.record(step("Brightness", &[("FILL", 80, 90)]));
+        history.record(step("Temperature", &[("KEY", 150, 160)]));
+
+        let steps = history.undo_steps(None);
+        assert_eq!(steps.len(), 2);
+        let brightness = &steps[1];
+        assert_eq!(brightness.changes.len(), 2);
+        assert_eq!(brightness.changes[0].before, state(100));
+        assert_eq!(brightness.changes[0].after, state(150));
+
+        // Changes cancelling each other out leave nothing to undo
+        history.record(step("Temperature", &[("KEY", 160, 150)]));
+        assert_eq!(history.undo_steps(None).len(), 1);
+
+        // Changes far apart are separate steps
+        let mut later = step("Brightness", &[("KEY", 150, 170)]);
+        later.time += chrono::Duration::seconds(10);
+        history.record(later);
+        assert_eq!(history.undo_steps(None).len(), 2);
+    }
+
+    #[test]
+    fn test_bounded_per_device() {
+        let mut history = ChangeHistory::new(2);
+        history.record(step("Both", &[("KEY", 100, 110), ("FILL", 100, 110)]));
+        history.record(step("Key", &[("KEY", 110, 120)]));
+        history.record(step("Fill", &[("FILL", 110, 120)]));
+        history.record(step("Key", &[("KEY", 120, 130)]));
+
+        // The oldest change to the key light is dropped, the fill light's kept
+        let steps = history.undo_steps(None);
+        assert_eq!(steps.len(), 4);
+        assert_eq!(steps[3].changes.len(), 1);
+        assert_eq!(steps[3].changes[0].serial_number, "FILL");
+        assert_eq!(history.undo_steps(Some("KEY")).len(), 2);
+
+        history.record(step("Fill", &[("FILL", 120, 130)]));
+        assert_eq!(history.undo_steps(None).len(), 4);
+        assert_eq!(history.undo_steps(Some("FILL")).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_recording_sink() {
+        let inner = Arc::new(RecordingDeviceSink::new(&["KEY"]));
+        let history = ChangeHistoryState::default();
+        let sink = ChangeRecordingSink::new(
+            inner.clone(),
+            history.clone(),
+            ChangeSource::Automation,
+            "Camera monitor",
+        );
+        let before = inner.device_state("KEY");
+
+        sink.apply_lighting_state(
+            "KEY",
+            &LightingState {
+                power_on: Some(true),
+                brightness_percentage: None,
+                temperature_kelvin: Some(5000),
+                preset: None,
+            },
+        )
+        .await
+        .unwrap();
+
+        let steps = history.lock().unwrap().undo_steps(None);
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].source, ChangeSource::Automation);
+        assert_eq!(steps[0].description, "Camera monitor");
+        assert_eq!(steps[0].changes[0].before, before);
+        assert_eq!(steps[0].changes[0].after, inner.device_state("KEY"));
+    }
+}
@@ -2,6 +2,16 @@
 ///
 /// This module re-exports all composables used throughout the application.
 
+export * from './useCalendar'
 export * from './useCameraMonitor'
+export * from './useCircadian'
 export * from './useDevice'
+export * from './useHistory'
 export * from './usePresets'
+export * from './useProfiles'
+export * from './useRules'
+export * from './useScenes'
+export * from './useScheduler'
+export * from './useTimers'
+export * from './useTriggers'
+export * from './useUndo'
@@ -0,0 +1,81 @@
+import type { LightingState } from './useCameraMonitor'
+import type { DeviceSet } from './useTriggers'
+import { invoke } from '@tauri-apps/api/core'
+import { ref } from 'vue'
+
+export interface CalendarConfig {
+  enabled: boolean
+  path: string
+  leadMinutes: number
+  requireVideoLink: boolean
+  keywords: string[]
+  devices: DeviceSet
+  action: LightingState
+}
+
+export interface Meeting {
+  summary: string
+  start: string
+  end: string
+}
+
+export interface CalendarStatus {
+  enabled: boolean
+  error: string | null
+  warming: Meeting | null
+  upcoming: Meeting[]
+}
+
+export function useCalendar() {
+  const config = ref<CalendarConfig>({
+    enabled: false,
+    path: '',
+    leadMinutes: 5,
+    requireVideoLink: true,
+    keywords: [],
+    devices: 'allDevices',
+    action: { powerOn: true, brightnessPercentage: null, temperatureKelvin: null },
+  })
+  const status = ref<CalendarStatus | null>(null)
+
+  const loadStatus = async () => {
+    try {
+      status.value = await invoke<CalendarStatus>('get_calendar_status')
+    }
+    catch (error) {
+      console.error('[Calendar] Failed to load status:', error)
+    }
+  }
+
+  const loadConfig = async () => {
+    try {
+      config.value = await invoke<CalendarConfig>('get_calendar_config')
+    }
+    catch (error) {
+      console.error('[Calendar] Failed to load config:', error)
+    }
+  }
+
+  const updateConfig = async (newConfig: CalendarConfig) => {
+    try {
+      await invoke('update_calendar_config', { config: newConfig })
+      config.value = newConfig
+      // Give the watcher a moment to read the calendar
+      await new Promise(resolve => setTimeout(resolve, 500))
+      await loadStatus()
+    }
+    catch (error: any) {
+      const errorMessage = error?.message || error?.toString() || 'Unknown error'
+      console.error('[Calendar] Failed to update config:', error)
+      throw new Error(`Failed to save calendar settings: ${errorMessage}`)
+    }
+  }
+
+  return {
+    config,
+    status,
+    loadConfig,
+    loadStatus,
+    updateConfig,
+  }
+}
@@ -1,5 +1,7 @@
+import type { UnlistenFn } from '@tauri-apps/api/event'
 import { invoke } from '@tauri-apps/api/core'
-import { computed, onMounted, ref } from 'vue'
+import { listen } from '@tauri-apps/api/event'
+import { computed, onMounted, onUnmounted, ref } from 'vue'
 
 export interface CameraMonitorState {
   isMonitoring: boolean
@@ -8,12 +10,40 @@ export interface CameraMonitorState {
   isLoading: boolean
 }
 
+export interface LightingState {
+  powerOn?: boolean | null
+  brightnessPercentage?: number | null
+  temperatureKelvin?: number | null
+  preset?: string | null
+}
+
+export type OverridePolicy = 'respectOverride' | 'reassert'
+
 export interface AutoToggleConfig {
   enabled: boolean
+  activitySource: 'camera' | 'microphone' | 'cameraOrMicrophone'
   strategy: 'allDevices' | { selectedDevice: { serialNumber: string } }
-  debounceMs: number
+  onDelayMs: number
+  offGraceMs: number
+  inCallState: LightingState
+  overridePolicy: OverridePolicy
+}
+
+export interface CameraSession {
+  started_at: string
+  ended_at: string | null
+  cameras: string[]
+  processes: string[]
+  affected_devices: string[]
 }
 
+export interface AutoToggleApplied {
+  action: 'inCallState' | 'restore'
+  serial_numbers: string[]
+}
+
+const CAMERA_EVENTS = ['camera-session-started', 'camera-session-ended', 'auto-toggle-applied']
+
 export function useCameraMonitor() {
   // State
   const state = ref<CameraMonitorState>({
@@ -26,10 +56,17 @@ export function useCameraMonitor() {
   // Config state
   const config = ref<AutoToggleConfig>({
     enabled: false,
+    activitySource: 'camera',
     strategy: 'allDevices',
-    debounceMs: 3000,
+    onDelayMs: 1000,
+    offGraceMs: 3000,
+    inCallState: { powerOn: true },
+    overridePolicy: 'respectOverride',
   })
 
+  // Recorded camera sessions, most recent first
+  const history = ref<CameraSession[]>([])
+
   // Computed properties
   const isEnabled = computed(() => config.value.enabled)
   const isMonitoring = computed(() => state.value.isMonitoring)
@@ -53,6 +90,15 @@ export function useCameraMonitor() {
     }
   }
 
+  const loadHistory = async () => {
+    try {
+      history.value = await invoke<CameraSession[]>('get_camera_history')
+    }
+    catch (error) {
+      console.error('[Camera Monitor] Failed to load history:', error)
+    }
+  }
+
   // API calls
   const startMonitoring = async () => {
     try {
@@ -106,6 +152,7 @@ export function useCameraMonitor() {
 
       // Update local state only if backend save succeeds
       config.value = updatedConfig
+      await refreshStatus()
     }
     catch (error: any) {
       const errorMessage = error?.message || error?.toString() || 'Unknown error'
@@ -117,8 +164,12 @@ export function useCameraMonitor() {
   const resetConfig = () => {
     config.value = {
       enabled: false,
+      activitySource: 'camera',
       strategy: 'allDevices',
-      debounceMs: 3000,
+      onDelayMs: 1000,
+      offGraceMs: 3000,
+      inCallState: { powerOn: true },
+      overridePolicy: 'respectOverride',
     }
   }
 
@@ -135,20 +186,31 @@ export function useCameraMonitor() {
   }
 
   // Lifecycle
+  const unlisteners: UnlistenFn[] = []
+
   onMounted(async () => {
     await loadConfig()
     await refreshStatus()
+    await loadHistory()
 
-    // Auto-start monitoring if enabled
-    if (config.value.enabled && !state.value.isMonitoring) {
-      await startMonitoring()
+    // Session changes are pushed by the backend, no polling needed
+    for (const event of CAMERA_EVENTS) {
+      unlisteners.push(await listen(event, async () => {
+        await refreshStatus()
+        await loadHistory()
+      }))
     }
   })
 
+  onUnmounted(() => {
+    unlisteners.forEach(unlisten => unlisten())
+  })
+
   return {
     // State
     state: computed(() => state.value),
     config: computed(() => config.value),
+    history: computed(() => history.value),
 
     // Computed
     isEnabled,
@@ -161,6 +223,8 @@ export function useCameraMonitor() {
     stopMonitoring,
     toggleMonitoring,
     refreshStatus,
+    loadHistory,
+    loadConfig,
     updateConfig,
     resetConfig,
   }
@@ -0,0 +1,97 @@
+import type { DeviceSet } from './useTriggers'
+import { invoke } from '@tauri-apps/api/core'
+import { ref } from 'vue'
+
+export type DayEvent = 'sunrise' | 'solarNoon' | 'sunset'
+
+export interface CircadianAnchor {
+  event: DayEvent
+  offsetMinutes: number
+  temperatureKelvin: number
+  brightnessPercentage: number | null
+}
+
+export interface CircadianConfig {
+  enabled: boolean
+  latitude: number
+  longitude: number
+  anchors: CircadianAnchor[]
+  devices: DeviceSet
+}
+
+export interface CircadianTarget {
+  temperatureKelvin: number
+  brightnessPercentage: number | null
+}
+
+export interface CircadianStatus {
+  enabled: boolean
+  paused: boolean
+  sunriseMinutes: number
+  solarNoonMinutes: number
+  sunsetMinutes: number
+  target: CircadianTarget | null
+}
+
+export function useCircadian() {
+  const config = ref<CircadianConfig>({
+    enabled: false,
+    latitude: 51.48,
+    longitude: 0,
+    anchors: [],
+    devices: 'allDevices',
+  })
+  const status = ref<CircadianStatus | null>(null)
+
+  const loadStatus = async () => {
+    try {
+      status.value = await invoke<CircadianStatus>('get_circadian_status')
+    }
+    catch (error) {
+      console.error('[Circadian] Failed to load status:', error)
+    }
+  }
+
+  const loadConfig = async () => {
+    try {
+      config.value = await invoke<CircadianConfig>('get_circadian_config')
+    }
+    catch (error) {
+      console.error('[Circadian] Failed to load config:', error)
+    }
+  }
+
+  const updateConfig = async (newConfig: CircadianConfig) => {
+    try {
+      await invoke('update_circadian_config', { config: newConfig })
+      config.value = newConfig
+      await loadStatus()
+    }
+    catch (error: any) {
+      const errorMessage = error?.message || error?.toString() || 'Unknown error'
+      console.error('[Circadian] Failed to update config:', error)
+      throw new Error(`Failed to save circadian schedule: ${errorMessage}`)
+    }
+  }
+
+  const resume = async () => {
+    try {
+      await invoke('resume_circadian_schedule')
+      await loadStatus()
+    }
+    catch (error: any) {
+      const errorMessage = error?.message || error?.toString() || 'Unknown error'
+      console.error('[Circadian] Failed to resume schedule:', error)
+      throw new Error(`Failed to resume circadian schedule: ${errorMessage}`)
+    }
+  }
+
+  return {
+    config,
+    status,
+    loadConfig,
+    loadStatus,
+    updateConfig,
+    resume,
+  }
+}
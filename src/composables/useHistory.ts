@@ -0,0 +1,77 @@
+import { invoke } from '@tauri-apps/api/core'
+import { ref } from 'vue'
+
+export interface HistoryConfig {
+  enabled: boolean
+  retentionDays: number
+}
+
+export interface HistorySample {
+  time: string
+  state: {
+    is_on: boolean
+    brightness_lumens: number
+    temperature_kelvin: number
+  } | null
+}
+
+export interface DeviceHistory {
+  serialNumber: string
+  samples: HistorySample[]
+}
+
+export function useHistory() {
+  const config = ref<HistoryConfig>({ enabled: true, retentionDays: 30 })
+
+  const loadConfig = async () => {
+    try {
+      config.value = await invoke<HistoryConfig>('get_history_config')
+    }
+    catch (error) {
+      console.error('[History] Failed to load config:', error)
+    }
+  }
+
+  const updateConfig = async (newConfig: HistoryConfig) => {
+    try {
+      await invoke('update_history_config', { config: newConfig })
+      config.value = newConfig
+    }
+    catch (error: any) {
+      const errorMessage = error?.message || error?.toString() || 'Unknown error'
+      console.error('[History] Failed to update config:', error)
+      throw new Error(`Failed to save history settings: ${errorMessage}`)
+    }
+  }
+
+  const queryHistory = async (serialNumber: string, from: Date, to: Date) => {
+    return await invoke<DeviceHistory>('query_device_history', {
+      serialNumber,
+      from: from.toISOString(),
+      to: to.toISOString(),
+    })
+  }
+
+  // Save the samples of a range as a CSV file through the webview's download
+  const exportCsv = async (serialNumber: string, from: Date, to: Date) => {
+    const csv = await invoke<string>('export_device_history_csv', {
+      serialNumber,
+      from: from.toISOString(),
+      to: to.toISOString(),
+    })
+    const url = URL.createObjectURL(new Blob([csv], { type: 'text/csv' }))
+    const link = document.createElement('a')
+    link.href = url
+    link.download = `litra-${serialNumber}-${from.toISOString().slice(0, 10)}.csv`
+    link.click()
+    URL.revokeObjectURL(url)
+  }
+
+  return {
+    config,
+    loadConfig,
+    updateConfig,
+    queryHistory,
+    exportCsv,
+  }
+}
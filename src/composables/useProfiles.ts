@@ -0,0 +1,70 @@
+import type { AutoToggleConfig, LightingState } from './useCameraMonitor'
+import type { SceneDevice } from './useScenes'
+import { invoke } from '@tauri-apps/api/core'
+import { ref } from 'vue'
+
+export interface Profile {
+  id: string
+  name: string
+  lightingState: LightingState
+  autoToggle: AutoToggleConfig
+  devices: SceneDevice[]
+}
+
+export interface ProfilesStatus {
+  active: string | null
+  profiles: Profile[]
+}
+
+export function useProfiles() {
+  const profiles = ref<Profile[]>([])
+  const activeProfile = ref<string | null>(null)
+
+  const loadProfiles = async () => {
+    try {
+      const status = await invoke<ProfilesStatus>('get_profiles')
+      profiles.value = status.profiles
+      activeProfile.value = status.active
+    }
+    catch (error) {
+      console.error('[Profiles] Failed to load profiles:', error)
+    }
+  }
+
+  // Profiles are created from the current settings and lights
+  const createProfile = async (name: string, lightingState: LightingState) => {
+    const profile = await invoke<Profile>('create_profile', { name, lightingState })
+    await loadProfiles()
+    return profile
+  }
+
+  const duplicateProfile = async (id: string, name: string) => {
+    const profile = await invoke<Profile>('duplicate_profile', { id, name })
+    await loadProfiles()
+    return profile
+  }
+
+  const switchProfile = async (id: string) => {
+    try {
+      return await invoke<Profile>('switch_profile', { id })
+    }
+    finally {
+      await loadProfiles()
+    }
+  }
+
+  const deleteProfile = async (id: string) => {
+    await invoke('delete_profile', { id })
+    await loadProfiles()
+  }
+
+  return {
+    profiles,
+    activeProfile,
+    loadProfiles,
+    createProfile,
+    duplicateProfile,
+    switchProfile,
+    deleteProfile,
+  }
+}
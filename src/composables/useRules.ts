@@ -0,0 +1,102 @@
+import type { LightingState } from './useCameraMonitor'
+import type { DeviceSet, SignalCondition } from './useTriggers'
+import { invoke } from '@tauri-apps/api/core'
+import { ref } from 'vue'
+
+export type Weekday = 'Mon' | 'Tue' | 'Wed' | 'Thu' | 'Fri' | 'Sat' | 'Sun'
+
+export type RuleCondition
+  = | { all: RuleCondition[] }
+    | { any: RuleCondition[] }
+    | { not: RuleCondition }
+    | { timeWindow: { start: string, end: string } }
+    | { weekdays: { days: Weekday[] } }
+    | { signal: { source: string, condition: SignalCondition } }
+    | { deviceConnected: { serialNumber: string } }
+
+export interface Rule {
+  name: string
+  enabled: boolean
+  priority: number
+  condition: RuleCondition
+  devices: DeviceSet
+  action: LightingState
+}
+
+export type LightingField = 'power' | 'brightness' | 'temperature'
+
+export type RuleOutcome
+  = | 'disabled'
+    | 'notMatched'
+    | 'noAction'
+    | { applied: { fields: LightingField[] } }
+    | { overridden: { by: string } }
+
+export interface ConditionExplanation {
+  description: string
+  holds: boolean
+  children: ConditionExplanation[]
+}
+
+export interface RuleExplanation {
+  name: string
+  priority: number
+  outcome: RuleOutcome
+  condition: ConditionExplanation
+}
+
+export interface DeviceRulesExplanation {
+  serialNumber: string
+  current: {
+    is_on: boolean
+    brightness_lumens: number
+    temperature_kelvin: number
+  }
+  state: LightingState
+  summary: string
+  rules: RuleExplanation[]
+}
+
+export function useRules() {
+  const rules = ref<Rule[]>([])
+  const explanations = ref<DeviceRulesExplanation[]>([])
+
+  const loadRules = async () => {
+    try {
+      rules.value = await invoke<Rule[]>('get_rules')
+    }
+    catch (error) {
+      console.error('[Rules] Failed to load rules:', error)
+    }
+  }
+
+  const loadExplanations = async () => {
+    try {
+      explanations.value = await invoke<DeviceRulesExplanation[]>('explain_rules')
+    }
+    catch (error) {
+      console.error('[Rules] Failed to explain rules:', error)
+    }
+  }
+
+  const updateRules = async (newRules: Rule[]) => {
+    try {
+      await invoke('update_rules', { rules: newRules })
+      rules.value = newRules
+      await loadExplanations()
+    }
+    catch (error: any) {
+      const errorMessage = error?.message || error?.toString() || 'Unknown error'
+      console.error('[Rules] Failed to update rules:', error)
+      throw new Error(`Failed to save rules: ${errorMessage}`)
+    }
+  }
+
+  return {
+    rules,
+    explanations,
+    loadRules,
+    loadExplanations,
+    updateRules,
+  }
+}
@@ -0,0 +1,65 @@
+import { invoke } from '@tauri-apps/api/core'
+import { ref } from 'vue'
+
+export interface SceneDevice {
+  serialNumber: string
+  deviceType: string
+  state: {
+    is_on: boolean
+    brightness_lumens: number
+    temperature_kelvin: number
+  }
+}
+
+export interface Scene {
+  id: string
+  name: string
+  devices: SceneDevice[]
+}
+
+export interface SceneApplied {
+  applied: string[]
+  missing: string[]
+}
+
+export function useScenes() {
+  const scenes = ref<Scene[]>([])
+
+  const loadScenes = async () => {
+    try {
+      scenes.value = await invoke<Scene[]>('get_scenes')
+    }
+    catch (error) {
+      console.error('[Scenes] Failed to load scenes:', error)
+    }
+  }
+
+  const captureScene = async (name: string, serialNumbers?: string[]) => {
+    const scene = await invoke<Scene>('capture_scene', { name, serialNumbers })
+    await loadScenes()
+    return scene
+  }
+
+  const updateScene = async (scene: Scene) => {
+    await invoke('update_scene', { scene })
+    await loadScenes()
+  }
+
+  const deleteScene = async (id: string) => {
+    await invoke('delete_scene', { id })
+    await loadScenes()
+  }
+
+  const applyScene = async (id: string, fadeMs?: number) => {
+    return await invoke<SceneApplied>('apply_scene', { id, fadeMs })
+  }
+
+  return {
+    scenes,
+    loadScenes,
+    captureScene,
+    updateScene,
+    deleteScene,
+    applyScene,
+  }
+}
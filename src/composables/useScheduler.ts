@@ -0,0 +1,65 @@
+import type { LightingState } from './useCameraMonitor'
+import type { DeviceSet } from './useTriggers'
+import { invoke } from '@tauri-apps/api/core'
+import { ref } from 'vue'
+
+export type CatchUpPolicy = 'skip' | 'always' | { runIfWithin: { minutes: number } }
+
+export interface ScheduledJob {
+  name: string
+  enabled: boolean
+  schedule: string
+  devices: DeviceSet
+  action: LightingState
+  catchUp: CatchUpPolicy
+}
+
+export interface ScheduledJobStatus {
+  name: string
+  nextRun: string | null
+  lastRun: string | null
+}
+
+export function useScheduler() {
+  const jobs = ref<ScheduledJob[]>([])
+  const status = ref<ScheduledJobStatus[]>([])
+
+  const loadStatus = async () => {
+    try {
+      status.value = await invoke<ScheduledJobStatus[]>('get_scheduled_job_status')
+    }
+    catch (error) {
+      console.error('[Scheduler] Failed to load job status:', error)
+    }
+  }
+
+  const loadJobs = async () => {
+    try {
+      jobs.value = await invoke<ScheduledJob[]>('get_scheduled_jobs')
+    }
+    catch (error) {
+      console.error('[Scheduler] Failed to load jobs:', error)
+    }
+  }
+
+  const updateJobs = async (newJobs: ScheduledJob[]) => {
+    try {
+      await invoke('update_scheduled_jobs', { jobs: newJobs })
+      jobs.value = newJobs
+      await loadStatus()
+    }
+    catch (error: any) {
+      const errorMessage = error?.message || error?.toString() || 'Unknown error'
+      console.error('[Scheduler] Failed to update jobs:', error)
+      throw new Error(`Failed to save scheduled jobs: ${errorMessage}`)
+    }
+  }
+
+  return {
+    jobs,
+    status,
+    loadJobs,
+    loadStatus,
+    updateJobs,
+  }
+}
@@ -0,0 +1,109 @@
+import type { UnlistenFn } from '@tauri-apps/api/event'
+import type { DeviceSet } from './useTriggers'
+import { invoke } from '@tauri-apps/api/core'
+import { listen } from '@tauri-apps/api/event'
+import { useIntervalFn } from '@vueuse/core'
+import { onMounted, onUnmounted, ref } from 'vue'
+
+export type TimerKind = 'sleepTimer' | 'maxOnTime'
+
+export interface TimerInfo {
+  kind: TimerKind
+  serialNumber: string | null
+  remainingSeconds: number
+}
+
+export interface MaxOnTimeConfig {
+  enabled: boolean
+  maxOnMinutes: number
+  devices: DeviceSet
+}
+
+export function useTimers() {
+  const timers = ref<TimerInfo[]>([])
+  const maxOnTime = ref<MaxOnTimeConfig>({
+    enabled: false,
+    maxOnMinutes: 240,
+    devices: 'allDevices',
+  })
+
+  const loadTimers = async () => {
+    try {
+      timers.value = await invoke<TimerInfo[]>('get_timers')
+    }
+    catch (error) {
+      console.error('[Timers] Failed to load timers:', error)
+    }
+  }
+
+  const setSleepTimer = async (minutes: number, serialNumber: string | null = null) => {
+    try {
+      await invoke('set_sleep_timer', { serialNumber, minutes })
+      await loadTimers()
+    }
+    catch (error: any) {
+      const errorMessage = error?.message || error?.toString() || 'Unknown error'
+      console.error('[Timers] Failed to set sleep timer:', error)
+      throw new Error(`Failed to set sleep timer: ${errorMessage}`)
+    }
+  }
+
+  const cancelSleepTimer = async (serialNumber: string | null = null) => {
+    try {
+      await invoke('cancel_sleep_timer', { serialNumber })
+      await loadTimers()
+    }
+    catch (error: any) {
+      const errorMessage = error?.message || error?.toString() || 'Unknown error'
+      console.error('[Timers] Failed to cancel sleep timer:', error)
+      throw new Error(`Failed to cancel sleep timer: ${errorMessage}`)
+    }
+  }
+
+  const loadMaxOnTime = async () => {
+    try {
+      maxOnTime.value = await invoke<MaxOnTimeConfig>('get_max_on_time_config')
+    }
+    catch (error) {
+      console.error('[Timers] Failed to load max on-time config:', error)
+    }
+  }
+
+  const updateMaxOnTime = async (config: MaxOnTimeConfig) => {
+    try {
+      await invoke('update_max_on_time_config', { config })
+      maxOnTime.value = config
+      await loadTimers()
+    }
+    catch (error: any) {
+      const errorMessage = error?.message || error?.toString() || 'Unknown error'
+      console.error('[Timers] Failed to update max on-time config:', error)
+      throw new Error(`Failed to save max on-time config: ${errorMessage}`)
+    }
+  }
+
+  // Timers run in the backend, so the countdowns are refreshed from there
+  useIntervalFn(loadTimers, 5000)
+
+  let unlisten: UnlistenFn | null = null
+
+  onMounted(async () => {
+    await loadTimers()
+    // Sleep timers can also be set from the tray
+    unlisten = await listen('timers-changed', loadTimers)
+  })
+
+  onUnmounted(() => {
+    unlisten?.()
+  })
+
+  return {
+    timers,
+    maxOnTime,
+    loadTimers,
+    setSleepTimer,
+    cancelSleepTimer,
+    loadMaxOnTime,
+    updateMaxOnTime,
+  }
+}
@@ -0,0 +1,172 @@
+import type { LightingState, OverridePolicy } from './useCameraMonitor'
+import { invoke } from '@tauri-apps/api/core'
+import { ref } from 'vue'
+
+export type SignalValue = boolean | number
+
+export type SignalCondition =
+  | 'isTrue'
+  | 'isFalse'
+  | { above: { threshold: number } }
+  | { below: { threshold: number } }
+
+export type DeviceSet = 'allDevices' | { devices: { serialNumbers: string[] } }
+
+export interface TriggerBinding {
+  name: string
+  enabled: boolean
+  source: string
+  condition: SignalCondition
+  devices: DeviceSet
+  action: LightingState
+  restoreOnRelease: boolean
+  overridePolicy?: OverridePolicy
+}
+
+export interface SessionLockConfig {
+  enabled: boolean
+  devices: DeviceSet
+}
+
+export interface DisplaySleepConfig {
+  enabled: boolean
+  devices: DeviceSet
+}
+
+export interface IdleConfig {
+  enabled: boolean
+  dimAfterMinutes: number
+  dimBrightnessPercentage: number
+  offAfterMinutes: number
+  devices: DeviceSet
+}
+
+export function useTriggers() {
+  const bindings = ref<TriggerBinding[]>([])
+  const signals = ref<Record<string, SignalValue>>({})
+  const sessionLock = ref<SessionLockConfig>({
+    enabled: false,
+    devices: 'allDevices',
+  })
+  const displaySleep = ref<DisplaySleepConfig>({
+    enabled: false,
+    devices: 'allDevices',
+  })
+  const idle = ref<IdleConfig>({
+    enabled: false,
+    dimAfterMinutes: 5,
+    dimBrightnessPercentage: 10,
+    offAfterMinutes: 15,
+    devices: 'allDevices',
+  })
+
+  const loadBindings = async () => {
+    try {
+      bindings.value = await invoke<TriggerBinding[]>('get_trigger_bindings')
+    }
+    catch (error) {
+      console.error('[Triggers] Failed to load bindings:', error)
+    }
+  }
+
+  const updateBindings = async (newBindings: TriggerBinding[]) => {
+    try {
+      await invoke('update_trigger_bindings', { bindings: newBindings })
+      bindings.value = newBindings
+    }
+    catch (error: any) {
+      const errorMessage = error?.message || error?.toString() || 'Unknown error'
+      console.error('[Triggers] Failed to update bindings:', error)
+      throw new Error(`Failed to save trigger bindings: ${errorMessage}`)
+    }
+  }
+
+  const refreshSignals = async () => {
+    try {
+      signals.value = await invoke<Record<string, SignalValue>>('get_trigger_signals')
+    }
+    catch (error) {
+      console.error('[Triggers] Failed to refresh signals:', error)
+    }
+  }
+
+  const loadSessionLock = async () => {
+    try {
+      sessionLock.value = await invoke<SessionLockConfig>('get_session_lock_config')
+    }
+    catch (error) {
+      console.error('[Triggers] Failed to load session lock config:', error)
+    }
+  }
+
+  const updateSessionLock = async (config: SessionLockConfig) => {
+    try {
+      await invoke('update_session_lock_config', { config })
+      sessionLock.value = config
+    }
+    catch (error: any) {
+      const errorMessage = error?.message || error?.toString() || 'Unknown error'
+      console.error('[Triggers] Failed to update session lock config:', error)
+      throw new Error(`Failed to save session lock config: ${errorMessage}`)
+    }
+  }
+
+  const loadDisplaySleep = async () => {
+    try {
+      displaySleep.value = await invoke<DisplaySleepConfig>('get_display_sleep_config')
+    }
+    catch (error) {
+      console.error('[Triggers] Failed to load display sleep config:', error)
+    }
+  }
+
+  const updateDisplaySleep = async (config: DisplaySleepConfig) => {
+    try {
+      await invoke('update_display_sleep_config', { config })
+      displaySleep.value = config
+    }
+    catch (error: any) {
+      const errorMessage = error?.message || error?.toString() || 'Unknown error'
+      console.error('[Triggers] Failed to update display sleep config:', error)
+      throw new Error(`Failed to save display sleep config: ${errorMessage}`)
+    }
+  }
+
+  const loadIdle = async () => {
+    try {
+      idle.value = await invoke<IdleConfig>('get_idle_config')
+    }
+    catch (error) {
+      console.error('[Triggers] Failed to load idle config:', error)
+    }
+  }
+
+  const updateIdle = async (config: IdleConfig) => {
+    try {
+      await invoke('update_idle_config', { config })
+      idle.value = config
+    }
+    catch (error: any) {
+      const errorMessage = error?.message || error?.toString() || 'Unknown error'
+      console.error('[Triggers] Failed to update idle config:', error)
+      throw new Error(`Failed to save idle config: ${errorMessage}`)
+    }
+  }
+
+  return {
+    bindings,
+    signals,
+    sessionLock,
+    displaySleep,
+    idle,
+    loadBindings,
+    updateBindings,
+    refreshSignals,
+    loadSessionLock,
+    updateSessionLock,
+    loadDisplaySleep,
+    updateDisplaySleep,
+    loadIdle,
+    updateIdle,
+  }
+}
@@ -0,0 +1,90 @@
+import { invoke } from '@tauri-apps/api/core'
+import { computed, ref } from 'vue'
+
+export type ChangeSource = 'ui' | 'tray' | 'automation'
+
+export interface DeviceChange {
+  serialNumber: string
+  before: {
+    is_on: boolean
+    brightness_lumens: number
+    temperature_kelvin: number
+  }
+  after: {
+    is_on: boolean
+    brightness_lumens: number
+    temperature_kelvin: number
+  }
+}
+
+export interface ChangeStep {
+  source: ChangeSource
+  description: string
+  time: string
+  changes: DeviceChange[]
+}
+
+export interface ChangeHistoryStatus {
+  undo: ChangeStep[]
+  redo: ChangeStep[]
+}
+
+export function useUndo() {
+  const history = ref<ChangeHistoryStatus>({ undo: [], redo: [] })
+
+  // Undo and redo skip the changes made by automation
+  const isUserChange = (step: ChangeStep) => step.source !== 'automation'
+  const nextUndo = computed(() => history.value.undo.find(isUserChange))
+  const nextRedo = computed(() => history.value.redo.find(isUserChange))
+  const canUndo = computed(() => nextUndo.value !== undefined)
+  const canRedo = computed(() => nextRedo.value !== undefined)
+
+  const loadHistory = async () => {
+    try {
+      history.value = await invoke<ChangeHistoryStatus>('get_change_history', { serialNumber: null })
+    }
+    catch (error) {
+      console.error('[Undo] Failed to load change history:', error)
+    }
+  }
+
+  // Both return the change undone or redone, or null if there was none
+  const undo = async () => {
+    try {
+      return await invoke<ChangeStep | null>('undo_last_change')
+    }
+    catch (error: any) {
+      const errorMessage = error?.message || error?.toString() || 'Unknown error'
+      console.error('[Undo] Failed to undo:', error)
+      throw new Error(`Failed to undo: ${errorMessage}`)
+    }
+    finally {
+      await loadHistory()
+    }
+  }
+
+  const redo = async () => {
+    try {
+      return await invoke<ChangeStep | null>('redo_change')
+    }
+    catch (error: any) {
+      const errorMessage = error?.message || error?.toString() || 'Unknown error'
+      console.error('[Undo] Failed to redo:', error)
+      throw new Error(`Failed to redo: ${errorMessage}`)
+    }
+    finally {
+      await loadHistory()
+    }
+  }
+
+  return {
+    history,
+    nextUndo,
+    nextRedo,
+    canUndo,
+    canRedo,
+    loadHistory,
+    undo,
+    redo,
+  }
+}
@@ -368,6 +368,15 @@ export const useDeviceStore = defineStore('device', () => {
     }
   }
 
+  // Preset tracking, reported by the backend when a device moves to or away
+  // from a preset
+  const setActivePreset = (serialNumber: string, presetId: string | null): void => {
+    const device = devices.value.find(d => d.serial_number === serialNumber)
+    if (device) {
+      device.active_preset = presetId
+    }
+  }
+
   // Selection management
   const selectDevice = (serialNumber: string | null): void => {
     selectedDeviceSerial.value = serialNumber
@@ -410,6 +419,7 @@ export const useDeviceStore = defineStore('device', () => {
     getTemperatureInfo,
     setBrightnessInLumen,
     setTemperatureInKelvin,
+    setActivePreset,
     selectDevice,
     selectFirstDevice,
   }
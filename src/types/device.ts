@@ -42,6 +42,9 @@ export interface DeviceInfo {
 
   /** Maximum color temperature supported (typically 6500K) */
   max_temperature_kelvin: number
+
+  /** ID of the preset matching the current brightness and temperature, if any */
+  active_preset: string | null
 }
 
 /**